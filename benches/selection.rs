@@ -0,0 +1,90 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Benchmarks for deciding about and removing the entries of huge
+//! directories.
+//!
+//! Run with `cargo bench`. Each benchmark fills a temporary directory with
+//! empty files and keeps a handful of them.
+
+use std::{fs, hint::black_box, path::Path};
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use leave::LeaveOptions;
+use tempfile::TempDir;
+
+/// Numbers of entries in the directories benchmarked.
+const SIZES: &[u64] = &[1_000, 10_000, 100_000];
+
+/// Number of entries kept in each directory.
+const KEPT: u64 = 10;
+
+/// Creates a temporary directory containing `count` empty files.
+fn make_dir(count: u64) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..count {
+        fs::write(dir.path().join(format!("file{i}")), "").unwrap();
+    }
+    dir
+}
+
+/// Returns options which keep the first [`KEPT`] files.
+fn options() -> LeaveOptions {
+    (0..KEPT).fold(LeaveOptions::new(), |options, i| {
+        options.keep(format!("file{i}"))
+    })
+}
+
+fn plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plan");
+    for &size in SIZES {
+        let dir = make_dir(size);
+        group.throughput(Throughput::Elements(size));
+        group.bench_with_input(BenchmarkId::from_parameter(size), dir.path(), |b, path| {
+            b.iter(|| black_box(options().plan(path).unwrap()));
+        });
+    }
+    group.finish();
+}
+
+fn remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove");
+    group.sample_size(10);
+    for &size in SIZES {
+        group.throughput(Throughput::Elements(size));
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter_batched(
+                || make_dir(size),
+                |dir| {
+                    remove_all(dir.path());
+                    dir
+                },
+                BatchSize::PerIteration,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// Removes everything but the kept files from the given directory.
+fn remove_all(path: &Path) {
+    leave::execute(options().plan(path).unwrap()).unwrap();
+}
+
+criterion_group!(benches, plan, remove);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! NTFS alternate data stream handling, for `--strip-zone-identifier`.
+//!
+//! Deleting a file on NTFS always deletes all of its streams, so removal
+//! needs no special handling. Kept files, however, may carry a
+//! `Zone.Identifier` stream recording that they were downloaded from the
+//! internet, which makes Windows show security warnings when they're opened.
+
+use std::{ffi::OsString, fs, io::ErrorKind};
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// Name of the stream Windows uses to mark downloaded files.
+const ZONE_IDENTIFIER: &str = ":Zone.Identifier";
+
+/// Removes the `Zone.Identifier` stream from every regular file in the
+/// current directory.
+pub fn strip_zone_identifiers() -> eyre::Result<()> {
+    for entry in fs::read_dir(".").wrap_err(tr!("Can't list contents of ."))? {
+        let entry = entry.wrap_err(tr!("Can't read directory entry"))?;
+        if !entry.file_type().is_ok_and(|t| t.is_file()) {
+            continue;
+        }
+        let mut stream = OsString::from(entry.path());
+        stream.push(ZONE_IDENTIFIER);
+        match fs::remove_file(&stream) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                return Err(err)
+                    .wrap_err_with(|| tr!("Can't remove {path}", path = stream.to_string_lossy()));
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Expansion of `@FILE` response-file arguments.
+//!
+//! An argument of the form `@FILE` is replaced by the lines of `FILE`, one
+//! argument per line. This allows passing keep lists too large for the
+//! command line. Arguments after `--` are never expanded, so a file whose
+//! name starts with `@` can be given as `-- @name` or `./@name`.
+
+use std::{ffi::OsString, fs, path::Path};
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// Expands response-file arguments in the given arguments. The first argument
+/// is the program name and is never expanded.
+pub fn expand<I>(args: I) -> eyre::Result<Vec<OsString>>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut expanded = Vec::new();
+    let mut args = args.into_iter();
+    expanded.extend(args.next());
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            expanded.push(arg);
+            expanded.extend(args);
+            break;
+        }
+        match arg.as_encoded_bytes().strip_prefix(b"@") {
+            Some(path) if !path.is_empty() => {
+                let path = Path::new(bytes_to_os_str(path));
+                let contents = fs::read(path).wrap_err_with(|| {
+                    tr!("Can't read argument file {path}", path = path.display())
+                })?;
+                expanded.extend(
+                    contents
+                        .split(|&b| b == b'\n')
+                        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+                        .filter(|line| !line.is_empty())
+                        .map(|line| bytes_to_os_str(line).to_owned()),
+                );
+            }
+            _ => expanded.push(arg),
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(unix)]
+fn bytes_to_os_str(bytes: &[u8]) -> &std::ffi::OsStr {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bytes)
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_str(bytes: &[u8]) -> &std::ffi::OsStr {
+    // Argument files must be UTF-8 on platforms without byte-string paths
+    std::ffi::OsStr::new(std::str::from_utf8(bytes).unwrap_or_default())
+}
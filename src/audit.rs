@@ -0,0 +1,329 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Tamper-evident audit log of removals, for `--audit-log`.
+//!
+//! Each removal appends one line of tab-separated fields:
+//!
+//! ```text
+//! PREV_HASH  TIME  USER  ACTION  SIZE  CONTENT_HASH  PATH  HASH
+//! ```
+//!
+//! `HASH` is the SHA-256 of the rest of the line, and `PREV_HASH` is the
+//! `HASH` of the previous line (all zeros for the first), so editing,
+//! inserting, or removing a record breaks the chain from that point on.
+//! `CONTENT_HASH` is only recorded for quarantined regular files, since
+//! those are the only ones whose contents can be recovered.
+//!
+//! The chain alone can't reveal records cut off the end of the log, so the
+//! number of records in each log and the hash of the last are also kept in
+//! the `audit-heads` journal in the [state directory](crate::state). It's
+//! only checked on the machine and by the user which wrote the log, and
+//! someone who can rewrite both files can still truncate the log unnoticed.
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::SystemTime,
+};
+
+use eyre::{Context, bail, eyre};
+use sha2::{Digest, Sha256};
+
+use crate::{datetime, i18n::tr, state};
+
+/// `PREV_HASH` of the first record.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Number of fields in a record.
+const FIELD_COUNT: usize = 8;
+
+/// Name of the journal of the number of records in each audit log and the
+/// hash of the last.
+const HEADS_NAME: &str = "audit-heads";
+
+/// An audit log opened for appending.
+pub struct AuditLog {
+    file: File,
+    /// Absolute path of the log, under which its head is recorded.
+    path: PathBuf,
+    count: usize,
+    last_hash: String,
+    user: String,
+}
+
+impl AuditLog {
+    /// Opens the audit log at the given path, creating it if necessary, and
+    /// continues its hash chain.
+    pub fn open(path: &Path) -> eyre::Result<Self> {
+        let (count, last_hash) = match fs::read_to_string(path) {
+            Ok(contents) => match contents.lines().next_back() {
+                Some(line) => (
+                    contents.lines().count(),
+                    line_hash(line)
+                        .ok_or_else(|| {
+                            eyre!(tr!(
+                                "Malformed last record in {path}",
+                                path = path.display()
+                            ))
+                        })?
+                        .to_owned(),
+                ),
+                None => (0, GENESIS_HASH.to_owned()),
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => (0, GENESIS_HASH.to_owned()),
+            Err(err) => {
+                return Err(err).wrap_err_with(|| tr!("Can't read {path}", path = path.display()));
+            }
+        };
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .wrap_err_with(|| tr!("Can't open {path}", path = path.display()))?;
+        Ok(Self {
+            file,
+            path: std::path::absolute(path)
+                .wrap_err_with(|| tr!("Can't make {path} absolute", path = path.display()))?,
+            count,
+            last_hash,
+            user: invoking_user(),
+        })
+    }
+
+    /// Appends a record of a removal.
+    pub fn record(
+        &mut self,
+        action: &str,
+        path: &Path,
+        size: Option<u64>,
+        content_hash: Option<&str>,
+    ) -> eyre::Result<()> {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let body = [
+            self.last_hash.as_str(),
+            &datetime::format_iso8601(secs),
+            &escape(&self.user),
+            action,
+            &size.map_or_else(|| "-".to_owned(), |size| size.to_string()),
+            content_hash.unwrap_or("-"),
+            &escape(&path.to_string_lossy()),
+        ]
+        .join("\t");
+        let hash = hex_digest(body.as_bytes());
+        writeln!(self.file, "{body}\t{hash}").wrap_err(tr!("Can't write to audit log"))?;
+        self.count += 1;
+        self.last_hash = hash;
+        write_head(&self.path, self.count, &self.last_hash)
+    }
+}
+
+/// Returns the SHA-256 hash of the given file's contents.
+pub fn hash_file(path: &Path) -> eyre::Result<String> {
+    let mut file =
+        File::open(path).wrap_err_with(|| tr!("Can't open {path}", path = path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?;
+        if n == 0 {
+            return Ok(hex(&hasher.finalize()));
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+/// Checks the hash chain of the audit log at the given path.
+///
+/// Implements the `leave audit verify` command.
+pub fn verify(path: &Path) -> eyre::Result<ExitCode> {
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?;
+    let mut expected_prev = GENESIS_HASH;
+    let mut count = 0;
+    for (i, line) in contents.lines().enumerate() {
+        let number = i + 1;
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != FIELD_COUNT {
+            bail!(tr!(
+                "Record {number} of {path} is malformed",
+                number = number,
+                path = path.display()
+            ));
+        }
+        if fields[0] != expected_prev {
+            bail!(tr!(
+                "Record {number} of {path} doesn't follow the previous record",
+                number = number,
+                path = path.display()
+            ));
+        }
+        let (body, hash) = line.rsplit_once('\t').unwrap_or_default();
+        if hex_digest(body.as_bytes()) != hash {
+            bail!(tr!(
+                "Record {number} of {path} has been modified",
+                number = number,
+                path = path.display()
+            ));
+        }
+        expected_prev = hash;
+        count += 1;
+    }
+    if let Some((expected_count, expected_hash)) = read_head(path)? {
+        if count < expected_count {
+            bail!(tr!(
+                "{path} has been truncated: {expected} records were written, but {count} remain",
+                path = path.display(),
+                expected = expected_count,
+                count = count
+            ));
+        }
+        // The chain could have been rebuilt from some point on
+        let head = contents.lines().nth(expected_count.saturating_sub(1));
+        if expected_count > 0 && head.and_then(line_hash) != Some(expected_hash.as_str()) {
+            bail!(tr!(
+                "Record {number} of {path} has been modified",
+                number = expected_count,
+                path = path.display()
+            ));
+        }
+    }
+    println!(
+        "{}",
+        tr!(
+            "{path}: {count} records verified",
+            path = path.display(),
+            count = count
+        )
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns the number of records in the audit log at the given path and the
+/// hash of the last, as recorded when the log was last written, if it was
+/// written by this user on this machine.
+fn read_head(path: &Path) -> eyre::Result<Option<(usize, String)>> {
+    let heads_path = state::dir()?.join(HEADS_NAME);
+    let heads = match fs::read_to_string(&heads_path) {
+        Ok(heads) => heads,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err)
+                .wrap_err_with(|| tr!("Can't read {path}", path = heads_path.display()));
+        }
+    };
+    let path = std::path::absolute(path)
+        .wrap_err_with(|| tr!("Can't make {path} absolute", path = path.display()))?;
+    Ok(heads
+        .lines()
+        .filter_map(parse_head)
+        .find(|(head_path, _, _)| *head_path == path)
+        .map(|(_, count, hash)| (count, hash)))
+}
+
+/// Records the number of records in the audit log at the given absolute path
+/// and the hash of the last.
+fn write_head(path: &Path, count: usize, hash: &str) -> eyre::Result<()> {
+    let dir = state::dir()?;
+    fs::create_dir_all(&dir).wrap_err_with(|| tr!("Can't create {path}", path = dir.display()))?;
+    let heads_path = dir.join(HEADS_NAME);
+    let heads = match fs::read_to_string(&heads_path) {
+        Ok(heads) => heads,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err)
+                .wrap_err_with(|| tr!("Can't read {path}", path = heads_path.display()));
+        }
+    };
+    let mut updated = String::new();
+    for line in heads.lines() {
+        if parse_head(line).is_none_or(|(head_path, _, _)| head_path != path) {
+            updated.push_str(line);
+            updated.push('\n');
+        }
+    }
+    let _ = writeln!(
+        updated,
+        "{}\t{count}\t{hash}\t{}",
+        state::now(),
+        state::escape(&path.to_string_lossy())
+    );
+    state::replace(&heads_path, &updated)
+}
+
+/// Parses a record of the heads journal into the path of the log, the number
+/// of records, and the hash of the last.
+fn parse_head(line: &str) -> Option<(PathBuf, usize, String)> {
+    let mut fields = line.split('\t').skip(1);
+    let count = fields.next()?.parse().ok()?;
+    let hash = fields.next()?.to_owned();
+    let path = PathBuf::from(state::unescape(fields.next()?));
+    Some((path, count, hash))
+}
+
+/// Returns the SHA-256 hash of the given data, in hexadecimal.
+fn hex_digest(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// Formats a hash in hexadecimal.
+fn hex(hash: &[u8]) -> String {
+    hash.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+/// Returns the `HASH` field of the given record.
+fn line_hash(line: &str) -> Option<&str> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    (fields.len() == FIELD_COUNT).then(|| fields[FIELD_COUNT - 1])
+}
+
+/// Escapes backslashes, tabs, and newlines, which would otherwise break the
+/// record format.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+/// Returns a description of the user running leave. On Unix, it's the name
+/// and ID of the real user, which unlike environment variables such as
+/// `$USER` can't be set by the caller.
+fn invoking_user() -> String {
+    #[cfg(unix)]
+    {
+        let uid = rustix::process::getuid().as_raw();
+        match uzers::get_user_by_uid(uid) {
+            Some(user) => format!("{} ({uid})", user.name().to_string_lossy()),
+            None => uid.to_string(),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        std::env::var("USERNAME").unwrap_or_else(|_| "unknown".to_owned())
+    }
+}
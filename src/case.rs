@@ -0,0 +1,102 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Case-insensitive matching of keep arguments, for `--ignore-case`.
+//!
+//! On case-insensitive file systems, such as the defaults on macOS and
+//! Windows, a keep argument like `readme.md` exists even though the entry is
+//! named `README.md`. Keep arguments are therefore resolved to the names the
+//! entries actually have, whether or not `--ignore-case` is given.
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Folds the case of the given path so that paths differing only in case are
+/// equal.
+pub fn fold(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().to_lowercase())
+}
+
+/// Resolves keep arguments to the names of the entries they name, listing
+/// each directory only once however many arguments are in it.
+#[derive(Debug)]
+pub struct Resolver<'a> {
+    /// The directory relative paths start from.
+    root: &'a Path,
+    /// Names of the entries of each directory listed so far.
+    listings: HashMap<PathBuf, Vec<OsString>>,
+}
+
+impl<'a> Resolver<'a> {
+    /// Creates a resolver for paths relative to `root`.
+    pub fn new(root: &'a Path) -> Self {
+        Self {
+            root,
+            listings: HashMap::new(),
+        }
+    }
+
+    /// Returns `path` with each component spelled like the entry it names,
+    /// or `None` if some component doesn't name an entry.
+    ///
+    /// A component which differs in case from its entry's name is only
+    /// resolved if `ignore_case` is true or the file system finds the entry
+    /// anyway.
+    pub fn resolve(&mut self, path: &Path, ignore_case: bool) -> Option<PathBuf> {
+        let mut resolved = PathBuf::new();
+        for component in path.components() {
+            let Component::Normal(name) = component else {
+                resolved.push(component);
+                continue;
+            };
+            let dir = self.root.join(&resolved);
+            let real = self.find_entry(&dir, name)?;
+            if real != name && !ignore_case && !dir.join(name).exists() {
+                return None;
+            }
+            resolved.push(real);
+        }
+        Some(resolved)
+    }
+
+    /// Finds the entry of `dir` named `name`, or failing that, one whose name
+    /// differs from it only in case.
+    fn find_entry(&mut self, dir: &Path, name: &OsStr) -> Option<OsString> {
+        let names = match self.listings.entry(dir.to_owned()) {
+            Entry::Occupied(listing) => listing.into_mut(),
+            Entry::Vacant(listing) => listing.insert(
+                fs::read_dir(dir)
+                    .ok()?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.file_name()))
+                    .collect(),
+            ),
+        };
+        if names.iter().any(|entry| entry == name) {
+            return Some(name.to_owned());
+        }
+        let folded = name.to_string_lossy().to_lowercase();
+        names
+            .iter()
+            .find(|entry| entry.to_string_lossy().to_lowercase() == folded)
+            .cloned()
+    }
+}
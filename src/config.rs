@@ -0,0 +1,496 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Loading default options from a configuration file.
+//!
+//! The file is read from `$LEAVE_CONFIG` if it's set, and otherwise from
+//! `leave/config.toml` in `$XDG_CONFIG_HOME` (`~/.config` by default). It
+//! uses a subset of TOML: one `key = value` pair per line, where values are
+//! booleans, integers, strings, or single-line arrays of these, and `#` starts
+//! a comment. Other TOML syntax, such as tables, floats, and values spanning
+//! several lines, is rejected with an error rather than misread. Patterns in
+//! `keep` may refer to environment variables as `${NAME}`. Colors are set
+//! with `theme`, as described in [`crate::theme`].
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context, bail, eyre};
+
+use crate::i18n::tr;
+
+/// Environment variable overriding the path of the configuration file.
+const ENV_VAR: &str = "LEAVE_CONFIG";
+
+/// A value in the configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    Array(Vec<Value>),
+}
+
+/// A setting from the configuration file.
+#[derive(Debug)]
+pub struct Setting {
+    pub key: String,
+    pub value: Value,
+    /// Where the setting came from, as `PATH:LINE`, for error messages.
+    pub location: String,
+}
+
+impl Setting {
+    /// Returns the value as a boolean, or fails if it's not one.
+    pub fn as_bool(&self) -> eyre::Result<bool> {
+        match self.value {
+            Value::Bool(value) => Ok(value),
+            _ => bail!(tr!(
+                "{location}: {key} must be true or false",
+                location = self.location,
+                key = self.key
+            )),
+        }
+    }
+
+    /// Returns the value as a non-negative integer, or fails if it's not one.
+    pub fn as_count(&self) -> eyre::Result<usize> {
+        match self.value {
+            Value::Integer(value) if let Ok(value) = usize::try_from(value) => Ok(value),
+            _ => bail!(tr!(
+                "{location}: {key} must be a non-negative integer",
+                location = self.location,
+                key = self.key
+            )),
+        }
+    }
+
+    /// Returns the value as a string, or fails if it's not one.
+    pub fn as_str(&self) -> eyre::Result<&str> {
+        match &self.value {
+            Value::String(s) => Ok(s),
+            _ => bail!(tr!(
+                "{location}: {key} must be a string",
+                location = self.location,
+                key = self.key
+            )),
+        }
+    }
+
+    /// Returns the value as a list of strings, or fails if it's not one.
+    pub fn as_strings(&self) -> eyre::Result<Vec<&str>> {
+        let not_strings = || {
+            eyre!(tr!(
+                "{location}: {key} must be a list of strings",
+                location = self.location,
+                key = self.key
+            ))
+        };
+        let Value::Array(values) = &self.value else {
+            return Err(not_strings());
+        };
+        values
+            .iter()
+            .map(|value| match value {
+                Value::String(s) => Ok(s.as_str()),
+                _ => Err(not_strings()),
+            })
+            .collect()
+    }
+}
+
+/// Reads the settings from the configuration file. Returns no settings if
+/// the default file doesn't exist, but fails if the file named by
+/// `$LEAVE_CONFIG` doesn't.
+pub fn load() -> eyre::Result<Vec<Setting>> {
+    let (path, required) = match env::var_os(ENV_VAR).filter(|path| !path.is_empty()) {
+        Some(path) => (PathBuf::from(path), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Vec::new()),
+        },
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => parse(&text, &path),
+        Err(err) if err.kind() == ErrorKind::NotFound && !required => Ok(Vec::new()),
+        Err(err) => Err(err).wrap_err_with(|| tr!("Can't read {path}", path = path.display())),
+    }
+}
+
+/// Returns the default path of the configuration file.
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config_home.join("leave").join("config.toml"))
+}
+
+/// Parses the contents of a configuration file.
+fn parse(text: &str, path: &Path) -> eyre::Result<Vec<Setting>> {
+    let mut settings = Vec::new();
+    // Line numbers of the keys set so far
+    let mut lines = HashMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let location = format!("{}:{}", path.display(), i + 1);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            bail!(tr!(
+                "{location}: Tables aren't supported; settings go at the top level",
+                location = location
+            ));
+        }
+        let Some((key, rest)) = line.split_once('=') else {
+            bail!(tr!("{location}: Expected key = value", location = location));
+        };
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            bail!(tr!(
+                "{location}: Invalid key {key}",
+                location = location,
+                key = format!("{key:?}")
+            ));
+        }
+        let mut chars = rest.trim_start().chars().peekable();
+        let value = parse_value(&mut chars).wrap_err(location.clone())?;
+        let rest: String = chars.collect();
+        let rest = rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            bail!(tr!(
+                "{location}: Unexpected {rest} after value",
+                location = location,
+                rest = format!("{rest:?}")
+            ));
+        }
+        let key = key.replace('-', "_");
+        if let Some(line) = lines.insert(key.clone(), i + 1) {
+            bail!(tr!(
+                "{location}: {key} is already set on line {line}",
+                location = location,
+                key = key,
+                line = line
+            ));
+        }
+        settings.push(Setting {
+            key,
+            value,
+            location,
+        });
+    }
+    Ok(settings)
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// Parses a value, consuming it from `chars`.
+fn parse_value(chars: &mut Chars) -> eyre::Result<Value> {
+    let start: String = chars.clone().take(3).collect();
+    if start == "\"\"\"" || start == "'''" {
+        bail!(tr!("Multi-line strings aren't supported"));
+    }
+    match chars.peek() {
+        Some('{') => bail!(tr!("Inline tables aren't supported")),
+        Some('"') => parse_basic_string(chars).map(Value::String),
+        Some('\'') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('\'') => return Ok(Value::String(s)),
+                    Some(c) => s.push(c),
+                    None => bail!(tr!("Unterminated string")),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut values = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                if chars.next_if_eq(&']').is_some() {
+                    return Ok(Value::Array(values));
+                }
+                if chars.peek().is_none() {
+                    bail!(tr!("Arrays must be on a single line"));
+                }
+                values.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                if chars.next_if_eq(&',').is_none() {
+                    skip_whitespace(chars);
+                    if chars.next_if_eq(&']').is_some() {
+                        return Ok(Value::Array(values));
+                    }
+                    bail!(tr!("Expected , or ] in array"));
+                }
+            }
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(c) =
+                chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+')
+            {
+                word.push(c);
+            }
+            if chars.peek() == Some(&'.') {
+                bail!(tr!("Only whole numbers are supported"));
+            }
+            match word.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => word
+                    .replace('_', "")
+                    .parse()
+                    .map(Value::Integer)
+                    .map_err(|_| eyre!(tr!("Invalid value {word}", word = format!("{word:?}")))),
+            }
+        }
+        None => bail!(tr!("Missing value")),
+    }
+}
+
+/// Parses a double-quoted string with escapes, consuming it from `chars`.
+fn parse_basic_string(chars: &mut Chars) -> eyre::Result<String> {
+    chars.next();
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => s.push(match chars.next() {
+                Some('"') => '"',
+                Some('\\') => '\\',
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some(c) => bail!(tr!("Unsupported escape \\{c} in string", c = c)),
+                None => bail!(tr!("Unterminated string")),
+            }),
+            Some(c) => s.push(c),
+            None => bail!(tr!("Unterminated string")),
+        }
+    }
+}
+
+/// Skips whitespace in `chars`.
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the given file contents, returning the keys and values.
+    fn parse_str(text: &str) -> eyre::Result<Vec<(String, Value)>> {
+        let settings = parse(text, Path::new("config.toml"))?;
+        Ok(settings
+            .into_iter()
+            .map(|setting| (setting.key, setting.value))
+            .collect())
+    }
+
+    /// Returns the message of the error from parsing the given contents.
+    fn parse_error(text: &str) -> String {
+        format!("{:#}", parse_str(text).unwrap_err())
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(
+            parse_str("a = true\nb = false\nc = 1_000\nd = -3").unwrap(),
+            [
+                ("a".into(), Value::Bool(true)),
+                ("b".into(), Value::Bool(false)),
+                ("c".into(), Value::Integer(1000)),
+                ("d".into(), Value::Integer(-3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(
+            parse_str(r#"a = "x \"y\" \\ \t#""#).unwrap(),
+            [("a".into(), string("x \"y\" \\ \t#"))]
+        );
+        assert_eq!(
+            parse_str(r"a = 'C:\dir # not a comment'").unwrap(),
+            [("a".into(), string(r"C:\dir # not a comment"))]
+        );
+        assert_eq!(
+            parse_error(r#"a = "x"#),
+            "config.toml:1: Unterminated string"
+        );
+        assert_eq!(
+            parse_error(r#"a = "\q""#),
+            r"config.toml:1: Unsupported escape \q in string"
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(
+            parse_str(r#"a = [ "x", 'y' ,1, [true], ]"#).unwrap(),
+            [(
+                "a".into(),
+                Value::Array(vec![
+                    string("x"),
+                    string("y"),
+                    Value::Integer(1),
+                    Value::Array(vec![Value::Bool(true)]),
+                ])
+            )]
+        );
+        assert_eq!(
+            parse_str("a = []").unwrap(),
+            [("a".into(), Value::Array(Vec::new()))]
+        );
+        assert_eq!(
+            parse_error(r#"a = ["x" "y"]"#),
+            "config.toml:1: Expected , or ] in array"
+        );
+        assert_eq!(
+            parse_error(r#"a = ["x""#),
+            "config.toml:1: Expected , or ] in array"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let text = "# comment\n\n  # indented comment\na = 1 # trailing comment\n";
+        assert_eq!(parse_str(text).unwrap(), [("a".into(), Value::Integer(1))]);
+    }
+
+    #[test]
+    fn keys() {
+        // Unknown keys are parsed, and rejected when they're applied
+        assert_eq!(
+            parse_str("no-such-key = 1").unwrap(),
+            [("no_such_key".into(), Value::Integer(1))]
+        );
+        assert_eq!(
+            parse_error("bad key = 1"),
+            r#"config.toml:1: Invalid key "bad key""#
+        );
+        assert_eq!(parse_error(" = 1"), r#"config.toml:1: Invalid key """#);
+    }
+
+    #[test]
+    fn malformed_lines() {
+        assert_eq!(
+            parse_error("a = 1\njust words"),
+            "config.toml:2: Expected key = value"
+        );
+        assert_eq!(parse_error("a ="), "config.toml:1: Missing value");
+        assert_eq!(
+            parse_error("a = yes"),
+            r#"config.toml:1: Invalid value "yes""#
+        );
+        assert_eq!(
+            parse_error("a = 1 2"),
+            r#"config.toml:1: Unexpected "2" after value"#
+        );
+    }
+
+    #[test]
+    fn unsupported_syntax() {
+        assert_eq!(
+            parse_error("a = 1\n[table]\nb = 2"),
+            "config.toml:2: Tables aren't supported; settings go at the top level"
+        );
+        assert_eq!(
+            parse_error("a = 1\nb = 2\na = 3"),
+            "config.toml:3: a is already set on line 1"
+        );
+        assert_eq!(
+            parse_error("a-b = 1\na_b = 2"),
+            "config.toml:2: a_b is already set on line 1"
+        );
+        assert_eq!(
+            parse_error("a = [\n  \"x\",\n]"),
+            "config.toml:1: Arrays must be on a single line"
+        );
+        assert_eq!(
+            parse_error(r#"a = ["x","#),
+            "config.toml:1: Arrays must be on a single line"
+        );
+        assert_eq!(
+            parse_error(r#"a = """x""""#),
+            "config.toml:1: Multi-line strings aren't supported"
+        );
+        assert_eq!(
+            parse_error("a = '''x'''"),
+            "config.toml:1: Multi-line strings aren't supported"
+        );
+        assert_eq!(
+            parse_error("a = { b = 1 }"),
+            "config.toml:1: Inline tables aren't supported"
+        );
+        assert_eq!(
+            parse_error("a = 1.5"),
+            "config.toml:1: Only whole numbers are supported"
+        );
+        assert_eq!(
+            parse_error("a.b = 1"),
+            r#"config.toml:1: Invalid key "a.b""#
+        );
+        // Empty strings aren't the start of multi-line ones
+        assert_eq!(parse_str(r#"a = """#).unwrap(), [("a".into(), string(""))]);
+        assert_eq!(parse_str("a = ''").unwrap(), [("a".into(), string(""))]);
+    }
+
+    #[test]
+    fn conversions() {
+        let setting = |value| Setting {
+            key: "a".into(),
+            value,
+            location: "config.toml:1".into(),
+        };
+        assert_eq!(
+            setting(Value::Array(vec![string("x")]))
+                .as_strings()
+                .unwrap(),
+            ["x"]
+        );
+        assert!(
+            setting(Value::Array(vec![Value::Integer(1)]))
+                .as_strings()
+                .is_err()
+        );
+        assert!(setting(Value::Integer(-1)).as_count().is_err());
+        assert!(setting(string("true")).as_bool().is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Control socket for pausing, resuming, or cancelling a run, for
+//! `--control-socket`.
+//!
+//! The socket accepts one command per line (`pause`, `resume`, `cancel`,
+//! or `status`) and answers each with one line. Commands take effect between
+//! entries, so an entry which is already being removed is finished first.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{i18n::tr, interrupt};
+
+/// How often a paused run checks whether it was interrupted.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// State of a run which can be changed through the control socket.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RunState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// State shared between the removal loop and the socket listener.
+struct Shared {
+    state: Mutex<RunState>,
+    changed: Condvar,
+    done: AtomicUsize,
+    total: usize,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, RunState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Handles one command and returns the response.
+    fn handle(&self, command: &str) -> String {
+        let mut state = self.lock();
+        match (command, *state) {
+            (_, RunState::Cancelled) if command != "status" => "error: already cancelled".into(),
+            ("pause", _) => {
+                *state = RunState::Paused;
+                "paused".into()
+            }
+            ("resume", _) => {
+                *state = RunState::Running;
+                self.changed.notify_all();
+                "resumed".into()
+            }
+            ("cancel", _) => {
+                *state = RunState::Cancelled;
+                self.changed.notify_all();
+                "cancelled".into()
+            }
+            ("status", state) => {
+                let name = match state {
+                    RunState::Running => "running",
+                    RunState::Paused => "paused",
+                    RunState::Cancelled => "cancelled",
+                };
+                format!(
+                    "{name} {}/{}",
+                    self.done.load(Ordering::Relaxed),
+                    self.total
+                )
+            }
+            _ => format!("error: unknown command {command:?}"),
+        }
+    }
+}
+
+/// A listening control socket. The socket file is removed when this is
+/// dropped.
+pub struct Control {
+    shared: Arc<Shared>,
+    path: PathBuf,
+}
+
+impl Control {
+    /// Creates the control socket at the given path for a run which will
+    /// process `total` entries, and starts answering commands in the
+    /// background.
+    pub fn start(path: &Path, total: usize) -> eyre::Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(RunState::Running),
+            changed: Condvar::new(),
+            done: AtomicUsize::new(0),
+            total,
+        });
+        listen(path, Arc::clone(&shared))?;
+        Ok(Self {
+            shared,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Waits while the run is paused, or until it's interrupted. Returns false
+    /// if the run was cancelled.
+    pub fn checkpoint(&self) -> bool {
+        let mut state = self.shared.lock();
+        // The interrupt handler can't wake this up, so check it periodically
+        while *state == RunState::Paused && !interrupt::is_interrupted() {
+            (state, _) = self
+                .shared
+                .changed
+                .wait_timeout(state, INTERRUPT_POLL_INTERVAL)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *state != RunState::Cancelled
+    }
+
+    /// Records that an entry has been processed.
+    pub fn entry_done(&self) {
+        self.shared.done.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Binds the socket and spawns a thread which accepts connections to it. Each
+/// client is answered by its own thread, so an idle client can't keep others
+/// waiting.
+#[cfg(unix)]
+fn listen(path: &Path, shared: Arc<Shared>) -> eyre::Result<()> {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixListener,
+    };
+
+    use eyre::Context;
+
+    let listener = UnixListener::bind(path)
+        .wrap_err_with(|| tr!("Can't create control socket {path}", path = path.display()))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let Ok(reader) = stream.try_clone() else {
+                continue;
+            };
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || {
+                let mut writer = stream;
+                for line in BufReader::new(reader).lines() {
+                    let Ok(line) = line else { break };
+                    let response = shared.handle(line.trim());
+                    if writeln!(writer, "{response}").is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_path: &Path, _shared: Arc<Shared>) -> eyre::Result<()> {
+    eyre::bail!(tr!("--control-socket is only supported on Unix"));
+}
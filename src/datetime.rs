@@ -0,0 +1,199 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Calendar date helpers.
+
+use std::{
+    fmt::Write as _,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::i18n::tr;
+
+/// Returns the current UTC time formatted as `YYYYMMDDTHHMMSSZ`.
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let days = i64::try_from(secs / 86400).unwrap_or(0);
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// Formats a number of seconds since the Unix epoch as a UTC timestamp in
+/// the form `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_iso8601(secs: u64) -> String {
+    let days = i64::try_from(secs / 86400).unwrap_or(0);
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// Converts a number of days since the Unix epoch into a (year, month, day)
+/// date in the proleptic Gregorian calendar.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    (year, month as u32, day as u32)
+}
+
+/// A calendar date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// Creates a date, returning `None` if the month or day is out of range.
+    pub fn new(year: i64, month: u32, day: u32) -> Option<Self> {
+        ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some(Self { year, month, day })
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    /// Parses a date in `YYYY-MM-DD` format.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            tr!(
+                "invalid date {date}; expected YYYY-MM-DD",
+                date = format!("{s:?}")
+            )
+        };
+        let mut parts = s.splitn(3, '-');
+        let mut next = || parts.next().ok_or_else(invalid);
+        let year = next()?.parse().map_err(|_| invalid())?;
+        let month = next()?.parse().map_err(|_| invalid())?;
+        let day = next()?.parse().map_err(|_| invalid())?;
+        Date::new(year, month, day).ok_or_else(invalid)
+    }
+}
+
+/// Parses a human-friendly duration such as `10s`, `5m`, or `3h30m`.
+///
+/// Supported units are `s` (seconds), `m` (minutes), `h` (hours), `d` (days),
+/// and `w` (weeks). A bare number is interpreted as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || {
+        tr!(
+            "invalid duration {duration}; expected e.g. 30s, 5m, 3h30m, or 7d",
+            duration = format!("{s:?}")
+        )
+    };
+    if let Ok(secs) = s.parse() {
+        return Ok(Duration::from_secs(secs));
+    }
+    let mut total: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let value: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit_secs = match rest[digits..].chars().next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 60 * 60,
+            Some('d') => 24 * 60 * 60,
+            Some('w') => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        total = value
+            .checked_mul(unit_secs)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(invalid)?;
+        rest = &rest[digits + 1..];
+    }
+    if s.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// Formats a number of seconds since the Unix epoch as a UTC date and time
+/// using a subset of `strftime` conversions: `%Y`, `%y`, `%m`, `%d`, `%H`,
+/// `%M`, `%S`, `%j`, and `%%`.
+pub fn strftime(secs: u64, format: &str) -> Result<String, String> {
+    let days = i64::try_from(secs / 86400).unwrap_or(0);
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    let day_of_year = days - days_from_civil(year, 1, 1) + 1;
+
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let _ = match chars.next() {
+            Some('Y') => write!(out, "{year:04}"),
+            Some('y') => write!(out, "{:02}", year.rem_euclid(100)),
+            Some('m') => write!(out, "{month:02}"),
+            Some('d') => write!(out, "{day:02}"),
+            Some('H') => write!(out, "{:02}", rem / 3600),
+            Some('M') => write!(out, "{:02}", rem / 60 % 60),
+            Some('S') => write!(out, "{:02}", rem % 60),
+            Some('j') => write!(out, "{day_of_year:03}"),
+            Some('%') => write!(out, "%"),
+            Some(other) => return Err(tr!("unsupported date conversion %{other}", other = other)),
+            None => return Err("incomplete date conversion at end of format".to_owned()),
+        };
+    }
+    Ok(out)
+}
+
+/// Converts a (year, month, day) date in the proleptic Gregorian calendar
+/// into a number of days since the Unix epoch. This is the inverse of
+/// [`civil_from_days`].
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
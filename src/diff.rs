@@ -0,0 +1,114 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Comparing two plans or run reports, for `leave diff`.
+//!
+//! A plan is the output of a dry run with `--output json`, and a run report
+//! that of a real run. Both list one event per entry, so they can be compared
+//! with each other too, e.g. to check that a run did what was planned.
+
+use std::{collections::BTreeMap, fs, path::Path, process::ExitCode};
+
+use eyre::{Context, bail};
+use serde_json::Value;
+
+use crate::{i18n::tr, output::Output, theme::Marker};
+
+/// What a plan or report says about an entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Verdict {
+    /// Removed, or to be removed, including by moving it away.
+    Doomed,
+    /// Kept, for the given reason.
+    Kept(String),
+    /// Failed to be removed, or failed a check, with the given error.
+    Failed(String),
+}
+
+/// Prints the entries whose verdict differs between the plans or reports
+/// `old` and `new`, sorted by path. Entries which are newly kept or newly
+/// failing are printed with the reason. Exits with status 1 if there are
+/// any, like diff(1).
+pub fn run(old: &Path, new: &Path, out: &Output) -> eyre::Result<ExitCode> {
+    let old = read(old)?;
+    let new = read(new)?;
+    let mut changed = false;
+    for (path, verdict) in &new {
+        if old.get(path) == Some(verdict) {
+            continue;
+        }
+        // A different reason for keeping an entry doesn't change anything
+        if let (Some(Verdict::Kept(_)), Verdict::Kept(_)) = (old.get(path), verdict) {
+            continue;
+        }
+        changed = true;
+        let (label, marker, reason) = match verdict {
+            Verdict::Doomed => ("newly doomed", Marker::Removed, None),
+            Verdict::Kept(reason) => ("newly kept", Marker::Kept, Some(reason)),
+            Verdict::Failed(reason) => ("newly failing", Marker::Error, Some(reason)),
+        };
+        let label = out.paint(marker, label);
+        match reason {
+            Some(reason) if !reason.is_empty() => {
+                out.message(format_args!("{label} {path} ({reason})"));
+            }
+            _ => out.message(format_args!("{label} {path}")),
+        }
+    }
+    Ok(if changed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Reads the verdict on each entry from a plan or report. An entry's last
+/// event wins, since e.g. a dry run may plan to remove an entry and then
+/// find that it can't be.
+fn read(path: &Path) -> eyre::Result<BTreeMap<String, Verdict>> {
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?;
+    let mut verdicts = BTreeMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let location = || format!("{}:{}", path.display(), i + 1);
+        let event: Value = serde_json::from_str(line).wrap_err_with(location)?;
+        let field = |name: &str| event.get(name).and_then(Value::as_str);
+        let Some(action) = field("action") else {
+            bail!(tr!(
+                "{location}: Not an event written by --output json",
+                location = location()
+            ));
+        };
+        // Events such as warnings about dangling links aren't about an entry
+        let Some(entry) = field("path") else {
+            continue;
+        };
+        let reason = field("reason").unwrap_or_default().to_owned();
+        let verdict = match action {
+            "would remove" | "removed" | "trashed" | "quarantined" => Verdict::Doomed,
+            "skipped" => Verdict::Kept(reason),
+            "error" => Verdict::Failed(reason),
+            _ => continue,
+        };
+        verdicts.insert(entry.to_owned(), verdict);
+    }
+    Ok(verdicts)
+}
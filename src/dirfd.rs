@@ -0,0 +1,501 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Directory handles which removals are made relative to.
+//!
+//! Removing a tree by path leaves a window between listing a directory and
+//! removing its entries in which a directory on the path could be replaced by
+//! a link, redirecting the removal elsewhere. On Unix, a [`Dir`] holds an open
+//! file descriptor instead, and entries are listed, inspected and removed
+//! with `openat`, `fstatat` and `unlinkat` relative to it, so every step
+//! refers to the same directory. Subdirectories are opened without following
+//! links.
+//!
+//! The directory being cleaned is opened once, before anything is decided
+//! about it, and its entries are [`DirEntry`]s found by listing it, which
+//! are inspected through the handle of the directory they're in.
+//!
+//! Elsewhere, a [`Dir`] only holds a path and uses the
+//! [`platform`](crate::platform) functions.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use crate::{i18n::tr, permissions};
+
+/// An open directory.
+#[derive(Debug)]
+pub struct Dir {
+    #[cfg(unix)]
+    file: fs::File,
+    #[cfg(not(unix))]
+    path: PathBuf,
+}
+
+/// An entry in a [`Dir`].
+pub struct Entry {
+    pub name: OsString,
+    /// Whether the entry is a directory. Links are not followed.
+    pub is_dir: bool,
+    /// A description of the entry's type if it's a special file, like
+    /// [`permissions::special_type`].
+    pub special: Option<&'static str>,
+}
+
+/// An entry found by listing a [`Dir`]. Like [`fs::DirEntry`], it has a path,
+/// which is the path the directory was listed with joined with the entry's
+/// name, but it's inspected and removed through the directory's handle.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    dir: Arc<Dir>,
+    name: OsString,
+    path: PathBuf,
+    /// The entry's type, once it's been looked up.
+    file_type: OnceLock<fs::FileType>,
+}
+
+impl DirEntry {
+    /// Returns the path of the entry.
+    pub fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    /// Returns the name of the entry.
+    pub fn file_name(&self) -> OsString {
+        self.name.clone()
+    }
+
+    /// Returns the directory containing the entry.
+    pub fn dir(&self) -> &Arc<Dir> {
+        &self.dir
+    }
+
+    /// Returns the metadata of the entry, without following links.
+    ///
+    /// On Linux, the entry is opened relative to its directory with
+    /// `O_PATH`, which works for any type of entry and has no side effects.
+    /// Elsewhere, the metadata is looked up by path.
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            use rustix::fs::{Mode, OFlags};
+
+            let fd = rustix::fs::openat(
+                &self.dir.file,
+                &self.name,
+                OFlags::PATH | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+                Mode::empty(),
+            )?;
+            fs::File::from(fd).metadata()
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            fs::symlink_metadata(&self.path)
+        }
+    }
+
+    /// Returns the type of the entry, without following links. It's looked up
+    /// only once.
+    pub fn file_type(&self) -> io::Result<fs::FileType> {
+        if let Some(file_type) = self.file_type.get() {
+            return Ok(*file_type);
+        }
+        let file_type = self.metadata()?.file_type();
+        Ok(*self.file_type.get_or_init(|| file_type))
+    }
+
+    /// Lists the entries of the entry, which must be a directory. If it's a
+    /// symbolic link, it's followed only if `follow` is true.
+    pub fn read_dir(&self, follow: bool) -> io::Result<Vec<DirEntry>> {
+        Arc::new(self.dir.open(&self.name, follow)?).list(&self.path)
+    }
+}
+
+impl Dir {
+    /// Opens the directory at the given path, following links.
+    pub fn open_path(path: &Path) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            Self::open_at(rustix::fs::CWD, path.as_os_str(), true)
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(Self {
+                path: path.to_owned(),
+            })
+        }
+    }
+
+    /// Opens the directory containing `path`, which is relative to this
+    /// directory, and returns it along with the name of the entry in it.
+    /// Links on the way are followed, as they are when looking up a path.
+    fn open_parent<'a>(&self, path: &'a Path) -> io::Result<(Self, &'a OsStr)> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+        let mut parent = self.try_clone()?;
+        for component in path.parent().into_iter().flat_map(Path::components) {
+            match component {
+                Component::CurDir => (),
+                Component::Normal(name) => parent = parent.open(name, true)?,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Path leaves the directory",
+                    ));
+                }
+            }
+        }
+        Ok((parent, name))
+    }
+
+    /// Opens the subdirectory `name`. If it's a symbolic link, it's followed
+    /// only if `follow` is true; otherwise opening it fails.
+    pub fn open(&self, name: &OsStr, follow: bool) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            Self::open_at(&self.file, name, follow)
+        }
+        #[cfg(not(unix))]
+        {
+            let path = self.path.join(name);
+            let metadata = if follow {
+                fs::metadata(&path)?
+            } else {
+                fs::symlink_metadata(&path)?
+            };
+            if !metadata.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    "Not a directory",
+                ));
+            }
+            Ok(Self { path })
+        }
+    }
+
+    /// Returns the metadata of the directory itself.
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        #[cfg(unix)]
+        {
+            self.file.metadata()
+        }
+        #[cfg(not(unix))]
+        {
+            fs::metadata(&self.path)
+        }
+    }
+
+    /// Lists the names of the entries in the directory.
+    fn names(&self) -> io::Result<Vec<OsString>> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let mut names = Vec::new();
+            for entry in rustix::fs::Dir::read_from(&self.file)? {
+                let entry = entry?;
+                let name = OsStr::from_bytes(entry.file_name().to_bytes());
+                if name != "." && name != ".." {
+                    names.push(name.to_owned());
+                }
+            }
+            Ok(names)
+        }
+        #[cfg(not(unix))]
+        {
+            fs::read_dir(&self.path)?
+                .map(|entry| Ok(entry?.file_name()))
+                .collect()
+        }
+    }
+
+    /// Lists the entries in the directory.
+    pub fn entries(&self) -> io::Result<Vec<Entry>> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            use rustix::fs::FileType;
+
+            let mut entries = Vec::new();
+            for entry in rustix::fs::Dir::read_from(&self.file)? {
+                let entry = entry?;
+                let name = OsStr::from_bytes(entry.file_name().to_bytes());
+                if name == "." || name == ".." {
+                    continue;
+                }
+                // Not every file system reports types when listing
+                let file_type = match entry.file_type() {
+                    FileType::Unknown => FileType::from_raw_mode(self.stat(name)?.st_mode),
+                    file_type => file_type,
+                };
+                entries.push(Entry {
+                    name: name.to_owned(),
+                    is_dir: file_type == FileType::Directory,
+                    special: match file_type {
+                        FileType::Fifo => Some("fifo"),
+                        FileType::Socket => Some("socket"),
+                        FileType::BlockDevice => Some("block special file"),
+                        FileType::CharacterDevice => Some("character special file"),
+                        _ => None,
+                    },
+                });
+            }
+            Ok(entries)
+        }
+        #[cfg(not(unix))]
+        {
+            fs::read_dir(&self.path)?
+                .map(|entry| {
+                    let entry = entry?;
+                    let file_type = entry.file_type()?;
+                    Ok(Entry {
+                        name: entry.file_name(),
+                        is_dir: file_type.is_dir(),
+                        special: permissions::special_type(file_type),
+                    })
+                })
+                .collect()
+        }
+    }
+
+    /// Lists the entries in the directory as [`DirEntry`]s, whose paths are
+    /// `path` joined with their names.
+    pub fn list(self: &Arc<Self>, path: &Path) -> io::Result<Vec<DirEntry>> {
+        Ok(self
+            .names()?
+            .into_iter()
+            .map(|name| DirEntry {
+                dir: Arc::clone(self),
+                path: path.join(&name),
+                name,
+                file_type: OnceLock::new(),
+            })
+            .collect())
+    }
+
+    /// Returns whether `path`, which is relative to this directory, names an
+    /// existing entry. Like [`Path::try_exists`], links are followed, so a
+    /// dangling link doesn't exist.
+    pub fn exists(&self, path: &Path) -> io::Result<bool> {
+        match self.look_up(path) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Looks up `path`, which is relative to this directory, following
+    /// links, and fails if it can't be found.
+    fn look_up(&self, path: &Path) -> io::Result<()> {
+        let (parent, name) = self.open_parent(path)?;
+        #[cfg(unix)]
+        {
+            rustix::fs::statat(&parent.file, name, rustix::fs::AtFlags::empty())?;
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            fs::metadata(parent.path.join(name)).map(drop)
+        }
+    }
+
+    /// Removes the entry `name`, which isn't a directory.
+    pub fn remove_file(&self, name: &OsStr) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            rustix::fs::unlinkat(&self.file, name, rustix::fs::AtFlags::empty())?;
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            crate::platform::remove_file(&self.path.join(name))
+        }
+    }
+
+    /// Removes the empty directory or symbolic link `name`.
+    pub fn remove_dir(&self, name: &OsStr) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use rustix::fs::{AtFlags, FileType};
+
+            let flags = if FileType::from_raw_mode(self.stat(name)?.st_mode) == FileType::Symlink {
+                AtFlags::empty()
+            } else {
+                AtFlags::REMOVEDIR
+            };
+            rustix::fs::unlinkat(&self.file, name, flags)?;
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            crate::platform::remove_dir(&self.path.join(name))
+        }
+    }
+
+    /// Makes the directory itself readable and writable by the current user,
+    /// so that it can be emptied.
+    pub fn unprotect(&self) -> eyre::Result<()> {
+        #[cfg(unix)]
+        {
+            use eyre::Context;
+
+            let mut permissions = self
+                .metadata()
+                .wrap_err(tr!("Can't get metadata of directory"))?
+                .permissions();
+            if permissions::add_write_permission(&mut permissions, true) {
+                self.file
+                    .set_permissions(permissions)
+                    .wrap_err(tr!("Can't make directory writable"))?;
+            }
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            permissions::unprotect(&self.path).map(drop)
+        }
+    }
+
+    /// Makes the entry `name`, but not its contents, writable by the current
+    /// user, like [`permissions::unprotect`]. Symbolic links are left alone.
+    pub fn unprotect_entry(&self, name: &OsStr) -> eyre::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            use eyre::Context;
+            use rustix::fs::{AtFlags, FileType, Mode};
+
+            let print_name = Path::new(name).display();
+            let stat = self
+                .stat(name)
+                .wrap_err_with(|| tr!("Can't get metadata of {path}", path = print_name))?;
+            let file_type = FileType::from_raw_mode(stat.st_mode);
+            if file_type == FileType::Symlink {
+                return Ok(());
+            }
+            // The mode is narrower than u32 on some platforms
+            #[allow(clippy::useless_conversion)]
+            let mut permissions = fs::Permissions::from_mode(stat.st_mode.into());
+            if permissions::add_write_permission(&mut permissions, file_type == FileType::Directory)
+            {
+                rustix::fs::chmodat(
+                    &self.file,
+                    name,
+                    Mode::from_raw_mode(permissions.mode() as _),
+                    AtFlags::empty(),
+                )
+                .wrap_err_with(|| tr!("Can't make {path} writable", path = print_name))?;
+            }
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            permissions::unprotect(&self.path.join(name)).map(drop)
+        }
+    }
+
+    /// Returns another handle to the same directory.
+    fn try_clone(&self) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            Ok(Self {
+                file: self.file.try_clone()?,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(Self {
+                path: self.path.clone(),
+            })
+        }
+    }
+
+    /// Opens the directory `name` relative to `dirfd`.
+    #[cfg(unix)]
+    fn open_at(dirfd: impl std::os::fd::AsFd, name: &OsStr, follow: bool) -> io::Result<Self> {
+        use rustix::fs::{Mode, OFlags};
+
+        let mut flags = OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC;
+        if !follow {
+            flags |= OFlags::NOFOLLOW;
+        }
+        let fd = rustix::fs::openat(dirfd, name, flags, Mode::empty())?;
+        Ok(Self {
+            file: fs::File::from(fd),
+        })
+    }
+
+    /// Returns information about the entry `name` without following links.
+    #[cfg(unix)]
+    fn stat(&self, name: &OsStr) -> io::Result<rustix::fs::Stat> {
+        Ok(rustix::fs::statat(
+            &self.file,
+            name,
+            rustix::fs::AtFlags::SYMLINK_NOFOLLOW,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_and_looks_up_through_handle() {
+        let temp = tempfile::tempdir().unwrap();
+        let original = temp.path().join("original");
+        fs::create_dir_all(original.join("sub")).unwrap();
+        fs::write(original.join("file"), "").unwrap();
+        fs::write(original.join("sub/inner"), "").unwrap();
+        let dir = Arc::new(Dir::open_path(&original).unwrap());
+
+        // Replace the directory; the handle still refers to the original
+        let moved = temp.path().join("moved");
+        fs::rename(&original, &moved).unwrap();
+        fs::create_dir(&original).unwrap();
+        fs::write(original.join("impostor"), "").unwrap();
+
+        assert!(dir.exists(Path::new("file")).unwrap());
+        assert!(dir.exists(Path::new("./sub/inner")).unwrap());
+        assert!(!dir.exists(Path::new("impostor")).unwrap());
+        assert!(!dir.exists(Path::new("sub/missing")).unwrap());
+
+        let mut entries = dir.list(Path::new(".")).unwrap();
+        entries.sort_by_key(DirEntry::file_name);
+        let names: Vec<_> = entries.iter().map(DirEntry::path).collect();
+        assert_eq!(names, [Path::new("./file"), Path::new("./sub")]);
+        assert!(entries[1].file_type().unwrap().is_dir());
+        let children = entries[1].read_dir(false).unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].path(), Path::new("./sub/inner"));
+
+        entries[0]
+            .dir()
+            .remove_file(&entries[0].file_name())
+            .unwrap();
+        assert!(!moved.join("file").exists());
+        assert!(original.join("impostor").exists());
+    }
+}
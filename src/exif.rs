@@ -0,0 +1,194 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Reading capture dates from image metadata, for `--keep-exif-after` and
+//! `--keep-exif-before`.
+//!
+//! Supports JPEG files and TIFF-structured files (TIFF and most camera raw
+//! formats). Only as much of the file as is needed to find the metadata is
+//! read.
+
+use std::{
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+use eyre::Context;
+
+use crate::{datetime::Date, i18n::tr};
+
+/// Maximum number of bytes read from a TIFF-structured file.
+const TIFF_READ_LIMIT: u64 = 1 << 20;
+
+/// JPEG start-of-image marker.
+const JPEG_SOI: [u8; 2] = [0xFF, 0xD8];
+
+/// Identifier at the start of a JPEG APP1 segment containing EXIF data.
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+
+/// Returns the capture date recorded in the image at the given path, or `None`
+/// if the file isn't an image with EXIF metadata.
+///
+/// Prefers the original capture date, then the digitization date, then the
+/// modification date recorded in the metadata.
+pub fn capture_date(path: &Path) -> eyre::Result<Option<Date>> {
+    let mut file =
+        File::open(path).wrap_err_with(|| tr!("Can't open {path}", path = path.display()))?;
+    let mut magic = [0; 4];
+    match file.read_exact(&mut magic) {
+        Ok(()) => (),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| tr!("Can't read {path}", path = path.display()));
+        }
+    }
+
+    let tiff = if magic[..2] == JPEG_SOI {
+        read_jpeg_exif(&mut file, &magic[2..])
+            .wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?
+    } else if &magic == b"II*\0" || &magic == b"MM\0*" {
+        let mut buf = magic.to_vec();
+        file.take(TIFF_READ_LIMIT)
+            .read_to_end(&mut buf)
+            .wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?;
+        Some(buf)
+    } else {
+        None
+    };
+    Ok(tiff.and_then(|tiff| Tiff::new(&tiff)?.capture_date()))
+}
+
+/// Finds the EXIF APP1 segment of a JPEG file and returns its TIFF payload.
+///
+/// `after_soi` holds the bytes already read following the start-of-image
+/// marker.
+fn read_jpeg_exif(file: &mut File, after_soi: &[u8]) -> std::io::Result<Option<Vec<u8>>> {
+    let mut marker = [after_soi[0], after_soi[1]];
+    loop {
+        if marker[0] != 0xFF {
+            return Ok(None);
+        }
+        // Start of scan or end of image: no more metadata segments
+        if marker[1] == 0xDA || marker[1] == 0xD9 {
+            return Ok(None);
+        }
+        let mut len = [0; 2];
+        file.read_exact(&mut len)?;
+        let len = usize::from(u16::from_be_bytes(len)).saturating_sub(2);
+        let mut segment = vec![0; len];
+        file.read_exact(&mut segment)?;
+        if marker[1] == 0xE1 && segment.starts_with(EXIF_HEADER) {
+            return Ok(Some(segment.split_off(EXIF_HEADER.len())));
+        }
+        file.read_exact(&mut marker)?;
+    }
+}
+
+/// A parsed TIFF header, which is the container format of EXIF metadata.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let big_endian = match data.get(..2)? {
+            b"II" => false,
+            b"MM" => true,
+            _ => return None,
+        };
+        Some(Self { data, big_endian })
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Finds the entry with the given tag in the IFD at the given offset and
+    /// returns the offset of its 12-byte entry.
+    fn find_entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = usize::from(self.u16_at(ifd)?);
+        (0..count)
+            .map(|i| ifd + 2 + 12 * i)
+            .find(|&entry| self.u16_at(entry) == Some(tag))
+    }
+
+    /// Reads the value of an ASCII entry.
+    fn ascii(&self, entry: usize) -> Option<&'a [u8]> {
+        const TYPE_ASCII: u16 = 2;
+        if self.u16_at(entry + 2)? != TYPE_ASCII {
+            return None;
+        }
+        let len = usize::try_from(self.u32_at(entry + 4)?).ok()?;
+        let start = if len <= 4 {
+            entry + 8
+        } else {
+            usize::try_from(self.u32_at(entry + 8)?).ok()?
+        };
+        self.data.get(start..start.checked_add(len)?)
+    }
+
+    fn date(&self, ifd: usize, tag: u16) -> Option<Date> {
+        parse_exif_date(self.ascii(self.find_entry(ifd, tag)?)?)
+    }
+
+    fn capture_date(&self) -> Option<Date> {
+        let ifd0 = usize::try_from(self.u32_at(4)?).ok()?;
+        let exif_ifd = self
+            .find_entry(ifd0, TAG_EXIF_IFD)
+            .and_then(|entry| self.u32_at(entry + 8))
+            .and_then(|offset| usize::try_from(offset).ok());
+        exif_ifd
+            .and_then(|ifd| {
+                self.date(ifd, TAG_DATE_TIME_ORIGINAL)
+                    .or_else(|| self.date(ifd, TAG_DATE_TIME_DIGITIZED))
+            })
+            .or_else(|| self.date(ifd0, TAG_DATE_TIME))
+    }
+}
+
+/// Parses the date part of an EXIF timestamp, which has the form
+/// `YYYY:MM:DD HH:MM:SS`.
+fn parse_exif_date(value: &[u8]) -> Option<Date> {
+    let value = std::str::from_utf8(value.get(..10)?).ok()?;
+    let mut parts = value.split(':');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Date::new(year, month, day)
+}
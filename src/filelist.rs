@@ -0,0 +1,65 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Reading keep arguments from a file or standard input, for `--files-from`.
+
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// Reads the paths listed in the given file, or standard input if it's `-`.
+///
+/// Paths are separated by newlines, or by NUL bytes if `null` is true. Empty
+/// paths are ignored.
+pub fn read(path: &Path, null: bool) -> eyre::Result<Vec<PathBuf>> {
+    let contents = if path == Path::new("-") {
+        let mut contents = Vec::new();
+        io::stdin()
+            .read_to_end(&mut contents)
+            .wrap_err(tr!("Can't read standard input"))?;
+        contents
+    } else {
+        fs::read(path).wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?
+    };
+    let separator = if null { b'\0' } else { b'\n' };
+    Ok(contents
+        .split(|&byte| byte == separator)
+        .filter(|item| !item.is_empty())
+        .map(|item| PathBuf::from(bytes_to_os_string(item)))
+        .collect())
+}
+
+/// Converts bytes read from a file or another program to an OS string. Only
+/// Unix allows arbitrary bytes; elsewhere, the bytes must be UTF-8.
+#[cfg(unix)]
+pub fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes.to_vec())
+}
+
+#[cfg(not(unix))]
+pub fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
@@ -0,0 +1,258 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Matching entries by file type, for `--keep-type`.
+//!
+//! A type can be given as a file extension (`pdf`), a MIME type
+//! (`image/png`), a MIME type with a wildcard subtype (`text/*`), or the name
+//! of a group, which is shorthand for the latter (`image`). Types are
+//! determined from file extensions, and with `--sniff` also from the first
+//! bytes of each file, so files with a missing or wrong extension match too.
+
+use std::{fs::File, io::Read, path::Path};
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// Groups which can be given on their own, meaning every subtype.
+const GROUPS: &[&str] = &["audio", "font", "image", "text", "video"];
+
+/// MIME types of common file extensions.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("7z", "application/x-7z-compressed"),
+    ("aac", "audio/aac"),
+    ("avi", "video/x-msvideo"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("bz2", "application/x-bzip2"),
+    ("c", "text/x-c"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("epub", "application/epub+zip"),
+    ("flac", "audio/flac"),
+    ("gif", "image/gif"),
+    ("gz", "application/gzip"),
+    ("heic", "image/heic"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("m4a", "audio/mp4"),
+    ("md", "text/markdown"),
+    ("mkv", "video/x-matroska"),
+    ("mov", "video/quicktime"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ogg", "audio/ogg"),
+    ("otf", "font/otf"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("py", "text/x-python"),
+    ("rar", "application/vnd.rar"),
+    ("rs", "text/x-rust"),
+    ("rtf", "application/rtf"),
+    ("sh", "text/x-shellscript"),
+    ("svg", "image/svg+xml"),
+    ("tar", "application/x-tar"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("toml", "text/x-toml"),
+    ("ttf", "font/ttf"),
+    ("txt", "text/plain"),
+    ("wav", "audio/wav"),
+    ("webm", "video/webm"),
+    ("webp", "image/webp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("xls", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("xml", "text/xml"),
+    ("xz", "application/x-xz"),
+    ("yaml", "text/yaml"),
+    ("yml", "text/yaml"),
+    ("zip", "application/zip"),
+    ("zst", "application/zstd"),
+];
+
+/// Signatures at the start of files, and the MIME type they identify.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"II*\0", "image/tiff"),
+    (b"MM\0*", "image/tiff"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\0", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"Rar!\x1a\x07", "application/vnd.rar"),
+    (b"ID3", "audio/mpeg"),
+    (b"fLaC", "audio/flac"),
+    (b"OggS", "audio/ogg"),
+    (b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+/// Number of bytes read from each file to determine its type.
+const SNIFF_LEN: usize = 512;
+
+/// A type of entries to keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileType {
+    /// A file extension, lowercase and without the dot, and its MIME type if
+    /// it's known.
+    Extension(String, Option<&'static str>),
+    /// A MIME type. A subtype of `*` matches any subtype.
+    Mime(String),
+}
+
+impl FileType {
+    /// Parses a type given to `--keep-type`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim_start_matches('.').to_ascii_lowercase();
+        if s.is_empty() {
+            return Err("no type given".into());
+        }
+        if GROUPS.contains(&s.as_str()) {
+            return Ok(Self::Mime(format!("{s}/*")));
+        }
+        if let Some((kind, subtype)) = s.split_once('/') {
+            if kind.is_empty() || subtype.is_empty() || kind == "*" {
+                return Err(tr!("invalid MIME type {value}", value = format!("{s:?}")));
+            }
+            return Ok(Self::Mime(s));
+        }
+        let mime = mime_of_extension(&s);
+        Ok(Self::Extension(s, mime))
+    }
+
+    /// Returns whether a file with the given extension (lowercase) and
+    /// sniffed MIME type, if any, is of this type.
+    fn matches(&self, extension: Option<&str>, sniffed: Option<&str>) -> bool {
+        match self {
+            Self::Extension(ext, mime) => {
+                extension == Some(ext.as_str()) || (mime.is_some() && sniffed == *mime)
+            }
+            Self::Mime(pattern) => extension
+                .and_then(mime_of_extension)
+                .into_iter()
+                .chain(sniffed)
+                .any(|mime| mime_matches(pattern, mime)),
+        }
+    }
+}
+
+impl std::fmt::Display for FileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Extension(ext, _) => write!(f, ".{ext}"),
+            Self::Mime(mime) => f.write_str(mime),
+        }
+    }
+}
+
+/// Returns the first of `types` which the file at `path` is of. With
+/// `sniff`, the file's contents are read to determine its type too.
+pub fn find_match<'a>(
+    types: &'a [FileType],
+    path: &Path,
+    sniff: bool,
+) -> eyre::Result<Option<&'a FileType>> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    let sniffed = if sniff {
+        sniff_file(path).wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?
+    } else {
+        None
+    };
+    Ok(types
+        .iter()
+        .find(|kind| kind.matches(extension.as_deref(), sniffed)))
+}
+
+/// Returns the MIME type of files with the given extension, if known.
+fn mime_of_extension(extension: &str) -> Option<&'static str> {
+    EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, mime)| *mime)
+}
+
+/// Returns whether `mime` matches `pattern`, whose subtype may be `*`.
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime.split_once('/').is_some_and(|(other, _)| other == kind),
+        None => pattern == mime,
+    }
+}
+
+/// Determines the MIME type of a file from its first bytes. Files without a
+/// known signature are considered text if they're valid UTF-8 without NUL
+/// bytes.
+fn sniff_file(path: &Path) -> std::io::Result<Option<&'static str>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Ok(Some(mime));
+    }
+    // Containers whose signature isn't at the very start
+    if head.get(4..8) == Some(b"ftyp") {
+        return Ok(Some("video/mp4"));
+    }
+    if head.starts_with(b"RIFF") {
+        match head.get(8..12) {
+            Some(b"WEBP") => return Ok(Some("image/webp")),
+            Some(b"WAVE") => return Ok(Some("audio/wav")),
+            Some(b"AVI ") => return Ok(Some("video/x-msvideo")),
+            _ => {}
+        }
+    }
+    // A multi-byte character may be cut off at the end
+    let text = match std::str::from_utf8(&head) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+    Ok((text && !head.is_empty() && !head.contains(&0)).then_some("text/plain"))
+}
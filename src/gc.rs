@@ -0,0 +1,115 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Tidying the records leave keeps between runs, for `leave gc`.
+//!
+//! The journal of trashed entries loses the records of entries which left the
+//! trash some other way, snapshots are expired as described in
+//! [`snapshot::prune`], and temporary files left by interrupted rewrites of a
+//! journal are removed. Finally, the space taken by the
+//! [state directory](crate::state) is reported.
+
+use std::{fs, io::ErrorKind, path::Path, process::ExitCode, time::Duration};
+
+use eyre::Context;
+
+use crate::{i18n::tr, output::Output, size, snapshot, state, trash};
+
+/// Tidies the state directory, deleting snapshots taken more than
+/// `older_than` ago if given.
+pub fn run(older_than: Option<Duration>, out: &Output) -> eyre::Result<ExitCode> {
+    let dir = state::dir()?;
+    match fs::symlink_metadata(&dir) {
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            out.message(format_args!(
+                "{}",
+                tr!("{path} doesn't exist", path = dir.display())
+            ));
+            return Ok(ExitCode::SUCCESS);
+        }
+        result => {
+            result.wrap_err_with(|| tr!("Can't get metadata of {path}", path = dir.display()))?;
+        }
+    }
+
+    let forgotten = trash::compact()?;
+    if forgotten > 0 {
+        out.message(format_args!(
+            "{}",
+            tr!(
+                "Forgot {count} trashed entries which are no longer in the trash",
+                count = forgotten
+            )
+        ));
+    }
+    let mut had_failure = snapshot::prune(older_than, out)?;
+    had_failure |= remove_temporary_files(&dir, out);
+
+    let size = size::tree_size(&dir)?;
+    out.message(format_args!(
+        "{}",
+        tr!(
+            "{path} takes {size}",
+            path = dir.display(),
+            size = size::format_size(size)
+        )
+    ));
+    Ok(if had_failure {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Removes the temporary files which [`state::replace`] left in `dir` when
+/// it was interrupted. Returns whether removing any failed.
+fn remove_temporary_files(dir: &Path, out: &Output) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            crate::print_error(
+                out,
+                &eyre::Report::new(err)
+                    .wrap_err(tr!("Can't list contents of {dir}", dir = dir.display())),
+            );
+            return true;
+        }
+    };
+    let mut had_failure = false;
+    for entry in entries {
+        let result = entry
+            .wrap_err(tr!("Can't read directory entry"))
+            .and_then(|entry| {
+                let path = entry.path();
+                if path
+                    .extension()
+                    .is_some_and(|ext| ext == state::TEMPORARY_EXTENSION)
+                {
+                    fs::remove_file(&path)
+                        .wrap_err_with(|| tr!("Can't remove {path}", path = path.display()))?;
+                    out.message(format_args!("removed '{}'", path.display()));
+                }
+                Ok(())
+            });
+        if let Err(err) = result {
+            had_failure = true;
+            crate::print_error(out, &err);
+        }
+    }
+    had_failure
+}
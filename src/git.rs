@@ -0,0 +1,92 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Finding entries tracked or ignored by Git, for `--git` and
+//! `--ignored-only`.
+
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use eyre::{Context, bail};
+
+use crate::{filelist, i18n::tr};
+
+/// Returns the names of the entries of `dir` which are tracked by Git or
+/// contain tracked files, along with `.git` itself.
+pub fn tracked_entries(dir: &Path) -> eyre::Result<HashSet<OsString>> {
+    let mut names: HashSet<OsString> = ls_files(dir, &[])?
+        .iter()
+        .filter_map(|path| match path.components().next()? {
+            Component::Normal(name) => Some(name.to_owned()),
+            _ => None,
+        })
+        .collect();
+    if dir.join(".git").exists() {
+        names.insert(".git".into());
+    }
+    Ok(names)
+}
+
+/// Returns the names of the entries of `dir` which are entirely ignored by `.gitignore` files, `.git/info/exclude`, or the global
+/// excludes file. Directories which contain anything that isn't ignored are
+/// not included.
+pub fn ignored_entries(dir: &Path) -> eyre::Result<HashSet<OsString>> {
+    // With --directory, Git lists a directory itself only if all of its
+    // contents are ignored
+    Ok(ls_files(
+        dir,
+        &["--others", "--ignored", "--exclude-standard", "--directory"],
+    )?
+    .iter()
+    .filter_map(|path| {
+        let mut components = path.components();
+        match (components.next()?, components.next()) {
+            (Component::Normal(name), None) => Some(name.to_owned()),
+            _ => None,
+        }
+    })
+    .collect())
+}
+
+/// Runs `git ls-files` on `dir` with the given options and returns the
+/// listed paths, relative to it.
+fn ls_files(dir: &Path, options: &[&str]) -> eyre::Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["ls-files", "-z"])
+        .args(options)
+        .args(["--", "."])
+        .output()
+        .wrap_err(tr!("Can't run git"))?;
+    if !output.status.success() {
+        bail!(tr!(
+            "git ls-files failed: {error}",
+            error = String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output
+        .stdout
+        .split(|&byte| byte == b'\0')
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(filelist::bytes_to_os_string(path)))
+        .collect())
+}
@@ -0,0 +1,256 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Shell-style glob patterns for keep arguments, for `--glob`.
+//!
+//! Supported syntax is `*`, `?`, `[abc]`, `[a-z]`, `[!abc]`, and `\` to
+//! escape the next character. Like in the shell, wildcards don't match a
+//! leading `.`, so hidden files must be matched explicitly.
+
+use std::fmt;
+
+use eyre::bail;
+
+use crate::i18n::tr;
+
+/// One element of a pattern.
+#[derive(Debug, Clone)]
+enum Token {
+    Literal(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// `[...]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// A compiled glob pattern, matched against file names.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+    /// The pattern as it was given.
+    source: String,
+}
+
+impl Pattern {
+    /// Compiles the given pattern.
+    pub fn parse(pattern: &str) -> eyre::Result<Self> {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' => Token::AnyString,
+                '?' => Token::AnyChar,
+                '\\' => match chars.next() {
+                    Some(escaped) => Token::Literal(escaped),
+                    None => bail!(tr!(
+                        "Glob pattern {pattern} ends with an unescaped backslash",
+                        pattern = format!("{pattern:?}")
+                    )),
+                },
+                '[' => parse_class(&mut chars).ok_or_else(|| {
+                    eyre::eyre!(tr!(
+                        "Glob pattern {pattern} has an unclosed [",
+                        pattern = format!("{pattern:?}")
+                    ))
+                })?,
+                c => Token::Literal(c),
+            });
+        }
+        Ok(Self {
+            tokens,
+            source: pattern.to_owned(),
+        })
+    }
+
+    /// Returns whether the given file name matches the pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        // Wildcards never match a leading dot
+        if name.first() == Some(&'.') && !matches!(self.tokens.first(), Some(Token::Literal('.'))) {
+            return false;
+        }
+        matches_at(&self.tokens, &name)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Parses a character class, after its opening `[`. Returns `None` if it's
+/// not closed.
+fn parse_class(chars: &mut std::str::Chars<'_>) -> Option<Token> {
+    let mut negated = false;
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = chars.next()?;
+        match c {
+            '!' | '^' if first && !negated => {
+                negated = true;
+                continue;
+            }
+            // A ] right after the opening [ is a literal
+            ']' if !first => return Some(Token::Class { negated, ranges }),
+            _ => (),
+        }
+        first = false;
+        let start = if c == '\\' { chars.next()? } else { c };
+        let mut lookahead = chars.clone();
+        if lookahead.next() == Some('-')
+            && let Some(end) = lookahead.next()
+            && end != ']'
+        {
+            *chars = lookahead;
+            ranges.push((start, end));
+        } else {
+            ranges.push((start, start));
+        }
+    }
+}
+
+/// Matches the given tokens against the whole of `name`.
+///
+/// Only the most recent `*` is ever backtracked to: if the rest of the
+/// pattern fails to match after it, the `*` takes one more character and the
+/// rest is tried again. Earlier `*`s never need to take more, since whatever
+/// the later one could match, it can also match after them, so this takes
+/// time proportional to the product of the lengths at worst.
+fn matches_at(tokens: &[Token], name: &[char]) -> bool {
+    let mut t = 0;
+    let mut n = 0;
+    // The token after the last `*` seen, and the position in the name it was
+    // last tried at
+    let mut backtrack = None;
+    while n < name.len() {
+        match tokens.get(t) {
+            Some(Token::AnyString) => {
+                t += 1;
+                backtrack = Some((t, n));
+                continue;
+            }
+            Some(token) if token.matches(name[n]) => {
+                t += 1;
+                n += 1;
+                continue;
+            }
+            _ => (),
+        }
+        let Some((star_t, star_n)) = backtrack else {
+            return false;
+        };
+        t = star_t;
+        n = star_n + 1;
+        backtrack = Some((star_t, n));
+    }
+    tokens[t..]
+        .iter()
+        .all(|token| matches!(token, Token::AnyString))
+}
+
+impl Token {
+    /// Returns whether this token, which isn't `*`, matches the character.
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::AnyString | Token::AnyChar => true,
+            Token::Literal(literal) => *literal == c,
+            Token::Class { negated, ranges } => {
+                ranges
+                    .iter()
+                    .any(|&(start, end)| (start..=end).contains(&c))
+                    != *negated
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        Pattern::parse(pattern).unwrap().matches(name)
+    }
+
+    #[test]
+    fn literals() {
+        assert!(matches("foo.txt", "foo.txt"));
+        assert!(!matches("foo.txt", "foo.txt2"));
+        assert!(!matches("foo.txt", "foo.tx"));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.txt", "notes.txt"));
+        assert!(matches("*.txt", "a.b.txt"));
+        assert!(!matches("*.txt", "notes.txt.bak"));
+        assert!(matches("*", "anything"));
+        assert!(matches("**", ""));
+        assert!(matches("a*b*c", "aXbYc"));
+        assert!(matches("a*b*c", "abbbc"));
+        assert!(!matches("a*b*c", "acb"));
+        assert!(matches("?.rs", "a.rs"));
+        assert!(!matches("?.rs", "ab.rs"));
+        assert!(matches("*?", "a"));
+        assert!(!matches("*?", ""));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("[abc].txt", "b.txt"));
+        assert!(!matches("[abc].txt", "d.txt"));
+        assert!(matches("file[0-9]", "file7"));
+        assert!(matches("file[!0-9]", "filex"));
+        assert!(!matches("file[^0-9]", "file7"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(Pattern::parse("a\\").is_err());
+        assert!(Pattern::parse("[ab").is_err());
+    }
+
+    #[test]
+    fn leading_dot() {
+        assert!(!matches("*", ".hidden"));
+        assert!(!matches("?hidden", ".hidden"));
+        assert!(matches(".*", ".hidden"));
+    }
+
+    #[test]
+    fn many_stars() {
+        let name = "a".repeat(60);
+        assert!(!matches("*a*a*a*a*a*a*a*a*b", &name));
+        assert!(matches("*a*a*a*a*a*a*a*a*a", &name));
+        let pattern = format!("{}c", "*a".repeat(50));
+        assert!(!matches(&pattern, &"ab".repeat(5000)));
+    }
+}
@@ -0,0 +1,76 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Refusing to run in directories which should never be emptied, unless
+//! `--no-preserve-root` is given.
+//!
+//! The root directory and the user's home directory are always protected.
+//! More directories can be protected with `--protect` or by listing them in
+//! the `LEAVE_PROTECT` environment variable, separated like `PATH`.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context, bail};
+
+use crate::i18n::tr;
+
+/// Environment variable listing more protected directories.
+const ENV_VAR: &str = "LEAVE_PROTECT";
+
+/// Fails if `dir`, the directory being cleaned, is protected. `extra` holds
+/// directories protected with `--protect`.
+pub fn check(dir: &Path, extra: &[PathBuf]) -> eyre::Result<()> {
+    let cwd = std::fs::canonicalize(dir).wrap_err(tr!("Can't resolve current directory"))?;
+    if cwd.parent().is_none() {
+        bail!(tr!(
+            "Refusing to operate in the root directory. Use --no-preserve-root to override."
+        ));
+    }
+
+    let home = ["HOME", "USERPROFILE"]
+        .into_iter()
+        .find_map(env::var_os)
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from);
+    let from_env = env::var_os(ENV_VAR)
+        .map(|value| env::split_paths(&value).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let protected = home
+        .iter()
+        .chain(&from_env)
+        .chain(extra)
+        .filter(|path| !path.as_os_str().is_empty());
+    for path in protected {
+        if is_same_dir(path, &cwd) {
+            bail!(tr!(
+                "Refusing to operate in {path}, which is protected. Use --no-preserve-root to override.",
+                path = cwd.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether `path` refers to `dir`, which must be canonical.
+/// Protected directories which don't exist never match.
+fn is_same_dir(path: &Path, dir: &Path) -> bool {
+    std::fs::canonicalize(path).is_ok_and(|path| path == dir)
+}
@@ -0,0 +1,89 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Running user-supplied commands around removals, for `--pre-delete-hook` and
+//! `--post-run-hook`.
+//!
+//! Hook commands are run by the shell (`sh` on Unix and `cmd` on Windows), so
+//! they can use pipes, redirections, and so on.
+
+use std::{ffi::OsStr, path::Path, process::Command};
+
+use eyre::{Context, bail};
+
+use crate::i18n::tr;
+
+/// Environment variable holding the path of the entry about to be removed.
+const PATH_ENV_VAR: &str = "LEAVE_PATH";
+
+/// Runs the pre-delete hook for the given entry and returns whether it
+/// allowed the removal, i.e. exited successfully.
+///
+/// On Unix the path is also passed as the first positional parameter, so the
+/// hook can refer to it as `"$1"`.
+pub fn allows_removal(command: &str, path: &Path) -> eyre::Result<bool> {
+    let status = shell(command, Some(path.as_os_str()))
+        .env(PATH_ENV_VAR, path)
+        .status()
+        .wrap_err_with(|| {
+            tr!(
+                "Can't run pre-delete hook {command}",
+                command = format!("{command:?}")
+            )
+        })?;
+    Ok(status.success())
+}
+
+/// Runs the post-run hook with the given variables added to its environment.
+pub fn post_run(command: &str, vars: &[(&str, String)]) -> eyre::Result<()> {
+    let mut shell = shell(command, None);
+    for (name, value) in vars {
+        shell.env(name, value);
+    }
+    let status = shell.status().wrap_err_with(|| {
+        tr!(
+            "Can't run post-run hook {command}",
+            command = format!("{command:?}")
+        )
+    })?;
+    if !status.success() {
+        bail!(tr!(
+            "Post-run hook {command} failed with {status}",
+            command = format!("{command:?}"),
+            status = status
+        ));
+    }
+    Ok(())
+}
+
+/// Returns a command which runs the given command line using the shell.
+#[cfg(unix)]
+fn shell(command: &str, arg: Option<&OsStr>) -> Command {
+    let mut shell = Command::new("sh");
+    // The name after the command becomes $0, and the next argument $1
+    shell.arg("-c").arg(command).arg("sh").args(arg);
+    shell
+}
+
+/// Returns a command which runs the given command line using the shell.
+#[cfg(not(unix))]
+fn shell(command: &str, _arg: Option<&OsStr>) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
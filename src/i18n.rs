@@ -0,0 +1,219 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Translation of messages, in the style of gettext.
+//!
+//! Messages are written in English, and the English text is looked up in the
+//! catalog for the user's language, which is chosen from `LC_ALL`,
+//! `LC_MESSAGES`, or `LANG` like other programs do. Messages without a
+//! translation are shown in English.
+//!
+//! Catalogs are PO files in `locale/`, which are built into the binary. To
+//! add a language, translate `locale/de.po` into a new file and add it to
+//! [`CATALOGS`]. Placeholders such as `{path}` are kept as they are in the
+//! translation, and filled in afterwards.
+//!
+//! Only messages meant for people are translated. Output meant for scripts,
+//! such as the lines printed for each entry and JSON events, stays the same.
+
+use std::{collections::HashMap, env, fmt, sync::OnceLock};
+
+/// The built-in catalogs, by language code.
+const CATALOGS: &[(&str, &str)] = &[("de", include_str!("../locale/de.po"))];
+
+/// The translations for the user's language, by English message.
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Translates a message, then fills in its `{name}` placeholders with the
+/// given values, e.g. `tr!("Can't remove {path}", path = path.display())`.
+macro_rules! tr {
+    ($msgid:expr) => {
+        $crate::i18n::translate($msgid)
+    };
+    ($msgid:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::fill(
+            $crate::i18n::translate($msgid),
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+],
+        )
+    };
+}
+pub(crate) use tr;
+
+/// Returns the translation of the given message into the user's language, or
+/// the message itself if there is none.
+pub fn translate(msgid: &'static str) -> &'static str {
+    CATALOG
+        .get_or_init(load)
+        .get(msgid)
+        .map_or(msgid, String::as_str)
+}
+
+/// Replaces each `{name}` in the given message with the value of the same
+/// name. `{{` and `}}` stand for literal braces, like in `format!`.
+pub fn fill(message: &str, values: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(brace) = rest[start..].chars().next()
+            && after.starts_with(brace)
+        {
+            out.push(brace);
+            rest = &after[1..];
+            continue;
+        }
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            let (_, value) = values.iter().find(|(key, _)| *key == name)?;
+            Some((value, end))
+        });
+        if let Some((value, end)) = value {
+            out.push_str(&value.to_string());
+            rest = &after[end + 1..];
+        } else {
+            // Unknown placeholders are left as they are
+            out.push_str(&rest[start..=start]);
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Loads the catalog for the user's language, or an empty catalog if there is
+/// none.
+fn load() -> HashMap<String, String> {
+    let Some(locale) = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .find(|value| !value.is_empty())
+    else {
+        return HashMap::new();
+    };
+    // Locales look like language_TERRITORY.CODESET@modifier, e.g.
+    // de_AT.UTF-8. A catalog for the territory is preferred, if there is one.
+    let locale = locale.to_string_lossy();
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = locale.split('_').next().unwrap_or_default();
+    CATALOGS
+        .iter()
+        .find(|(code, _)| *code == locale)
+        .or_else(|| CATALOGS.iter().find(|(code, _)| *code == language))
+        .map(|(_, catalog)| parse(catalog))
+        .unwrap_or_default()
+}
+
+/// Parses a PO file into its translations. Only single messages are
+/// supported, without plural forms or contexts. Untranslated messages and the
+/// header are skipped.
+fn parse(catalog: &str) -> HashMap<String, String> {
+    let mut translations = HashMap::new();
+    let (mut msgid, mut msgstr) = (String::new(), String::new());
+    // Whether continuation lines are appended to the msgstr or the msgid
+    let mut in_msgstr = false;
+    for line in catalog.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("msgid ") {
+            if !msgid.is_empty() && !msgstr.is_empty() {
+                translations.insert(std::mem::take(&mut msgid), std::mem::take(&mut msgstr));
+            }
+            msgid = unquote(rest);
+            msgstr.clear();
+            in_msgstr = false;
+        } else if let Some(rest) = line.strip_prefix("msgstr ") {
+            msgstr = unquote(rest);
+            in_msgstr = true;
+        } else if line.starts_with('"') {
+            let field = if in_msgstr { &mut msgstr } else { &mut msgid };
+            field.push_str(&unquote(line));
+        }
+    }
+    if !msgid.is_empty() && !msgstr.is_empty() {
+        translations.insert(msgid, msgstr);
+    }
+    translations
+}
+
+/// Returns the contents of a quoted PO string, with escapes replaced.
+fn unquote(s: &str) -> String {
+    let s = s.strip_prefix('"').unwrap_or(s);
+    let s = s.strip_suffix('"').unwrap_or(s);
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => (),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the placeholders of a message, sorted.
+    fn placeholders(message: &str) -> Vec<String> {
+        let mut names: Vec<_> = message
+            .replace("{{", "")
+            .replace("}}", "")
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name.to_owned()))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn translations_keep_placeholders() {
+        for (code, catalog) in CATALOGS {
+            for (msgid, msgstr) in parse(catalog) {
+                assert_eq!(
+                    placeholders(&msgid),
+                    placeholders(&msgstr),
+                    "{code}: translation of {msgid:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fill_placeholders() {
+        let values: &[(&str, &dyn fmt::Display)] = &[("path", &"a/b"), ("count", &3)];
+        assert_eq!(fill("{count} removed: {path}", values), "3 removed: a/b");
+        assert_eq!(fill("{{path}} {unknown}", values), "{path} {unknown}");
+    }
+
+    #[test]
+    fn parse_wrapped_messages() {
+        let catalog = "msgid \"\"\nmsgstr \"\"\n\"Language: de\\n\"\n\n\
+                       msgid \"a \\\"b\\\"\"\nmsgstr \"\"\n\"c \"\n\"d\"\n\n\
+                       msgid \"untranslated\"\nmsgstr \"\"\n";
+        let translations = parse(catalog);
+        assert_eq!(translations.len(), 1);
+        assert_eq!(translations["a \"b\""], "c d");
+    }
+}
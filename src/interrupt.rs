@@ -0,0 +1,53 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Graceful interruption by Ctrl-C (`SIGINT`), `SIGTERM`, or `SIGHUP`.
+//!
+//! The first signal stops the run: no more entries are started, and a
+//! directory being removed with `-r` is left partly removed at the next
+//! entry inside it. The run then reports what was and wasn't removed and
+//! exits with [`EXIT_CODE`]. A second signal exits immediately.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// Exit code of an interrupted run, as a shell reports for a process killed
+/// by `SIGINT`.
+pub const EXIT_CODE: u8 = 130;
+
+/// Whether a signal has been received. This is global so that the recursive
+/// walk can check it.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Starts handling interrupting signals for the rest of the run.
+pub fn install() -> eyre::Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            std::process::exit(EXIT_CODE.into());
+        }
+    })
+    .wrap_err(tr!("Can't handle interrupts"))
+}
+
+/// Returns whether the run has been interrupted.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,135 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Keep files: lists of entries to always keep, for `.leavekeep` and
+//! `--keep-file`.
+//!
+//! Each line names one entry of the target directory. Blank lines and lines
+//! starting with `#` are ignored.
+//!
+//! A line `include FILE` reads the entries listed in another keep file as
+//! well, so a shared policy can be combined with additions for one
+//! directory. `FILE` is relative to the directory of the keep file including
+//! it, unless it's absolute or starts with `~`, the home directory. Included
+//! files may include others, but not themselves. An entry whose name starts
+//! with `include ` can be listed as `./include ...`.
+//!
+//! Lines may refer to environment variables as `${NAME}` and contain template
+//! variables such as `{hostname}`, which are expanded by
+//! [`vars::expand_env`] and [`vars::expand_str`] before the line is read.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context, OptionExt, bail};
+
+use crate::{i18n::tr, vars};
+
+/// Name of the keep file which is read automatically from the target
+/// directory.
+pub const DEFAULT_NAME: &str = ".leavekeep";
+
+/// The entries listed in a keep file, including those of the files it
+/// includes.
+pub struct KeepFile {
+    /// Names of the entries to keep.
+    pub names: Vec<String>,
+    /// The keep file and every file it includes.
+    pub files: Vec<PathBuf>,
+}
+
+/// Reads the entry names listed in the given keep file and the files it
+/// includes.
+pub fn read(path: &Path) -> eyre::Result<KeepFile> {
+    let mut keep_file = KeepFile {
+        names: Vec::new(),
+        files: Vec::new(),
+    };
+    read_into(path, &mut Vec::new(), &mut keep_file)?;
+    Ok(keep_file)
+}
+
+/// Reads the keep file at `path` into `keep_file`. `including` holds the
+/// canonical paths of the files whose includes led to it, to detect cycles.
+fn read_into(
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+    keep_file: &mut KeepFile,
+) -> eyre::Result<()> {
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?;
+    let canonical =
+        fs::canonicalize(path).wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?;
+    if let Some(start) = including.iter().position(|file| *file == canonical) {
+        let cycle: Vec<_> = including[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|file| file.display().to_string())
+            .collect();
+        bail!(tr!(
+            "Keep files include each other: {cycle}",
+            cycle = cycle.join(" -> ")
+        ));
+    }
+    including.push(canonical);
+    keep_file.files.push(path.to_owned());
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let location = format!("{}:{}", path.display(), i + 1);
+        let line = vars::expand_env(line)
+            .and_then(|line| vars::expand_str(&line))
+            .wrap_err(location.clone())?;
+        let Some(target) = line
+            .strip_prefix("include")
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+        else {
+            keep_file.names.push(line);
+            continue;
+        };
+        let included = resolve(path, target.trim())
+            .wrap_err_with(|| tr!("Invalid include at {location}", location = location))?;
+        if !included.try_exists().unwrap_or(true) {
+            bail!(tr!(
+                "{path}, included at {location}, doesn't exist",
+                path = included.display(),
+                location = location
+            ));
+        }
+        read_into(&included, including, keep_file)?;
+    }
+    including.pop();
+    Ok(())
+}
+
+/// Returns the path of the file `target` included by the keep file at
+/// `path`.
+fn resolve(path: &Path, target: &str) -> eyre::Result<PathBuf> {
+    if target == "~" || target.starts_with("~/") {
+        let home = env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .ok_or_eyre(tr!("HOME is not set"))?;
+        return Ok(PathBuf::from(home).join(target[1..].trim_start_matches('/')));
+    }
+    let parent = path.parent().unwrap_or(Path::new(""));
+    Ok(parent.join(target))
+}
@@ -0,0 +1,4564 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Leave: remove everything except the given files.
+//!
+//! Besides the `leave` command-line interface, which is run with [`run`],
+//! this crate offers a library interface for embedding leave's behavior in
+//! other tools. Options are set using [`LeaveOptions`], [`LeaveOptions::plan`]
+//! decides what to remove from a directory, and [`execute`] carries out the
+//! resulting [`Plan`]:
+//!
+//! ```no_run
+//! let plan = leave::LeaveOptions::new()
+//!     .keep("Cargo.toml")
+//!     .keep("src")
+//!     .recursive(true)
+//!     .plan("/path/to/project".as_ref())?;
+//! for path in plan.to_remove() {
+//!     println!("removing {}", path.display());
+//! }
+//! leave::execute(plan)?;
+//! # Ok::<(), eyre::Report>(())
+//! ```
+
+#![warn(clippy::pedantic)]
+#![deny(unsafe_code)]
+
+use std::{
+    cell::LazyCell,
+    collections::{HashMap, HashSet, VecDeque},
+    ffi::{OsStr, OsString},
+    fs,
+    io::{IsTerminal, Write},
+    num::{NonZeroU32, NonZeroUsize},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        Arc, Mutex, OnceLock, PoisonError,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, parser::ValueSource};
+use eyre::{Context, bail, eyre};
+
+use crate::{
+    audit::AuditLog,
+    control::Control,
+    datetime::Date,
+    dirfd::DirEntry,
+    filetype::FileType,
+    i18n::tr,
+    log::LogFormat,
+    output::{ColorChoice, Output, OutputFormat},
+    owner::Owner,
+    prompt::Answer,
+    quarantine::Quarantine,
+    regex::Regex,
+    size::Usage,
+    sort::SortOrder,
+    template::{EntryInfo, Template},
+    theme::{Marker, Theme},
+    timings::{Timings, Worker},
+    xattr::Xattr,
+};
+
+mod ads;
+mod argfile;
+mod audit;
+mod case;
+mod config;
+mod control;
+mod datetime;
+mod diff;
+mod dirfd;
+mod exif;
+mod filelist;
+mod filetype;
+mod gc;
+mod git;
+mod glob;
+mod guard;
+mod hooks;
+mod i18n;
+mod interrupt;
+mod keepfile;
+mod log;
+mod manifest;
+mod manpage;
+mod mounts;
+mod output;
+mod owner;
+mod packages;
+mod permissions;
+mod platform;
+mod preflight;
+mod preset;
+mod process;
+mod progress;
+mod prompt;
+mod quarantine;
+mod regex;
+mod remote;
+mod retry;
+mod rules;
+mod sanity;
+mod shell;
+mod size;
+mod snapshot;
+mod sort;
+mod state;
+mod sync;
+mod template;
+mod theme;
+mod throttle;
+mod timeout;
+mod timings;
+mod trash;
+mod tui;
+mod vars;
+mod watch;
+mod xattr;
+
+#[derive(Debug, Parser)]
+#[allow(clippy::struct_excessive_bools)]
+#[command(
+    about,
+    author,
+    version,
+    args_conflicts_with_subcommands = true,
+    after_help = "Arguments of the form @FILE are replaced by the lines of FILE.\n\n\
+                  Exit status is 0 on success, 1 if some entries couldn't be removed or another \
+                  error occurred, 2 if the arguments are invalid or likely a mistake, 3 if a \
+                  safety check refused the run, and 130 if the run was interrupted."
+)]
+struct CliOptions {
+    /// Files to leave present. May contain {date}, {date:FORMAT}, and
+    /// {hostname}
+    files: Vec<PathBuf>,
+
+    /// Treat files to leave as glob patterns, e.g. '*.rs'
+    #[arg(long, short)]
+    glob: bool,
+
+    /// Treat files to leave as regular expressions matched against entry
+    /// names, e.g. '^report-\d{4}\.pdf$'
+    #[arg(long, conflicts_with = "glob")]
+    regex: bool,
+
+    /// Match keep arguments, and the entries listed in keep files and the
+    /// manifest, regardless of case, e.g. 'readme.md' keeps README.md
+    #[arg(long)]
+    ignore_case: bool,
+
+    /// Remove entries matching the glob <PATTERN> even if they're kept by
+    /// arguments, keep files, Git, or --regex. May be given multiple times
+    #[arg(long, value_name = "PATTERN", value_parser = glob::Pattern::parse)]
+    exclude: Vec<glob::Pattern>,
+
+    /// Also remove hidden entries, whose names start with a dot. The default
+    /// is to keep them, unless the `LEAVE_HIDDEN` environment variable is set
+    /// to 'remove'
+    #[arg(long, overrides_with = "keep_hidden")]
+    hidden: bool,
+
+    /// Keep hidden entries, whose names start with a dot
+    #[arg(long, overrides_with = "hidden")]
+    keep_hidden: bool,
+
+    /// Don't read default options from the configuration file. A single
+    /// option it turns on can be turned off with --no-<OPTION> instead, e.g.
+    /// --no-force
+    #[arg(long)]
+    no_config: bool,
+
+    /// Turn off -r/--recursive if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "recursive")]
+    no_recursive: bool,
+
+    /// Turn off -d/--dirs if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "dirs")]
+    no_dirs: bool,
+
+    /// Turn off -f/--force if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "force")]
+    no_force: bool,
+
+    /// Turn off -v/--verbose if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "verbose")]
+    no_verbose: bool,
+
+    /// Turn off -q/--quiet if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "quiet")]
+    no_quiet: bool,
+
+    /// Turn off --summary if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "summary")]
+    no_summary: bool,
+
+    /// Turn off --report-size if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "report_size")]
+    no_report_size: bool,
+
+    /// Turn off --sync if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "sync")]
+    no_sync: bool,
+
+    /// Turn off --git if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "git")]
+    no_git: bool,
+
+    /// Turn off --ignore-case if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "ignore_case")]
+    no_ignore_case: bool,
+
+    /// Turn off --dialog if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "dialog")]
+    no_dialog: bool,
+
+    /// Turn off -t/--trash if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "trash")]
+    no_trash: bool,
+
+    /// Turn off --quarantine if the configuration file turns it on
+    #[arg(long, hide = true, overrides_with = "quarantine")]
+    no_quarantine: bool,
+
+    /// Glob patterns of entries to always keep, from the configuration file
+    #[arg(skip)]
+    config_keep: Vec<glob::Pattern>,
+
+    /// Patterns given with --glob, which also keep entries created after they
+    /// were expanded, e.g. with --watch
+    #[arg(skip)]
+    keep_globs: Vec<glob::Pattern>,
+
+    /// Compiled patterns given with --regex
+    #[arg(skip)]
+    keep_regexes: Vec<Regex>,
+
+    /// Read more files to leave present from <FILE>, one per line, or from
+    /// standard input if <FILE> is -
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
+    /// Separate paths read with --files-from by NUL instead of newline
+    #[arg(long, short = '0', requires = "files_from")]
+    null: bool,
+
+    /// Also keep the entries listed in <FILE>, one per line. A line `include
+    /// <OTHER>` also reads <OTHER>, relative to <FILE> unless it's absolute or
+    /// starts with `~`. A .leavekeep file in the target directory is always
+    /// read
+    #[arg(long, value_name = "FILE")]
+    keep_file: Vec<PathBuf>,
+
+    /// Keep the entries matching the rules in <FILE>, which uses .gitignore
+    /// syntax. Rules starting with `!` remove matching entries after all, and
+    /// later rules override earlier ones
+    #[arg(long, value_name = "FILE")]
+    rules: Option<PathBuf>,
+
+    /// Rules read from the file given with `--rules`
+    #[arg(skip)]
+    keep_rules: Option<rules::Rules>,
+
+    /// Keep the entries listed in <MANIFEST>, written by `leave snapshot`,
+    /// resetting the directory to the state it recorded
+    #[arg(long, value_name = "MANIFEST")]
+    manifest: Option<PathBuf>,
+
+    /// Absolute paths of the entries listed in the manifest
+    #[arg(skip)]
+    in_manifest: HashSet<PathBuf>,
+
+    /// Absolute paths of the directories whose contents are removed with
+    /// `--empty-kept-dirs`
+    #[arg(skip)]
+    emptied_dirs: HashSet<PathBuf>,
+
+    /// Symbolic links named as arguments, with the paths of their targets
+    /// relative to the current directory, for warning about links which would
+    /// be left dangling
+    #[arg(skip)]
+    link_targets: Vec<(PathBuf, PathBuf)>,
+
+    /// Absolute path of the current directory, once the keep list is prepared
+    #[arg(skip)]
+    cwd: PathBuf,
+
+    /// The directory being cleaned, which entries are listed from. This is
+    /// `.` on the command line, which changes into the directory first, and
+    /// its absolute path for the library, which doesn't
+    #[arg(skip = PathBuf::from("."))]
+    root: PathBuf,
+
+    /// The open directory being cleaned, once it's been opened
+    #[arg(skip)]
+    root_dir: OnceLock<Arc<dirfd::Dir>>,
+
+    /// How messages are printed during the run
+    #[arg(skip)]
+    out: Output,
+
+    /// The directories changed by the run, for `--sync`
+    #[arg(skip)]
+    changes: sync::Changes,
+
+    /// The log opened with `--log`
+    #[arg(skip)]
+    logger: log::Logger,
+
+    /// The entries to remove as measured for `--confirm`, kept so that they
+    /// needn't be measured again while removing them
+    #[arg(skip)]
+    measured: Mutex<Option<HashMap<PathBuf, Usage>>>,
+
+    /// Names of the keep arguments, if they're all entries of the current
+    /// directory, for matching entries against them by name alone
+    #[arg(skip)]
+    argument_names: Option<HashSet<PathBuf>>,
+
+    /// Absolute paths of the entries listed in keep files
+    #[arg(skip)]
+    kept_by_file: HashSet<PathBuf>,
+
+    /// Keep files tracked by Git, and directories containing them
+    #[arg(long)]
+    git: bool,
+
+    /// Absolute paths of the entries tracked by Git
+    #[arg(skip)]
+    tracked_by_git: HashSet<PathBuf>,
+
+    /// Only delete entries ignored by Git, e.g. build outputs
+    #[arg(long)]
+    ignored_only: bool,
+
+    /// Absolute paths of the entries ignored by Git
+    #[arg(skip)]
+    ignored_by_git: HashSet<PathBuf>,
+
+    /// Run as if started in <DIR>
+    #[arg(long, short = 'C', value_name = "DIR", global = true)]
+    chdir: Option<PathBuf>,
+
+    /// Clean <DIR> instead of the current directory. Unlike with -C, paths
+    /// given to other options, such as --log and --files-from, are still
+    /// relative to the current directory
+    #[arg(
+        long = "in",
+        value_name = "DIR",
+        conflicts_with_all = ["chdir", "remote"]
+    )]
+    in_dir: Option<PathBuf>,
+
+    /// Refuse to run in <DIR>, in addition to / and the home directory
+    #[arg(long, value_name = "DIR")]
+    protect: Vec<PathBuf>,
+
+    /// Allow running in / and other protected directories
+    #[arg(long)]
+    no_preserve_root: bool,
+
+    /// Recursively delete directories and their contents
+    #[arg(long, short)]
+    recursive: bool,
+
+    /// With -r, only remove entries up to <N> levels deep, counting entries in
+    /// the current directory as level 1. Directories with deeper entries are
+    /// left, along with their ancestors
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "recursive",
+        conflicts_with_all = ["trash", "quarantine"]
+    )]
+    max_depth: Option<NonZeroUsize>,
+
+    /// With -r, also remove directories kept for containing kept entries if
+    /// they end up empty, unless they're named as arguments
+    #[arg(long, requires = "recursive")]
+    prune_empty: bool,
+
+    /// Remove the contents of directories named as arguments without a
+    /// trailing slash, keeping only the directories themselves. Name a
+    /// directory as <DIR>/ to keep its contents too
+    #[arg(long)]
+    empty_kept_dirs: bool,
+
+    /// With -r, don't remove anything on a different file system than the
+    /// current directory, such as mounted volumes. This is the default
+    #[arg(long, overrides_with = "cross_file_systems")]
+    one_file_system: bool,
+
+    /// With -r, also remove the contents of other file systems mounted inside
+    /// the directories being removed
+    #[arg(long, overrides_with = "one_file_system")]
+    cross_file_systems: bool,
+
+    /// With -r, also remove directories which are or contain mount points on
+    /// the same file system, such as bind mounts. Without it, they're refused
+    /// before anything inside is removed
+    #[arg(long)]
+    allow_mounts: bool,
+
+    /// Treat symbolic links to directories as directories, so removing them
+    /// needs -r or -d. With -r, the contents of the directory a link points
+    /// to are removed along with the link
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Keep symbolic links which point to kept entries
+    #[arg(long)]
+    keep_symlink_targets: bool,
+
+    /// Keep the entries which symbolic links named as arguments point to, if
+    /// they're in the current directory. Without it, a warning is printed if
+    /// they would be removed
+    #[arg(long)]
+    keep_link_targets: bool,
+
+    /// Keep files which are hard links to kept files (Unix only)
+    #[arg(long)]
+    keep_hardlinks: bool,
+
+    /// Delete empty directories
+    #[arg(long, short)]
+    dirs: bool,
+
+    /// Continue even if keep arguments don't exist, and remove
+    /// write-protected entries
+    #[arg(long, short)]
+    force: bool,
+
+    /// With -f, continue even if missing keep arguments look like mistakes,
+    /// e.g. misspellings
+    #[arg(long)]
+    no_sanity_check: bool,
+
+    /// Print what would be removed without removing anything
+    #[arg(long, short = 'n')]
+    dry_run: bool,
+
+    /// Print the paths of the entries that would be removed, one per line,
+    /// without removing anything
+    #[arg(long, conflicts_with_all = ["format", "confirm", "interactive", "pick", "watch"])]
+    list: bool,
+
+    /// Like --list, but end each path with NUL instead of a newline, for use
+    /// with xargs -0
+    #[arg(long, conflicts_with_all = ["format", "confirm", "interactive", "pick", "watch"])]
+    print0: bool,
+
+    /// Print how many files, directories, and symbolic links would be removed
+    /// and kept, and their total size, without removing anything
+    #[arg(
+        long,
+        conflicts_with_all = ["list", "print0", "format", "confirm", "interactive", "pick", "watch"]
+    )]
+    stat: bool,
+
+    /// Print each entry as it's removed, including the contents of
+    /// directories removed with -r
+    #[arg(long, short)]
+    verbose: bool,
+
+    /// Don't print warnings or other non-fatal messages, only errors
+    #[arg(long, short, visible_alias = "no-warnings")]
+    quiet: bool,
+
+    /// When to color messages. The colors can be changed with a theme in the
+    /// configuration file
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t)]
+    color: ColorChoice,
+
+    /// Colors from the configuration file
+    #[arg(skip)]
+    theme: Theme,
+
+    /// Show a progress line while removing. This is the default when
+    /// standard error is a terminal, unless -v or machine-readable output is
+    /// used
+    #[arg(long, overrides_with = "no_progress")]
+    progress: bool,
+
+    /// Don't show a progress line
+    #[arg(long, overrides_with = "progress")]
+    no_progress: bool,
+
+    /// Remove at most <RATE> entries per second, 100 by default, and on Linux
+    /// use the idle I/O priority, so that other workloads aren't slowed down
+    #[arg(long, value_name = "RATE", num_args = 0..=1, require_equals = true, default_missing_value = "100")]
+    nice_io: Option<NonZeroU32>,
+
+    /// Remove up to <N> entries at once
+    #[arg(long, short, value_name = "N", default_value_t = NonZeroUsize::MIN, conflicts_with = "interactive")]
+    jobs: NonZeroUsize,
+
+    /// Ask before removing each entry
+    #[arg(long, short)]
+    interactive: bool,
+
+    /// List what would be removed and kept, then ask once before removing
+    /// anything
+    #[arg(long, conflicts_with = "dry_run")]
+    confirm: bool,
+
+    /// With --confirm, ask in a graphical dialog when standard input isn't a
+    /// terminal, e.g. when started from a file manager. Uses zenity or
+    /// kdialog, or osascript on macOS
+    #[arg(long, requires = "confirm")]
+    dialog: bool,
+
+    /// Show the entries in a terminal interface to pick which of those
+    /// selected for removal to keep, then remove the rest
+    #[arg(long, conflicts_with_all = ["confirm", "interactive", "watch"])]
+    pick: bool,
+
+    /// Keep running, removing new entries as they appear, until interrupted
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "dry_run",
+            "confirm",
+            "snapshot",
+            "keep_recent",
+            "keep_largest",
+            "control_socket"
+        ]
+    )]
+    watch: bool,
+
+    /// With --watch, wait until nothing has changed for <DURATION> before
+    /// removing new entries
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "1s",
+        value_parser = datetime::parse_duration,
+        requires = "watch"
+    )]
+    debounce: Duration,
+
+    /// Move entries into a .leave-trash-<TIMESTAMP> directory instead of deleting them
+    #[arg(long)]
+    quarantine: bool,
+
+    /// Like --quarantine, but also record the moved entries so the run can be
+    /// reverted with `leave undo`
+    #[arg(long, conflicts_with = "trash")]
+    journal: bool,
+
+    /// Move entries to the trash instead of deleting them
+    #[arg(long, short, conflicts_with = "quarantine")]
+    trash: bool,
+
+    /// Keep entries which belong to an installed package (Linux only)
+    #[arg(long)]
+    keep_packaged: bool,
+
+    /// Keep entries which process <PID> has open or is running in (Linux only)
+    #[arg(long, value_name = "PID")]
+    protect_pid: Vec<u32>,
+
+    /// Keep files which other processes have open, and directories containing
+    /// them, instead of removing them (Linux and Windows only)
+    #[arg(long)]
+    skip_in_use: bool,
+
+    /// Keep images whose EXIF capture date is on or after <DATE> (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    keep_exif_after: Option<Date>,
+
+    /// Keep images whose EXIF capture date is before <DATE> (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    keep_exif_before: Option<Date>,
+
+    /// Keep entries modified less than <DURATION> ago (e.g. 10s, 5m)
+    #[arg(long, value_name = "DURATION", value_parser = datetime::parse_duration)]
+    settle: Option<Duration>,
+
+    /// Keep entries modified less than <DURATION> ago (e.g. 7d, 3h30m)
+    #[arg(long, value_name = "DURATION", value_parser = datetime::parse_duration)]
+    newer_than: Option<Duration>,
+
+    /// Keep entries modified more than <DURATION> ago (e.g. 7d, 3h30m)
+    #[arg(long, value_name = "DURATION", value_parser = datetime::parse_duration)]
+    older_than: Option<Duration>,
+
+    /// Keep the <N> most recently modified entries
+    #[arg(long, value_name = "N")]
+    keep_recent: Option<usize>,
+
+    /// Absolute paths of the entries kept by --keep-recent
+    #[arg(skip)]
+    recent: HashSet<PathBuf>,
+
+    /// Keep the <N> largest entries. The size of a directory is that of its
+    /// contents
+    #[arg(long, value_name = "N")]
+    keep_largest: Option<usize>,
+
+    /// Absolute paths of the entries kept by --keep-largest
+    #[arg(skip)]
+    largest: HashSet<PathBuf>,
+
+    /// Keep entries smaller than <SIZE> (e.g. 512, 10K, 100M, 2G)
+    ///
+    /// With -r/--recursive, the size of a directory is that of its contents.
+    #[arg(long, value_name = "SIZE", value_parser = size::parse_size)]
+    smaller_than: Option<u64>,
+
+    /// Keep entries larger than <SIZE> (e.g. 512, 10K, 100M, 2G)
+    ///
+    /// With -r/--recursive, the size of a directory is that of its contents.
+    #[arg(long, value_name = "SIZE", value_parser = size::parse_size)]
+    larger_than: Option<u64>,
+
+    /// Also delete sockets, FIFOs, and device nodes, including those inside
+    /// directories removed with -r
+    #[arg(long)]
+    special: bool,
+
+    /// Also delete entries owned by other users
+    #[arg(long)]
+    all_owners: bool,
+
+    /// Keep entries owned by <USER> or, with a leading colon, by <:GROUP>,
+    /// given by name or ID (Unix only). May be repeated
+    #[arg(long, value_name = "[:]OWNER", value_parser = Owner::parse)]
+    keep_owned_by: Vec<Owner>,
+
+    /// Keep entries with the extended attribute <NAME>, or with it set to
+    /// <VALUE> if given (Linux and macOS only). May be repeated
+    #[arg(long, value_name = "NAME[=VALUE]", value_parser = Xattr::parse)]
+    keep_xattr: Vec<Xattr>,
+
+    /// Keep files of <TYPE>: an extension (pdf), a MIME type (image/png or
+    /// text/*), or a group (audio, font, image, text, video). May be repeated
+    #[arg(long, value_name = "TYPE", value_parser = FileType::parse)]
+    keep_type: Vec<FileType>,
+
+    /// With --keep-type, also determine types from the contents of files
+    #[arg(long, requires = "keep_type")]
+    sniff: bool,
+
+    /// Keep the sources, manifests, and other files a project of type <NAME>
+    /// needs: rust, node, python, or latex. Presets can be changed and added
+    /// in the configuration file. May be repeated
+    #[arg(long, value_name = "NAME")]
+    preset: Vec<String>,
+
+    /// Presets from the configuration file, by name
+    #[arg(skip)]
+    config_presets: HashMap<String, Vec<String>>,
+
+    /// Patterns of the presets given with --preset, with the preset's name
+    #[arg(skip)]
+    preset_keep: Vec<(String, glob::Pattern)>,
+
+    /// Also delete files which look like in-progress downloads (e.g. *.part)
+    #[arg(long)]
+    delete_partial: bool,
+
+    /// Times to retry removing a file locked by another process (Windows only)
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    sharing_retries: u32,
+
+    /// Times to retry a removal which failed in a way that may be temporary,
+    /// such as a busy file or a stale handle on a network file system,
+    /// waiting twice as long before each attempt. Files locked by another
+    /// process are counted separately, against --sharing-retries
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retries: u32,
+
+    /// Give up on each removal of a file or directory, or each move with
+    /// --trash or --quarantine, after <DURATION> (e.g. 30s), so a hung network
+    /// file system can't stall the run. The entry is recorded as failed, or
+    /// as partially removed if some of its contents were. Abandoned removals
+    /// are left running in the background until leave exits
+    #[arg(long, value_name = "DURATION", value_parser = datetime::parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Show paths in messages relative to the target directory
+    #[arg(long, conflicts_with = "absolute")]
+    relative: bool,
+
+    /// Show paths in messages as absolute paths
+    #[arg(long)]
+    absolute: bool,
+
+    /// Remove the Zone.Identifier stream from kept files (Windows only)
+    #[arg(long)]
+    strip_zone_identifier: bool,
+
+    /// Snapshot the ZFS dataset or Btrfs subvolume before removing anything.
+    /// Old snapshots can be deleted with leave gc --older-than
+    #[arg(long)]
+    snapshot: bool,
+
+    /// Run on a remote host over SSH, in the given directory. Options which
+    /// name local files, such as --log and --files-from, can't be used with it
+    #[arg(
+        long,
+        value_name = "[USER@]HOST[:DIR]",
+        conflicts_with_all = [
+            "chdir",
+            "log",
+            "files_from",
+            "keep_file",
+            "rules",
+            "manifest",
+            "audit_log",
+            "control_socket",
+        ]
+    )]
+    remote: Option<String>,
+
+    /// Command used to run leave on the remote host, split into words at
+    /// whitespace, e.g. 'sudo leave' (default: leave)
+    #[arg(long, value_name = "CMD", requires = "remote")]
+    remote_command: Option<String>,
+
+    /// Print a line for each entry using <TEMPLATE>, e.g. '{action}\t{path}'.
+    /// Fields: action, path, size, mtime, type, reason
+    #[arg(long, value_name = "TEMPLATE", value_parser = Template::parse)]
+    format: Option<Template>,
+
+    /// Report each entry as a JSON object on its own line, with other
+    /// messages on standard error
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t,
+        conflicts_with = "format",
+        requires_if("sh", "dry_run")
+    )]
+    output: OutputFormat,
+
+    /// The order to process entries in. Defaults to name when entries are
+    /// listed, e.g. with -n, -v, --explain, or --output, so the output is
+    /// the same between runs, and to none otherwise
+    #[arg(long, value_name = "ORDER", value_enum)]
+    sort: Option<SortOrder>,
+
+    /// Don't remove anything if more than <N> entries would be removed
+    #[arg(long, value_name = "N")]
+    max_delete: Option<usize>,
+
+    /// Check that every entry can be removed before removing any, and don't
+    /// remove anything if one can't
+    #[arg(long, conflicts_with = "dry_run")]
+    all_or_nothing: bool,
+
+    /// Print the entries left in the directory after removal, ended with NUL
+    /// if --print0 is given
+    #[arg(long)]
+    list_remaining: bool,
+
+    /// Append a tamper-evident record of each removal to <FILE>
+    #[arg(long, value_name = "FILE")]
+    audit_log: Option<PathBuf>,
+
+    /// Append a timestamped line for each removed entry, including the
+    /// contents of directories removed with -r, and each failure to <FILE>
+    #[arg(long, value_name = "FILE")]
+    log: Option<PathBuf>,
+
+    /// Format of the lines written with --log
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t,
+        requires = "log"
+    )]
+    log_format: LogFormat,
+
+    /// Accept pause, resume, cancel, and status commands on a Unix socket at
+    /// <PATH> while removing entries
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+
+    /// Flush each directory changed by removals to disk afterwards
+    #[arg(long)]
+    sync: bool,
+
+    /// Run <CMD> with the shell before removing each entry, which is kept if
+    /// <CMD> fails. The entry's path is in `$LEAVE_PATH` and `$1`
+    #[arg(long, value_name = "CMD")]
+    pre_delete_hook: Option<String>,
+
+    /// Run <CMD> with the shell after the run, with the numbers of removed and
+    /// kept entries in `$LEAVE_REMOVED` and `$LEAVE_KEPT` and the outcome
+    /// ('success' or 'failure') in `$LEAVE_STATUS`
+    #[arg(long, value_name = "CMD")]
+    post_run_hook: Option<String>,
+
+    /// Print why each entry is kept or removed, e.g. 'kept: hidden'
+    #[arg(long)]
+    explain: bool,
+
+    /// Report how long each phase of the run took, and how many entries each
+    /// worker removed in how much time with --jobs
+    #[arg(long)]
+    timings: bool,
+
+    /// Print a summary of how many entries were removed and how much space
+    /// was freed
+    #[arg(long)]
+    summary: bool,
+
+    /// Print the disk space used by each removed entry, including the
+    /// contents of directories, followed by the total
+    #[arg(long)]
+    report_size: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Permanently delete directories created by --quarantine
+    Purge,
+
+    /// Restore the entries moved aside by the last run with --journal
+    Undo,
+
+    /// Permanently delete the entries which --trash moved to the trash
+    TrashEmpty {
+        /// Only delete entries trashed more than <DURATION> ago (e.g. 30d)
+        #[arg(long, value_name = "DURATION", value_parser = datetime::parse_duration)]
+        older_than: Option<Duration>,
+    },
+
+    /// Tidy the records leave keeps between runs and report their size
+    ///
+    /// Forgets trashed entries which are no longer in the trash and snapshots
+    /// which no longer exist, and removes temporary files left by interrupted
+    /// runs.
+    Gc {
+        /// Also delete the snapshots taken by --snapshot more than <DURATION>
+        /// ago (e.g. 90d)
+        #[arg(long, value_name = "DURATION", value_parser = datetime::parse_duration)]
+        older_than: Option<Duration>,
+    },
+
+    /// Print a manifest of the entries in the current directory, for
+    /// --manifest
+    Snapshot,
+
+    /// Explain whether a run with the given arguments would remove PATH
+    Why {
+        /// Entry in the current directory to explain
+        path: PathBuf,
+
+        /// Arguments of the run to evaluate, as they would be passed to leave
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// List the entries a run with the given arguments would remove, and fail
+    /// if there are any
+    Check {
+        /// Arguments of the run to check, as they would be passed to leave
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// Show the entries newly removed, kept, or failing between two plans or
+    /// reports written with --output json
+    ///
+    /// A plan is the output of a dry run, and a report that of a real run.
+    /// Exits with status 1 if any entry changed.
+    Diff {
+        /// Earlier plan or report
+        old: PathBuf,
+
+        /// Later plan or report
+        new: PathBuf,
+    },
+
+    /// Work with logs written by --audit-log
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+
+    /// Print a man page for leave in roff format, for packaging
+    Manpage,
+}
+
+#[derive(Debug, Subcommand)]
+enum AuditCommand {
+    /// Check that an audit log hasn't been tampered with
+    Verify {
+        /// Audit log to check
+        log: PathBuf,
+    },
+}
+
+/// File name suffixes used by browsers, download managers, and rsync for
+/// files which are still being transferred.
+const PARTIAL_DOWNLOAD_SUFFIXES: &[&str] = &[
+    ".part",
+    ".partial",
+    ".crdownload",
+    ".download",
+    ".tmp",
+    ".!qB",
+    ".~tmp~",
+];
+
+/// Where entries are moved instead of being deleted.
+enum Destination {
+    Quarantine(Quarantine),
+    Trash(trash::Journal),
+}
+
+impl Destination {
+    /// Returns where entries are moved with the given options, if anywhere.
+    fn from_options(cli: &CliOptions) -> Option<Self> {
+        if cli.quarantine {
+            Some(Destination::Quarantine(Quarantine::new(
+                cli.root.clone(),
+                cli.journal,
+            )))
+        } else if cli.trash {
+            Some(Destination::Trash(trash::Journal::default()))
+        } else {
+            None
+        }
+    }
+
+    /// Moves the given entry of the current directory into the destination.
+    /// Returns the entry's new path.
+    fn move_in(&self, path: &Path) -> eyre::Result<PathBuf> {
+        match self {
+            Destination::Quarantine(quarantine) => quarantine.move_in(path),
+            Destination::Trash(journal) => journal.move_in(path),
+        }
+    }
+}
+
+/// What happened to an entry given to [`process_entry`].
+enum Outcome {
+    /// The entry was deleted, or would have been in a dry run.
+    Removed,
+    /// The entry was moved to the given path.
+    Moved(PathBuf),
+    /// The user declined to remove the entry when prompted.
+    Declined,
+    /// The directory was kept, for the given reason, because some entries
+    /// inside it were left, though the others were removed.
+    Truncated(&'static str),
+}
+
+impl Outcome {
+    /// Returns why the entry was kept, if it was.
+    fn kept_reason(&self) -> Option<&'static str> {
+        match self {
+            Self::Removed | Self::Moved(_) => None,
+            Self::Declined => Some("declined at prompt"),
+            Self::Truncated(reason) => Some(reason),
+        }
+    }
+}
+
+/// Metadata of a directory entry, fetched when it's first needed and then
+/// reused, since each fetch is a system call.
+struct EntryMetadata<'a> {
+    entry: &'a DirEntry,
+    metadata: Option<fs::Metadata>,
+}
+
+impl<'a> EntryMetadata<'a> {
+    fn new(entry: &'a DirEntry) -> Self {
+        Self {
+            entry,
+            metadata: None,
+        }
+    }
+
+    /// Returns the metadata of the entry, without following symbolic links.
+    fn get(&mut self) -> std::io::Result<&fs::Metadata> {
+        let metadata = match self.metadata.take() {
+            Some(metadata) => metadata,
+            None => self.entry.metadata()?,
+        };
+        Ok(self.metadata.insert(metadata))
+    }
+}
+
+/// What to do with a directory entry.
+enum Decision {
+    /// Remove the entry.
+    Remove,
+    /// Keep the entry, for the given reason.
+    Keep(String),
+    /// Keep the directory, but decide about each of its entries, since some
+    /// of them are kept.
+    Descend,
+}
+
+impl CliOptions {
+    /// Formats a path for display in messages according to the
+    /// `--relative`/`--absolute` options. Without either option, the path is
+    /// shown as-is, or relative to the directory being cleaned if it's in it.
+    fn display_path(&self, path: &Path) -> PathBuf {
+        if !self.relative && !self.absolute {
+            return self.root_relative(path);
+        }
+        let Ok(absolute) = std::path::absolute(path) else {
+            return path.to_owned();
+        };
+        if self.relative
+            && let Ok(cwd) = std::path::absolute(".")
+            && let Ok(relative) = absolute.strip_prefix(&cwd)
+        {
+            if relative.as_os_str().is_empty() {
+                return PathBuf::from(".");
+            }
+            return relative.to_owned();
+        }
+        absolute
+    }
+
+    /// Returns whether anything would be kept, once the keep list is
+    /// prepared. Without any criteria every entry is removed, which is likely
+    /// a mistake.
+    ///
+    /// Every option which keeps entries must be checked here.
+    fn has_keep_criteria(&self) -> bool {
+        !self.files.is_empty()
+            || !self.keep_globs.is_empty()
+            || !self.keep_regexes.is_empty()
+            || !self.config_keep.is_empty()
+            || !self.preset_keep.is_empty()
+            || !self.kept_by_file.is_empty()
+            || !self.in_manifest.is_empty()
+            || self.keep_rules.is_some()
+            || self.git
+            || self.ignored_only
+            || self.keep_recent.is_some()
+            || self.keep_largest.is_some()
+            || !self.keep_type.is_empty()
+            || !self.keep_xattr.is_empty()
+            || !self.keep_owned_by.is_empty()
+            || self.keep_exif_after.is_some()
+            || self.keep_exif_before.is_some()
+            || self.keep_packaged
+            || !self.protect_pid.is_empty()
+            || self.skip_in_use
+            || self.settle.is_some()
+            || self.newer_than.is_some()
+            || self.older_than.is_some()
+            || self.smaller_than.is_some()
+            || self.larger_than.is_some()
+    }
+
+    /// Returns how many times failed removals are retried, and how long each
+    /// attempt may take.
+    fn retry_limits(&self) -> retry::Limits {
+        retry::Limits {
+            sharing: self.sharing_retries,
+            transient: self.retries,
+            timeout: self.timeout,
+        }
+    }
+
+    /// Returns the order to process entries in. Unless `--sort` is given,
+    /// entries are sorted by name if they're listed.
+    fn sort_order(&self) -> SortOrder {
+        self.sort.unwrap_or(
+            if self.dry_run
+                || self.verbose
+                || self.explain
+                || self.confirm
+                || self.format.is_some()
+                || self.output != OutputFormat::Text
+            {
+                SortOrder::Name
+            } else {
+                SortOrder::None
+            },
+        )
+    }
+
+    /// Returns the open directory being cleaned, opening it the first time.
+    /// Its entries are listed, inspected and removed through it, so they're
+    /// those of the same directory throughout the run, even if it's moved or
+    /// replaced meanwhile.
+    fn root_dir(&self) -> eyre::Result<&Arc<dirfd::Dir>> {
+        if let Some(dir) = self.root_dir.get() {
+            return Ok(dir);
+        }
+        let dir = dirfd::Dir::open_path(&self.root)
+            .wrap_err_with(|| tr!("Can't open {dir}", dir = self.root.display()))?;
+        Ok(self.root_dir.get_or_init(|| Arc::new(dir)))
+    }
+
+    /// Returns the path of `path`, which is relative to the directory being
+    /// cleaned, as seen from the current directory.
+    fn at_root(&self, path: &Path) -> PathBuf {
+        if self.root == Path::new(".") {
+            path.to_owned()
+        } else {
+            self.root.join(path)
+        }
+    }
+
+    /// Returns the path of an entry of the directory being cleaned relative
+    /// to it, starting with `./` like the paths of entries listed from the
+    /// current directory. Other paths are returned as-is.
+    fn root_relative(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if self.root != Path::new(".") => Path::new(".").join(relative),
+            _ => path.to_owned(),
+        }
+    }
+
+    /// Returns the absolute path of an entry found by listing the current
+    /// directory. Unlike [`std::path::absolute`], this doesn't ask the OS for
+    /// the current directory each time, which adds up in huge directories.
+    fn entry_absolute(&self, path: &Path) -> PathBuf {
+        self.cwd.join(path.strip_prefix(".").unwrap_or(path))
+    }
+
+    /// Returns the key under which an absolute path is stored in and looked
+    /// up from the sets of kept paths, which is case-folded with
+    /// `--ignore-case`.
+    fn case_key(&self, path: PathBuf) -> PathBuf {
+        if self.ignore_case {
+            case::fold(&path)
+        } else {
+            path
+        }
+    }
+}
+
+/// Options for removing entries using the library interface. These are the
+/// same as the command-line options of the same names.
+#[derive(Debug)]
+pub struct LeaveOptions {
+    cli: CliOptions,
+}
+
+impl Default for LeaveOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LeaveOptions {
+    /// Creates options which keep nothing and only remove files.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            cli: CliOptions::parse_from(["leave"]),
+        }
+    }
+
+    /// Keeps the given entry, relative to the directory being cleaned.
+    #[must_use]
+    pub fn keep(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cli.files.push(path.into());
+        self
+    }
+
+    /// Treats kept entries as glob patterns.
+    #[must_use]
+    pub fn glob(mut self, glob: bool) -> Self {
+        self.cli.glob = glob;
+        self
+    }
+
+    /// Matches kept entries regardless of case.
+    #[must_use]
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.cli.ignore_case = ignore_case;
+        self
+    }
+
+    /// Also keeps entries tracked by Git.
+    #[must_use]
+    pub fn git(mut self, git: bool) -> Self {
+        self.cli.git = git;
+        self
+    }
+
+    /// Removes directories and their contents recursively.
+    #[must_use]
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.cli.recursive = recursive;
+        self
+    }
+
+    /// Only removes entries up to the given number of levels deep, with
+    /// [`recursive`](Self::recursive).
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: Option<NonZeroUsize>) -> Self {
+        self.cli.max_depth = max_depth;
+        self
+    }
+
+    /// Removes empty directories.
+    #[must_use]
+    pub fn dirs(mut self, dirs: bool) -> Self {
+        self.cli.dirs = dirs;
+        self
+    }
+
+    /// Allows kept entries which don't exist and removes write-protected
+    /// entries.
+    #[must_use]
+    pub fn force(mut self, force: bool) -> Self {
+        self.cli.force = force;
+        self
+    }
+
+    /// With [`force`](Self::force), refuses to plan if kept entries which
+    /// don't exist look like mistakes, e.g. misspellings. On by default.
+    #[must_use]
+    pub fn sanity_check(mut self, sanity_check: bool) -> Self {
+        self.cli.no_sanity_check = !sanity_check;
+        self
+    }
+
+    /// Also removes hidden entries, whose names start with a dot.
+    #[must_use]
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.cli.hidden = hidden;
+        self
+    }
+
+    /// Checks that every entry can be removed before removing any, and
+    /// makes [`execute`] fail without removing anything if one can't.
+    #[must_use]
+    pub fn all_or_nothing(mut self, all_or_nothing: bool) -> Self {
+        self.cli.all_or_nothing = all_or_nothing;
+        self
+    }
+
+    /// Moves entries into a quarantine directory instead of deleting them.
+    #[must_use]
+    pub fn quarantine(mut self, quarantine: bool) -> Self {
+        self.cli.quarantine = quarantine;
+        self
+    }
+
+    /// Decides which entries of the given directory to remove and which to
+    /// keep, without removing anything.
+    ///
+    /// The plan holds the absolute path of the directory, so it can be
+    /// executed later regardless of the current working directory, which
+    /// isn't changed. Paths in the plan are relative to the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be read, or if the options are
+    /// likely a mistake, e.g. a kept entry doesn't exist.
+    pub fn plan(mut self, dir: &Path) -> eyre::Result<Plan> {
+        self.cli.root = std::path::absolute(dir)
+            .wrap_err_with(|| tr!("Can't make {path} absolute", path = dir.display()))?;
+        guard::check(&self.cli.root, &self.cli.protect)?;
+        self.cli.root_dir()?;
+        prepare_keep_list(&mut self.cli)?;
+        let absolute_files = validate_args(&self.cli)?;
+        check_sanity(&self.cli)?;
+        let entries = read_root(&self.cli)?;
+        let (remove, keep, had_failure) = select_entries(&self.cli, &absolute_files, entries)?;
+        Ok(Plan {
+            cli: self.cli,
+            remove,
+            keep,
+            had_failure,
+        })
+    }
+}
+
+/// The entries to remove from a directory and the entries to keep, as
+/// decided by [`LeaveOptions::plan`].
+#[derive(Debug)]
+pub struct Plan {
+    cli: CliOptions,
+    remove: Vec<DirEntry>,
+    keep: Kept,
+    /// Whether an error occurred while deciding about an entry.
+    had_failure: bool,
+}
+
+impl Plan {
+    /// Returns the paths of the entries to remove, relative to the directory.
+    pub fn to_remove(&self) -> impl Iterator<Item = PathBuf> {
+        self.remove
+            .iter()
+            .map(|entry| self.cli.root_relative(&entry.path()))
+    }
+
+    /// Returns the paths of the entries to keep, relative to the directory,
+    /// along with the reason.
+    pub fn to_keep(&self) -> impl Iterator<Item = (PathBuf, &str)> {
+        self.keep
+            .iter()
+            .map(|(entry, reason)| (self.cli.root_relative(&entry.path()), reason.as_str()))
+    }
+}
+
+/// Removes the entries in the given plan. Errors for individual entries are
+/// printed to standard error.
+///
+/// # Errors
+///
+/// Returns an error if any entry couldn't be removed or decided about while
+/// planning.
+pub fn execute(plan: Plan) -> eyre::Result<()> {
+    if plan.cli.all_or_nothing {
+        preflight(&plan.cli, &plan.remove, plan.had_failure)?;
+    }
+    let (had_failure, _) = remove_entries(&plan.cli, plan.remove, None, None, None);
+    if had_failure || plan.had_failure {
+        bail!(tr!("Some entries couldn't be removed"));
+    }
+    Ok(())
+}
+
+const MISTAKE_MSG: &str = "This is likely a mistake. To continue anyways, use -f/--force.";
+
+/// Environment variable setting whether hidden entries are removed when
+/// neither `--hidden` nor `--keep-hidden` is given: `keep` or `remove`.
+const HIDDEN_ENV_VAR: &str = "LEAVE_HIDDEN";
+
+/// Runs the `leave` command-line interface with the arguments of the current
+/// process.
+#[must_use]
+pub fn run() -> ExitCode {
+    let parsed =
+        argfile::expand(std::env::args_os()).and_then(|args| Ok((parse_args(&args)?, args)));
+    let (mut cli, args) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            print_error(&Output::default(), &err);
+            return ExitCode::FAILURE;
+        }
+    };
+    configure_output(&mut cli);
+    match main_fallible(&mut cli, &args) {
+        Ok(code) => code,
+        Err(failure) => {
+            let code = failure.exit_code();
+            print_error(&cli.out, &failure.into());
+            code
+        }
+    }
+}
+
+/// An error which ends a run, classified so that scripts can tell the
+/// classes apart by the exit code.
+#[derive(Debug)]
+enum Failure {
+    /// The arguments are invalid or likely a mistake, e.g. a kept file
+    /// doesn't exist.
+    Usage(eyre::Report),
+    /// A safety check refused the run, e.g. the directory is protected.
+    Refused(eyre::Report),
+    /// Any other error.
+    Other(eyre::Report),
+}
+
+impl Failure {
+    /// Returns the exit code to end the process with.
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Failure::Usage(_) => ExitCode::from(2),
+            Failure::Refused(_) => ExitCode::from(3),
+            Failure::Other(_) => ExitCode::FAILURE,
+        }
+    }
+}
+
+impl From<eyre::Report> for Failure {
+    fn from(err: eyre::Report) -> Self {
+        Failure::Other(err)
+    }
+}
+
+impl From<Failure> for eyre::Report {
+    fn from(failure: Failure) -> Self {
+        match failure {
+            Failure::Usage(err) | Failure::Refused(err) | Failure::Other(err) => err,
+        }
+    }
+}
+
+/// Wraps the actual error-prone logic so we can conveniently use `?` after
+/// errors.
+/// Returns `ExitCode::FAILURE` if at least one error occurred while removing
+/// files, or `ExitCode::SUCCESS` if successful.
+fn main_fallible(cli: &mut CliOptions, args: &[OsString]) -> Result<ExitCode, Failure> {
+    // Journaling is quarantining with a record
+    cli.quarantine |= cli.journal;
+    // Listing and statistics are dry runs which print only those
+    cli.list |= cli.print0;
+    cli.dry_run |= cli.list || cli.stat;
+    apply_hidden_default(cli)?;
+    let mut timings = Timings::new();
+
+    check_platform(cli).map_err(Failure::Usage)?;
+    if cli.out.is_shell() && (cli.trash || cli.quarantine) {
+        return Err(Failure::Usage(eyre!(tr!(
+            "--output sh can't be used with --trash or --quarantine"
+        ))));
+    }
+
+    // Hand the whole run off to the remote host
+    if let Some(spec) = &cli.remote {
+        let remote_command = cli.remote_command.as_deref().unwrap_or("leave");
+        return Ok(remote::run(spec, remote_command, &args[1..])?);
+    }
+
+    enter_target_dir(cli)?;
+
+    if let Some(command) = &cli.command {
+        return Ok(run_command(command, &cli.out)?);
+    }
+
+    // Previewing is harmless, so only actual removals are guarded
+    if !cli.no_preserve_root && !cli.dry_run {
+        guard::check(&cli.root, &cli.protect).map_err(Failure::Refused)?;
+    }
+
+    // Everything from here on is looked up through the directory's handle
+    cli.root_dir()?;
+    prepare_keep_list(cli)?;
+    let absolute_files = validate_args(cli).map_err(Failure::Usage)?;
+    if !cli.dry_run {
+        check_sanity(cli).map_err(Failure::Refused)?;
+    }
+    // Open the audit log up front so nothing is removed without a record
+    let mut audit_log = cli
+        .audit_log
+        .as_deref()
+        .filter(|_| !cli.dry_run)
+        .map(AuditLog::open)
+        .transpose()?;
+    if let Some(path) = cli.log.as_deref().filter(|_| !cli.dry_run) {
+        cli.logger.open(path, cli.log_format)?;
+    }
+    timings.lap("validation");
+
+    if cli.watch {
+        return Ok(watch(cli, &absolute_files, audit_log.as_mut())?);
+    }
+
+    if cli.snapshot && !cli.dry_run {
+        let name = snapshot::create().wrap_err(tr!("Can't create snapshot"))?;
+        cli.out.notice(tr!("Created snapshot {name}", name = name));
+        timings.lap("snapshot");
+    }
+
+    // Scan directory
+    let entries = read_root(cli)?;
+    timings.lap("scan");
+
+    // Find entries to remove
+    let (doomed, kept, mut had_failure) = select_entries(cli, &absolute_files, entries)?;
+    timings.lap("matching");
+
+    if cli.list || cli.stat {
+        if cli.list {
+            list_doomed(cli, doomed)?;
+        } else {
+            print_stats(cli, &doomed, &kept);
+        }
+        // Nothing was removed, so everything remains
+        if cli.list_remaining {
+            list_remaining(cli)?;
+        }
+        return Ok(exit_code(had_failure));
+    }
+
+    if let Some(max) = cli.max_delete
+        && doomed.len() > max
+    {
+        return Err(Failure::Refused(eyre!(tr!(
+            "{count} entries would be removed, which is more than the limit of {max} set by --max-delete",
+            count = doomed.len(),
+            max = max
+        ))));
+    }
+
+    if cli.all_or_nothing {
+        preflight(cli, &doomed, had_failure).map_err(Failure::Refused)?;
+    }
+
+    let Some((doomed, kept)) = review_plan(cli, doomed, kept)? else {
+        cli.out.notice(tr!("Nothing was removed"));
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    // Do removal
+    let selected = doomed.len();
+    // Dry runs don't remove anything, so pruning must know what would be gone
+    let doomed_paths: HashSet<PathBuf> = if cli.prune_empty && cli.dry_run {
+        doomed.iter().map(DirEntry::path).collect()
+    } else {
+        HashSet::new()
+    };
+    let (removal_failed, mut removed) =
+        remove_selected(cli, doomed, audit_log.as_mut(), &mut timings)?;
+    had_failure |= removal_failed;
+    if cli.prune_empty && !interrupt::is_interrupted() {
+        let (prune_failed, pruned) = prune_empty(cli, &kept, &absolute_files, doomed_paths);
+        had_failure |= prune_failed;
+        removed += pruned;
+    }
+    timings.lap("removal");
+
+    // Entries which were selected but not removed were kept too
+    had_failure = finish_run(cli, removed, kept.len() + selected - removed, had_failure);
+
+    if cli.timings {
+        timings.report();
+    }
+
+    Ok(exit_code(had_failure))
+}
+
+/// Runs one of the subcommands, which work independently of the options for
+/// removal runs.
+fn run_command(command: &Command, out: &Output) -> eyre::Result<ExitCode> {
+    match command {
+        Command::Purge => quarantine::purge(out),
+        Command::Undo => quarantine::undo(out),
+        Command::TrashEmpty { older_than } => {
+            trash::empty(older_than.unwrap_or(Duration::ZERO), out)
+        }
+        Command::Gc { older_than } => gc::run(*older_than, out),
+        Command::Snapshot => {
+            manifest::write(&mut std::io::stdout().lock()).map(|()| ExitCode::SUCCESS)
+        }
+        Command::Why { path, args } => explain(path, args),
+        Command::Check { args } => check(args),
+        Command::Diff { old, new } => diff::run(old, new, out),
+        Command::Audit {
+            command: AuditCommand::Verify { log },
+        } => audit::verify(log),
+        Command::Manpage => {
+            manpage::write(&mut std::io::stdout().lock()).map(|()| ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Removes the selected entries, handling interrupts and the control socket
+/// while doing so.
+///
+/// Returns whether removing any entry failed, and the number of entries
+/// removed.
+fn remove_selected(
+    cli: &CliOptions,
+    doomed: Vec<DirEntry>,
+    audit_log: Option<&mut AuditLog>,
+    timings: &mut Timings,
+) -> eyre::Result<(bool, usize)> {
+    interrupt::install()?;
+    if let Some(rate) = cli.nice_io
+        && !cli.dry_run
+    {
+        throttle::start(rate, &cli.out);
+    }
+    let control = cli
+        .control_socket
+        .as_deref()
+        .map(|path| Control::start(path, doomed.len()))
+        .transpose()?;
+    Ok(remove_entries(
+        cli,
+        doomed,
+        audit_log,
+        control.as_ref(),
+        Some(timings),
+    ))
+}
+
+/// Removes the directories which were kept for containing kept entries, for
+/// `--prune-empty`, if they ended up empty and aren't named as arguments.
+/// Directories are visited bottom-up, so pruning one can empty its parent.
+/// For dry runs, `doomed` holds the paths of the entries which would have
+/// been removed.
+///
+/// Returns whether removing any directory failed, and the number removed.
+fn prune_empty(
+    cli: &CliOptions,
+    kept: &Kept,
+    absolute_files: &HashSet<PathBuf>,
+    mut doomed: HashSet<PathBuf>,
+) -> (bool, usize) {
+    let mut dirs: Vec<&DirEntry> = kept
+        .iter()
+        .filter(|(_, reason)| reason == DESCEND_REASON)
+        .map(|(entry, _)| entry)
+        .collect();
+    dirs.sort_by_key(|entry| std::cmp::Reverse(entry.path().components().count()));
+    let mut had_failure = false;
+    let mut pruned = 0;
+    for entry in dirs {
+        let path = entry.path();
+        let result = std::path::absolute(&path)
+            .wrap_err(tr!("Can't make path absolute"))
+            .and_then(|absolute| {
+                if absolute_files.contains(&cli.case_key(absolute)) {
+                    return Ok(false);
+                }
+                let children = entry.read_dir(false).wrap_err_with(|| {
+                    tr!(
+                        "Can't list contents of {dir}",
+                        dir = cli.display_path(&path).display()
+                    )
+                })?;
+                let is_empty = children.iter().all(|child| doomed.contains(&child.path()));
+                if is_empty && !cli.dry_run {
+                    let parent = Arc::clone(entry.dir());
+                    let name = entry.file_name();
+                    retry::run(cli.retry_limits(), move || parent.remove_dir(&name))
+                        .wrap_err_with(|| {
+                            tr!(
+                                "Can't remove {path}",
+                                path = cli.display_path(&path).display()
+                            )
+                        })?;
+                }
+                Ok(is_empty)
+            });
+        match result {
+            Ok(false) => (),
+            Ok(true) if cli.dry_run => {
+                // Its contents are removed first, so it's empty by then
+                print_dry_run(cli, &path, true, false);
+                report_removed(cli, entry, None, "would remove");
+                doomed.insert(path);
+                pruned += 1;
+            }
+            Ok(true) => {
+                report_removed(cli, entry, None, "removed");
+                cli.changes.entry_changed(&path);
+                pruned += 1;
+            }
+            Err(err) => {
+                had_failure = true;
+                print_error(&cli.out, &err);
+            }
+        }
+    }
+    had_failure |= !sync_changed(cli);
+    (had_failure, pruned)
+}
+
+/// Flushes the directories changed by removals to disk, with `--sync`,
+/// printing any errors which occur. Returns false if any failed.
+fn sync_changed(cli: &CliOptions) -> bool {
+    let mut ok = true;
+    for dir in cli.changes.take() {
+        if let Err(err) = sync::sync_dir(&dir) {
+            ok = false;
+            print_error(&cli.out, &err);
+        }
+    }
+    ok
+}
+
+/// Returns the exit code of a run which has finished removing entries.
+fn exit_code(had_failure: bool) -> ExitCode {
+    if interrupt::is_interrupted() {
+        ExitCode::from(interrupt::EXIT_CODE)
+    } else if had_failure {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Changes into the directory given with `-C` or `--in`, if any.
+fn enter_target_dir(cli: &mut CliOptions) -> eyre::Result<()> {
+    if let Some(dir) = &cli.chdir {
+        std::env::set_current_dir(dir)
+            .wrap_err_with(|| tr!("Can't chdir into {dir}", dir = dir.display()))?;
+    }
+    if let Some(dir) = cli.in_dir.clone() {
+        anchor_option_paths(cli)?;
+        std::env::set_current_dir(&dir)
+            .wrap_err_with(|| tr!("Can't enter {dir}", dir = dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Makes the relative paths given to options other than keep arguments
+/// absolute, for `--in`, so they still refer to the same files once the
+/// target directory is entered.
+fn anchor_option_paths(cli: &mut CliOptions) -> eyre::Result<()> {
+    let anchor = |path: &mut PathBuf| -> eyre::Result<()> {
+        if path.is_relative() {
+            *path = std::path::absolute(&*path)
+                .wrap_err_with(|| tr!("Can't make {path} absolute", path = path.display()))?;
+        }
+        Ok(())
+    };
+    // - means standard input
+    if let Some(path) = cli
+        .files_from
+        .as_mut()
+        .filter(|path| *path != Path::new("-"))
+    {
+        anchor(path)?;
+    }
+    for path in cli
+        .keep_file
+        .iter_mut()
+        .chain(&mut cli.rules)
+        .chain(&mut cli.protect)
+        .chain(&mut cli.manifest)
+        .chain(&mut cli.audit_log)
+        .chain(&mut cli.log)
+        .chain(&mut cli.control_socket)
+    {
+        anchor(path)?;
+    }
+    Ok(())
+}
+
+/// Sets up how messages are printed for the rest of the run.
+fn configure_output(cli: &mut CliOptions) {
+    let progress = !cli.no_progress
+        && (cli.progress
+            || (std::io::stderr().is_terminal()
+                && !cli.verbose
+                && cli.format.is_none()
+                && cli.output == OutputFormat::Text));
+    cli.out = Output::new(
+        cli.output,
+        cli.quiet,
+        cli.color,
+        std::mem::take(&mut cli.theme),
+        progress,
+    );
+}
+
+/// Fails if an option given isn't supported on this platform.
+fn check_platform(cli: &CliOptions) -> eyre::Result<()> {
+    if cli.strip_zone_identifier && !cfg!(windows) {
+        bail!(tr!("--strip-zone-identifier is only supported on Windows"));
+    }
+    if cli.trash && !cfg!(any(unix, windows)) {
+        bail!(tr!("--trash is only supported on Unix, macOS, and Windows"));
+    }
+    if cli.sync && !cfg!(unix) {
+        bail!(tr!("--sync is only supported on Unix"));
+    }
+    if cli.keep_hardlinks && !cfg!(unix) {
+        bail!(tr!("--keep-hardlinks is only supported on Unix"));
+    }
+    if cli.skip_in_use && !cfg!(any(target_os = "linux", windows)) {
+        bail!(tr!("--skip-in-use is only supported on Linux and Windows"));
+    }
+    Ok(())
+}
+
+/// Removes entries which aren't kept from the current directory whenever it
+/// changes, for `--watch`. Only returns if an error occurs while watching.
+fn watch(
+    cli: &CliOptions,
+    absolute_files: &HashSet<PathBuf>,
+    mut audit_log: Option<&mut AuditLog>,
+) -> eyre::Result<ExitCode> {
+    let watcher = watch::Watcher::new()?;
+    loop {
+        let entries = read_root(cli)?;
+        // Failures are already reported, and shouldn't stop the watch
+        let (doomed, _, _) = select_entries(cli, absolute_files, entries)?;
+        if !doomed.is_empty() {
+            remove_entries(cli, doomed, audit_log.as_deref_mut(), None, None);
+        }
+        // Entries kept by --settle must be checked again once they settle
+        if watcher.wait(cli.settle)? {
+            watcher.debounce(cli.debounce)?;
+        }
+    }
+}
+
+/// Does the work which follows the removal of entries: stripping zone
+/// identifiers, listing what remains, and running the post-run hook. Returns
+/// whether the run failed.
+fn finish_run(cli: &CliOptions, removed: usize, kept: usize, mut had_failure: bool) -> bool {
+    had_failure |= cli.logger.had_failure();
+    if cli.strip_zone_identifier
+        && !cli.dry_run
+        && let Err(err) = ads::strip_zone_identifiers()
+    {
+        had_failure = true;
+        print_error(&cli.out, &err);
+    }
+
+    if cli.list_remaining
+        && let Err(err) = list_remaining(cli)
+    {
+        had_failure = true;
+        print_error(&cli.out, &err);
+    }
+
+    if let Some(command) = &cli.post_run_hook {
+        let vars = [
+            ("LEAVE_REMOVED", removed.to_string()),
+            ("LEAVE_KEPT", kept.to_string()),
+            (
+                "LEAVE_STATUS",
+                if had_failure { "failure" } else { "success" }.to_owned(),
+            ),
+        ];
+        if let Err(err) = hooks::post_run(command, &vars) {
+            had_failure = true;
+            print_error(&cli.out, &err);
+        }
+    }
+    had_failure
+}
+
+/// Parses the given command-line arguments, exiting if they're invalid, and
+/// fills in defaults from the configuration file.
+fn parse_args(args: &[OsString]) -> eyre::Result<CliOptions> {
+    let matches = CliOptions::command().get_matches_from(args);
+    let mut cli = CliOptions::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if !cli.no_config {
+        apply_config(&mut cli, &matches)?;
+    }
+    Ok(cli)
+}
+
+/// Applies the settings from the configuration file, except those which were
+/// given on the command line.
+fn apply_config(cli: &mut CliOptions, matches: &ArgMatches) -> eyre::Result<()> {
+    let explicit = |ids: &[&str]| {
+        ids.iter()
+            .any(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+    };
+    // Styles set for single markers, along with where they're set
+    let mut theme_styles: Vec<(Marker, String, String)> = Vec::new();
+    // Only one destination can be used, so giving any overrides both settings
+    let destination_given = explicit(&[
+        "trash",
+        "quarantine",
+        "journal",
+        "no_trash",
+        "no_quarantine",
+    ]);
+    for setting in config::load()? {
+        let key = setting.key.as_str();
+        let flag = match key {
+            "recursive" => &mut cli.recursive,
+            "dirs" => &mut cli.dirs,
+            "force" => &mut cli.force,
+            "verbose" => &mut cli.verbose,
+            "quiet" => &mut cli.quiet,
+            "summary" => &mut cli.summary,
+            "report_size" => &mut cli.report_size,
+            "sync" => &mut cli.sync,
+            "git" => &mut cli.git,
+            "ignore_case" => &mut cli.ignore_case,
+            "dialog" => &mut cli.dialog,
+            "hidden" => {
+                if !explicit(&[key, "keep_hidden"]) {
+                    cli.hidden = setting.as_bool()?;
+                }
+                continue;
+            }
+            "trash" | "quarantine" if destination_given => continue,
+            "trash" => &mut cli.trash,
+            "quarantine" => &mut cli.quarantine,
+            "max_delete" => {
+                if !explicit(&[key]) {
+                    cli.max_delete = Some(setting.as_count()?);
+                }
+                continue;
+            }
+            "protect" => {
+                // Protections add up rather than being overridden
+                let paths = setting.as_strings()?;
+                cli.protect.extend(paths.into_iter().map(PathBuf::from));
+                continue;
+            }
+            "keep" => {
+                for pattern in setting.as_strings()? {
+                    let pattern = vars::expand_env(pattern)
+                        .and_then(|pattern| vars::expand_str(&pattern))
+                        .wrap_err(setting.location.clone())?;
+                    cli.config_keep
+                        .push(glob::Pattern::parse(&pattern).wrap_err(setting.location.clone())?);
+                }
+                continue;
+            }
+            "theme" => {
+                let name = setting.as_str()?;
+                let theme = Theme::preset(name).wrap_err(setting.location.clone())?;
+                // Styles set for single markers apply on top of the theme
+                cli.theme = theme;
+                for (marker, style, location) in &theme_styles {
+                    cli.theme.set(*marker, style).wrap_err(location.clone())?;
+                }
+                continue;
+            }
+            _ if let Some(marker) = key.strip_prefix("theme_").and_then(Marker::from_name) => {
+                let style = setting.as_str()?;
+                cli.theme
+                    .set(marker, style)
+                    .wrap_err(setting.location.clone())?;
+                theme_styles.push((marker, style.to_owned(), setting.location.clone()));
+                continue;
+            }
+            _ if key.starts_with("preset_") => {
+                let name = &key["preset_".len()..];
+                let patterns = setting.as_strings()?;
+                cli.config_presets.insert(
+                    name.into(),
+                    patterns.into_iter().map(str::to_owned).collect(),
+                );
+                continue;
+            }
+            _ => bail!(tr!(
+                "{location}: Unknown setting {key}",
+                location = setting.location,
+                key = key
+            )),
+        };
+        let value = setting.as_bool()?;
+        // Flags turned off with --no-<FLAG> count as given too
+        if !explicit(&[key, &format!("no_{key}")]) {
+            *flag = value;
+        }
+    }
+    // Only one destination can be used
+    if cli.trash && cli.quarantine {
+        bail!(tr!(
+            "The configuration file can't enable both trash and quarantine"
+        ));
+    }
+    Ok(())
+}
+
+/// Decides whether to remove hidden entries using the environment if neither
+/// `--hidden` nor `--keep-hidden` was given.
+fn apply_hidden_default(cli: &mut CliOptions) -> eyre::Result<()> {
+    if cli.hidden || cli.keep_hidden {
+        return Ok(());
+    }
+    match std::env::var_os(HIDDEN_ENV_VAR) {
+        None => (),
+        Some(value) if value == "keep" || value.is_empty() => (),
+        Some(value) if value == "remove" => cli.hidden = true,
+        Some(value) => bail!(tr!(
+            "{var} must be 'keep' or 'remove', not {value}",
+            var = HIDDEN_ENV_VAR,
+            value = format!("{:?}", value.display())
+        )),
+    }
+    Ok(())
+}
+
+/// Adds keep arguments read with `--files-from`, expands variables and globs
+/// in them, and reads keep files and Git's tracked and ignored files.
+fn prepare_keep_list(cli: &mut CliOptions) -> Result<(), Failure> {
+    if let Some(path) = &cli.files_from {
+        let files = filelist::read(path, cli.null)?;
+        cli.files.extend(files);
+    }
+    compile_regexes(cli).map_err(Failure::Usage)?;
+    load_presets(cli).map_err(Failure::Usage)?;
+    expand_vars(cli).map_err(Failure::Usage)?;
+    expand_globs(cli).map_err(Failure::Usage)?;
+    // Excluded arguments aren't kept, so they're not checked for mistakes
+    // either
+    let exclude = &cli.exclude;
+    cli.files.retain(|file| !is_excluded(exclude, file));
+    resolve_case(cli);
+    if cli.empty_kept_dirs {
+        find_emptied_dirs(cli)?;
+    }
+    find_link_targets(cli)?;
+    load_keep_files(cli)?;
+    if let Some(path) = &cli.rules {
+        cli.keep_rules = Some(rules::Rules::read(path)?);
+        // Keep the rules file itself if it's in the target directory
+        let absolute = std::path::absolute(path)
+            .wrap_err_with(|| tr!("Can't make {path} absolute", path = path.display()))?;
+        cli.kept_by_file.insert(cli.case_key(absolute));
+    }
+    if let Some(path) = &cli.manifest {
+        // Keep the manifest itself if it's in the target directory
+        let names = manifest::read(path)?;
+        for path in names.iter().chain([path]) {
+            let absolute = std::path::absolute(path)
+                .wrap_err_with(|| tr!("Can't make {path} absolute", path = path.display()))?;
+            cli.in_manifest.insert(cli.case_key(absolute));
+        }
+    }
+    find_recent(cli)?;
+    find_largest(cli)?;
+    if cli.git {
+        cli.tracked_by_git = absolute_paths(cli, &git::tracked_entries(&cli.root)?)?;
+    }
+    if cli.ignored_only {
+        cli.ignored_by_git = absolute_paths(cli, &git::ignored_entries(&cli.root)?)?;
+    }
+    index_arguments(cli)?;
+    Ok(())
+}
+
+/// Records the current directory and, if every keep argument names an entry
+/// of it, the names of the arguments, so entries can be matched against them
+/// without making each entry's path absolute.
+fn index_arguments(cli: &mut CliOptions) -> eyre::Result<()> {
+    cli.cwd = std::path::absolute(&cli.root)
+        .wrap_err(tr!("Can't get path to current working directory"))?;
+    let names: Option<HashSet<PathBuf>> = cli
+        .files
+        .iter()
+        .map(|file| {
+            let absolute = std::path::absolute(cli.at_root(file)).ok()?;
+            let name = absolute
+                .file_name()
+                .filter(|_| absolute.parent() == Some(&cli.cwd))?;
+            Some(cli.case_key(name.into()))
+        })
+        .collect();
+    // Emptied directories are descended into, which needs their full paths
+    cli.argument_names = names.filter(|_| cli.emptied_dirs.is_empty());
+    Ok(())
+}
+
+/// Returns whether the given entry's name matches one of the `--exclude`
+/// patterns.
+fn is_excluded(exclude: &[glob::Pattern], path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy();
+        exclude.iter().any(|pattern| pattern.matches(&name))
+    })
+}
+
+/// Spells keep arguments like the entries they name, so they match even if
+/// they differ in case and either `--ignore-case` was given or the file
+/// system is case-insensitive. Arguments which name no entry are left as-is.
+/// Trailing slashes are kept, since they matter for `--empty-kept-dirs`.
+fn resolve_case(cli: &mut CliOptions) {
+    let mut resolver = case::Resolver::new(&cli.root);
+    for file in &mut cli.files {
+        if let Some(mut resolved) = resolver.resolve(file, cli.ignore_case) {
+            if has_trailing_slash(file) {
+                resolved.push("");
+            }
+            *file = resolved;
+        }
+    }
+}
+
+/// Returns whether the given path ends with a path separator.
+fn has_trailing_slash(path: &Path) -> bool {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .last()
+        .is_some_and(|&byte| std::path::is_separator(byte.into()))
+}
+
+/// Compiles the keep arguments as regular expressions if `--regex` was given.
+/// They're taken out of the list of files, since they aren't paths.
+fn compile_regexes(cli: &mut CliOptions) -> eyre::Result<()> {
+    if !cli.regex {
+        return Ok(());
+    }
+    cli.keep_regexes = std::mem::take(&mut cli.files)
+        .iter()
+        .map(|pattern| Regex::parse(&pattern.to_string_lossy()))
+        .collect::<eyre::Result<_>>()?;
+    Ok(())
+}
+
+/// Compiles the patterns of the presets given with `--preset`.
+fn load_presets(cli: &mut CliOptions) -> eyre::Result<()> {
+    for name in &cli.preset {
+        // Names are spelled like configuration keys, which use underscores
+        let name = name.replace('-', "_");
+        for pattern in preset::patterns(&name, &cli.config_presets)? {
+            let pattern = vars::expand_str(&pattern)
+                .and_then(|pattern| glob::Pattern::parse(&pattern))
+                .wrap_err_with(|| tr!("Invalid pattern in preset {name}", name = name))?;
+            cli.preset_keep.push((name.clone(), pattern));
+        }
+    }
+    Ok(())
+}
+
+/// Finds the directories named as arguments without a trailing slash, whose
+/// contents are removed with `--empty-kept-dirs`. Symbolic links to
+/// directories are kept as they are.
+fn find_emptied_dirs(cli: &mut CliOptions) -> eyre::Result<()> {
+    let mut emptied = HashSet::new();
+    for file in &cli.files {
+        if has_trailing_slash(file)
+            || !fs::symlink_metadata(cli.at_root(file)).is_ok_and(|metadata| metadata.is_dir())
+        {
+            continue;
+        }
+        let absolute = std::path::absolute(cli.at_root(file)).wrap_err_with(|| {
+            tr!(
+                "Can't make {path} absolute",
+                path = cli.display_path(file).display()
+            )
+        })?;
+        emptied.insert(cli.case_key(absolute));
+    }
+    cli.emptied_dirs = emptied;
+    Ok(())
+}
+
+/// Finds the targets of the symbolic links named as arguments which are in
+/// the current directory. With `--keep-link-targets`, they're kept as if they
+/// were arguments too; otherwise they're remembered to warn about.
+fn find_link_targets(cli: &mut CliOptions) -> eyre::Result<()> {
+    let cwd =
+        fs::canonicalize(&cli.root).wrap_err(tr!("Can't get path to current working directory"))?;
+    let mut targets = Vec::new();
+    for file in &cli.files {
+        let path = cli.at_root(file);
+        if !fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_symlink()) {
+            continue;
+        }
+        // Dangling links have nothing left to lose
+        let Ok(target) = fs::canonicalize(&path) else {
+            continue;
+        };
+        // Targets in subdirectories are only removed with -r
+        if let Ok(target) = target.strip_prefix(&cwd)
+            && target.parent().is_some()
+            && (cli.recursive || target.components().count() == 1)
+        {
+            targets.push((file.clone(), target.to_owned()));
+        }
+    }
+    if cli.keep_link_targets {
+        cli.files
+            .extend(targets.into_iter().map(|(_, target)| target));
+    } else {
+        cli.link_targets = targets;
+    }
+    Ok(())
+}
+
+/// Warns about symbolic links named as arguments whose targets would be
+/// removed, leaving them dangling.
+fn warn_dangling_links(cli: &CliOptions, doomed: &[DirEntry]) {
+    for (link, target) in &cli.link_targets {
+        let removed = doomed.iter().any(|entry| {
+            let path = cli.root_relative(&entry.path());
+            target.starts_with(path.strip_prefix(".").unwrap_or(&path))
+        });
+        if !removed {
+            continue;
+        }
+        let link = cli.display_path(link);
+        let target = cli.display_path(target);
+        let will = if cli.dry_run { "would" } else { "will" };
+        if cli.out.is_json() {
+            let reason = format!("target {} {will} be removed", target.display());
+            output::event("warning", Some(&link), None, &reason);
+        }
+        let message = if cli.dry_run {
+            "Keeping {link}, but its target {target} would be removed. Use --keep-link-targets to keep it too."
+        } else {
+            "Keeping {link}, but its target {target} will be removed. Use --keep-link-targets to keep it too."
+        };
+        cli.out.warning(tr!(
+            message,
+            link = link.display(),
+            target = target.display(),
+        ));
+    }
+}
+
+/// Finds the most recently modified entries if `--keep-recent` was given.
+/// Hidden entries are only considered if they may be removed.
+fn find_recent(cli: &mut CliOptions) -> eyre::Result<()> {
+    let Some(count) = cli.keep_recent else {
+        return Ok(());
+    };
+    let mut entries = Vec::new();
+    for entry in read_root(cli)? {
+        if !cli.hidden && entry.file_name().as_encoded_bytes().starts_with(b".") {
+            continue;
+        }
+        let modified = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .wrap_err_with(|| {
+                tr!(
+                    "Can't get modification time of {path}",
+                    path = cli.display_path(&entry.path()).display()
+                )
+            })?;
+        entries.push((modified, entry.file_name()));
+    }
+    entries.sort_unstable_by(|a, b| b.cmp(a));
+    let names = entries
+        .into_iter()
+        .take(count)
+        .map(|(_, name)| name)
+        .collect();
+    cli.recent = absolute_paths(cli, &names)?;
+    Ok(())
+}
+
+/// Finds the largest entries if `--keep-largest` was given. Like with
+/// `--keep-recent`, hidden entries are only considered if they may be
+/// removed.
+fn find_largest(cli: &mut CliOptions) -> eyre::Result<()> {
+    let Some(count) = cli.keep_largest else {
+        return Ok(());
+    };
+    let mut entries = Vec::new();
+    for entry in read_root(cli)? {
+        if !cli.hidden && entry.file_name().as_encoded_bytes().starts_with(b".") {
+            continue;
+        }
+        let size = size::tree_size(&entry.path())?;
+        entries.push((size, entry.file_name()));
+    }
+    entries.sort_unstable_by(|a, b| b.cmp(a));
+    let names = entries
+        .into_iter()
+        .take(count)
+        .map(|(_, name)| name)
+        .collect();
+    cli.largest = absolute_paths(cli, &names)?;
+    Ok(())
+}
+
+/// Lists the entries of the directory being cleaned.
+fn read_root(cli: &CliOptions) -> eyre::Result<Vec<DirEntry>> {
+    cli.root_dir()?
+        .list(&cli.root)
+        .wrap_err_with(|| tr!("Can't list contents of {dir}", dir = cli.root.display()))
+}
+
+/// Returns the absolute paths of the given entries of the directory being
+/// cleaned.
+fn absolute_paths(cli: &CliOptions, names: &HashSet<OsString>) -> eyre::Result<HashSet<PathBuf>> {
+    names
+        .iter()
+        .map(|name| {
+            std::path::absolute(cli.at_root(Path::new(name)))
+                .wrap_err(tr!("Can't make path absolute"))
+        })
+        .collect()
+}
+
+/// Reads `.leavekeep` and the files given with `--keep-file`. Unlike keep
+/// arguments, listed entries don't need to exist, so a keep file can be
+/// shared between directories.
+fn load_keep_files(cli: &mut CliOptions) -> eyre::Result<()> {
+    let mut keep_files = cli.keep_file.clone();
+    let default = cli.at_root(Path::new(keepfile::DEFAULT_NAME));
+    let has_default = default
+        .try_exists()
+        .wrap_err_with(|| tr!("Can't check if {path} exists", path = default.display()))?;
+    if has_default {
+        keep_files.push(default);
+    }
+    for keep_file in &keep_files {
+        let keepfile::KeepFile { names, files } = keepfile::read(keep_file)?;
+        // Keep the keep files themselves if they're in the target directory
+        let paths: Vec<PathBuf> = names
+            .iter()
+            .map(|name| cli.at_root(Path::new(name)))
+            .chain(files)
+            .collect();
+        for path in paths {
+            let absolute = std::path::absolute(&path)
+                .wrap_err_with(|| tr!("Can't make {path} absolute", path = path.display()))?;
+            cli.kept_by_file.insert(cli.case_key(absolute));
+        }
+    }
+    Ok(())
+}
+
+/// Expands `{date}`, `{hostname}`, etc. in keep arguments.
+fn expand_vars(cli: &mut CliOptions) -> eyre::Result<()> {
+    cli.files = cli
+        .files
+        .iter()
+        .map(|arg| vars::expand(arg.as_os_str()).map(PathBuf::from))
+        .collect::<Result<_, _>>()?;
+    Ok(())
+}
+
+/// Replaces keep arguments with the entries they match when `--glob` is given.
+/// Like in the shell, patterns which match nothing are left as-is, so they're
+/// reported as missing.
+fn expand_globs(cli: &mut CliOptions) -> eyre::Result<()> {
+    if !cli.glob {
+        return Ok(());
+    }
+    let names: Vec<String> = read_root(cli)?
+        .iter()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    let mut files = Vec::new();
+    for arg in &cli.files {
+        let text = arg.to_string_lossy();
+        let text = text.trim_start_matches("./");
+        if text.contains('/') {
+            bail!(tr!(
+                "Glob pattern {pattern} must match entries of the current directory",
+                pattern = arg.display()
+            ));
+        }
+        let pattern = glob::Pattern::parse(text)?;
+        let mut matched: Vec<PathBuf> = names
+            .iter()
+            .filter(|name| pattern.matches(name))
+            .map(PathBuf::from)
+            .collect();
+        cli.keep_globs.push(pattern);
+        if matched.is_empty() {
+            files.push(arg.clone());
+        } else {
+            matched.sort();
+            files.append(&mut matched);
+        }
+    }
+    cli.files = files;
+    Ok(())
+}
+
+/// Parses the arguments of a removal run given to a subcommand like `why` or
+/// `check`, and expands variables in its keep arguments.
+fn parse_run_args(args: &[OsString]) -> eyre::Result<CliOptions> {
+    let args: Vec<OsString> = std::iter::once(OsString::from("leave"))
+        .chain(args.iter().cloned())
+        .collect();
+    let mut cli = parse_args(&args)?;
+    if cli.command.is_some() || cli.remote.is_some() || cli.chdir.is_some() || cli.in_dir.is_some()
+    {
+        bail!(tr!(
+            "The arguments must describe a removal run in the current directory"
+        ));
+    }
+    configure_output(&mut cli);
+    cli.quarantine |= cli.journal;
+    apply_hidden_default(&mut cli)?;
+    prepare_keep_list(&mut cli)?;
+    Ok(cli)
+}
+
+/// Performs the scan and matching of a run with the given arguments and lists
+/// the entries it would remove, without removing them. Fails if there are any.
+fn check(args: &[OsString]) -> eyre::Result<ExitCode> {
+    let cli = parse_run_args(args)?;
+    let absolute_files = validate_args(&cli)?;
+    let entries = read_root(&cli)?;
+    let (mut doomed, _, had_failure) = select_entries(&cli, &absolute_files, entries)?;
+    doomed.sort_by_key(DirEntry::file_name);
+    for entry in &doomed {
+        println!("{}", cli.display_path(&entry.path()).display());
+    }
+    if !doomed.is_empty() {
+        cli.out.error(&[tr!(
+            "{count} unexpected entries found",
+            count = doomed.len()
+        )]);
+    }
+    Ok(if had_failure || !doomed.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Runs the decision pipeline of a run with the given arguments for a single
+/// entry and prints the verdict, without removing anything.
+fn explain(path: &Path, args: &[OsString]) -> eyre::Result<ExitCode> {
+    let cli = parse_run_args(args)?;
+    let absolute_files = validate_args(&cli)?;
+
+    // Decide about each ancestor of the path in turn, since the path is only
+    // reached if they're all descended into
+    let print_path = path.display();
+    let absolute = std::path::absolute(path)
+        .wrap_err_with(|| tr!("Can't make {path} absolute", path = print_path))?;
+    let cwd =
+        std::path::absolute(".").wrap_err(tr!("Can't get path to current working directory"))?;
+    let names: Option<Vec<&OsStr>> = absolute.strip_prefix(&cwd).ok().and_then(|relative| {
+        relative
+            .components()
+            .map(|component| match component {
+                std::path::Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect()
+    });
+    let Some(names) = names.filter(|names| !names.is_empty()) else {
+        println!(
+            "{}",
+            tr!(
+                "{path} would be kept: not in the current directory",
+                path = print_path
+            )
+        );
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    let mut entries = read_root(&cli)?;
+    for (i, name) in names.iter().enumerate() {
+        let entry = entries
+            .into_iter()
+            .find(|entry| entry.file_name() == *name)
+            .ok_or_else(|| eyre!(tr!("{path} doesn't exist", path = print_path)))?;
+        let entry_path = entry.path();
+        let mut decision = select_entry(&cli, &absolute_files, &entry)?;
+        if matches!(decision, Decision::Remove)
+            && let Some((_, reason)) = protect_entries(&cli, vec![entry.clone()])?.1.pop()
+        {
+            decision = Decision::Keep(reason.into());
+        }
+
+        let is_last = i + 1 == names.len();
+        let ancestor = cli.display_path(&entry_path);
+        let ancestor = ancestor.display();
+        match decision {
+            Decision::Descend if !is_last => {
+                entries = entry
+                    .read_dir(false)
+                    .wrap_err_with(|| tr!("Can't list contents of {dir}", dir = ancestor))?;
+                continue;
+            }
+            Decision::Descend => println!(
+                "{}",
+                tr!(
+                    "{path} would be kept: contains kept entries",
+                    path = print_path
+                )
+            ),
+            Decision::Remove if is_last => {
+                println!("{}", tr!("{path} would be removed", path = print_path));
+            }
+            Decision::Remove => println!(
+                "{}",
+                tr!(
+                    "{path} would be removed along with {ancestor}",
+                    path = print_path,
+                    ancestor = ancestor
+                )
+            ),
+            Decision::Keep(reason) if is_last => println!(
+                "{}",
+                tr!(
+                    "{path} would be kept: {reason}",
+                    path = print_path,
+                    reason = reason
+                )
+            ),
+            Decision::Keep(reason) => println!(
+                "{}",
+                tr!(
+                    "{path} would be kept along with {ancestor}: {reason}",
+                    path = print_path,
+                    ancestor = ancestor,
+                    reason = reason
+                )
+            ),
+        }
+        break;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Decides which of the scanned entries to remove.
+///
+/// Returns the entries to remove, the entries kept along with the reason, and
+/// whether an error occurred for any entry.
+fn select_entries(
+    cli: &CliOptions,
+    absolute_files: &HashSet<PathBuf>,
+    mut entries: Vec<DirEntry>,
+) -> eyre::Result<(Vec<DirEntry>, Kept, bool)> {
+    let mut had_failure = false;
+    let mut doomed = Vec::new();
+    let mut kept = Vec::new();
+    sort::sort(cli.sort_order(), &mut entries);
+    let mut queue = VecDeque::from(entries);
+    while let Some(entry) = queue.pop_front() {
+        let result = select_entry(cli, absolute_files, &entry).map(|decision| (decision, entry));
+        match result {
+            Ok((Decision::Remove, entry)) => doomed.push(entry),
+            Ok((Decision::Keep(reason), entry)) => {
+                print_entry(cli, &entry, "kept", &reason);
+                kept.push((entry, reason));
+            }
+            Ok((Decision::Descend, entry)) => {
+                print_entry(cli, &entry, "kept", DESCEND_REASON);
+                let dir = entry.path();
+                let children = entry.read_dir(false);
+                kept.push((entry, DESCEND_REASON.into()));
+                match children {
+                    Ok(mut children) => {
+                        sort::sort(cli.sort_order(), &mut children);
+                        queue.extend(children);
+                    }
+                    Err(err) => {
+                        had_failure = true;
+                        print_error(
+                            &cli.out,
+                            &eyre::Report::new(err).wrap_err(tr!(
+                                "Can't list contents of {dir}",
+                                dir = cli.display_path(&dir).display()
+                            )),
+                        );
+                    }
+                }
+            }
+            Err(err) => {
+                had_failure = true;
+                print_error(&cli.out, &err);
+            }
+        }
+    }
+    let protected;
+    (doomed, protected) = protect_entries(cli, doomed)?;
+    for (entry, reason) in protected {
+        print_entry(cli, &entry, "kept", reason);
+        kept.push((entry, reason.into()));
+    }
+    if cli.keep_symlink_targets {
+        let links;
+        (links, doomed) = doomed
+            .into_iter()
+            .partition(|entry| links_to_kept(entry, &kept));
+        for entry in links {
+            print_entry(cli, &entry, "kept", "links to a kept entry");
+            kept.push((entry, "links to a kept entry".into()));
+        }
+    }
+    if cli.keep_hardlinks {
+        let inodes: HashSet<_> = kept
+            .iter()
+            .filter_map(|(entry, _)| linked_inode(entry))
+            .collect();
+        let links;
+        (links, doomed) = doomed
+            .into_iter()
+            .partition(|entry| linked_inode(entry).is_some_and(|inode| inodes.contains(&inode)));
+        for entry in links {
+            print_entry(cli, &entry, "kept", "hard link to a kept file");
+            kept.push((entry, "hard link to a kept file".into()));
+        }
+    }
+    warn_dangling_links(cli, &doomed);
+    if cli.explain {
+        explain_entries(cli, &doomed, &kept);
+    }
+    Ok((doomed, kept, had_failure))
+}
+
+/// Prints why each entry is kept or removed, sorted by path, for
+/// `--explain`.
+fn explain_entries(cli: &CliOptions, doomed: &[DirEntry], kept: &Kept) {
+    let mut lines: Vec<(PathBuf, String)> = kept
+        .iter()
+        .map(|(entry, reason)| {
+            let marker = if [PACKAGED_REASON, PROTECTED_PID_REASON].contains(&reason.as_str()) {
+                Marker::Protected
+            } else {
+                Marker::Kept
+            };
+            let explanation = tr!("kept: {reason}", reason = reason);
+            (entry.path(), cli.out.paint(marker, &explanation))
+        })
+        .chain(doomed.iter().map(|entry| {
+            let explanation = tr!("deleted: no rule matched");
+            (entry.path(), cli.out.paint(Marker::Removed, explanation))
+        }))
+        .collect();
+    lines.sort();
+    for (path, explanation) in lines {
+        cli.out.message(format_args!(
+            "{}: {explanation}",
+            cli.display_path(&path).display()
+        ));
+    }
+}
+
+/// Entries kept by [`select_entries`], along with the reason.
+type Kept = Vec<(DirEntry, String)>;
+
+/// Checks that every entry in `doomed` can be removed, for
+/// `--all-or-nothing`. Fails without removing anything if one can't, or if
+/// an error occurred while selecting entries.
+fn preflight(cli: &CliOptions, doomed: &[DirEntry], had_failure: bool) -> eyre::Result<()> {
+    if had_failure {
+        bail!(tr!(
+            "Not all entries could be checked, so nothing was removed"
+        ));
+    }
+    let device = if cli.cross_file_systems || cli.quarantine || cli.trash {
+        None
+    } else {
+        let metadata =
+            fs::metadata(&cli.root).wrap_err(tr!("Can't get metadata of current directory"))?;
+        Some(device_id(&metadata))
+    };
+    let probe = preflight::Probe {
+        recursive: cli.recursive,
+        dirs: cli.dirs,
+        force: cli.force,
+        moved: cli.quarantine || cli.trash,
+        follow_symlinks: cli.follow_symlinks,
+        device,
+    };
+    if !doomed.is_empty() {
+        preflight::check_root(&cli.root)?;
+    }
+    let mut failures = 0;
+    for entry in doomed {
+        if let Err(err) = probe.check(&entry.path()) {
+            failures += 1;
+            print_error(
+                &cli.out,
+                &err.wrap_err(tr!(
+                    "Can't remove {path}",
+                    path = cli.display_path(&entry.path()).display()
+                )),
+            );
+        }
+    }
+    if failures > 0 {
+        bail!(tr!(
+            "{failures} entries can't be removed, so nothing was removed",
+            failures = failures
+        ));
+    }
+    Ok(())
+}
+
+/// Lets the user review the selection with `--confirm` or `--pick`. Returns
+/// the entries to remove and keep, or [`None`] if the user cancelled.
+fn review_plan(
+    cli: &CliOptions,
+    doomed: Vec<DirEntry>,
+    mut kept: Kept,
+) -> eyre::Result<Option<(Vec<DirEntry>, Kept)>> {
+    if cli.confirm && !confirm_plan(cli, &doomed, &kept)? {
+        return Ok(None);
+    }
+    if !cli.pick {
+        return Ok(Some((doomed, kept)));
+    }
+    let label = |entry: &DirEntry| cli.display_path(&entry.path()).display().to_string();
+    let mut items: Vec<tui::Item> = doomed
+        .iter()
+        .map(|entry| tui::Item {
+            label: label(entry),
+            kept_reason: None,
+            keep: false,
+        })
+        .chain(kept.iter().map(|(entry, reason)| tui::Item {
+            label: label(entry),
+            kept_reason: Some(reason.clone()),
+            keep: true,
+        }))
+        .collect();
+    if !tui::pick(&mut items, cli.out.theme())? {
+        return Ok(None);
+    }
+    let mut remaining = Vec::new();
+    for (entry, item) in doomed.into_iter().zip(items) {
+        if item.keep {
+            print_entry(cli, &entry, "kept", PICKED_REASON);
+            kept.push((entry, PICKED_REASON.into()));
+        } else {
+            remaining.push(entry);
+        }
+    }
+    Ok(Some((remaining, kept)))
+}
+
+/// Lists the entries which will be removed and kept, along with totals, and
+/// asks once whether to continue. Returns whether the user agreed.
+fn confirm_plan(cli: &CliOptions, doomed: &[DirEntry], kept: &Kept) -> eyre::Result<bool> {
+    let use_dialog = !prompt::is_interactive();
+    if use_dialog && !cli.dialog {
+        bail!(tr!(
+            "--confirm needs a terminal to ask for confirmation; standard input isn't one"
+        ));
+    }
+    let measured = measure_entries(doomed);
+    let mut usage = Usage::default();
+    eprintln!("{}", tr!("Entries to remove:"));
+    for entry in doomed {
+        eprintln!("  {}", cli.display_path(&entry.path()).display());
+        if let Some(entry_usage) = measured.get(&entry.path()) {
+            usage.add(entry_usage.clone());
+        }
+    }
+    *cli.measured.lock().unwrap_or_else(PoisonError::into_inner) = Some(measured);
+    eprintln!("{}", tr!("Entries to keep:"));
+    for (entry, reason) in kept {
+        eprintln!("  {} ({reason})", cli.display_path(&entry.path()).display());
+    }
+    let totals = tr!(
+        "{removed} entries ({size}) will be removed and {kept} kept.",
+        removed = doomed.len(),
+        size = size::format_size(usage.freed_bytes()),
+        kept = kept.len()
+    );
+    eprintln!("{totals}");
+    if use_dialog {
+        let question = format!("{totals}\n\n{}", tr!("Continue?"));
+        return prompt::confirm_dialog(&question)?.ok_or_else(|| {
+            eyre!(tr!(
+                "--dialog needs zenity or kdialog and a graphical display to ask for confirmation"
+            ))
+        });
+    }
+    prompt::confirm(tr!("continue? [y/N]"))
+}
+
+/// Entries kept by [`protect_entries`], along with the reason.
+type Protected = Vec<(DirEntry, &'static str)>;
+
+/// Reason for keeping directories which are descended into because they
+/// contain kept entries.
+const DESCEND_REASON: &str = "contains kept entries";
+
+/// Reason for keeping entries which other processes are using, for
+/// `--skip-in-use`.
+const IN_USE_REASON: &str = "in use by another process";
+
+/// Reason for keeping entries the user picked with `--pick`.
+const PICKED_REASON: &str = "picked to keep";
+
+/// Reason for keeping entries owned by a package, for `--keep-packaged`.
+const PACKAGED_REASON: &str = "owned by an installed package";
+
+/// Reason for keeping entries open in a process given with `--protect-pid`.
+const PROTECTED_PID_REASON: &str = "in use by a protected process";
+
+/// Reason given for directories left because `--max-depth` was reached.
+const DEEP_REASON: &str = "has entries deeper than --max-depth";
+
+/// Reason given for directories left because they contain special files.
+const SPECIAL_REASON: &str = "contains special files";
+
+/// Returns whether the given entry is a symbolic link to one of the kept
+/// entries, or to something inside one, for `--keep-symlink-targets`.
+/// Dangling links never point to a kept entry.
+fn links_to_kept(entry: &DirEntry, kept: &Kept) -> bool {
+    let is_link = entry
+        .file_type()
+        .is_ok_and(|file_type| file_type.is_symlink());
+    let Some(target) = is_link
+        .then(|| fs::canonicalize(entry.path()).ok())
+        .flatten()
+    else {
+        return false;
+    };
+    kept.iter().any(|(kept, reason)| {
+        fs::canonicalize(kept.path()).is_ok_and(|kept| {
+            // Only some entries inside a directory we descended into are kept
+            target == kept || (reason != DESCEND_REASON && target.starts_with(&kept))
+        })
+    })
+}
+
+/// Returns the device and inode numbers of the given file, if it has more
+/// than one hard link, for `--keep-hardlinks`.
+#[cfg(unix)]
+fn linked_inode(entry: &DirEntry) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = entry.metadata().ok()?;
+    (!metadata.is_dir() && metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+/// Returns the device and inode numbers of the given file, if it has more
+/// than one hard link, for `--keep-hardlinks`.
+#[cfg(not(unix))]
+fn linked_inode(_entry: &DirEntry) -> Option<(u64, u64)> {
+    None
+}
+
+/// Applies the protections which need information about all selected entries
+/// at once. Returns the entries which are still to be removed and those which
+/// were protected, along with the reason.
+fn protect_entries(
+    cli: &CliOptions,
+    mut doomed: Vec<DirEntry>,
+) -> eyre::Result<(Vec<DirEntry>, Protected)> {
+    let mut protected = Vec::new();
+    if cli.keep_packaged {
+        let owned;
+        (doomed, owned) = partition_packaged(doomed)?;
+        protected.extend(owned.into_iter().map(|entry| (entry, PACKAGED_REASON)));
+    }
+    if !cli.protect_pid.is_empty() {
+        let mut open_paths = Vec::new();
+        for &pid in &cli.protect_pid {
+            open_paths.extend(process::open_paths(pid)?);
+        }
+        let in_use;
+        (in_use, doomed) = doomed.into_iter().partition(|entry| {
+            std::path::absolute(entry.path())
+                .is_ok_and(|path| process::is_in_use(&path, &open_paths))
+        });
+        protected.extend(
+            in_use
+                .into_iter()
+                .map(|entry| (entry, PROTECTED_PID_REASON)),
+        );
+    }
+    Ok((doomed, protected))
+}
+
+/// Removes the given entries, printing any errors which occur.
+///
+/// Returns whether removing any entry failed, and the number of entries
+/// removed.
+fn remove_entries(
+    cli: &CliOptions,
+    doomed: Vec<DirEntry>,
+    audit_log: Option<&mut AuditLog>,
+    control: Option<&Control>,
+    timings: Option<&mut Timings>,
+) -> (bool, usize) {
+    if cli.sync && !cli.dry_run {
+        cli.changes.start();
+    }
+    // Reuse the measurements made for --confirm, if any
+    let measured = cli
+        .measured
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+        .or_else(|| (cli.summary || cli.report_size).then(|| measure_entries(&doomed)));
+    if let Some(measured) = &measured {
+        cli.out
+            .progress
+            .set_total_bytes(measured.values().map(Usage::total_bytes).sum());
+    }
+    let remover = Remover {
+        cli,
+        in_use: cli.skip_in_use.then(|| process::InUse::scan(&cli.out)),
+        destination: Destination::from_options(cli).map(Arc::new),
+        audit_log: audit_log.map(Mutex::new),
+        control,
+        action: if cli.dry_run {
+            "would remove"
+        } else if cli.quarantine {
+            "quarantined"
+        } else if cli.trash {
+            "trashed"
+        } else {
+            "removed"
+        },
+        ask: AtomicBool::new(cli.interactive && !cli.dry_run),
+        had_failure: AtomicBool::new(false),
+        stopped: AtomicBool::new(false),
+        cancelled: AtomicBool::new(false),
+        removed: AtomicUsize::new(0),
+        usage: measured.is_some().then(|| Mutex::new(Usage::default())),
+        measured,
+    };
+
+    let total = doomed.len();
+    let workers = remover.remove_all(doomed);
+    let processed: usize = workers.iter().map(|worker| worker.entries).sum();
+    if let Some(timings) = timings {
+        timings.record_workers(workers);
+    }
+    cli.out.progress.finish();
+    if remover.cancelled.load(Ordering::Relaxed) {
+        eprintln!(
+            "{}",
+            tr!(
+                "Cancelled; {count} entries were not removed",
+                count = total - processed
+            )
+        );
+    }
+    if interrupt::is_interrupted() {
+        eprintln!(
+            "{}",
+            tr!(
+                "Interrupted; {removed} entries were removed and {left} were not",
+                removed = remover.removed.load(Ordering::Relaxed),
+                left = total - remover.removed.load(Ordering::Relaxed)
+            )
+        );
+    }
+
+    if let Some(usage) = &remover.usage {
+        let usage = std::mem::take(&mut *usage.lock().unwrap_or_else(PoisonError::into_inner));
+        if cli.report_size {
+            eprintln!("{}\ttotal", size::format_size(usage.freed_disk_bytes()));
+        }
+        if cli.summary {
+            print_summary(cli, &usage);
+        }
+    }
+
+    if !sync_changed(cli) {
+        remover.had_failure.store(true, Ordering::Relaxed);
+    }
+    (
+        remover.had_failure.into_inner(),
+        remover.removed.into_inner(),
+    )
+}
+
+/// Measures the entries to remove before any of them is, for the summary,
+/// size report and progress line. Removing a file with several hard links
+/// lowers the link count of the others, so measuring each entry just before
+/// removing it would miss links among them. Failing to measure an entry only
+/// affects the totals, so it isn't an error.
+fn measure_entries(doomed: &[DirEntry]) -> HashMap<PathBuf, Usage> {
+    doomed
+        .iter()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let usage = Usage::measure(&path).ok()?;
+            Some((path, usage))
+        })
+        .collect()
+}
+
+/// State shared by the threads removing entries.
+struct Remover<'a> {
+    cli: &'a CliOptions,
+    /// What other processes are using, if `--skip-in-use` is given.
+    in_use: Option<process::InUse>,
+    destination: Option<Arc<Destination>>,
+    audit_log: Option<Mutex<&'a mut AuditLog>>,
+    control: Option<&'a Control>,
+    /// Action to report for removed entries.
+    action: &'static str,
+    /// Whether to ask before removing each entry. Answering "all" clears it.
+    ask: AtomicBool,
+    had_failure: AtomicBool,
+    /// Set when no more entries should be removed.
+    stopped: AtomicBool,
+    /// Set when the run was cancelled through the control socket.
+    cancelled: AtomicBool,
+    /// Number of entries removed.
+    removed: AtomicUsize,
+    /// What was removed, if a summary or size report was requested.
+    usage: Option<Mutex<Usage>>,
+    /// What each entry to remove holds, measured before anything was
+    /// removed, if a summary or size report was requested.
+    measured: Option<HashMap<PathBuf, Usage>>,
+}
+
+impl Remover<'_> {
+    /// Removes the given entries with `--jobs` workers, which take entries
+    /// from a shared queue until it's empty or the run is stopped. Returns
+    /// what each worker did.
+    fn remove_all(&self, doomed: Vec<DirEntry>) -> Vec<Worker> {
+        let queue = Mutex::new(doomed.into_iter());
+        let work = || {
+            let mut worker = Worker::default();
+            while !self.stopped.load(Ordering::Relaxed) {
+                let Some(entry) = queue.lock().unwrap_or_else(PoisonError::into_inner).next()
+                else {
+                    break;
+                };
+                let start = Instant::now();
+                if self.remove(&entry) {
+                    worker.entries += 1;
+                }
+                worker.busy += start.elapsed();
+            }
+            worker
+        };
+        if self.cli.jobs.get() == 1 {
+            return vec![work()];
+        }
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.cli.jobs.get())
+                .map(|_| scope.spawn(work))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_default())
+                .collect()
+        })
+    }
+
+    /// Removes one entry, printing any errors which occur. Returns false if
+    /// the entry was skipped because the run was stopped.
+    fn remove(&self, entry: &DirEntry) -> bool {
+        let cli = self.cli;
+        // A paused run is interrupted by returning from the checkpoint
+        if let Some(control) = self.control
+            && !control.checkpoint()
+        {
+            self.cancelled.store(true, Ordering::Relaxed);
+            self.had_failure.store(true, Ordering::Relaxed);
+            self.stop();
+            return false;
+        }
+        if interrupt::is_interrupted() {
+            self.had_failure.store(true, Ordering::Relaxed);
+            self.stop();
+            return false;
+        }
+        if self.skip_in_use(entry) {
+            return true;
+        }
+
+        if self.ask.load(Ordering::Relaxed) {
+            let question = tr!(
+                "remove {path}? [y/N/a]",
+                path = cli.display_path(&entry.path()).display()
+            );
+            match prompt::ask(&question) {
+                Ok(Answer::Yes) => (),
+                Ok(Answer::All) => self.ask.store(false, Ordering::Relaxed),
+                Ok(Answer::No) => {
+                    print_entry(cli, entry, "kept", "declined at prompt");
+                    return true;
+                }
+                Err(err) => {
+                    self.fail(&err);
+                    self.stop();
+                    return false;
+                }
+            }
+        }
+
+        if !self.hook_allows(entry) {
+            return true;
+        }
+
+        // Gather metadata for output before the entry is gone
+        let metadata = (self.audit_log.is_some()
+            || cli.verbose
+            || cli.format.as_ref().is_some_and(Template::needs_metadata)
+            || cli.out.is_json())
+        .then(|| entry.metadata().ok())
+        .flatten();
+        let usage = self
+            .measured
+            .as_ref()
+            .and_then(|measured| measured.get(&entry.path()).cloned());
+        throttle::wait();
+        let result = process_entry(self, entry);
+        if let Some(usage) = &usage {
+            cli.out.progress.advance(usage.total_bytes());
+        }
+        match result {
+            Ok(outcome) if let Some(reason) = outcome.kept_reason() => {
+                print_entry_with(cli, entry, metadata.as_ref(), "kept", reason);
+            }
+            Ok(outcome) => {
+                self.removed.fetch_add(1, Ordering::Relaxed);
+                report_removed(cli, entry, metadata.as_ref(), self.action);
+                if let Some(usage) = usage {
+                    self.add_usage(entry, usage);
+                }
+                let moved_path = match outcome {
+                    Outcome::Moved(path) => Some(path),
+                    _ => None,
+                };
+                if !cli.dry_run {
+                    cli.changes.entry_changed(&entry.path());
+                }
+                if let Some(moved_path) = &moved_path {
+                    cli.changes.entry_changed(moved_path);
+                }
+                if let Some(log) = &self.audit_log
+                    && let Err(err) = audit_entry(
+                        &mut log.lock().unwrap_or_else(PoisonError::into_inner),
+                        moved_path.as_deref(),
+                        entry,
+                        metadata.as_ref(),
+                        self.action,
+                    )
+                {
+                    // Stop rather than remove anything without a record
+                    self.fail(&err);
+                    self.stop();
+                }
+            }
+            // If an error occurs, print it but don't abort
+            Err(err) => self.report_failure(entry, metadata.as_ref(), &err),
+        }
+        if let Some(control) = self.control {
+            control.entry_done();
+        }
+        true
+    }
+
+    /// Reports that removing the entry failed, or was abandoned partway
+    /// through with `--timeout`, and marks the run as failed.
+    fn report_failure(
+        &self,
+        entry: &DirEntry,
+        metadata: Option<&fs::Metadata>,
+        err: &eyre::Report,
+    ) {
+        let cli = self.cli;
+        let reason = format_error_reason(err);
+        let action = if timeout::is_partial(err) {
+            "partially removed"
+        } else {
+            "failed"
+        };
+        print_entry_with(cli, entry, metadata, action, &reason);
+        if !cli.dry_run {
+            cli.logger.failed(&cli.out, &entry.path(), &reason);
+        }
+        // The entry's event already reports the error
+        self.had_failure.store(true, Ordering::Relaxed);
+        print_error_text(&cli.out, err);
+    }
+
+    /// Runs the pre-delete hook, if any, and returns whether it allows
+    /// removing the entry.
+    fn hook_allows(&self, entry: &DirEntry) -> bool {
+        let Some(command) = &self.cli.pre_delete_hook else {
+            return true;
+        };
+        match hooks::allows_removal(command, &entry.path()) {
+            Ok(true) => true,
+            Ok(false) => {
+                print_entry(self.cli, entry, "kept", "rejected by pre-delete hook");
+                false
+            }
+            Err(err) => {
+                self.fail(&err);
+                false
+            }
+        }
+    }
+
+    /// Returns whether the entry is kept because another process is using it,
+    /// with `--skip-in-use`. Such entries are checked right before removal,
+    /// and reported with a warning.
+    fn skip_in_use(&self, entry: &DirEntry) -> bool {
+        let path = entry.path();
+        if !self
+            .in_use
+            .as_ref()
+            .is_some_and(|in_use| in_use.contains(&path))
+        {
+            return false;
+        }
+        self.cli.out.warning(tr!(
+            "Skipping {path}, which is in use by another process",
+            path = self.cli.display_path(&path).display()
+        ));
+        print_entry(self.cli, entry, "kept", IN_USE_REASON);
+        true
+    }
+
+    /// Adds a removed entry's usage to the total, and prints it for
+    /// `--report-size`.
+    fn add_usage(&self, entry: &DirEntry, usage: Usage) {
+        if self.cli.report_size {
+            eprintln!(
+                "{}\t{}",
+                size::format_size(usage.freed_disk_bytes()),
+                self.cli.display_path(&entry.path()).display()
+            );
+        }
+        if let Some(total) = &self.usage {
+            total
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .add(usage);
+        }
+    }
+
+    /// Prints an error and marks the run as failed.
+    fn fail(&self, err: &eyre::Report) {
+        self.had_failure.store(true, Ordering::Relaxed);
+        print_error(&self.cli.out, err);
+    }
+
+    /// Stops removing entries.
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Prints the output for an entry which was removed.
+fn report_removed(
+    cli: &CliOptions,
+    entry: &DirEntry,
+    metadata: Option<&fs::Metadata>,
+    action: &str,
+) {
+    print_entry_with(cli, entry, metadata, action, "");
+    if !cli.dry_run {
+        cli.out.progress.removed(&cli.display_path(&entry.path()));
+        cli.logger.removed(&cli.out, action, &entry.path());
+    }
+    if cli.verbose && !cli.dry_run {
+        let kind = if metadata.is_some_and(fs::Metadata::is_dir) {
+            " directory"
+        } else {
+            ""
+        };
+        cli.out.message(format_args!(
+            "{}{kind} '{}'",
+            cli.out.paint(Marker::Removed, action),
+            cli.display_path(&entry.path()).display()
+        ));
+    }
+}
+
+/// Prints a one-line summary of what was removed, e.g. `removed 37 files, 4
+/// directories, freed 1.2 GiB`. The space of files with hard links which
+/// weren't removed is reported as pending, since it isn't freed yet.
+fn print_summary(cli: &CliOptions, usage: &Usage) {
+    let message = if cli.dry_run {
+        tr!("would remove {files}, {dirs}, would free {size}")
+    } else if cli.quarantine {
+        tr!("quarantined {files}, {dirs}, moved {size}")
+    } else if cli.trash {
+        tr!("trashed {files}, {dirs}, moved {size}")
+    } else {
+        tr!("removed {files}, {dirs}, freed {size}")
+    };
+    let mut summary = i18n::fill(
+        message,
+        &[
+            // Links are files as far as the summary is concerned
+            ("files", &count_files(usage.files + usage.symlinks)),
+            ("dirs", &count_dirs(usage.dirs)),
+            ("size", &size::format_size(usage.freed_bytes())),
+        ],
+    );
+    let pending = usage.pending_bytes();
+    if pending > 0 {
+        summary.push_str(", ");
+        summary.push_str(&tr!(
+            "{size} pending on other hard links",
+            size = size::format_size(pending)
+        ));
+    }
+    eprintln!("{summary}");
+}
+
+/// Formats a number of files, e.g. `3 files`.
+fn count_files(n: u64) -> String {
+    if n == 1 {
+        tr!("{count} file", count = n)
+    } else {
+        tr!("{count} files", count = n)
+    }
+}
+
+/// Formats a number of directories, e.g. `3 directories`.
+fn count_dirs(n: u64) -> String {
+    if n == 1 {
+        tr!("{count} directory", count = n)
+    } else {
+        tr!("{count} directories", count = n)
+    }
+}
+
+/// Formats a number of symbolic links, e.g. `3 symbolic links`.
+fn count_symlinks(n: u64) -> String {
+    if n == 1 {
+        tr!("{count} symbolic link", count = n)
+    } else {
+        tr!("{count} symbolic links", count = n)
+    }
+}
+
+/// Appends a record of a removed, quarantined, or trashed entry to the audit
+/// log.
+fn audit_entry(
+    log: &mut AuditLog,
+    moved_path: Option<&Path>,
+    entry: &DirEntry,
+    metadata: Option<&fs::Metadata>,
+    action: &str,
+) -> eyre::Result<()> {
+    let path = std::path::absolute(entry.path()).wrap_err(tr!("Can't make path absolute"))?;
+    // Only files which were moved aside still have contents to fingerprint
+    let content_hash = match (moved_path, metadata) {
+        (Some(path), Some(metadata)) if metadata.is_file() => Some(audit::hash_file(path)?),
+        _ => None,
+    };
+    log.record(
+        action,
+        &path,
+        metadata.map(fs::Metadata::len),
+        content_hash.as_deref(),
+    )
+}
+
+/// Prints the paths of the entries which would be removed, sorted, for
+/// `--list` and `--print0`.
+fn list_doomed(cli: &CliOptions, mut doomed: Vec<DirEntry>) -> eyre::Result<()> {
+    doomed.sort_by_key(DirEntry::path);
+    write_paths(cli, &doomed)
+}
+
+/// Prints the paths of the given entries, each ended with NUL if `--print0`
+/// is given or with a newline otherwise.
+fn write_paths(cli: &CliOptions, entries: &[DirEntry]) -> eyre::Result<()> {
+    let terminator = if cli.print0 { b'\0' } else { b'\n' };
+    let mut stdout = std::io::stdout().lock();
+    for entry in entries {
+        let path = cli.display_path(&entry.path());
+        stdout
+            .write_all(path.as_os_str().as_encoded_bytes())
+            .and_then(|()| stdout.write_all(&[terminator]))
+            .wrap_err(tr!("Can't write to standard output"))?;
+    }
+    stdout
+        .flush()
+        .wrap_err(tr!("Can't write to standard output"))
+}
+
+/// Prints how many entries of each type would be removed and kept, and their
+/// total size, for `--stat`.
+fn print_stats(cli: &CliOptions, doomed: &[DirEntry], kept: &Kept) {
+    let measure = |entry: &DirEntry| {
+        Usage::measure(&entry.path()).unwrap_or_else(|err| {
+            // Failing to measure an entry only affects the totals
+            cli.out.warning(format_args!("{err:#}"));
+            Usage::default()
+        })
+    };
+    let mut removed = Usage::default();
+    for entry in doomed {
+        removed.add(measure(entry));
+    }
+    let mut remaining = Usage::default();
+    for (entry, reason) in kept {
+        // The contents of directories we descended into are counted on their
+        // own
+        if reason == DESCEND_REASON {
+            remaining.dirs += 1;
+        } else {
+            remaining.add(measure(entry));
+        }
+    }
+    // Only what's freed counts as removed, while everything kept counts
+    for (message, usage, size) in [
+        (
+            tr!("remove: {files}, {dirs}, {links}, {size}"),
+            &removed,
+            removed.freed_bytes(),
+        ),
+        (
+            tr!("keep: {files}, {dirs}, {links}, {size}"),
+            &remaining,
+            remaining.total_bytes(),
+        ),
+    ] {
+        println!(
+            "{}",
+            i18n::fill(
+                message,
+                &[
+                    ("files", &count_files(usage.files)),
+                    ("dirs", &count_dirs(usage.dirs)),
+                    ("links", &count_symlinks(usage.symlinks)),
+                    ("size", &size::format_size(size)),
+                ]
+            )
+        );
+    }
+}
+
+/// Prints the entries remaining in the current directory, sorted by name.
+/// Paths are ended with NUL if `--print0` is given.
+fn list_remaining(cli: &CliOptions) -> eyre::Result<()> {
+    let mut entries = read_root(cli)?;
+    entries.sort_by_key(DirEntry::file_name);
+    if cli.print0 {
+        return write_paths(cli, &entries);
+    }
+    for entry in entries {
+        if cli.format.is_some() || cli.out.is_json() {
+            print_entry(cli, &entry, "remaining", "");
+        } else {
+            cli.out.message(format_args!(
+                "{}",
+                cli.display_path(&entry.path()).display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the keep arguments for likely mistakes and returns their absolute
+/// paths.
+fn validate_args(cli: &CliOptions) -> eyre::Result<HashSet<PathBuf>> {
+    // Check arguments given to make sure they exist. If a user runs `leave
+    // file.txt` but `file.txt` doesn't exist, it's probably a typo and we
+    // shouldn't delete anything. The `-f, --force` flag overrides this.
+    if !cli.force {
+        if !cli.has_keep_criteria() {
+            bail!("{} {}", tr!("No files provided."), tr!(MISTAKE_MSG));
+        }
+
+        let missing = missing_args(cli)?;
+        if !missing.is_empty() {
+            let entry_names = root_entry_names(cli)?;
+            let problems = sanity::check(cli.files.len(), &missing, &entry_names);
+            bail!(
+                "{} {}",
+                report_missing(cli, &missing, &problems),
+                tr!(MISTAKE_MSG)
+            );
+        }
+    }
+
+    // Get absolute paths to all arguments
+    let cwd_absolute = std::path::absolute(&cli.root)
+        .wrap_err(tr!("Can't get path to current working directory"))?;
+    cli.files
+        .iter()
+        .map(|p| -> eyre::Result<PathBuf> {
+            let abs_path = std::path::absolute(cli.at_root(p)).wrap_err_with(|| {
+                tr!(
+                    "Can't make {path} absolute",
+                    path = cli.display_path(p).display()
+                )
+            })?;
+            // Entries of subdirectories are only cleaned with -r
+            let is_nested = abs_path
+                .parent()
+                .is_some_and(|parent| *parent != cwd_absolute);
+            if !abs_path.starts_with(&cwd_absolute)
+                || abs_path == cwd_absolute
+                || (is_nested && !cli.recursive)
+            {
+                bail!(
+                    "{} {}",
+                    tr!(
+                        "{path} is not in the current directory; it would be removed anyways.",
+                        path = cli.display_path(p).display()
+                    ),
+                    tr!(MISTAKE_MSG)
+                )
+            }
+            Ok(cli.case_key(abs_path))
+        })
+        .collect()
+}
+
+/// Returns whether the keep argument `arg` exists. Arguments inside the
+/// directory being cleaned are looked up through its handle, so they're
+/// checked against the same directory whose entries are selected.
+fn arg_exists(cli: &CliOptions, arg: &Path) -> eyre::Result<bool> {
+    let inside = arg.file_name().is_some()
+        && arg.components().all(|component| {
+            matches!(
+                component,
+                std::path::Component::Normal(_) | std::path::Component::CurDir
+            )
+        });
+    if inside {
+        Ok(cli.root_dir()?.exists(arg)?)
+    } else {
+        Ok(cli.at_root(arg).try_exists()?)
+    }
+}
+
+/// Refuses a run with `-f` if the keep arguments which don't exist look like
+/// mistakes, unless `--no-sanity-check` is given. Without `-f`,
+/// [`validate_args`] refuses any keep argument which doesn't exist.
+fn check_sanity(cli: &CliOptions) -> eyre::Result<()> {
+    if !cli.force || cli.no_sanity_check {
+        return Ok(());
+    }
+    let missing = missing_args(cli)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let entry_names = root_entry_names(cli)?;
+    let problems = sanity::check(cli.files.len(), &missing, &entry_names);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "{} {}",
+        report_missing(cli, &missing, &problems),
+        tr!("Refusing to continue, even with -f. Use --no-sanity-check to override.")
+    )
+}
+
+/// Returns the keep arguments which don't exist.
+fn missing_args(cli: &CliOptions) -> eyre::Result<Vec<&PathBuf>> {
+    let mut missing = Vec::new();
+    for arg in &cli.files {
+        let exists = arg_exists(cli, arg).wrap_err_with(|| {
+            tr!(
+                "Can't check if {path} exists",
+                path = cli.display_path(arg).display()
+            )
+        })?;
+        if !exists {
+            missing.push(arg);
+        }
+    }
+    Ok(missing)
+}
+
+/// Returns the names of the entries in the directory being cleaned, for the
+/// sanity checks.
+fn root_entry_names(cli: &CliOptions) -> eyre::Result<Vec<OsString>> {
+    Ok(read_root(cli)?.iter().map(DirEntry::file_name).collect())
+}
+
+/// Prints warnings about keep arguments which don't exist, with targeted
+/// hints where the sanity checks found a likely cause. Returns the message to
+/// abort with.
+fn report_missing(
+    cli: &CliOptions,
+    missing: &[&PathBuf],
+    problems: &[sanity::Problem<'_>],
+) -> String {
+    for arg in missing {
+        let hint = problems.iter().find(|problem| {
+            matches!(
+                problem,
+                sanity::Problem::CaseMismatch { arg: other, .. }
+                    | sanity::Problem::Typo { arg: other, .. }
+                    | sanity::Problem::UnmatchedGlob { arg: other } if other == arg
+            )
+        });
+        match hint {
+            Some(problem) => cli.out.warning(problem),
+            None => cli.out.warning(tr!(
+                "{arg} doesn't exist.",
+                arg = cli.display_path(arg).display()
+            )),
+        }
+    }
+    problems
+        .iter()
+        .find(|problem| matches!(problem, sanity::Problem::NothingKept))
+        .map_or_else(
+            || tr!("One or more provided files don't exist.").to_owned(),
+            ToString::to_string,
+        )
+}
+
+/// Splits the given entries into those which don't belong to an installed
+/// package and those which do.
+fn partition_packaged(doomed: Vec<DirEntry>) -> eyre::Result<(Vec<DirEntry>, Vec<DirEntry>)> {
+    let doomed_absolute: Vec<PathBuf> = doomed
+        .iter()
+        .map(|entry| std::path::absolute(entry.path()))
+        .collect::<Result<_, _>>()
+        .wrap_err(tr!("Can't make path absolute"))?;
+    let owned = packages::owned_paths(doomed_absolute.iter().map(PathBuf::as_path))?;
+    let (owned_entries, unowned_entries): (Vec<_>, Vec<_>) = doomed
+        .into_iter()
+        .zip(doomed_absolute)
+        .partition(|(_, path)| owned.contains(path));
+    Ok((
+        unowned_entries
+            .into_iter()
+            .map(|(entry, _)| entry)
+            .collect(),
+        owned_entries.into_iter().map(|(entry, _)| entry).collect(),
+    ))
+}
+
+/// Decides about an entry named as an argument or containing one, if it is.
+fn select_argument(
+    cli: &CliOptions,
+    absolute_files: &HashSet<PathBuf>,
+    entry: &DirEntry,
+) -> eyre::Result<Option<Decision>> {
+    // Entries of the current directory are matched by name when every
+    // argument names one
+    if let Some(names) = &cli.argument_names
+        && entry.path().parent() == Some(&cli.root)
+    {
+        let is_argument = names.contains(&cli.case_key(entry.file_name().into()));
+        return Ok(is_argument.then(|| Decision::Keep("listed as an argument".into())));
+    }
+
+    let entry_key = &cli.case_key(cli.entry_absolute(&entry.path()));
+    if absolute_files.contains(entry_key) {
+        // Only the directory itself is kept, so its contents are selected
+        // like any others
+        if cli.emptied_dirs.contains(entry_key) {
+            return Ok(Some(Decision::Descend));
+        }
+        return Ok(Some(Decision::Keep("listed as an argument".into())));
+    }
+
+    // Descend into directories containing kept entries, which only happens
+    // for arguments inside subdirectories
+    if absolute_files
+        .iter()
+        .any(|file| file != entry_key && file.starts_with(entry_key))
+        && entry
+            .file_type()
+            .wrap_err_with(|| {
+                tr!(
+                    "Can't get type of {path}",
+                    path = cli.display_path(&entry.path()).display()
+                )
+            })?
+            .is_dir()
+    {
+        return Ok(Some(Decision::Descend));
+    }
+    Ok(None)
+}
+
+/// Decides whether a directory entry should be removed.
+fn select_entry(
+    cli: &CliOptions,
+    absolute_files: &HashSet<PathBuf>,
+    entry: &DirEntry,
+) -> eyre::Result<Decision> {
+    let print_path = cli.display_path(&entry.path());
+    let print_path = print_path.display();
+
+    // Never move the trash into itself
+    if cli.trash && trash::is_trash_dir(&entry.file_name()) {
+        return Ok(Decision::Keep("trash directory".into()));
+    }
+
+    // Never move quarantine directories into another quarantine directory
+    if cli.quarantine && quarantine::is_quarantine_dir(&entry.file_name()) {
+        return Ok(Decision::Keep("quarantine directory".into()));
+    }
+
+    // Never delete the snapshot we may have just created
+    if cli.snapshot && snapshot::is_snapshot_dir(&entry.file_name()) {
+        return Ok(Decision::Keep("snapshot directory".into()));
+    }
+
+    // Skip if matches one of the arguments
+    if let Some(decision) = select_argument(cli, absolute_files, entry)? {
+        return Ok(decision);
+    }
+    // Excluded entries skip the rules which would keep them by name
+    let excluded = is_excluded(&cli.exclude, &entry.path());
+    if !excluded && let Some(reason) = keep_by_name(cli, entry) {
+        return Ok(Decision::Keep(reason));
+    }
+
+    // Descend into directories containing entries kept by --rules
+    if !excluded
+        && let Some(rules) = &cli.keep_rules
+        && entry
+            .file_type()
+            .wrap_err_with(|| tr!("Can't get type of {path}", path = print_path))?
+            .is_dir()
+        && rules.keeps_inside(&entry.path())?
+    {
+        return Ok(Decision::Descend);
+    }
+
+    let mut metadata = EntryMetadata::new(entry);
+    if !cli.keep_owned_by.is_empty() {
+        let metadata = metadata
+            .get()
+            .wrap_err_with(|| tr!("Can't get metadata of {path}", path = print_path))?;
+        if let Some(owner) = cli.keep_owned_by.iter().find(|owner| owner.owns(metadata)) {
+            return Ok(Decision::Keep(owner.reason()));
+        }
+    }
+
+    for xattr in &cli.keep_xattr {
+        if xattr.matches(&entry.path()).wrap_err_with(|| {
+            tr!(
+                "Can't read extended attributes of {path}",
+                path = print_path
+            )
+        })? {
+            return Ok(Decision::Keep(xattr.reason()));
+        }
+    }
+
+    if !cli.keep_type.is_empty()
+        && !entry
+            .file_type()
+            .wrap_err_with(|| tr!("Can't get type of {path}", path = print_path))?
+            .is_dir()
+        && let Some(kind) = filetype::find_match(&cli.keep_type, &entry.path(), cli.sniff)?
+    {
+        return Ok(Decision::Keep(format!("is of type {kind}")));
+    }
+
+    // Skip hidden entries, which are often configuration or metadata such as
+    // .git and .env
+    if !cli.hidden && entry.file_name().as_encoded_bytes().starts_with(b".") {
+        return Ok(Decision::Keep("hidden".into()));
+    }
+
+    // Skip special files (sockets, FIFOs, devices), which may be in use by a
+    // running service
+    if !cli.special {
+        let metadata = metadata
+            .get()
+            .wrap_err_with(|| tr!("Can't get metadata of {path}", path = print_path))?;
+        let file_type = metadata.file_type();
+        if !file_type.is_file() && !file_type.is_dir() && !file_type.is_symlink() {
+            cli.out.warning(tr!(
+                "Skipping special file {path} ({kind}). Use --special to remove it.",
+                path = print_path,
+                kind = tr!(permissions::describe_type(metadata))
+            ));
+            return Ok(Decision::Keep("special file".into()));
+        }
+    }
+
+    // Skip other users' files, which in shared directories likely belong to
+    // someone else's work
+    if !cli.all_owners {
+        let metadata = metadata
+            .get()
+            .wrap_err_with(|| tr!("Can't get metadata of {path}", path = print_path))?;
+        if permissions::is_owned_by_other(metadata) {
+            cli.out.warning(tr!(
+                "Skipping {path}, which is owned by another user. Use --all-owners to remove it.",
+                path = print_path
+            ));
+            return Ok(Decision::Keep("owned by another user".into()));
+        }
+    }
+
+    // Skip entries matching the age and size filters
+    if let Some(reason) = filter_entry(cli, entry, &mut metadata)? {
+        return Ok(Decision::Keep(reason.into()));
+    }
+
+    // Skip in-progress downloads, since deleting them would corrupt the
+    // transfer
+    if !cli.delete_partial && is_partial_download(&entry.file_name()) {
+        cli.out.warning(tr!(
+            "Keeping {path}, which looks like an in-progress download. Use --delete-partial to remove it.",
+            path = print_path
+        ));
+        return Ok(Decision::Keep("in-progress download".into()));
+    }
+
+    // Skip photos taken within the requested date range
+    if let Some(reason) = keep_by_capture_date(cli, entry)? {
+        return Ok(Decision::Keep(reason));
+    }
+
+    Ok(Decision::Remove)
+}
+
+/// Checks the given entry against `--keep-exif-after` and
+/// `--keep-exif-before` and returns the reason to keep it, if any.
+fn keep_by_capture_date(cli: &CliOptions, entry: &DirEntry) -> eyre::Result<Option<String>> {
+    if (cli.keep_exif_after.is_some() || cli.keep_exif_before.is_some())
+        && entry
+            .file_type()
+            .wrap_err_with(|| {
+                tr!(
+                    "Can't get type of {path}",
+                    path = cli.display_path(&entry.path()).display()
+                )
+            })?
+            .is_file()
+        && let Some(date) = exif::capture_date(&entry.path())?
+        && (cli.keep_exif_after.is_some_and(|after| date >= after)
+            || cli.keep_exif_before.is_some_and(|before| date < before))
+    {
+        return Ok(Some(format!(
+            "taken on {:04}-{:02}-{:02}",
+            date.year, date.month, date.day
+        )));
+    }
+    Ok(None)
+}
+
+/// Checks the given entry against the rules which keep entries by name, and
+/// returns the reason to keep it, if any.
+fn keep_by_name(cli: &CliOptions, entry: &DirEntry) -> Option<String> {
+    let name = entry.file_name();
+    let name = name.to_string_lossy();
+    if let Some(regex) = cli.keep_regexes.iter().find(|regex| regex.is_match(&name)) {
+        return Some(format!("matches regular expression '{regex}'"));
+    }
+    // The absolute path is only made if one of the lists of paths is in use
+    let entry_absolute = LazyCell::new(|| cli.entry_absolute(&entry.path()));
+    let entry_key = LazyCell::new(|| cli.case_key(entry_absolute.clone()));
+    if !cli.kept_by_file.is_empty() && cli.kept_by_file.contains(&*entry_key) {
+        return Some("listed in a keep file".into());
+    }
+    if !cli.in_manifest.is_empty() && cli.in_manifest.contains(&*entry_key) {
+        return Some("listed in the manifest".into());
+    }
+    if !cli.tracked_by_git.is_empty() && cli.tracked_by_git.contains(&*entry_absolute) {
+        return Some("tracked by git".into());
+    }
+    if let Some(pattern) = cli.keep_globs.iter().find(|pattern| pattern.matches(&name)) {
+        return Some(format!("matches glob '{pattern}'"));
+    }
+    if let Some(pattern) = cli
+        .config_keep
+        .iter()
+        .find(|pattern| pattern.matches(&name))
+    {
+        return Some(format!("matches configured keep pattern '{pattern}'"));
+    }
+    if let Some((preset, pattern)) = cli
+        .preset_keep
+        .iter()
+        .find(|(_, pattern)| pattern.matches(&name))
+    {
+        return Some(format!("matches pattern '{pattern}' of preset {preset}"));
+    }
+    let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+    if let Some(rule) = cli
+        .keep_rules
+        .as_ref()
+        .and_then(|rules| rules.keeps(&cli.root_relative(&entry.path()), is_dir))
+    {
+        return Some(format!("matches rule '{rule}'"));
+    }
+    if !cli.recent.is_empty() && cli.recent.contains(&*entry_absolute) {
+        return Some("recently modified".into());
+    }
+    if !cli.largest.is_empty() && cli.largest.contains(&*entry_absolute) {
+        return Some("one of the largest entries".into());
+    }
+    if cli.ignored_only && !cli.ignored_by_git.contains(&*entry_absolute) {
+        return Some("not ignored by git".into());
+    }
+    None
+}
+
+/// Checks the given entry against the age and size filters (`--settle`,
+/// `--newer-than`, `--older-than`, `--smaller-than`, and `--larger-than`) and
+/// returns the reason to keep it, if any.
+fn filter_entry(
+    cli: &CliOptions,
+    entry: &DirEntry,
+    metadata: &mut EntryMetadata,
+) -> eyre::Result<Option<&'static str>> {
+    let print_path = cli.display_path(&entry.path());
+    let print_path = print_path.display();
+
+    // Skip entries which may still be being written to, or whose age the
+    // user asked to keep
+    if cli.settle.is_some() || cli.newer_than.is_some() || cli.older_than.is_some() {
+        let modified = metadata
+            .get()
+            .and_then(fs::Metadata::modified)
+            .wrap_err_with(|| tr!("Can't get modification time of {path}", path = print_path))?;
+        // A modification time in the future counts as recent
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if cli.settle.is_some_and(|settle| age < settle) {
+            return Ok(Some("modified too recently"));
+        }
+        if cli.newer_than.is_some_and(|newer_than| age < newer_than) {
+            return Ok(Some("newer than --newer-than"));
+        }
+        if cli.older_than.is_some_and(|older_than| age > older_than) {
+            return Ok(Some("older than --older-than"));
+        }
+    }
+
+    // Skip entries whose size the user asked to keep
+    if cli.smaller_than.is_some() || cli.larger_than.is_some() {
+        let metadata = metadata
+            .get()
+            .wrap_err_with(|| tr!("Can't get metadata of {path}", path = print_path))?;
+        let size = if cli.recursive && metadata.is_dir() {
+            size::tree_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+        if cli
+            .smaller_than
+            .is_some_and(|smaller_than| size < smaller_than)
+        {
+            return Ok(Some("smaller than --smaller-than"));
+        }
+        if cli
+            .larger_than
+            .is_some_and(|larger_than| size > larger_than)
+        {
+            return Ok(Some("larger than --larger-than"));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns whether the given file name looks like that of a file which is
+/// still being downloaded.
+fn is_partial_download(name: &std::ffi::OsStr) -> bool {
+    let name = name.as_encoded_bytes();
+    PARTIAL_DOWNLOAD_SUFFIXES
+        .iter()
+        .any(|suffix| name.ends_with(suffix.as_bytes()))
+}
+
+/// Removes a directory entry which was selected for removal.
+///
+/// Returns [`Outcome::Declined`] if the user declined to remove the entry when
+/// prompted.
+fn process_entry(remover: &Remover, entry: &DirEntry) -> eyre::Result<Outcome> {
+    let cli = remover.cli;
+    let destination = remover.destination.as_ref();
+    let path = entry.path();
+    let print_path = cli.display_path(&path);
+    let print_path = print_path.display();
+
+    let file_type = entry
+        .file_type()
+        .wrap_err_with(|| tr!("Can't get type of {path}", path = print_path))?;
+
+    // Like rm, ask before removing write-protected entries unless -f is given.
+    // Unlike rm, they're kept rather than removed if there's no one to ask.
+    if !cli.force && !cli.dry_run {
+        let metadata = entry
+            .metadata()
+            .wrap_err_with(|| tr!("Can't get metadata of {path}", path = print_path))?;
+        if permissions::is_write_protected(&path, &metadata) {
+            let kind = tr!(permissions::describe_type(&metadata));
+            if !prompt::is_interactive() {
+                bail!(tr!(
+                    "Not removing write-protected {kind} '{path}', since standard input isn't a terminal to ask on. Use -f to remove it anyways.",
+                    kind = kind,
+                    path = print_path
+                ));
+            }
+            let question = tr!(
+                "remove write-protected {kind} '{path}'?",
+                kind = kind,
+                path = print_path
+            );
+            if !prompt::confirm(&question)? {
+                return Ok(Outcome::Declined);
+            }
+        }
+    }
+
+    // Links are only moved as they are, so they're never followed then
+    let is_dir = file_type.is_dir()
+        || (cli.follow_symlinks
+            && destination.is_none()
+            && file_type.is_symlink()
+            && fs::metadata(&path).is_ok_and(|metadata| metadata.is_dir()));
+    let outcome = remove_path(cli, destination, entry, is_dir)
+        .wrap_err_with(|| tr!("Can't remove {path}", path = print_path))?;
+    if matches!(outcome, Outcome::Truncated(_)) {
+        return Ok(outcome);
+    }
+    if cli.dry_run {
+        print_dry_run(cli, &path, is_dir, cli.recursive);
+    }
+    Ok(outcome)
+}
+
+/// Prints that the given entry would be removed in a dry run, or the shell
+/// command removing it with `--output sh`. Directories are removed along with
+/// their contents if `recursive` is true, and with rmdir(1) otherwise.
+fn print_dry_run(cli: &CliOptions, path: &Path, is_dir: bool, recursive: bool) {
+    if cli.out.is_shell() {
+        let command = match (is_dir, recursive, cli.force) {
+            (false, _, false) => "rm",
+            (false, _, true) => "rm -f",
+            (true, true, false) => "rm -r",
+            (true, true, true) => "rm -rf",
+            (true, false, _) => "rmdir",
+        };
+        output::shell_command(command, &cli.display_path(path));
+    } else if cli.format.is_none() && !cli.out.is_json() {
+        println!(
+            "{} {}",
+            cli.out.paint(Marker::DryRun, "would remove"),
+            cli.display_path(path).display()
+        );
+    }
+}
+
+/// Removes the given entry through the handle of the directory containing
+/// it, or moves it into `destination` if given.
+fn remove_path(
+    cli: &CliOptions,
+    destination: Option<&Arc<Destination>>,
+    entry: &DirEntry,
+    is_dir: bool,
+) -> eyre::Result<Outcome> {
+    let path = entry.path();
+    let remove = || -> eyre::Result<Outcome> {
+        if is_dir {
+            delete_dir(cli, destination, entry)
+        } else if cli.dry_run {
+            Ok(Outcome::Removed)
+        } else if let Some(destination) = destination {
+            move_into(cli, destination, &path)
+        } else {
+            let parent = Arc::clone(entry.dir());
+            let name = entry.file_name();
+            retry::run(cli.retry_limits(), move || parent.remove_file(&name))
+                .map(|()| Outcome::Removed)
+        }
+    };
+    let result = remove();
+
+    // Like rm -f, clear write protection and try again
+    if cli.force
+        && let Err(err) = &result
+        && permissions::is_permission_error(err)
+    {
+        return permissions::make_writable(&path).and_then(|()| remove());
+    }
+    result
+}
+
+/// Moves the entry at the given path into `destination`, abandoning the move
+/// if it takes too long with `--timeout`.
+fn move_into(
+    cli: &CliOptions,
+    destination: &Arc<Destination>,
+    path: &Path,
+) -> eyre::Result<Outcome> {
+    let Some(limit) = cli.timeout else {
+        return destination.move_in(path).map(Outcome::Moved);
+    };
+    let destination = Arc::clone(destination);
+    let path = path.to_owned();
+    timeout::run(limit, move || destination.move_in(&path))?.map(Outcome::Moved)
+}
+
+/// Deletes a directory according to the CLI options given.
+///
+/// If `destination` is given, the directory is moved into it instead of being
+/// deleted, subject to the same checks.
+fn delete_dir(
+    cli: &CliOptions,
+    destination: Option<&Arc<Destination>>,
+    entry: &DirEntry,
+) -> eyre::Result<Outcome> {
+    let path = entry.path();
+    let dir = path.as_path();
+    if cli.recursive {
+        if destination.is_none() {
+            check_mounts(cli, dir)?;
+        }
+        // If recursive directory deletion is enabled, we can delete all directories
+        if cli.dry_run {
+            return check_tree(cli, entry, 1);
+        } else if let Some(destination) = destination {
+            return move_into(cli, destination, dir);
+        } else if !cli.cross_file_systems {
+            let device = cli
+                .root_dir()?
+                .metadata()
+                .wrap_err(tr!("Can't get metadata of current directory"))
+                .map(|metadata| device_id(&metadata))?;
+            return remove_tree(cli, entry.dir(), dir, Some(device), 1);
+        } else if cfg!(unix)
+            || cli.verbose
+            || cli.force
+            || cli.max_depth.is_some()
+            || is_link(dir)
+            || cli.out.progress.is_enabled()
+            || throttle::is_enabled()
+            || cli.timeout.is_some()
+            || cli.sync
+            || !cli.special
+        {
+            // remove_dir_all() would work by path rather than through the
+            // directory's handle, would only remove a link, and can't report
+            // progress, stop at a given depth, be throttled, time out each
+            // removal, record the directories it changed, or skip special
+            // files
+            return remove_tree(cli, entry.dir(), dir, None, 1);
+        }
+        let dir = dir.to_owned();
+        retry::run(cli.retry_limits(), move || platform::remove_dir_all(&dir))?;
+    } else if !cli.dirs {
+        // If recursive and empty directory deletion are disabled, we can't delete any directories
+        bail!(tr!("Is a directory"));
+    } else {
+        // We can delete empty directories only
+
+        // Check if directory is empty
+        let is_empty = entry
+            .read_dir(cli.follow_symlinks)
+            .wrap_err_with(|| {
+                tr!(
+                    "Can't list contents of {dir}",
+                    dir = cli.display_path(dir).display()
+                )
+            })?
+            .is_empty();
+
+        if !is_empty {
+            bail!(tr!("Directory is not empty"));
+        } else if cli.dry_run {
+            // Nothing to do
+        } else if let Some(destination) = destination {
+            return move_into(cli, destination, dir);
+        } else {
+            let parent = Arc::clone(entry.dir());
+            let name = entry.file_name();
+            retry::run(cli.retry_limits(), move || parent.remove_dir(&name))?;
+        }
+    }
+
+    Ok(Outcome::Removed)
+}
+
+/// Fails if the given directory is or contains mount points which may not be
+/// removed, listing them: those on the same file system as the current
+/// directory need `--allow-mounts`, and others need `--cross-file-systems`.
+fn check_mounts(cli: &CliOptions, dir: &Path) -> eyre::Result<()> {
+    if cli.allow_mounts && cli.cross_file_systems {
+        return Ok(());
+    }
+    let device = cli
+        .root_dir()?
+        .metadata()
+        .wrap_err(tr!("Can't get metadata of current directory"))
+        .map(|metadata| device_id(&metadata))?;
+    let (same, other): (Vec<PathBuf>, Vec<PathBuf>) =
+        mounts::find(dir)?.into_iter().partition(|mount| {
+            fs::metadata(mount).is_ok_and(|metadata| device_id(&metadata) == device)
+        });
+    let list = |mounts: &[PathBuf]| {
+        mounts
+            .iter()
+            .map(|mount| cli.display_path(mount).display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let print_dir = cli.display_path(dir);
+    let print_dir = print_dir.display();
+    if !cli.allow_mounts && !same.is_empty() {
+        bail!(tr!(
+            "{dir} is or contains mount points ({mounts}). Use --allow-mounts to remove it.",
+            dir = print_dir,
+            mounts = list(&same)
+        ));
+    }
+    if !cli.cross_file_systems && !other.is_empty() {
+        bail!(tr!(
+            "{dir} is or contains other file systems ({mounts}). Use --cross-file-systems to remove it.",
+            dir = print_dir,
+            mounts = list(&other)
+        ));
+    }
+    Ok(())
+}
+
+/// Recursively removes a directory like [`fs::remove_dir_all`], printing each
+/// entry inside it as it's removed if `-v`/`--verbose` is given. The directory
+/// itself isn't printed.
+///
+/// `parent` is the open directory containing `dir`. Each directory is opened
+/// relative to its parent and emptied through its handle, so replacing a
+/// directory with a link during the walk can't redirect the removal.
+///
+/// If `device` is given, fails instead of removing any directory on another
+/// device, i.e. file system.
+///
+/// With `-f`/`--force`, write protection is cleared on each directory before
+/// it's emptied, and on each file which can't be removed otherwise.
+///
+/// If `dir` is a symbolic link, as with `--follow-symlinks`, the directory it
+/// points to is emptied and the link is removed. Links inside are never
+/// followed.
+///
+/// `depth` is the level of `dir`, where entries in the current directory are
+/// at level 1. Directories whose contents are deeper than `--max-depth` are
+/// left untouched, and so are special files unless `--special` is given, as
+/// well as their ancestors. Returns [`Outcome::Truncated`] if `dir` was left.
+///
+/// With `--timeout`, a removal which is abandoned after others in `dir` were
+/// made fails with a [`timeout::Partial`] error.
+fn remove_tree(
+    cli: &CliOptions,
+    parent: &Arc<dirfd::Dir>,
+    dir: &Path,
+    device: Option<u64>,
+    depth: usize,
+) -> eyre::Result<Outcome> {
+    let print_dir = cli.display_path(dir);
+    let name = dir.file_name().unwrap_or(dir.as_os_str());
+    // A link is only followed if it was given to be removed
+    let open = || parent.open(name, depth == 1 && cli.follow_symlinks);
+    let mut handle = open();
+    if cli.force
+        && let Err(err) = &handle
+        && err.kind() == std::io::ErrorKind::PermissionDenied
+    {
+        parent.unprotect_entry(name)?;
+        handle = open();
+    }
+    let handle =
+        Arc::new(handle.wrap_err_with(|| tr!("Can't open {dir}", dir = print_dir.display()))?);
+    if let Some(device) = device {
+        let metadata = handle
+            .metadata()
+            .wrap_err_with(|| tr!("Can't get metadata of {dir}", dir = print_dir.display()))?;
+        if device_id(&metadata) != device {
+            bail!(tr!(
+                "{dir} is on a different file system. Use --cross-file-systems to remove it.",
+                dir = print_dir.display()
+            ));
+        }
+    }
+    let entries = handle
+        .entries()
+        .wrap_err_with(|| tr!("Can't list contents of {dir}", dir = print_dir.display()))?;
+    if !entries.is_empty()
+        && cli
+            .max_depth
+            .is_some_and(|max_depth| depth >= max_depth.get())
+    {
+        leave_deep_dir(cli, dir);
+        return Ok(Outcome::Truncated(DEEP_REASON));
+    }
+    if cli.force {
+        handle
+            .unprotect()
+            .wrap_err_with(|| tr!("Can't make {dir} writable", dir = print_dir.display()))?;
+    }
+    let mut outcome = Outcome::Removed;
+    let mut removed_any = false;
+    for entry in entries {
+        if interrupt::is_interrupted() {
+            bail!(tr!("Interrupted"));
+        }
+        let path = dir.join(&entry.name);
+        if let Some(kind) = entry.special
+            && !cli.special
+        {
+            leave_special_file(cli, &path, kind);
+            outcome = Outcome::Truncated(SPECIAL_REASON);
+            continue;
+        }
+        throttle::wait();
+        let print_path_buf = cli.display_path(&path);
+        let print_path = print_path_buf.display();
+        if entry.is_dir {
+            let dir_outcome = remove_tree(cli, &handle, &path, device, depth + 1)
+                .map_err(|err| timeout::mark_partial(err, removed_any))?;
+            removed_any = true;
+            if dir_outcome.kept_reason().is_some() {
+                outcome = dir_outcome;
+                continue;
+            }
+            cli.changes.entry_changed(&path);
+            cli.out.progress.removed(&print_path_buf);
+            cli.logger.removed(&cli.out, "removed", &path);
+            if cli.verbose {
+                cli.out.message(format_args!(
+                    "{} directory '{print_path}'",
+                    cli.out.paint(Marker::Removed, "removed")
+                ));
+            }
+        } else {
+            remove_tree_file(cli, &handle, &entry.name)
+                .wrap_err_with(|| tr!("Can't remove {path}", path = print_path))
+                .map_err(|err| timeout::mark_partial(err, removed_any))?;
+            removed_any = true;
+            cli.changes.entry_changed(&path);
+            cli.out.progress.removed(&print_path_buf);
+            cli.logger.removed(&cli.out, "removed", &path);
+            if cli.verbose {
+                cli.out.message(format_args!(
+                    "{} '{print_path}'",
+                    cli.out.paint(Marker::Removed, "removed")
+                ));
+            }
+        }
+    }
+    if outcome.kept_reason().is_some() {
+        return Ok(outcome);
+    }
+    drop(handle);
+    let parent = Arc::clone(parent);
+    let name = name.to_owned();
+    retry::run(cli.retry_limits(), move || parent.remove_dir(&name))
+        .map_err(|err| timeout::mark_partial(err, removed_any))?;
+    Ok(Outcome::Removed)
+}
+
+/// Removes the file with the given name from `dir`, for [`remove_tree`].
+fn remove_tree_file(cli: &CliOptions, dir: &Arc<dirfd::Dir>, name: &OsStr) -> eyre::Result<()> {
+    let remove = || {
+        let dir = Arc::clone(dir);
+        let name = name.to_owned();
+        retry::run(cli.retry_limits(), move || dir.remove_file(&name))
+    };
+    let result = remove();
+    // Read-only files can't be removed on Windows
+    if cli.force
+        && let Err(err) = &result
+        && permissions::is_permission_error(err)
+    {
+        return dir.unprotect_entry(name).and_then(|()| remove());
+    }
+    result
+}
+
+/// Returns what [`remove_tree`] would do with `dir`, at level `depth`, for
+/// dry runs: whether it could remove it without going deeper than
+/// `--max-depth` or removing special files. Like it, prints a notice for each
+/// entry which would be left.
+fn check_tree(cli: &CliOptions, dir: &DirEntry, depth: usize) -> eyre::Result<Outcome> {
+    if cli.max_depth.is_none() && cli.special {
+        return Ok(Outcome::Removed);
+    }
+    let dir_path = dir.path();
+    let print_dir = cli.display_path(&dir_path);
+    // A link is only followed if it was given to be removed
+    let entries = dir
+        .read_dir(depth == 1 && cli.follow_symlinks)
+        .wrap_err_with(|| tr!("Can't list contents of {dir}", dir = print_dir.display()))?;
+    if cli
+        .max_depth
+        .is_some_and(|max_depth| depth >= max_depth.get())
+        && !entries.is_empty()
+    {
+        leave_deep_dir(cli, &dir_path);
+        return Ok(Outcome::Truncated(DEEP_REASON));
+    }
+    let mut outcome = Outcome::Removed;
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type().wrap_err_with(|| {
+            tr!(
+                "Can't get type of {path}",
+                path = cli.display_path(&path).display()
+            )
+        })?;
+        if let Some(kind) = permissions::special_type(file_type)
+            && !cli.special
+        {
+            leave_special_file(cli, &path, kind);
+            outcome = Outcome::Truncated(SPECIAL_REASON);
+        } else if file_type.is_dir() {
+            let dir_outcome = check_tree(cli, &entry, depth + 1)?;
+            if dir_outcome.kept_reason().is_some() {
+                outcome = dir_outcome;
+            }
+        }
+    }
+    Ok(outcome)
+}
+
+/// Tells the user that a directory is left because its contents are deeper
+/// than `--max-depth`.
+fn leave_deep_dir(cli: &CliOptions, dir: &Path) {
+    let message = if cli.dry_run {
+        "would leave {dir}: it has entries deeper than --max-depth"
+    } else {
+        "leaving {dir}: it has entries deeper than --max-depth"
+    };
+    cli.out
+        .notice(tr!(message, dir = cli.display_path(dir).display()));
+}
+
+/// Warns that a special file inside a directory being removed is left, since
+/// `--special` wasn't given.
+fn leave_special_file(cli: &CliOptions, path: &Path, kind: &'static str) {
+    cli.out.warning(tr!(
+        "Skipping special file {path} ({kind}). Use --special to remove it.",
+        path = cli.display_path(path).display(),
+        kind = tr!(kind)
+    ));
+}
+
+/// Returns whether the given path is a symbolic link.
+fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink())
+}
+
+/// Returns the ID of the device containing the file with the given metadata.
+#[cfg(unix)]
+fn device_id(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.dev()
+}
+
+/// Returns the ID of the device containing the file with the given metadata.
+/// Devices can't be told apart using only the standard library on this
+/// platform, so everything is considered to be on the same one.
+#[cfg(not(unix))]
+fn device_id(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Prints a line describing what was done with an entry, if an output
+/// template or JSON output was requested.
+fn print_entry(cli: &CliOptions, entry: &DirEntry, action: &str, reason: &str) {
+    if cli.format.is_some() || cli.out.is_json() {
+        let metadata = (cli.out.is_json()
+            || cli.format.as_ref().is_some_and(Template::needs_metadata))
+        .then(|| entry.metadata().ok())
+        .flatten();
+        print_entry_with(cli, entry, metadata.as_ref(), action, reason);
+    }
+}
+
+/// Like [`print_entry`], but uses metadata gathered earlier.
+fn print_entry_with(
+    cli: &CliOptions,
+    entry: &DirEntry,
+    metadata: Option<&fs::Metadata>,
+    action: &str,
+    reason: &str,
+) {
+    if cli.out.is_json() {
+        let action = match action {
+            "kept" => "skipped",
+            "failed" => "error",
+            action => action,
+        };
+        output::event(
+            action,
+            Some(&cli.display_path(&entry.path())),
+            metadata,
+            reason,
+        );
+    } else if let Some(template) = &cli.format {
+        let info = EntryInfo {
+            action,
+            path: &cli.display_path(&entry.path()),
+            metadata,
+            reason,
+        };
+        println!("{}", template.render(&info));
+    }
+}
+
+/// Formats the cause chain of an error from [`process_entry`] in a single
+/// line, separated by colons, without the outermost context (which just
+/// names the entry).
+fn format_error_reason(error: &eyre::Report) -> String {
+    let skip = usize::from(error.chain().len() > 1);
+    error
+        .chain()
+        .skip(skip)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Prints the given error to standard error, and as an event if JSON output
+/// is enabled.
+///
+/// Prints the full cause chain in a single line, separated by colons.
+fn print_error(out: &Output, error: &eyre::Report) {
+    let message = print_error_text(out, error);
+    if out.is_json() {
+        output::event("error", None, None, &message);
+    }
+}
+
+/// Like [`print_error`], but never prints an event. Returns the printed
+/// message.
+fn print_error_text(out: &Output, error: &eyre::Report) -> String {
+    let chain: Vec<_> = error.chain().map(ToString::to_string).collect();
+    out.error(&chain);
+    chain.join(": ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the sorted names of the given entries.
+    fn names<'a>(entries: impl IntoIterator<Item = &'a DirEntry>) -> Vec<String> {
+        let mut names: Vec<String> = entries
+            .into_iter()
+            .map(|entry| entry.path().display().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Selects the entries of `dir` to remove and to keep, keeping `args`,
+    /// matching entries against the arguments by name alone or by their
+    /// absolute paths.
+    fn select(
+        args: &[&str],
+        ignore_case: bool,
+        dir: &Path,
+        by_name: bool,
+    ) -> (Vec<String>, Vec<String>) {
+        let options = args
+            .iter()
+            .fold(LeaveOptions::new(), |options, arg| options.keep(*arg))
+            .force(true)
+            .recursive(true)
+            .hidden(true)
+            .ignore_case(ignore_case);
+        let mut cli = options.cli;
+        cli.root = dir.to_owned();
+        prepare_keep_list(&mut cli).unwrap();
+        assert!(cli.argument_names.is_some(), "{args:?}");
+        if !by_name {
+            cli.argument_names = None;
+        }
+        let absolute_files = validate_args(&cli).unwrap();
+        let entries = read_root(&cli).unwrap();
+        let (doomed, kept, _) = select_entries(&cli, &absolute_files, entries).unwrap();
+        (names(&doomed), names(kept.iter().map(|(entry, _)| entry)))
+    }
+
+    #[test]
+    fn arguments_match_by_name_like_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "B", "c.txt", "d", ".hidden"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/a"), "").unwrap();
+
+        for (args, ignore_case) in [
+            (&["a", "./c.txt", "sub"][..], false),
+            (&["b", "missing", ".hidden"], false),
+            (&["A", "b", "SUB"], true),
+        ] {
+            let by_name = select(args, ignore_case, dir.path(), true);
+            assert_eq!(by_name, select(args, ignore_case, dir.path(), false));
+            assert!(
+                !by_name.0.is_empty() && !by_name.1.is_empty(),
+                "{by_name:?}"
+            );
+        }
+    }
+}
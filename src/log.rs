@@ -0,0 +1,168 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Log of what was removed and what failed, for `--log`.
+//!
+//! Each removed entry, including the contents of directories removed with
+//! `-r`, and each entry which couldn't be removed is appended as one line.
+//! In text format, lines are tab-separated fields:
+//!
+//! ```text
+//! TIME  ACTION  PATH  ERROR
+//! ```
+//!
+//! where `ERROR` is only present for failures. In JSON format, each line is an
+//! object like `{"time":"...","action":"removed","path":"/abs/foo"}`. Paths
+//! are absolute, so logs from different directories can be combined.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use clap::ValueEnum;
+use eyre::Context;
+
+use crate::{
+    datetime,
+    i18n::tr,
+    output::{self, Output},
+};
+
+/// Format of the lines in the log.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Tab-separated fields
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// The log of a run, which is only written to once opened.
+#[derive(Debug, Default)]
+pub struct Logger {
+    log: Mutex<Option<Log>>,
+}
+
+#[derive(Debug)]
+struct Log {
+    file: File,
+    path: PathBuf,
+    format: LogFormat,
+    /// Whether writing to the log has failed, in which case it's not written
+    /// to again.
+    failed: bool,
+}
+
+impl Logger {
+    /// Opens the log at the given path for the rest of the run, creating it
+    /// if necessary.
+    pub fn open(&self, path: &Path, format: LogFormat) -> eyre::Result<()> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .wrap_err_with(|| tr!("Can't open {path}", path = path.display()))?;
+        *self.log.lock().unwrap_or_else(PoisonError::into_inner) = Some(Log {
+            file,
+            path: path.to_owned(),
+            format,
+            failed: false,
+        });
+        Ok(())
+    }
+
+    /// Logs that the entry at `path` was removed, or moved with `action` such
+    /// as "quarantined". Errors writing the log are printed to `out`.
+    pub fn removed(&self, out: &Output, action: &str, path: &Path) {
+        self.write(out, action, path, "");
+    }
+
+    /// Logs that the entry at `path` couldn't be removed because of `error`.
+    pub fn failed(&self, out: &Output, path: &Path, error: &str) {
+        self.write(out, "failed", path, error);
+    }
+
+    /// Returns whether writing to the log failed at some point during the
+    /// run.
+    pub fn had_failure(&self) -> bool {
+        self.log
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .is_some_and(|log| log.failed)
+    }
+
+    /// Appends a line to the log, if one is open. If writing fails, an error
+    /// is printed and the log is abandoned.
+    fn write(&self, out: &Output, action: &str, path: &Path, error: &str) {
+        let mut log = self.log.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(log) = log.as_mut().filter(|log| !log.failed) else {
+            return;
+        };
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+        let path = path.to_string_lossy();
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let time = datetime::format_iso8601(secs);
+        let line = match log.format {
+            LogFormat::Text => {
+                let mut fields = vec![time.as_str(), action, &path];
+                if !error.is_empty() {
+                    fields.push(error);
+                }
+                fields
+                    .into_iter()
+                    .map(escape)
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            }
+            LogFormat::Json => {
+                let mut line = String::from("{");
+                output::push_field(&mut line, "time", &time);
+                output::push_field(&mut line, "action", action);
+                output::push_field(&mut line, "path", &path);
+                if !error.is_empty() {
+                    output::push_field(&mut line, "error", error);
+                }
+                line.push('}');
+                line
+            }
+        };
+        if let Err(err) = writeln!(log.file, "{line}") {
+            log.failed = true;
+            out.error(&[
+                tr!("Can't write to {path}", path = log.path.display()),
+                err.to_string(),
+            ]);
+        }
+    }
+}
+
+/// Escapes backslashes, tabs, and newlines, which would otherwise break the
+/// line format.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,98 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Manifests: records of the entries in a directory, written by
+//! `leave snapshot` and read by `--manifest` to reset the directory to the
+//! recorded state.
+//!
+//! A manifest starts with a comment line, followed by the name of each entry
+//! on its own line, sorted. Backslashes and newlines in names are escaped as
+//! `\\` and `\n`, and a leading `#` as `\#`, so that every name fits on one
+//! line and isn't mistaken for a comment. Blank lines and lines starting with
+//! `#` are ignored when reading.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use eyre::Context;
+
+use crate::{filelist::bytes_to_os_string, i18n::tr};
+
+/// First line of every manifest.
+const HEADER: &[u8] = b"# leave manifest\n";
+
+/// Writes a manifest of the current directory to `out`.
+pub fn write(out: &mut impl Write) -> eyre::Result<()> {
+    let mut names: Vec<_> = fs::read_dir(".")
+        .wrap_err(tr!("Can't list contents of ."))?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<Result<_, _>>()
+        .wrap_err(tr!("Can't read directory entry"))?;
+    names.sort();
+    let mut contents = HEADER.to_vec();
+    for name in names {
+        let name = name.as_encoded_bytes();
+        if name.starts_with(b"#") {
+            contents.push(b'\\');
+        }
+        for &byte in name {
+            match byte {
+                b'\\' => contents.extend(b"\\\\"),
+                b'\n' => contents.extend(b"\\n"),
+                byte => contents.push(byte),
+            }
+        }
+        contents.push(b'\n');
+    }
+    out.write_all(&contents)
+        .wrap_err(tr!("Can't write manifest"))
+}
+
+/// Reads the entry names listed in the given manifest.
+pub fn read(path: &Path) -> eyre::Result<Vec<PathBuf>> {
+    let contents =
+        fs::read(path).wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?;
+    Ok(contents
+        .split(|&byte| byte == b'\n')
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .map(|line| PathBuf::from(bytes_to_os_string(&unescape(line))))
+        .collect())
+}
+
+/// Reverses the escaping done by [`write`].
+fn unescape(line: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(line.len());
+    let mut bytes = line.iter();
+    while let Some(&byte) = bytes.next() {
+        match (byte, bytes.as_slice().first()) {
+            (b'\\', Some(b'n')) => {
+                out.push(b'\n');
+                bytes.next();
+            }
+            (b'\\', Some(&escaped @ (b'\\' | b'#'))) => {
+                out.push(escaped);
+                bytes.next();
+            }
+            (byte, _) => out.push(byte),
+        }
+    }
+    out
+}
@@ -0,0 +1,91 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Man page generation for `leave manpage`.
+//!
+//! The page is built from the same definitions as `--help`, so it stays in
+//! sync with the options, with an extra section of examples.
+
+use std::io::Write;
+
+use clap::CommandFactory;
+use clap_mangen::{
+    Man,
+    roff::{Roff, bold, roman},
+};
+use eyre::Context;
+
+use crate::{CliOptions, i18n::tr};
+
+/// Commands shown in the EXAMPLES section, each with a description.
+const EXAMPLES: &[(&str, &str)] = &[
+    (
+        "leave main.rs",
+        "Remove everything in the current directory except main.rs.",
+    ),
+    (
+        "leave -r src Cargo.toml",
+        "Also remove directories and their contents, except src and Cargo.toml.",
+    ),
+    (
+        "leave -n -r src",
+        "Print what would be removed without removing anything.",
+    ),
+    (
+        "leave -g '*.rs'",
+        "Keep every entry whose name ends with .rs.",
+    ),
+    (
+        "leave -r --empty-kept-dirs build",
+        "Keep the build directory itself, but remove everything inside it.",
+    ),
+    (
+        "leave --print0 -r src | xargs -0 du -sh",
+        "Show the size of each entry which would be removed, without removing it.",
+    ),
+    (
+        "leave check -r src Cargo.toml",
+        "Fail if the current directory contains anything but src and Cargo.toml.",
+    ),
+];
+
+/// Writes the man page in roff format.
+pub fn write(out: &mut dyn Write) -> eyre::Result<()> {
+    let man = Man::new(CliOptions::command());
+    let mut examples = Roff::new();
+    examples.control("SH", ["EXAMPLES"]);
+    for (command, description) in EXAMPLES {
+        examples.control("TP", []);
+        examples.text([bold(*command)]);
+        examples.text([roman(*description)]);
+    }
+
+    // Rendered section by section to put the examples before the notes from
+    // the end of --help
+    man.render_title(out)
+        .and_then(|()| man.render_name_section(out))
+        .and_then(|()| man.render_synopsis_section(out))
+        .and_then(|()| man.render_description_section(out))
+        .and_then(|()| man.render_options_section(out))
+        .and_then(|()| man.render_subcommands_section(out))
+        .and_then(|()| examples.to_writer(out))
+        .and_then(|()| man.render_extra_section(out))
+        .and_then(|()| man.render_version_section(out))
+        .and_then(|()| man.render_authors_section(out))
+        .wrap_err(tr!("Can't write man page"))
+}
@@ -0,0 +1,105 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Finding mount points inside directories being removed, which are refused
+//! without `--allow-mounts`.
+//!
+//! On Linux, mount points are read from `/proc/self/mountinfo`, which also
+//! lists bind mounts. Those can't be told apart from other directories by
+//! their device, since they're on the same file system. Elsewhere, only other
+//! file systems are noticed, by the device check made while removing.
+
+use std::path::{Path, PathBuf};
+
+use crate::i18n::tr;
+
+/// Mount points read from `mountinfo`, which is only read once per run.
+#[cfg(target_os = "linux")]
+static MOUNT_POINTS: std::sync::OnceLock<Vec<PathBuf>> = std::sync::OnceLock::new();
+
+/// Returns the mount points at or inside the given directory, as paths
+/// starting with `dir`.
+#[cfg(target_os = "linux")]
+pub fn find(dir: &Path) -> eyre::Result<Vec<PathBuf>> {
+    use eyre::Context;
+
+    let canonical = std::fs::canonicalize(dir)
+        .wrap_err_with(|| tr!("Can't resolve {path}", path = dir.display()))?;
+    Ok(mount_points()?
+        .iter()
+        .filter_map(|mount_point| {
+            let inside = mount_point.strip_prefix(&canonical).ok()?;
+            Some(dir.join(inside))
+        })
+        .collect())
+}
+
+/// Returns all mount points.
+#[cfg(target_os = "linux")]
+fn mount_points() -> eyre::Result<&'static [PathBuf]> {
+    use eyre::Context;
+
+    if let Some(mount_points) = MOUNT_POINTS.get() {
+        return Ok(mount_points);
+    }
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+        .wrap_err(tr!("Can't read /proc/self/mountinfo"))?;
+    let mount_points = mountinfo
+        .lines()
+        // The mount point is the fifth field
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(|field| PathBuf::from(unescape(field)))
+        .collect();
+    Ok(MOUNT_POINTS.get_or_init(|| mount_points))
+}
+
+/// Returns the mount points at or inside the given directory. They can't be
+/// listed on this platform, so there are none.
+#[cfg(not(target_os = "linux"))]
+#[allow(clippy::unnecessary_wraps)]
+pub fn find(_dir: &Path) -> eyre::Result<Vec<PathBuf>> {
+    Ok(Vec::new())
+}
+
+/// Decodes the octal escapes, such as `\040` for a space, used for special
+/// characters in `mountinfo` fields.
+#[cfg(target_os = "linux")]
+fn unescape(field: &str) -> std::ffi::OsString {
+    use std::os::unix::ffi::OsStringExt;
+
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 4).filter(|digits| {
+            bytes[i] == b'\\' && digits.iter().all(|digit| (b'0'..=b'7').contains(digit))
+        });
+        if let Some(digits) = escape {
+            out.push(
+                digits
+                    .iter()
+                    .fold(0u8, |value, digit| value.wrapping_mul(8) + (digit - b'0')),
+            );
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    std::ffi::OsString::from_vec(out)
+}
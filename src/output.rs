@@ -0,0 +1,259 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Machine-readable output, for `--output json` and `--output sh`.
+//!
+//! In JSON mode, each event is printed on standard output as a single-line
+//! JSON object, e.g. `{"action":"removed","path":"./foo","type":"file"}`.
+//! In shell mode, which only applies to dry runs, the commands which would
+//! remove each entry are printed instead, e.g. `rm -r -- ./foo`. Either way,
+//! human-readable messages are moved to standard error so they don't
+//! interfere.
+
+use std::{
+    env,
+    fmt::{self, Write as _},
+    fs::Metadata,
+    io::{self, IsTerminal},
+    path::Path,
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    i18n::tr,
+    progress::Progress,
+    shell, template,
+    theme::{Marker, Theme},
+};
+
+/// How to report what was done with each entry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable messages
+    #[default]
+    Text,
+    /// One JSON object per event (newline-delimited JSON)
+    Json,
+    /// A shell script of the commands which would remove each entry (only
+    /// with --dry-run)
+    Sh,
+}
+
+/// When to color messages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color messages printed on a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// How messages are printed during a run: the output format, whether
+/// warnings are suppressed, how messages are colored, and the progress line,
+/// which is cleared before anything else is printed. It's part of the options
+/// rather than global, so runs made through the library don't affect each
+/// other.
+#[derive(Debug, Default)]
+pub struct Output {
+    format: OutputFormat,
+    /// Whether warnings and other non-fatal messages are suppressed, for
+    /// `-q`/`--quiet`.
+    quiet: bool,
+    /// Whether errors and warnings are colored, with causes on separate
+    /// lines.
+    color: bool,
+    /// Whether messages on standard output are colored.
+    color_stdout: bool,
+    theme: Theme,
+    pub progress: Progress,
+}
+
+impl Output {
+    /// Creates the output for a run. Whether to color is decided here for
+    /// the whole run.
+    pub fn new(
+        format: OutputFormat,
+        quiet: bool,
+        color: ColorChoice,
+        theme: Theme,
+        progress: bool,
+    ) -> Self {
+        let color_on = |is_terminal: bool| match color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && is_terminal
+            }
+        };
+        Self {
+            format,
+            quiet,
+            color: color_on(io::stderr().is_terminal()),
+            color_stdout: color_on(io::stdout().is_terminal()),
+            theme,
+            progress: Progress::new(progress),
+        }
+    }
+
+    /// Returns the theme, if messages on standard error are colored.
+    pub fn theme(&self) -> Option<&Theme> {
+        self.color.then_some(&self.theme)
+    }
+
+    /// Returns the given text in the style of the given marker, if messages
+    /// printed with [`Output::message`] are colored.
+    pub fn paint(&self, marker: Marker, text: &str) -> String {
+        let color = if self.format == OutputFormat::Text {
+            self.color_stdout
+        } else {
+            self.color
+        };
+        if color {
+            self.theme.paint(marker, text)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// Returns whether JSON output is enabled.
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    /// Returns whether shell script output is enabled.
+    pub fn is_shell(&self) -> bool {
+        self.format == OutputFormat::Sh
+    }
+
+    /// Prints a human-readable message, on standard output normally or on
+    /// standard error if machine-readable output is enabled.
+    pub fn message(&self, args: fmt::Arguments) {
+        self.progress.clear();
+        if self.format == OutputFormat::Text {
+            println!("{args}");
+        } else {
+            eprintln!("{args}");
+        }
+    }
+
+    /// Prints an error, given as its chain of causes starting with the
+    /// outermost, on standard error. Errors are never suppressed.
+    ///
+    /// With color, the outermost message is highlighted and each cause is
+    /// indented on its own line. Otherwise, the causes are joined with colons
+    /// on a single line.
+    pub fn error(&self, chain: &[String]) {
+        self.progress.clear();
+        if self.color {
+            let mut out = String::new();
+            for (i, cause) in chain.iter().enumerate() {
+                if i == 0 {
+                    let _ = write!(
+                        out,
+                        "{} \x1b[1m{cause}\x1b[0m",
+                        self.theme.paint(Marker::Error, tr!("Error:"))
+                    );
+                } else {
+                    let _ = write!(out, "\n  \x1b[2m{}\x1b[0m {cause}", tr!("caused by:"));
+                }
+            }
+            eprintln!("{out}");
+        } else {
+            eprintln!("{} {}", tr!("Error:"), chain.join(": "));
+        }
+    }
+
+    /// Prints a warning on standard error, unless `-q`/`--quiet` is given.
+    pub fn warning(&self, message: impl fmt::Display) {
+        if self.quiet {
+            return;
+        }
+        self.progress.clear();
+        if self.color {
+            eprintln!("\x1b[1;33m{}\x1b[0m {message}", tr!("Warning:"));
+        } else {
+            eprintln!("{} {message}", tr!("Warning:"));
+        }
+    }
+
+    /// Prints an informational message on standard error, unless
+    /// `-q`/`--quiet` is given.
+    pub fn notice(&self, message: impl fmt::Display) {
+        if !self.quiet {
+            self.progress.clear();
+            eprintln!("{message}");
+        }
+    }
+}
+
+/// Prints a shell command running `command` on `path`. Options can't be
+/// confused with the path, which is quoted.
+pub fn shell_command(command: &str, path: &Path) {
+    println!("{command} -- {}", shell::quote(path.as_os_str()));
+}
+
+/// Prints a JSON event. The type is omitted if no metadata is given, and the
+/// reason is omitted if it's empty.
+pub fn event(action: &str, path: Option<&Path>, metadata: Option<&Metadata>, reason: &str) {
+    let mut out = String::from("{");
+    push_field(&mut out, "action", action);
+    if let Some(path) = path {
+        push_field(&mut out, "path", &path.to_string_lossy());
+    }
+    if let Some(metadata) = metadata {
+        push_field(&mut out, "type", template::type_name(metadata));
+    }
+    if !reason.is_empty() {
+        push_field(&mut out, "reason", reason);
+    }
+    out.push('}');
+    println!("{out}");
+}
+
+/// Appends a `"key":"value"` pair to a JSON object being built.
+pub fn push_field(out: &mut String, key: &str, value: &str) {
+    if !out.ends_with('{') {
+        out.push(',');
+    }
+    push_string(out, key);
+    out.push(':');
+    push_string(out, value);
+}
+
+/// Appends a JSON string literal.
+fn push_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
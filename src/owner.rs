@@ -0,0 +1,111 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Resolving users and groups, for `--keep-owned-by`.
+//!
+//! Names are looked up in `/etc/passwd` and `/etc/group`, since the standard
+//! library can't query the system's user database. Numeric IDs are accepted
+//! too, for users and groups which aren't listed there.
+
+use std::fs::Metadata;
+
+use crate::i18n::tr;
+
+/// A user or group whose entries are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    /// Whether this is a group rather than a user.
+    group: bool,
+    id: u32,
+    /// The name or ID as given, for messages.
+    name: String,
+}
+
+impl Owner {
+    /// Parses `USER` or `:GROUP`, each given by name or numeric ID, like
+    /// chown(1).
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if !cfg!(unix) {
+            return Err("--keep-owned-by is only supported on Unix".into());
+        }
+        let (group, name) = match s.strip_prefix(':') {
+            Some(name) => (true, name),
+            None => (false, s),
+        };
+        if name.is_empty() {
+            return Err("no user or group given".into());
+        }
+        let id = if let Ok(id) = name.parse() {
+            id
+        } else {
+            let database = if group { "/etc/group" } else { "/etc/passwd" };
+            lookup(database, name)?.ok_or_else(|| {
+                let name = format!("{name:?}");
+                if group {
+                    tr!("no such group {name}", name = name)
+                } else {
+                    tr!("no such user {name}", name = name)
+                }
+            })?
+        };
+        Ok(Self {
+            group,
+            id,
+            name: name.into(),
+        })
+    }
+
+    /// Returns whether the entry with the given metadata belongs to this
+    /// user or group.
+    #[cfg(unix)]
+    pub fn owns(&self, metadata: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        let id = if self.group {
+            metadata.gid()
+        } else {
+            metadata.uid()
+        };
+        id == self.id
+    }
+
+    /// Returns whether the entry with the given metadata belongs to this
+    /// user or group.
+    #[cfg(not(unix))]
+    pub fn owns(&self, _metadata: &Metadata) -> bool {
+        false
+    }
+
+    /// Returns the reason to keep entries owned by this user or group.
+    pub fn reason(&self) -> String {
+        let kind = if self.group { "group" } else { "user" };
+        format!("owned by {kind} {}", self.name)
+    }
+}
+
+/// Finds the ID of the entry with the given name in a file formatted like
+/// `/etc/passwd` or `/etc/group`, where the name and ID are the first and
+/// third colon-separated fields.
+fn lookup(database: &str, name: &str) -> Result<Option<u32>, String> {
+    let contents = std::fs::read_to_string(database)
+        .map_err(|err| tr!("can't read {path}: {error}", path = database, error = err))?;
+    Ok(contents
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 2 && fields[0] == name)
+        .and_then(|fields| fields[2].parse().ok()))
+}
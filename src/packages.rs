@@ -0,0 +1,134 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Querying the system package manager for file ownership, for
+//! `--keep-packaged`.
+//!
+//! Ownership is looked up using `dpkg-query` on Debian-like systems and `rpm`
+//! on RPM-based systems, whichever is found first.
+
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use eyre::{Context, bail};
+
+use crate::i18n::tr;
+
+/// Maximum number of paths passed to a single `dpkg-query` invocation.
+const BATCH_SIZE: usize = 256;
+
+/// Returns the subset of the given absolute paths which belong to an installed
+/// package.
+pub fn owned_paths<'a, I>(paths: I) -> eyre::Result<HashSet<PathBuf>>
+where
+    I: IntoIterator<Item = &'a Path>,
+{
+    if !cfg!(target_os = "linux") {
+        bail!(tr!("--keep-packaged is only supported on Linux"));
+    }
+
+    let paths: Vec<&Path> = paths.into_iter().collect();
+    if is_available("dpkg-query") {
+        let mut owned = HashSet::new();
+        for batch in paths.chunks(BATCH_SIZE) {
+            owned.extend(dpkg_owned(batch)?);
+        }
+        Ok(owned)
+    } else if is_available("rpm") {
+        let mut owned = HashSet::new();
+        for path in paths {
+            if rpm_owns(path)? {
+                owned.insert(path.to_owned());
+            }
+        }
+        Ok(owned)
+    } else {
+        bail!(tr!(
+            "Can't find dpkg-query or rpm to look up package ownership"
+        ));
+    }
+}
+
+/// Returns whether the given program can be executed.
+fn is_available(program: &str) -> bool {
+    match Command::new(program)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(_) => true,
+        Err(err) => err.kind() != ErrorKind::NotFound,
+    }
+}
+
+/// Queries `dpkg-query` for which of the given paths are owned by a package.
+fn dpkg_owned(paths: &[&Path]) -> eyre::Result<HashSet<PathBuf>> {
+    // dpkg-query exits with status 1 if any path isn't found, so we don't
+    // check the exit status and instead look only at the output.
+    let output = Command::new("dpkg-query")
+        .arg("--search")
+        .args(paths)
+        .stderr(Stdio::null())
+        .output()
+        .wrap_err(tr!("Can't run dpkg-query"))?;
+
+    // Output lines have the form `pkg1, pkg2: /path`. Diversion information
+    // lines also contain ": " but never name one of our paths exactly.
+    let queried: HashSet<&OsStr> = paths.iter().map(|p| p.as_os_str()).collect();
+    let owned = output
+        .stdout
+        .split(|&b| b == b'\n')
+        .filter_map(|line| {
+            let pos = line.windows(2).position(|w| w == b": ")?;
+            let path = bytes_to_os_str(&line[pos + 2..]);
+            queried.get(path).map(PathBuf::from)
+        })
+        .collect();
+    Ok(owned)
+}
+
+/// Queries `rpm` for whether the given path is owned by a package.
+fn rpm_owns(path: &Path) -> eyre::Result<bool> {
+    let status = Command::new("rpm")
+        .arg("--query")
+        .arg("--file")
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .wrap_err(tr!("Can't run rpm"))?;
+    Ok(status.success())
+}
+
+#[cfg(unix)]
+fn bytes_to_os_str(bytes: &[u8]) -> &OsStr {
+    use std::os::unix::ffi::OsStrExt;
+    OsStr::from_bytes(bytes)
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_str(bytes: &[u8]) -> &OsStr {
+    // Only reachable on Linux; see owned_paths()
+    OsStr::new(std::str::from_utf8(bytes).unwrap_or_default())
+}
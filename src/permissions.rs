@@ -0,0 +1,178 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Clearing write protection so `--force` can remove protected entries, like
+//! `rm -f` does.
+//!
+//! On Windows, files with the read-only attribute can't be deleted. On Unix,
+//! entries inside a directory without write permission can't be deleted, so
+//! recursive removal of a tree containing such a directory fails.
+
+use std::{fs, io, path::Path};
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// Returns whether the given error was caused by a lack of permissions.
+pub fn is_permission_error(err: &eyre::Report) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|err| err.kind() == io::ErrorKind::PermissionDenied)
+}
+
+/// Returns whether the current user lacks write permission on the given
+/// entry. Symbolic links are never write-protected.
+pub fn is_write_protected(path: &Path, metadata: &fs::Metadata) -> bool {
+    if metadata.file_type().is_symlink() {
+        return false;
+    }
+    #[cfg(unix)]
+    {
+        rustix::fs::access(path, rustix::fs::Access::WRITE_OK).is_err()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        metadata.permissions().readonly()
+    }
+}
+
+/// Returns whether the given entry is owned by a user other than the current
+/// one.
+pub fn is_owned_by_other(metadata: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::{os::unix::fs::MetadataExt, sync::OnceLock};
+
+        // Looked up once, since this is checked for every entry
+        static EUID: OnceLock<u32> = OnceLock::new();
+        metadata.uid() != *EUID.get_or_init(|| rustix::process::geteuid().as_raw())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+/// Returns a description of the type of the given entry, in the style of
+/// `rm`'s prompts.
+pub fn describe_type(metadata: &fs::Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "symbolic link"
+    } else if file_type.is_file() && metadata.len() == 0 {
+        "regular empty file"
+    } else if file_type.is_file() {
+        "regular file"
+    } else {
+        describe_special_type(file_type)
+    }
+}
+
+/// Returns a description of the given type, like [`describe_type`], if it's
+/// that of a special file: a socket, FIFO, or device node.
+pub fn special_type(file_type: fs::FileType) -> Option<&'static str> {
+    (!file_type.is_file() && !file_type.is_dir() && !file_type.is_symlink())
+        .then(|| describe_special_type(file_type))
+}
+
+#[cfg(unix)]
+fn describe_special_type(file_type: fs::FileType) -> &'static str {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block special file"
+    } else if file_type.is_char_device() {
+        "character special file"
+    } else {
+        "file"
+    }
+}
+
+#[cfg(not(unix))]
+fn describe_special_type(_file_type: fs::FileType) -> &'static str {
+    "file"
+}
+
+/// Makes the given entry, and everything inside it if it's a directory,
+/// writable by the current user so it can be removed.
+///
+/// Symbolic links are not followed.
+pub fn make_writable(path: &Path) -> eyre::Result<()> {
+    if unprotect(path)? {
+        let entries = fs::read_dir(path)
+            .wrap_err_with(|| tr!("Can't list contents of {dir}", dir = path.display()))?;
+        for entry in entries {
+            let entry = entry.wrap_err(tr!("Can't read directory entry"))?;
+            make_writable(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Makes the given entry, but not its contents, writable by the current user.
+/// Returns whether the entry is a directory.
+///
+/// Symbolic links are not followed.
+pub fn unprotect(path: &Path) -> eyre::Result<bool> {
+    let metadata = fs::symlink_metadata(path)
+        .wrap_err_with(|| tr!("Can't get metadata of {path}", path = path.display()))?;
+    if metadata.file_type().is_symlink() {
+        return Ok(false);
+    }
+
+    let mut permissions = metadata.permissions();
+    if add_write_permission(&mut permissions, metadata.is_dir()) {
+        fs::set_permissions(path, permissions)
+            .wrap_err_with(|| tr!("Can't make {path} writable", path = path.display()))?;
+    }
+    Ok(metadata.is_dir())
+}
+
+/// Adds owner write permission (and, for directories, read and search
+/// permission, which are needed to empty them). Returns whether the
+/// permissions were changed.
+#[cfg(unix)]
+pub fn add_write_permission(permissions: &mut fs::Permissions, is_dir: bool) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    let needed = if is_dir { 0o700 } else { 0o200 };
+    let mode = permissions.mode();
+    permissions.set_mode(mode | needed);
+    mode & needed != needed
+}
+
+/// Clears the read-only attribute. Returns whether the permissions were
+/// changed.
+#[cfg(not(unix))]
+pub fn add_write_permission(permissions: &mut fs::Permissions, _is_dir: bool) -> bool {
+    let was_readonly = permissions.readonly();
+    // Only clears the read-only attribute on Windows, unlike on Unix where it
+    // would make the file world-writable
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    was_readonly
+}
@@ -0,0 +1,170 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Removal primitives which hide the differences between platforms.
+//!
+//! On Unix, these are the same as the standard library's functions. On
+//! Windows, they handle these quirks:
+//!
+//! - Path parsing treats some names specially: reserved device names such as
+//!   `CON` or `nul.txt` refer to devices rather than files, and trailing dots
+//!   and spaces are stripped. Entries with such names, which can be created
+//!   by other tools, are removed using verbatim (`\\?\`) paths, which skip the
+//!   parsing. Paths longer than `MAX_PATH` are already made verbatim by the
+//!   standard library.
+//! - Symbolic links to directories and junctions are directories as far as
+//!   removal is concerned, so they're removed with `RemoveDirectory`, while
+//!   Unix treats all links as files.
+//!
+//! Read-only attributes and sharing violations are handled by the
+//! [`permissions`](crate::permissions) and [`retry`](crate::retry)
+//! modules.
+
+use std::{fs, io, path::Path};
+
+/// Removes a file, or a symbolic link to anything but a directory on Windows.
+// Unix removes files relative to an open directory instead
+#[cfg_attr(unix, allow(dead_code))]
+pub fn remove_file(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        if is_dir_link(path) {
+            return fs::remove_dir(verbatim(path));
+        }
+        fs::remove_file(verbatim(path))
+    }
+    #[cfg(not(windows))]
+    {
+        fs::remove_file(path)
+    }
+}
+
+/// Removes an empty directory, or a symbolic link to a directory.
+// Unix removes directories relative to an open directory instead
+#[cfg_attr(unix, allow(dead_code))]
+pub fn remove_dir(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        fs::remove_dir(verbatim(path))
+    }
+    #[cfg(not(windows))]
+    {
+        if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink()) {
+            fs::remove_file(path)
+        } else {
+            fs::remove_dir(path)
+        }
+    }
+}
+
+/// Removes a directory and everything inside it. Links inside aren't
+/// followed.
+#[cfg_attr(unix, allow(dead_code))]
+pub fn remove_dir_all(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        fs::remove_dir_all(verbatim(path))
+    }
+    #[cfg(not(windows))]
+    {
+        fs::remove_dir_all(path)
+    }
+}
+
+/// Returns whether the given path is a symbolic link to a directory or a
+/// junction.
+#[cfg(windows)]
+fn is_dir_link(path: &Path) -> bool {
+    use std::os::windows::fs::FileTypeExt;
+
+    fs::symlink_metadata(verbatim(path)).is_ok_and(|metadata| {
+        let file_type = metadata.file_type();
+        file_type.is_symlink_dir() || (file_type.is_symlink() && metadata.is_dir())
+    })
+}
+
+/// Returns a verbatim form of `path` if one of its components can't be used
+/// in a regular path, or `path` itself otherwise.
+#[cfg(windows)]
+fn verbatim(path: &Path) -> std::borrow::Cow<'_, Path> {
+    use std::{
+        borrow::Cow,
+        ffi::OsString,
+        path::{Component, PathBuf},
+    };
+
+    let needs_verbatim = path
+        .components()
+        .any(|component| matches!(component, Component::Normal(name) if is_special_name(name)));
+    if !needs_verbatim {
+        return Cow::Borrowed(path);
+    }
+    // Build the absolute path component by component, since resolving it
+    // with the Win32 API would interpret the special names
+    let mut absolute = if path.is_absolute() {
+        PathBuf::new()
+    } else {
+        match std::path::absolute(".") {
+            Ok(cwd) => cwd,
+            Err(_) => return Cow::Borrowed(path),
+        }
+    };
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    let absolute = absolute.into_os_string();
+    let text = absolute.to_string_lossy().into_owned();
+    let verbatim = if text.starts_with(r"\\?\") {
+        absolute
+    } else if let Some(unc) = text.strip_prefix(r"\\") {
+        OsString::from(format!(r"\\?\UNC\{unc}"))
+    } else {
+        let mut verbatim = OsString::from(r"\\?\");
+        verbatim.push(&absolute);
+        verbatim
+    };
+    Cow::Owned(PathBuf::from(verbatim))
+}
+
+/// Returns whether Win32 path parsing would change the meaning of the given
+/// name: it's a reserved device name, with or without an extension, or it
+/// ends with a dot or space.
+#[cfg(windows)]
+fn is_special_name(name: &std::ffi::OsStr) -> bool {
+    let name = name.to_string_lossy();
+    if name.ends_with(['.', ' ']) {
+        return true;
+    }
+    let stem = name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(' ');
+    let stem = stem.to_ascii_uppercase();
+    match stem.as_bytes() {
+        b"CON" | b"PRN" | b"AUX" | b"NUL" | b"CONIN$" | b"CONOUT$" => true,
+        [prefix @ .., b'1'..=b'9'] => matches!(prefix, b"COM" | b"LPT"),
+        _ => false,
+    }
+}
@@ -0,0 +1,158 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Checking that every selected entry can be removed before removing any,
+//! for `--all-or-nothing`.
+//!
+//! The checks cover the reasons removal usually fails: directories which
+//! need `-r` or `-d`, missing permissions on the directories entries are
+//! removed from, and mounted file systems inside trees. They can't rule out
+//! everything (e.g. another process changing the tree in the meantime), so a
+//! run which passes them can still fail partway.
+
+use std::{fs, path::Path};
+
+use eyre::{Context, bail};
+
+use crate::{device_id, i18n::tr};
+
+/// How entries would be removed.
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Probe {
+    /// Whether directories are removed with their contents (`-r`).
+    pub recursive: bool,
+    /// Whether empty directories are removed (`-d`).
+    pub dirs: bool,
+    /// Whether write protection is cleared before removing (`-f`).
+    pub force: bool,
+    /// Whether entries are moved rather than deleted, so their contents
+    /// aren't touched.
+    pub moved: bool,
+    /// Whether links to directories are treated as directories.
+    pub follow_symlinks: bool,
+    /// The device which trees must stay on, unless other file systems are
+    /// crossed.
+    pub device: Option<u64>,
+}
+
+/// Fails if the entries of `dir`, the directory being cleaned, can't be
+/// removed at all.
+pub fn check_root(dir: &Path) -> eyre::Result<()> {
+    // Clearing write protection only applies to the entries themselves
+    check_writable(dir, false).wrap_err(tr!("Can't remove entries of the current directory"))
+}
+
+impl Probe {
+    /// Fails with the reason if the entry at `path` can't be removed.
+    pub fn check(&self, path: &Path) -> eyre::Result<()> {
+        let mut metadata = fs::symlink_metadata(path).wrap_err(tr!("Can't get metadata"))?;
+        if self.follow_symlinks && !self.moved && metadata.is_symlink() {
+            metadata = fs::metadata(path).unwrap_or(metadata);
+        }
+        if !metadata.is_dir() {
+            return check_file(&metadata, self.force);
+        }
+        if !self.recursive {
+            if !self.dirs {
+                bail!(tr!("Is a directory"));
+            }
+            let is_empty = fs::read_dir(path)
+                .wrap_err(tr!("Can't list contents"))?
+                .next()
+                .is_none();
+            if !is_empty {
+                bail!(tr!("Directory is not empty"));
+            }
+        } else if !self.moved {
+            self.check_tree(path)?;
+        }
+        Ok(())
+    }
+
+    /// Fails if the contents of the given directory, recursively, can't be
+    /// removed.
+    fn check_tree(&self, dir: &Path) -> eyre::Result<()> {
+        let metadata = fs::metadata(dir)
+            .wrap_err_with(|| tr!("Can't get metadata of {dir}", dir = dir.display()))?;
+        if self
+            .device
+            .is_some_and(|device| device_id(&metadata) != device)
+        {
+            bail!(tr!(
+                "{dir} is on a different file system",
+                dir = dir.display()
+            ));
+        }
+        check_writable(dir, self.force)?;
+        let entries = fs::read_dir(dir)
+            .wrap_err_with(|| tr!("Can't list contents of {dir}", dir = dir.display()))?;
+        for entry in entries {
+            let entry = entry.wrap_err(tr!("Can't read directory entry"))?;
+            let metadata = entry.metadata().wrap_err_with(|| {
+                tr!(
+                    "Can't get metadata of {path}",
+                    path = entry.path().display()
+                )
+            })?;
+            if metadata.is_dir() {
+                self.check_tree(&entry.path())?;
+            } else {
+                check_file(&metadata, self.force)
+                    .wrap_err_with(|| tr!("Can't remove {path}", path = entry.path().display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fails if entries can't be removed from the given directory, i.e. it
+/// can't be listed and written to. With `force`, directories we own are
+/// fine, since their permissions would be changed.
+#[cfg(unix)]
+fn check_writable(dir: &Path, force: bool) -> eyre::Result<()> {
+    use rustix::fs::{Access, access};
+
+    if force {
+        let metadata = fs::metadata(dir)
+            .wrap_err_with(|| tr!("Can't get metadata of {dir}", dir = dir.display()))?;
+        if !crate::permissions::is_owned_by_other(&metadata) {
+            return Ok(());
+        }
+    }
+    access(dir, Access::READ_OK | Access::WRITE_OK | Access::EXEC_OK)
+        .map_err(std::io::Error::from)
+        .wrap_err_with(|| tr!("Can't write to {path}", path = dir.display()))
+}
+
+/// Fails if entries can't be removed from the given directory. Removing
+/// entries only depends on the entries themselves on this platform.
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn check_writable(_dir: &Path, _force: bool) -> eyre::Result<()> {
+    Ok(())
+}
+
+/// Fails if the non-directory entry with the given metadata can't be
+/// removed. Only Windows refuses to remove read-only files.
+fn check_file(metadata: &fs::Metadata, force: bool) -> eyre::Result<()> {
+    if cfg!(windows) && !force && metadata.permissions().readonly() {
+        bail!(tr!("File is read-only"));
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Keep patterns for common types of project directories, for `--preset`.
+//!
+//! The configuration file can replace a preset or define a new one with
+//! `preset_<name> = [...]`. An element `"..."` in the list stands for the
+//! built-in patterns, so a preset can be extended rather than replaced.
+
+use std::collections::HashMap;
+
+use eyre::eyre;
+
+use crate::i18n::tr;
+
+/// Entries kept by every built-in preset.
+const COMMON: &[&str] = &[
+    ".git",
+    ".gitignore",
+    ".editorconfig",
+    "README*",
+    "LICENSE*",
+    "COPYING*",
+    "CHANGELOG*",
+];
+
+/// The built-in presets and the patterns of the entries they keep, besides
+/// [`COMMON`].
+const PRESETS: &[(&str, &[&str])] = &[
+    (
+        "latex",
+        &[
+            "*.tex",
+            "*.bib",
+            "*.sty",
+            "*.cls",
+            "*.bst",
+            "*.bbx",
+            "*.cbx",
+            ".latexmkrc",
+            "latexmkrc",
+            "Makefile",
+            "figures",
+            "images",
+        ],
+    ),
+    (
+        "node",
+        &[
+            "package.json",
+            "package-lock.json",
+            "npm-shrinkwrap.json",
+            "yarn.lock",
+            "pnpm-lock.yaml",
+            "tsconfig*.json",
+            ".npmrc",
+            ".nvmrc",
+            "src",
+            "lib",
+            "public",
+            "test",
+            "tests",
+        ],
+    ),
+    (
+        "python",
+        &[
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "requirements*.txt",
+            "Pipfile",
+            "Pipfile.lock",
+            "poetry.lock",
+            "uv.lock",
+            "tox.ini",
+            "MANIFEST.in",
+            ".python-version",
+            "*.py",
+            "src",
+            "tests",
+            "docs",
+        ],
+    ),
+    (
+        "rust",
+        &[
+            "Cargo.toml",
+            "Cargo.lock",
+            "build.rs",
+            "rust-toolchain",
+            "rust-toolchain.toml",
+            ".cargo",
+            "src",
+            "tests",
+            "benches",
+            "examples",
+        ],
+    ),
+];
+
+/// Stands for a preset's built-in patterns in a configured preset.
+const BUILT_IN: &str = "...";
+
+/// Returns the patterns of the preset with the given name, taking presets from
+/// the configuration file over the built-in ones.
+pub fn patterns(
+    name: &str,
+    configured: &HashMap<String, Vec<String>>,
+) -> eyre::Result<Vec<String>> {
+    let built_in = || {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, patterns)| COMMON.iter().chain(*patterns).map(ToString::to_string))
+    };
+    let Some(configured) = configured.get(name) else {
+        return built_in().map(Iterator::collect).ok_or_else(|| {
+            let names: Vec<_> = PRESETS.iter().map(|(preset, _)| *preset).collect();
+            eyre!(tr!(
+                "Unknown preset {name}; the built-in presets are {presets}",
+                name = name,
+                presets = names.join(", ")
+            ))
+        });
+    };
+    let mut patterns = Vec::new();
+    for pattern in configured {
+        if pattern == BUILT_IN {
+            patterns.extend(built_in().ok_or_else(|| {
+                eyre!(tr!(
+                    "Preset {name} has no built-in patterns to extend",
+                    name = name
+                ))
+            })?);
+        } else {
+            patterns.push(pattern.clone());
+        }
+    }
+    Ok(patterns)
+}
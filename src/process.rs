@@ -0,0 +1,166 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Finding the files processes have open, for `--protect-pid` and
+//! `--skip-in-use`.
+//!
+//! On Linux, open files are read from `/proc/<PID>/fd`, along with the
+//! process's working directory. On Windows, where processes' open files can't
+//! be listed, a file is in use if it can't be opened exclusively.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context, bail};
+
+use crate::{i18n::tr, output::Output};
+
+/// Returns the paths of the files and directories the given process has open,
+/// including its working directory.
+///
+/// Non-file descriptors such as pipes and sockets are skipped.
+pub fn open_paths(pid: u32) -> eyre::Result<Vec<PathBuf>> {
+    if !cfg!(target_os = "linux") {
+        bail!(tr!("--protect-pid is only supported on Linux"));
+    }
+    open_paths_of(pid)
+}
+
+/// Returns the paths the given process has open, like [`open_paths`].
+fn open_paths_of(pid: u32) -> eyre::Result<Vec<PathBuf>> {
+    let proc_dir = PathBuf::from(format!("/proc/{pid}"));
+    let mut links = vec![proc_dir.join("cwd")];
+    let fd_dir = proc_dir.join("fd");
+    for entry in fs::read_dir(&fd_dir)
+        .wrap_err_with(|| tr!("Can't list open files of process {pid}", pid = pid))?
+    {
+        let entry = entry.wrap_err(tr!("Can't read directory entry"))?;
+        links.push(entry.path());
+    }
+
+    let mut paths = Vec::with_capacity(links.len());
+    for link in links {
+        match fs::read_link(&link) {
+            Ok(target) if target.is_absolute() => paths.push(target),
+            // Pipes, sockets, etc. have targets like "pipe:[1234]"
+            Ok(_) => (),
+            // The descriptor may have been closed since listing it
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => {
+                return Err(err).wrap_err_with(|| tr!("Can't read {path}", path = link.display()));
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Returns whether the given absolute path is, or contains, any of the given
+/// open paths.
+pub fn is_in_use(path: &Path, open_paths: &[PathBuf]) -> bool {
+    open_paths.iter().any(|open| open.starts_with(path))
+}
+
+/// The entries which other processes are using, for `--skip-in-use`.
+pub struct InUse {
+    /// Paths which other processes have open. Listing them once is much
+    /// faster than scanning every process for each entry.
+    #[cfg(target_os = "linux")]
+    open_paths: Vec<PathBuf>,
+}
+
+impl InUse {
+    /// Finds the files other processes have open. Processes whose files
+    /// can't be listed, such as those of other users without privileges, are
+    /// skipped, and failing to list processes at all is a warning printed to
+    /// `out`.
+    pub fn scan(out: &Output) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            let mut open_paths = Vec::new();
+            let own_pid = std::process::id();
+            match fs::read_dir("/proc") {
+                Ok(entries) => {
+                    let pids = entries
+                        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok());
+                    for pid in pids.filter(|&pid| pid != own_pid) {
+                        open_paths.extend(open_paths_of(pid).unwrap_or_default());
+                    }
+                }
+                Err(err) => out.warning(tr!(
+                    "Can't list processes to find entries in use: {error}",
+                    error = err
+                )),
+            }
+            Self { open_paths }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = out;
+            Self {}
+        }
+    }
+
+    /// Returns whether another process is using the given entry, or anything
+    /// inside it.
+    pub fn contains(&self, path: &Path) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            std::path::absolute(path).is_ok_and(|path| is_in_use(&path, &self.open_paths))
+        }
+        #[cfg(windows)]
+        {
+            is_locked(path)
+        }
+        #[cfg(not(any(target_os = "linux", windows)))]
+        {
+            let _ = path;
+            false
+        }
+    }
+}
+
+/// Returns whether the given file, or any file inside the given directory,
+/// can't be opened exclusively because another process has it open.
+#[cfg(windows)]
+fn is_locked(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return false;
+    };
+    if metadata.is_dir() {
+        return fs::read_dir(path).is_ok_and(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .any(|entry| is_locked(&entry.path()))
+        });
+    }
+    // A share mode of 0 denies other opens, so this fails with a sharing
+    // violation if the file is open elsewhere
+    fs::OpenOptions::new()
+        .read(true)
+        .share_mode(0)
+        .open(path)
+        .is_err_and(|err| err.raw_os_error() == Some(ERROR_SHARING_VIOLATION))
+}
+
+/// Win32 error code for a sharing violation.
+#[cfg(windows)]
+const ERROR_SHARING_VIOLATION: i32 = 32;
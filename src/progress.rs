@@ -0,0 +1,238 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! A progress line on standard error, for `--progress`.
+//!
+//! The line shows how many entries were removed so far, including the
+//! contents of directories removed with `-r`, and the last one removed. It's
+//! redrawn at most every [`INTERVAL`] so that drawing doesn't slow down the
+//! removal, and cleared before other messages are printed.
+//!
+//! When the entries were measured beforehand, as for `--summary` and
+//! `--confirm`, the line also shows how many of their bytes were processed
+//! and estimates the time left from that, since a single huge file can take
+//! as long as thousands of small ones.
+
+use std::{
+    path::Path,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{i18n::tr, size};
+
+/// Minimum time between redraws.
+const INTERVAL: Duration = Duration::from_millis(100);
+
+/// Maximum number of characters of the path to show.
+const MAX_PATH_LEN: usize = 60;
+
+/// The progress line of a run.
+#[derive(Debug, Default)]
+pub struct Progress {
+    /// Whether the line is shown.
+    enabled: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    removed: u64,
+    /// Total size of the entries being removed, if they were measured.
+    total_bytes: Option<u64>,
+    /// Size of the entries processed so far, out of `total_bytes`.
+    done_bytes: u64,
+    /// When processing bytes started, for estimating the time left.
+    started: Option<Instant>,
+    last_draw: Option<Instant>,
+    /// Whether the line is currently on the screen.
+    drawn: bool,
+}
+
+impl Progress {
+    /// Creates a progress line, which is only shown if `enabled` is true.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns whether the progress line is shown.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sets the total size of the entries about to be removed, so progress is
+    /// shown in bytes too.
+    pub fn set_total_bytes(&self, total: u64) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.total_bytes = Some(total);
+        state.done_bytes = 0;
+        state.started = Some(Instant::now());
+    }
+
+    /// Counts `bytes` more of the total as processed. The line is redrawn on
+    /// the next removal.
+    pub fn advance(&self, bytes: u64) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.done_bytes += bytes;
+    }
+
+    /// Counts the given entry as removed, and redraws the line if it's due.
+    pub fn removed(&self, path: &Path) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.removed += 1;
+        let now = Instant::now();
+        if state
+            .last_draw
+            .is_some_and(|last| now.duration_since(last) < INTERVAL)
+        {
+            return;
+        }
+        state.last_draw = Some(now);
+        state.drawn = true;
+        let path = path.to_string_lossy();
+        // Show the end of long paths, which is the most specific part
+        let skip = path.chars().count().saturating_sub(MAX_PATH_LEN);
+        let path = if skip > 0 {
+            format!("...{}", path.chars().skip(skip + 3).collect::<String>())
+        } else {
+            path.into_owned()
+        };
+        let line = match state.total_bytes {
+            Some(total) => state.bytes_line(total, now, &path),
+            None => tr!(
+                "{count} removed: {path}",
+                count = state.removed,
+                path = path
+            ),
+        };
+        eprint!("\r\x1b[K{line}");
+    }
+
+    /// Clears the line, so that another message can be printed. It's drawn
+    /// again on the next update.
+    pub fn clear(&self) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.drawn {
+            eprint!("\r\x1b[K");
+            state.drawn = false;
+        }
+    }
+
+    /// Clears the line and resets the count, once removal is done.
+    pub fn finish(&self) {
+        self.clear();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        *state = State::default();
+    }
+}
+
+impl State {
+    /// Formats the line showing the bytes processed out of `total`, and the
+    /// time left once it can be estimated.
+    fn bytes_line(&self, total: u64, now: Instant, path: &str) -> String {
+        let done = self.done_bytes.min(total);
+        let percent = (done * 100).checked_div(total).unwrap_or(100);
+        let elapsed = self
+            .started
+            .map_or(Duration::ZERO, |started| now.duration_since(started));
+        let done_str = size::format_size(done);
+        let total_str = size::format_size(total);
+        if done == 0 || done == total {
+            return tr!(
+                "{count} removed, {done} of {total} ({percent}%): {path}",
+                count = self.removed,
+                done = done_str,
+                total = total_str,
+                percent = percent,
+                path = path
+            );
+        }
+        // Assume the rest goes at the same rate as so far
+        #[allow(clippy::cast_precision_loss)]
+        let left = elapsed.mul_f64((total - done) as f64 / done as f64);
+        tr!(
+            "{count} removed, {done} of {total} ({percent}%), {left} left: {path}",
+            count = self.removed,
+            done = done_str,
+            total = total_str,
+            percent = percent,
+            left = format_time_left(left),
+            path = path
+        )
+    }
+}
+
+/// Formats an estimated time left, e.g. `1h05m`, `3m20s`, or `12s`.
+fn format_time_left(left: Duration) -> String {
+    let secs = left.as_secs();
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m")
+    } else if minutes > 0 {
+        format!("{minutes}m{secs:02}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_line() {
+        let started = Instant::now();
+        let state = State {
+            removed: 3,
+            total_bytes: Some(4096),
+            done_bytes: 1024,
+            started: Some(started),
+            ..State::default()
+        };
+        assert_eq!(
+            state.bytes_line(4096, started + Duration::from_secs(10), "./file"),
+            "3 removed, 1.0 KiB of 4.0 KiB (25%), 30s left: ./file"
+        );
+        assert_eq!(
+            state.bytes_line(1024, started + Duration::from_secs(10), "./file"),
+            "3 removed, 1.0 KiB of 1.0 KiB (100%): ./file"
+        );
+    }
+
+    #[test]
+    fn time_left() {
+        assert_eq!(format_time_left(Duration::from_secs(12)), "12s");
+        assert_eq!(format_time_left(Duration::from_secs(200)), "3m20s");
+        assert_eq!(format_time_left(Duration::from_mins(65)), "1h05m");
+    }
+}
@@ -0,0 +1,158 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Interactive yes/no prompts.
+//!
+//! Prompts are normally read from the terminal. When there's none, e.g. when
+//! Leave is started from a file manager, a yes/no question can instead be
+//! asked in a graphical dialog using `zenity` or `kdialog`, or `osascript` on
+//! macOS.
+
+use std::{
+    env,
+    io::{self, BufRead, ErrorKind, IsTerminal, Write},
+    process::{Command, Stdio},
+    sync::{Mutex, PoisonError},
+};
+
+use eyre::{Context, bail};
+
+use crate::i18n::tr;
+
+/// Returns whether prompts can be shown, i.e. whether standard input is a
+/// terminal.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+/// An answer to a prompt which also offers answering yes to all remaining
+/// questions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    No,
+    All,
+}
+
+/// Asks the given question on standard error and returns whether the user
+/// answered yes. Anything other than an answer starting with `y` or `Y`
+/// counts as no.
+pub fn confirm(question: &str) -> eyre::Result<bool> {
+    Ok(read_answer(question)?.starts_with(['y', 'Y']))
+}
+
+/// Like [`confirm`], but an answer starting with `a` or `A` means yes to all.
+pub fn ask(question: &str) -> eyre::Result<Answer> {
+    let answer = read_answer(question)?;
+    Ok(if answer.starts_with(['y', 'Y']) {
+        Answer::Yes
+    } else if answer.starts_with(['a', 'A']) {
+        Answer::All
+    } else {
+        Answer::No
+    })
+}
+
+/// Held while a prompt is shown, so prompts from different threads don't mix.
+static PROMPT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Asks the given question on standard error and returns the answer, without
+/// leading whitespace.
+fn read_answer(question: &str) -> eyre::Result<String> {
+    let _guard = PROMPT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    eprint!("leave: {question} ");
+    io::stderr().flush().wrap_err(tr!("Can't write prompt"))?;
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .wrap_err(tr!("Can't read answer"))?;
+    Ok(answer.trim_start().to_owned())
+}
+
+/// Asks the given question in a graphical dialog. Returns whether the user
+/// answered yes, or `None` if no dialog program or display is available.
+pub fn confirm_dialog(question: &str) -> eyre::Result<Option<bool>> {
+    for (program, args) in dialog_commands(question) {
+        let status = match Command::new(program)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+        {
+            Ok(status) => status,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).wrap_err(tr!("Can't run {program}", program = program));
+            }
+        };
+        // All of these exit with status 1 when the dialog is cancelled
+        return match status.code() {
+            Some(0) => Ok(Some(true)),
+            Some(1) => Ok(Some(false)),
+            _ => bail!(tr!(
+                "{program} failed to show the dialog ({status})",
+                program = program,
+                status = status
+            )),
+        };
+    }
+    Ok(None)
+}
+
+/// Returns the dialog programs to try, in order, along with their arguments.
+fn dialog_commands(question: &str) -> Vec<(&'static str, Vec<&str>)> {
+    if cfg!(target_os = "macos") {
+        // The question is passed as an argument to avoid quoting it
+        return vec![(
+            "osascript",
+            vec![
+                "-e",
+                "on run argv",
+                "-e",
+                "display dialog (item 1 of argv) with title \"leave\" \
+                 buttons {\"Cancel\", \"Continue\"} default button \"Cancel\" \
+                 with icon caution",
+                "-e",
+                "end run",
+                question,
+            ],
+        )];
+    }
+    let has_display = ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|name| env::var_os(name).is_some_and(|value| !value.is_empty()));
+    if !cfg!(unix) || !has_display {
+        return Vec::new();
+    }
+    vec![
+        (
+            "zenity",
+            vec![
+                "--question",
+                "--title=leave",
+                "--no-markup",
+                "--no-wrap",
+                "--text",
+                question,
+            ],
+        ),
+        ("kdialog", vec!["--title", "leave", "--yesno", question]),
+    ]
+}
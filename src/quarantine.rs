@@ -0,0 +1,264 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Quarantine mode: moving entries aside instead of deleting them.
+//!
+//! Quarantined entries are renamed into a `.leave-trash-<TIMESTAMP>`
+//! directory inside the target directory. Because this is a rename within
+//! the same filesystem, it's effectively instant even for huge trees.
+//!
+//! With `--journal`, each quarantined entry is also recorded in a journal
+//! file inside the quarantine directory, one escaped relative path per line,
+//! so that `leave undo` can put the entries back where they were.
+
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Mutex, PoisonError},
+};
+
+use eyre::{Context, OptionExt, bail};
+
+use crate::{datetime, i18n::tr, output::Output};
+
+/// Prefix of the names of quarantine directories.
+const PREFIX: &str = ".leave-trash-";
+
+/// Name of the journal file inside a quarantine directory.
+const JOURNAL_NAME: &str = ".leave-journal";
+
+/// A quarantine directory which is created the first time something is moved
+/// into it.
+pub struct Quarantine {
+    /// The directory being cleaned, which the quarantine directory is created
+    /// in.
+    root: PathBuf,
+    dir: Mutex<Option<PathBuf>>,
+    /// The journal file, if journaling is enabled and the directory has been
+    /// created.
+    journal: Option<Mutex<Option<File>>>,
+}
+
+impl Quarantine {
+    /// Creates a quarantine for the entries of `root`, which records moved
+    /// entries in a journal if `journal` is true.
+    pub fn new(root: PathBuf, journal: bool) -> Self {
+        Self {
+            root,
+            dir: Mutex::new(None),
+            journal: journal.then(|| Mutex::new(None)),
+        }
+    }
+
+    /// Moves the given entry of the directory being cleaned into the
+    /// quarantine directory, creating it if necessary. Returns the entry's new
+    /// path.
+    pub fn move_in(&self, path: &Path) -> eyre::Result<PathBuf> {
+        let dir = {
+            let mut dir = self.dir.lock().unwrap_or_else(PoisonError::into_inner);
+            match &*dir {
+                Some(dir) => dir.clone(),
+                None => dir.insert(create_dir(&self.root)?).clone(),
+            }
+        };
+        // Entries of subdirectories keep their relative path, so they can't
+        // collide with entries of the same name elsewhere
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        relative
+            .file_name()
+            .ok_or_eyre(tr!("Path has no file name"))?;
+        let destination = dir.join(relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)
+                .wrap_err_with(|| tr!("Can't create {path}", path = parent.display()))?;
+        }
+        fs::rename(path, &destination)
+            .wrap_err_with(|| tr!("Can't move into {dir}", dir = dir.display()))?;
+        if let Some(journal) = &self.journal {
+            let journal_path = dir.join(JOURNAL_NAME);
+            let mut journal = journal.lock().unwrap_or_else(PoisonError::into_inner);
+            let file = match &mut *journal {
+                Some(file) => file,
+                None => journal.insert(
+                    File::options()
+                        .create(true)
+                        .append(true)
+                        .open(&journal_path)
+                        .wrap_err_with(|| {
+                            tr!("Can't open {path}", path = journal_path.display())
+                        })?,
+                ),
+            };
+            writeln!(file, "{}", escape(&relative.to_string_lossy()))
+                .wrap_err_with(|| tr!("Can't write to {path}", path = journal_path.display()))?;
+        }
+        Ok(destination)
+    }
+}
+
+/// Restores the entries of the most recent journaled quarantine directory in
+/// the current directory to where they were.
+///
+/// Entries whose original path exists again aren't restored. They stay in the
+/// quarantine directory along with their journal entries, so undoing can be
+/// retried after moving the new entries out of the way.
+pub fn undo(out: &Output) -> eyre::Result<ExitCode> {
+    let mut journaled = Vec::new();
+    for entry in fs::read_dir(".").wrap_err(tr!("Can't list contents of ."))? {
+        let entry = entry.wrap_err(tr!("Can't read directory entry"))?;
+        if is_quarantine_dir(&entry.file_name()) && entry.path().join(JOURNAL_NAME).is_file() {
+            journaled.push(entry.path());
+        }
+    }
+    // Names contain a timestamp, so the last one is the most recent
+    journaled.sort();
+    let Some(dir) = journaled.pop() else {
+        bail!(tr!("No run recorded with --journal to undo"));
+    };
+
+    let journal_path = dir.join(JOURNAL_NAME);
+    let journal = fs::read_to_string(&journal_path)
+        .wrap_err_with(|| tr!("Can't read {path}", path = journal_path.display()))?;
+    let mut remaining = Vec::new();
+    // Restore in reverse order, so that nothing is restored into a directory
+    // which was removed after it
+    for line in journal.lines().rev() {
+        let relative = PathBuf::from(unescape(line));
+        if let Err(err) = restore(&dir, &relative) {
+            crate::print_error(out, &err);
+            remaining.push(line);
+        }
+    }
+
+    if remaining.is_empty() {
+        fs::remove_dir_all(&dir)
+            .wrap_err_with(|| tr!("Can't remove {path}", path = dir.display()))?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    remaining.reverse();
+    let mut contents = remaining.join("\n");
+    contents.push('\n');
+    fs::write(&journal_path, contents)
+        .wrap_err_with(|| tr!("Can't write to {path}", path = journal_path.display()))?;
+    Ok(ExitCode::FAILURE)
+}
+
+/// Moves an entry from the quarantine directory back to the given path
+/// relative to the current directory.
+fn restore(dir: &Path, relative: &Path) -> eyre::Result<()> {
+    let source = dir.join(relative);
+    let print_path = relative.display();
+    if fs::symlink_metadata(relative).is_ok() {
+        bail!(tr!(
+            "Can't restore {path}: it already exists",
+            path = print_path
+        ));
+    }
+    if let Some(parent) = relative
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .wrap_err_with(|| tr!("Can't create {path}", path = parent.display()))?;
+    }
+    fs::rename(&source, relative).wrap_err_with(|| tr!("Can't restore {path}", path = print_path))
+}
+
+/// Escapes a path for the journal, so that it fits on one line.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Reverses [`escape`].
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            (c, _) => out.push(c),
+        }
+    }
+    out
+}
+
+/// Returns whether the given file name is that of a quarantine directory.
+pub fn is_quarantine_dir(name: &OsStr) -> bool {
+    name.as_encoded_bytes().starts_with(PREFIX.as_bytes())
+}
+
+/// Creates a new, uniquely-named quarantine directory in `root` and returns
+/// its path.
+fn create_dir(root: &Path) -> eyre::Result<PathBuf> {
+    let base = format!("{PREFIX}{}", datetime::timestamp());
+    let mut path = root.join(&base);
+    let mut n = 1;
+    loop {
+        match fs::create_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                path = root.join(format!("{base}-{n}"));
+                n += 1;
+            }
+            Err(err) => {
+                return Err(err).wrap_err_with(|| {
+                    tr!(
+                        "Can't create quarantine directory {path}",
+                        path = path.display()
+                    )
+                });
+            }
+        }
+    }
+}
+
+/// Deletes all quarantine directories in the current directory.
+pub fn purge(out: &Output) -> eyre::Result<ExitCode> {
+    let mut had_failure = false;
+    for entry_result in fs::read_dir(".").wrap_err(tr!("Can't list contents of ."))? {
+        let result = entry_result
+            .wrap_err(tr!("Can't read directory entry"))
+            .and_then(|entry| {
+                if is_quarantine_dir(&entry.file_name()) && entry.file_type()?.is_dir() {
+                    let path = entry.path();
+                    fs::remove_dir_all(&path)
+                        .wrap_err_with(|| tr!("Can't remove {path}", path = path.display()))?;
+                }
+                Ok(())
+            });
+        if let Err(err) = result {
+            had_failure = true;
+            crate::print_error(out, &err);
+        }
+    }
+    Ok(if had_failure {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
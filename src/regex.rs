@@ -0,0 +1,139 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Regular expressions for keep arguments, for `--regex`.
+//!
+//! Patterns use the syntax of the [`regex`] crate, which covers the usual
+//! literals, classes, groups, alternation, and repetitions. Its matching runs
+//! in time linear in the length of the name, so no pattern can make a run
+//! hang, and patterns which would compile too large are rejected.
+//!
+//! Like in most regex engines, a pattern matches if it matches anywhere in
+//! the name, so use `^` and `$` to match the whole name.
+
+use std::fmt;
+
+use eyre::WrapErr;
+
+use crate::i18n::tr;
+
+/// A compiled regular expression, matched against file names.
+#[derive(Debug)]
+pub struct Regex(regex::Regex);
+
+impl Regex {
+    /// Compiles the given pattern.
+    pub fn parse(pattern: &str) -> eyre::Result<Self> {
+        regex::Regex::new(pattern).map(Self).wrap_err_with(|| {
+            tr!(
+                "Invalid regular expression {pattern}",
+                pattern = format!("{pattern:?}")
+            )
+        })
+    }
+
+    /// Returns whether the pattern matches anywhere in the given name.
+    pub fn is_match(&self, name: &str) -> bool {
+        self.0.is_match(name)
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Regex;
+
+    /// Returns whether `pattern` matches `name`.
+    fn matches(pattern: &str, name: &str) -> bool {
+        Regex::parse(pattern).unwrap().is_match(name)
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(matches("^a", "abc"));
+        assert!(!matches("^b", "abc"));
+        assert!(matches("c$", "abc"));
+        assert!(!matches("b$", "abc"));
+        assert!(matches("^abc$", "abc"));
+        assert!(!matches("^ab$", "abc"));
+        // Without anchors, a pattern matches anywhere in the name
+        assert!(matches("b", "abc"));
+    }
+
+    #[test]
+    fn alternation() {
+        assert!(matches(r"\.(rs|md)$", "main.rs"));
+        assert!(matches(r"\.(rs|md)$", "README.md"));
+        assert!(!matches(r"\.(rs|md)$", "notes.txt"));
+        assert!(matches("^(?:a|bc)$", "bc"));
+        assert!(!matches("^(?:a|bc)$", "abc"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("^[a-c]+$", "cab"));
+        assert!(!matches("^[a-c]+$", "cad"));
+        assert!(matches("^[^a-c]$", "d"));
+        assert!(!matches("^[^a-c]$", "a"));
+        assert!(matches(r"^\d\w\s$", "1_ "));
+        assert!(!matches(r"^\D", "1"));
+        assert!(matches("^[]a]$", "]"));
+    }
+
+    #[test]
+    fn bounded_repeats() {
+        assert!(matches(r"^\d{4}$", "2024"));
+        assert!(!matches(r"^\d{4}$", "24"));
+        assert!(matches("^a{2,}$", "aaa"));
+        assert!(!matches("^a{2,}$", "a"));
+        assert!(matches("^a{1,2}b$", "aab"));
+        assert!(!matches("^a{1,2}b$", "aaab"));
+        assert!(matches("^a{0}b$", "b"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in ["(a", "a)", "*a", "a{2,1}", "[a", r"\q"] {
+            assert!(Regex::parse(pattern).is_err(), "pattern: {pattern:?}");
+        }
+    }
+
+    /// Tests that nested repetitions which match the same text in many ways
+    /// don't take exponential time.
+    #[test]
+    fn ambiguous_repetition() {
+        let name = "a".repeat(40);
+        assert!(!matches("^(a|a)*b$", &name));
+        assert!(!matches("^(a*)*b$", &name));
+    }
+
+    /// Tests that a huge repetition of an empty group is either rejected or
+    /// matched without overflowing the stack.
+    #[test]
+    fn huge_empty_repetition() {
+        if let Ok(regex) = Regex::parse("(){999999}x") {
+            assert!(regex.is_match("x"));
+            assert!(!regex.is_match("y"));
+        }
+    }
+}
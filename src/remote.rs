@@ -0,0 +1,115 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Running leave on a remote host over SSH, for `--remote`.
+//!
+//! Rather than reimplementing the removal logic over SFTP, we run leave itself
+//! on the remote host, forwarding all of our arguments. This way every safety
+//! check runs exactly as it would locally, against the remote directory.
+//! Options naming local files, such as `--log`, can't be combined with
+//! `--remote`, since the remote leave would resolve them on its own host.
+
+use std::{
+    ffi::{OsStr, OsString},
+    process::{Command, ExitCode},
+};
+
+use eyre::{Context, bail};
+
+use crate::{i18n::tr, shell::quote};
+
+/// Environment variable which overrides the SSH program to use.
+const SSH_ENV_VAR: &str = "LEAVE_SSH";
+
+/// Options which are consumed locally and must not be forwarded.
+const LOCAL_OPTIONS: &[&str] = &["--remote", "--remote-command"];
+
+/// Runs leave on the host given by `spec`, which has the form
+/// `[USER@]HOST[:DIR]`, and returns its exit code.
+///
+/// `remote_command` is split into words at whitespace, each of which is
+/// quoted for the remote shell. `args` are this process's command-line
+/// arguments, excluding the program name.
+pub fn run(spec: &str, remote_command: &str, args: &[OsString]) -> eyre::Result<ExitCode> {
+    let (host, dir) = match spec.split_once(':') {
+        Some((host, dir)) => (host, Some(dir)),
+        None => (spec, None),
+    };
+    if host.is_empty() {
+        bail!(tr!(
+            "No host given in remote specification {spec}",
+            spec = format!("{spec:?}")
+        ));
+    }
+
+    // The command may have arguments of its own, e.g. `sudo leave`
+    let mut words = remote_command.split_whitespace();
+    let Some(program) = words.next() else {
+        bail!(tr!("--remote-command is empty"));
+    };
+    let mut command_line = quote(OsStr::new(program));
+    for word in words {
+        command_line.push(' ');
+        command_line.push_str(&quote(OsStr::new(word)));
+    }
+    if let Some(dir) = dir.filter(|dir| !dir.is_empty()) {
+        command_line.push_str(" -C ");
+        command_line.push_str(&quote(OsStr::new(dir)));
+    }
+    for arg in forwarded_args(args) {
+        command_line.push(' ');
+        command_line.push_str(&quote(arg));
+    }
+
+    let ssh = std::env::var_os(SSH_ENV_VAR).unwrap_or_else(|| "ssh".into());
+    let status = Command::new(&ssh)
+        .arg("--")
+        .arg(host)
+        .arg(&command_line)
+        .status()
+        .wrap_err_with(|| tr!("Can't run {path}", path = ssh.display()))?;
+    Ok(match status.code() {
+        Some(code) => ExitCode::from(u8::try_from(code).unwrap_or(1)),
+        None => ExitCode::FAILURE,
+    })
+}
+
+/// Filters out the options which only apply locally.
+fn forwarded_args(args: &[OsString]) -> Vec<&OsStr> {
+    let mut forwarded = Vec::with_capacity(args.len());
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            // Everything after -- is a file name
+            forwarded.push(arg.as_os_str());
+            forwarded.extend(iter.map(OsString::as_os_str));
+            break;
+        }
+        let bytes = arg.as_encoded_bytes();
+        if LOCAL_OPTIONS.iter().any(|opt| bytes == opt.as_bytes()) {
+            // Skip the option's value too
+            iter.next();
+        } else if !LOCAL_OPTIONS
+            .iter()
+            .any(|opt| bytes.starts_with(opt.as_bytes()) && bytes.get(opt.len()) == Some(&b'='))
+        {
+            forwarded.push(arg.as_os_str());
+        }
+    }
+    forwarded
+}
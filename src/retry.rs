@@ -0,0 +1,240 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Retrying of filesystem operations which fail temporarily.
+//!
+//! On Windows, a file which another process has open without
+//! `FILE_SHARE_DELETE` can't be removed, and the attempt fails with
+//! `ERROR_SHARING_VIOLATION`. This is usually temporary (e.g. an antivirus
+//! scan or an indexer), so we retry with exponential backoff before giving up.
+//!
+//! With `--retries`, other failures which may be temporary, such as busy files
+//! and stale handles on network file systems, are retried the same way on
+//! every platform.
+//!
+//! With `--timeout`, each attempt is also abandoned if it doesn't finish in
+//! time, using [`timeout::run`].
+
+use std::{io, sync::Arc, thread, time::Duration};
+
+use eyre::Context;
+
+use crate::{i18n::tr, timeout};
+
+/// Win32 error code for a sharing violation.
+const ERROR_SHARING_VIOLATION: i32 = 32;
+
+/// Delay before the first retry. Doubles after each attempt.
+const INITIAL_DELAY: Duration = Duration::from_millis(100);
+
+/// How many times to retry each kind of failure, and how long each attempt
+/// may take.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Retries for sharing violations, from `--sharing-retries`
+    pub sharing: u32,
+    /// Retries for other temporary failures, from `--retries`
+    pub transient: u32,
+    /// Time after which an attempt is abandoned, from `--timeout`. Abandoned
+    /// attempts aren't retried.
+    pub timeout: Option<Duration>,
+}
+
+/// Kinds of failures, which are retried up to different limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    /// Another process has the file open, see [`Limits::sharing`].
+    Sharing,
+    /// A failure which may go away by itself, see [`Limits::transient`].
+    Transient,
+    /// A failure which isn't retried.
+    Permanent,
+}
+
+/// Runs the given filesystem operation, retrying it if it fails in a way
+/// which may be temporary, up to the given limits.
+pub fn run<T: Send + 'static>(
+    limits: Limits,
+    op: impl Fn() -> io::Result<T> + Send + Sync + 'static,
+) -> eyre::Result<T> {
+    retry(limits, op, classify, thread::sleep)
+}
+
+/// Implements [`run`], with the way errors are classified and the way to wait
+/// between attempts given, so they can be replaced in tests.
+fn retry<T: Send + 'static>(
+    limits: Limits,
+    op: impl Fn() -> io::Result<T> + Send + Sync + 'static,
+    classify: impl Fn(&io::Error) -> Failure,
+    mut sleep: impl FnMut(Duration),
+) -> eyre::Result<T> {
+    let op = Arc::new(op);
+    let mut delay = INITIAL_DELAY;
+    // Each kind of failure is counted against its own limit
+    let (mut sharing_retries, mut transient_retries) = (0, 0);
+    loop {
+        let result = match limits.timeout {
+            Some(limit) => {
+                let op = Arc::clone(&op);
+                timeout::run(limit, move || op())?
+            }
+            None => op(),
+        };
+        match result {
+            Err(err) if classify(&err) == Failure::Sharing => {
+                if sharing_retries >= limits.sharing {
+                    // Finding the process would take the Restart Manager,
+                    // which isn't used
+                    return Err(err).wrap_err_with(|| {
+                        tr!(
+                            "File is in use by another process (tried {count} times). Which process can't be determined; Resource Monitor can find it under Associated Handles.",
+                            count = sharing_retries + 1
+                        )
+                    });
+                }
+                sharing_retries += 1;
+            }
+            Err(err) if classify(&err) == Failure::Transient && limits.transient > 0 => {
+                if transient_retries >= limits.transient {
+                    return Err(err).wrap_err_with(|| {
+                        tr!(
+                            "Gave up after trying {count} times",
+                            count = transient_retries + 1
+                        )
+                    });
+                }
+                transient_retries += 1;
+            }
+            result => return result.map_err(eyre::Report::from),
+        }
+        sleep(delay);
+        delay *= 2;
+    }
+}
+
+/// Returns the kind of the given failure. Directories can appear non-empty
+/// for a moment after their contents are removed, while network file systems
+/// or other processes release the files, so that's a transient failure.
+fn classify(err: &io::Error) -> Failure {
+    if cfg!(windows) && err.raw_os_error() == Some(ERROR_SHARING_VIOLATION) {
+        return Failure::Sharing;
+    }
+    match err.kind() {
+        io::ErrorKind::ResourceBusy
+        | io::ErrorKind::WouldBlock
+        | io::ErrorKind::Interrupted
+        | io::ErrorKind::TimedOut
+        | io::ErrorKind::StaleNetworkFileHandle
+        | io::ErrorKind::DirectoryNotEmpty => Failure::Transient,
+        _ => Failure::Permanent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    /// Returns an operation which fails with the given error kind `failures`
+    /// times before succeeding, and a count of its attempts.
+    fn flaky(
+        kind: io::ErrorKind,
+        failures: u32,
+    ) -> (
+        Arc<AtomicU32>,
+        impl Fn() -> io::Result<()> + Send + Sync + 'static,
+    ) {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&attempts);
+        let op = move || {
+            if counter.fetch_add(1, Ordering::Relaxed) < failures {
+                Err(io::Error::from(kind))
+            } else {
+                Ok(())
+            }
+        };
+        (attempts, op)
+    }
+
+    const LIMITS: Limits = Limits {
+        sharing: 5,
+        transient: 2,
+        timeout: None,
+    };
+
+    #[test]
+    fn retries_transient_failures() {
+        let (attempts, op) = flaky(io::ErrorKind::ResourceBusy, 2);
+        run(LIMITS, op).unwrap();
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn gives_up_after_transient_limit() {
+        let (attempts, op) = flaky(io::ErrorKind::ResourceBusy, 10);
+        let err = run(LIMITS, op).unwrap_err();
+        assert_eq!(err.to_string(), "Gave up after trying 3 times");
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn doesnt_retry_other_failures() {
+        let (attempts, op) = flaky(io::ErrorKind::PermissionDenied, 10);
+        assert!(run(LIMITS, op).is_err());
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+
+        let limits = Limits {
+            transient: 0,
+            ..LIMITS
+        };
+        let (attempts, op) = flaky(io::ErrorKind::ResourceBusy, 10);
+        assert!(run(limits, op).is_err());
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+
+    /// Treats every failure as a sharing violation, which only Windows
+    /// reports.
+    fn sharing(_: &io::Error) -> Failure {
+        Failure::Sharing
+    }
+
+    #[test]
+    fn retries_sharing_violations_with_backoff() {
+        let (attempts, op) = flaky(io::ErrorKind::Other, 3);
+        let mut delays = Vec::new();
+        retry(LIMITS, op, sharing, |delay| delays.push(delay)).unwrap();
+        assert_eq!(attempts.load(Ordering::Relaxed), 4);
+        assert_eq!(delays, [100, 200, 400].map(Duration::from_millis).to_vec());
+    }
+
+    #[test]
+    fn gives_up_after_sharing_limit() {
+        let (attempts, op) = flaky(io::ErrorKind::Other, 10);
+        let mut delays = Vec::new();
+        let err = retry(LIMITS, op, sharing, |delay| delays.push(delay)).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("File is in use by another process (tried 6 times)."),
+            "{err}"
+        );
+        assert_eq!(attempts.load(Ordering::Relaxed), 6);
+        assert_eq!(delays.len(), 5);
+        assert_eq!(delays.last(), Some(&Duration::from_millis(1600)));
+    }
+}
@@ -0,0 +1,207 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Rules files, for `--rules`: keep policies written in a syntax like
+//! `.gitignore`'s, except that patterns list what to keep.
+//!
+//! - Blank lines and lines starting with `#` are ignored.
+//! - A line starting with `!` un-keeps the entries it matches, so they're
+//!   removed after all.
+//! - The last rule matching an entry decides whether it's kept.
+//! - A pattern ending with `/` only matches directories.
+//! - A pattern containing another `/` is matched against the path relative
+//!   to the target directory; otherwise it's matched against the name of
+//!   entries at any depth.
+//! - `**` matches any number of directories.
+//!
+//! A leading `\` escapes `!` or `#`. Other wildcards are those of `--glob`, so
+//! they don't match a leading `.` either. Template variables such as
+//! `{hostname}` are expanded as in keep arguments.
+
+use std::{
+    fmt, fs,
+    path::{Component, Path},
+};
+
+use eyre::Context;
+
+use crate::{glob, i18n::tr, vars};
+
+/// The rules read from a rules file.
+#[derive(Debug, Clone)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+/// One line of a rules file.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// Whether the rule un-keeps matching entries, i.e. it starts with `!`.
+    negated: bool,
+    /// Whether the rule only matches directories.
+    dir_only: bool,
+    /// Whether the rule is matched against whole paths rather than names.
+    anchored: bool,
+    segments: Vec<Segment>,
+    /// The line as it was given.
+    source: String,
+}
+
+/// One `/`-separated part of a pattern.
+#[derive(Debug, Clone)]
+enum Segment {
+    Name(glob::Pattern),
+    /// `**`
+    AnyDirs,
+}
+
+impl Rules {
+    /// Reads the rules in the given file.
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| tr!("Can't read {path}", path = path.display()))?;
+        let rules = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| {
+                vars::expand_str(line.trim())
+                    .and_then(|line| Rule::parse(&line))
+                    .wrap_err_with(|| {
+                        tr!(
+                            "Invalid rule on line {line} of {path}",
+                            line = i + 1,
+                            path = path.display()
+                        )
+                    })
+            })
+            .collect::<eyre::Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns the rule which keeps the entry at `path`, relative to the
+    /// target directory, if any.
+    pub fn keeps(&self, path: &Path, is_dir: bool) -> Option<&Rule> {
+        let components = components(path);
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&components, is_dir))
+            .filter(|rule| !rule.negated)
+    }
+
+    /// Returns whether any entry inside the directory at `path` is kept.
+    pub fn keeps_inside(&self, path: &Path) -> eyre::Result<bool> {
+        let children = fs::read_dir(path)
+            .wrap_err_with(|| tr!("Can't list contents of {dir}", dir = path.display()))?;
+        for child in children {
+            let child = child.wrap_err(tr!("Can't read directory entry"))?;
+            let child_path = child.path();
+            let is_dir = child
+                .file_type()
+                .wrap_err_with(|| tr!("Can't get type of {path}", path = child_path.display()))?
+                .is_dir();
+            if self.keeps(&child_path, is_dir).is_some()
+                || (is_dir && self.keeps_inside(&child_path)?)
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Rule {
+    /// Parses one line of a rules file.
+    fn parse(line: &str) -> eyre::Result<Self> {
+        let source = line.to_owned();
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let anchored = pattern.contains('/');
+        let segments = pattern
+            .trim_start_matches('/')
+            .split('/')
+            .map(|segment| match segment {
+                "**" => Ok(Segment::AnyDirs),
+                segment => glob::Pattern::parse(segment).map(Segment::Name),
+            })
+            .collect::<eyre::Result<_>>()?;
+        Ok(Self {
+            negated,
+            dir_only,
+            anchored,
+            segments,
+            source,
+        })
+    }
+
+    /// Returns whether the rule matches the entry with the given path
+    /// components.
+    fn matches(&self, components: &[String], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            matches_segments(&self.segments, components)
+        } else {
+            components
+                .last()
+                .is_some_and(|name| matches_segments(&self.segments, std::slice::from_ref(name)))
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Returns whether the given pattern segments match the given path
+/// components. A trailing `**` only matches entries inside a directory, not
+/// the directory itself.
+fn matches_segments(segments: &[Segment], components: &[String]) -> bool {
+    match segments.split_first() {
+        None => components.is_empty(),
+        Some((Segment::AnyDirs, [])) => !components.is_empty(),
+        Some((Segment::AnyDirs, rest)) => {
+            (0..=components.len()).any(|skip| matches_segments(rest, &components[skip..]))
+        }
+        Some((Segment::Name(pattern), rest)) => {
+            components.split_first().is_some_and(|(name, components)| {
+                pattern.matches(name) && matches_segments(rest, components)
+            })
+        }
+    }
+}
+
+/// Splits a relative path into the names of its components.
+fn components(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
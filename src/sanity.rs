@@ -0,0 +1,224 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Policy sanity checks.
+//!
+//! When some keep arguments don't exist, these heuristics look for specific
+//! signs of what went wrong so the user gets a targeted message instead of a
+//! generic "doesn't exist" warning. With `-f`, which otherwise allows keep
+//! arguments that don't exist, a run in which they find a problem is still
+//! refused unless `--no-sanity-check` is given.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{glob, i18n::tr};
+
+/// A suspicious condition found in the keep arguments.
+#[derive(Debug)]
+pub enum Problem<'a> {
+    /// A keep argument doesn't exist, but an entry whose name differs only in
+    /// case does.
+    CaseMismatch { arg: &'a Path, entry: &'a OsStr },
+    /// A keep argument doesn't exist, but an entry whose name is only a few
+    /// edits away, likely a typo, does.
+    Typo { arg: &'a Path, entry: &'a OsStr },
+    /// A keep argument is a glob pattern which matches no entry, so the
+    /// shell, or `--glob`, left it as it was.
+    UnmatchedGlob { arg: &'a Path },
+    /// None of the keep arguments exist, so everything would be removed.
+    NothingKept,
+}
+
+impl fmt::Display for Problem<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Problem::CaseMismatch { arg, entry } => tr!(
+                "{arg} doesn't exist, but {entry} does. Keep arguments are case-sensitive unless --ignore-case is given.",
+                arg = arg.display(),
+                entry = entry.display(),
+            ),
+            Problem::Typo { arg, entry } => tr!(
+                "{arg} doesn't exist. Did you mean '{entry}'?",
+                arg = arg.display(),
+                entry = entry.display(),
+            ),
+            Problem::UnmatchedGlob { arg } => tr!(
+                "{arg} doesn't exist. It looks like a pattern, but it matched no entries.",
+                arg = arg.display(),
+            ),
+            Problem::NothingKept => {
+                tr!("None of the provided files exist, so everything would be removed.").to_owned()
+            }
+        };
+        f.write_str(&message)
+    }
+}
+
+/// Checks the keep arguments which don't exist (`missing`) against the names
+/// of the entries in the current directory.
+///
+/// `arg_count` is the total number of keep arguments.
+pub fn check<'a>(
+    arg_count: usize,
+    missing: &[&'a PathBuf],
+    entry_names: &'a [OsString],
+) -> Vec<Problem<'a>> {
+    let mut problems = Vec::new();
+    for arg in missing {
+        let Some(name) = arg.file_name() else {
+            continue;
+        };
+        let folded = name.to_string_lossy().to_lowercase();
+        if is_unmatched_glob(&name.to_string_lossy(), entry_names) {
+            problems.push(Problem::UnmatchedGlob { arg });
+        } else if let Some(entry) = entry_names
+            .iter()
+            .find(|entry| entry.to_string_lossy().to_lowercase() == folded)
+        {
+            problems.push(Problem::CaseMismatch { arg, entry });
+        } else if let Some(entry) = closest_name(&name.to_string_lossy(), entry_names) {
+            problems.push(Problem::Typo { arg, entry });
+        }
+    }
+    if arg_count > 0 && missing.len() == arg_count {
+        problems.push(Problem::NothingKept);
+    }
+    problems
+}
+
+/// Returns whether `name` is a glob pattern which matches none of the entry
+/// names.
+fn is_unmatched_glob(name: &str, entry_names: &[OsString]) -> bool {
+    name.contains(['*', '?', '['])
+        && glob::Pattern::parse(name).is_ok_and(|pattern| {
+            !entry_names
+                .iter()
+                .any(|entry| pattern.matches(&entry.to_string_lossy()))
+        })
+}
+
+/// Returns the entry name closest to `name`, if it's close enough to be a
+/// likely typo: at most one edit for every three characters, and at least
+/// one.
+fn closest_name<'a>(name: &str, entry_names: &'a [OsString]) -> Option<&'a OsStr> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    entry_names
+        .iter()
+        .map(|entry| (edit_distance(name, &entry.to_string_lossy()), entry))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, entry)| entry.as_os_str())
+}
+
+/// Returns the number of insertions, deletions, substitutions, and swaps of
+/// adjacent characters needed to turn `a` into `b` (the optimal string
+/// alignment distance).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Rows of the distance table for the previous two prefixes of `a`
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<OsString> {
+        names.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn unmatched_glob() {
+        let entries = names(&["notes.txt", "photo.jpg"]);
+        let arg = PathBuf::from("*.md");
+        let problems = check(1, &[&arg], &entries);
+        assert!(matches!(problems[0], Problem::UnmatchedGlob { arg: a } if a == arg));
+        assert!(matches!(problems[1], Problem::NothingKept));
+    }
+
+    #[test]
+    fn matched_glob() {
+        // A quoted pattern which matches is reported like any missing name
+        let entries = names(&["notes.txt", "photo.jpg"]);
+        let arg = PathBuf::from("*.txt");
+        let problems = check(2, &[&arg], &entries);
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+
+    #[test]
+    fn case_mismatch() {
+        let entries = names(&["README.md", "src"]);
+        let arg = PathBuf::from("readme.md");
+        let problems = check(2, &[&arg], &entries);
+        assert!(matches!(
+            problems[..],
+            [Problem::CaseMismatch { entry, .. }] if entry == "README.md"
+        ));
+
+        // Names which match exactly aren't missing, so only others are checked
+        let arg = PathBuf::from("Cargo.toml");
+        assert!(check(2, &[&arg], &entries).is_empty());
+    }
+
+    #[test]
+    fn typo() {
+        let entries = names(&["Cargo.toml", "src"]);
+        let arg = PathBuf::from("Cargo.tmol");
+        let problems = check(2, &[&arg], &entries);
+        assert!(matches!(
+            problems[..],
+            [Problem::Typo { entry, .. }] if entry == "Cargo.toml"
+        ));
+
+        let arg = PathBuf::from("unrelated");
+        assert!(check(2, &[&arg], &entries).is_empty());
+    }
+
+    #[test]
+    fn nothing_kept() {
+        let entries = names(&["a", "b"]);
+        let (x, y) = (PathBuf::from("xxxxxx"), PathBuf::from("yyyyyy"));
+        let problems = check(2, &[&x, &y], &entries);
+        assert!(matches!(problems[..], [Problem::NothingKept]));
+
+        // Some arguments exist
+        assert!(check(3, &[&x, &y], &entries).is_empty());
+        // An empty keep set, e.g. with only --keep-* options, isn't suspicious
+        assert!(check(0, &[], &entries).is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Parsing, measuring, and formatting sizes for `--smaller-than`,
+//! `--larger-than`, `--summary`, `--report-size`, and `--stat`.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// Parses a human-friendly size such as `512`, `10K`, `100M`, or `2G`.
+///
+/// Suffixes are binary multiples (`K` = 1024 bytes) and may be followed by
+/// `B` or `iB`, in either case. A bare number is interpreted as bytes.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let invalid = || {
+        tr!(
+            "invalid size {size}; expected e.g. 512, 10K, 100M, or 2G",
+            size = format!("{s:?}")
+        )
+    };
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let value: u64 = s[..digits].parse().map_err(|_| invalid())?;
+    let suffix = s[digits..].to_ascii_uppercase();
+    let suffix = suffix
+        .strip_suffix("IB")
+        .or_else(|| suffix.strip_suffix('B'))
+        .unwrap_or(&suffix);
+    let shift = match suffix {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(invalid()),
+    };
+    value.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+/// Returns the total size of the files in the given tree, without following
+/// symbolic links. Files with several hard links in the tree are counted
+/// once.
+pub fn tree_size(path: &Path) -> eyre::Result<u64> {
+    Ok(Usage::measure(path)?.total_bytes())
+}
+
+/// Numbers of files, directories, and symbolic links in a tree and their
+/// total size.
+///
+/// The space of a file with other hard links is only freed once all of them
+/// are removed, so such files are tracked by device and inode, and their
+/// size counts as freed only if every link was measured. The rest is
+/// pending: it's freed once the remaining links are removed too.
+#[derive(Debug, Default, Clone)]
+pub struct Usage {
+    /// Files, including special files.
+    pub files: u64,
+    pub dirs: u64,
+    pub symlinks: u64,
+    /// Apparent size of the entries with a single link.
+    bytes: u64,
+    /// Disk space used by the entries with a single link, which can be more
+    /// than their apparent size due to block rounding, or less for sparse
+    /// files.
+    disk_bytes: u64,
+    /// Files with more than one hard link, by device and inode number.
+    linked: HashMap<(u64, u64), LinkedFile>,
+}
+
+/// A file with more than one hard link, for [`Usage`].
+#[derive(Debug, Clone, Copy)]
+struct LinkedFile {
+    /// Number of links the file has.
+    nlink: u64,
+    /// Number of its links which were measured.
+    seen: u64,
+    bytes: u64,
+    disk_bytes: u64,
+}
+
+impl LinkedFile {
+    /// Returns whether all of the file's links were measured, so removing
+    /// them frees its space.
+    fn is_freed(&self) -> bool {
+        self.seen >= self.nlink
+    }
+}
+
+impl Usage {
+    /// Measures the given tree, without following symbolic links. Anything
+    /// which isn't a directory or a symbolic link counts as a file.
+    pub fn measure(path: &Path) -> eyre::Result<Self> {
+        let mut usage = Self::default();
+        usage.measure_into(path)?;
+        Ok(usage)
+    }
+
+    /// Adds the given tree to this measurement.
+    fn measure_into(&mut self, path: &Path) -> eyre::Result<()> {
+        let metadata = fs::symlink_metadata(path)
+            .wrap_err_with(|| tr!("Can't get metadata of {path}", path = path.display()))?;
+        if metadata.is_dir() {
+            self.dirs += 1;
+            self.disk_bytes += disk_usage(&metadata);
+            for entry in fs::read_dir(path)
+                .wrap_err_with(|| tr!("Can't list contents of {dir}", dir = path.display()))?
+            {
+                let entry = entry.wrap_err(tr!("Can't read directory entry"))?;
+                self.measure_into(&entry.path())?;
+            }
+            return Ok(());
+        }
+        if metadata.is_symlink() {
+            self.symlinks += 1;
+        } else {
+            self.files += 1;
+        }
+        if let Some((key, nlink)) = link_key(&metadata) {
+            self.linked
+                .entry(key)
+                .or_insert(LinkedFile {
+                    nlink,
+                    seen: 0,
+                    bytes: metadata.len(),
+                    disk_bytes: disk_usage(&metadata),
+                })
+                .seen += 1;
+        } else {
+            self.bytes += metadata.len();
+            self.disk_bytes += disk_usage(&metadata);
+        }
+        Ok(())
+    }
+
+    /// Adds another measurement to this one. Links to the same file in both
+    /// add up.
+    pub fn add(&mut self, other: Self) {
+        self.files += other.files;
+        self.dirs += other.dirs;
+        self.symlinks += other.symlinks;
+        self.bytes += other.bytes;
+        self.disk_bytes += other.disk_bytes;
+        for (key, file) in other.linked {
+            self.linked
+                .entry(key)
+                .and_modify(|linked| linked.seen += file.seen)
+                .or_insert(file);
+        }
+    }
+
+    /// Returns the apparent size of the files whose space removing them
+    /// frees.
+    pub fn freed_bytes(&self) -> u64 {
+        self.bytes + self.linked_sum(|file| file.is_freed().then_some(file.bytes))
+    }
+
+    /// Returns the disk space which removing the tree frees.
+    pub fn freed_disk_bytes(&self) -> u64 {
+        self.disk_bytes + self.linked_sum(|file| file.is_freed().then_some(file.disk_bytes))
+    }
+
+    /// Returns the apparent size of the files which have hard links outside
+    /// what was measured, so their space isn't freed yet.
+    pub fn pending_bytes(&self) -> u64 {
+        self.linked_sum(|file| (!file.is_freed()).then_some(file.bytes))
+    }
+
+    /// Returns the apparent size of all files, counting each file with
+    /// several links once.
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes + self.linked_sum(|file| Some(file.bytes))
+    }
+
+    /// Sums the sizes of the linked files picked by `size`.
+    fn linked_sum(&self, size: impl Fn(&LinkedFile) -> Option<u64>) -> u64 {
+        self.linked.values().filter_map(size).sum()
+    }
+}
+
+/// Returns the device and inode numbers of the file with the given metadata,
+/// along with its number of links, if it has more than one.
+#[cfg(unix)]
+fn link_key(metadata: &fs::Metadata) -> Option<((u64, u64), u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| ((metadata.dev(), metadata.ino()), metadata.nlink()))
+}
+
+/// Returns the device and inode numbers of the file with the given metadata,
+/// along with its number of links, if it has more than one. Link counts
+/// aren't available on this platform, so every file counts as having one.
+#[cfg(not(unix))]
+fn link_key(_metadata: &fs::Metadata) -> Option<((u64, u64), u64)> {
+    None
+}
+
+/// Returns the disk space used by the entry with the given metadata.
+#[cfg(unix)]
+fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // Blocks are always counted in 512-byte units
+    metadata.blocks() * 512
+}
+
+/// Returns the disk space used by the entry with the given metadata. Only
+/// the apparent size is available on this platform.
+#[cfg(not(unix))]
+fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Formats a number of bytes using binary units, e.g. `1.2 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
@@ -0,0 +1,328 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Filesystem snapshots taken before destructive runs, for `--snapshot`.
+//!
+//! ZFS datasets are snapshotted with `zfs snapshot`. Btrfs subvolumes are
+//! snapshotted read-only with `btrfs subvolume snapshot` into a
+//! `.leave-snapshots` directory at the root of the subvolume.
+//!
+//! Each snapshot is recorded in a journal in the
+//! [state directory](crate::state), so that `leave gc` can delete old
+//! snapshots and forget those which were deleted some other way.
+
+use std::{
+    ffi::OsStr,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use eyre::{Context, OptionExt, bail};
+
+use crate::{datetime, i18n::tr, output::Output, state};
+
+/// Name of the journal of snapshots in the state directory.
+const JOURNAL_NAME: &str = "snapshot-journal";
+
+/// Name of the directory holding Btrfs snapshots, at the root of the
+/// snapshotted subvolume.
+const BTRFS_SNAPSHOT_DIR: &str = ".leave-snapshots";
+
+/// Inode number of the root directory of every Btrfs subvolume.
+const BTRFS_SUBVOLUME_ROOT_INODE: u64 = 256;
+
+/// A mount point, as listed in `/proc/self/mountinfo`.
+struct Mount {
+    path: PathBuf,
+    fs_type: String,
+    source: String,
+}
+
+/// Creates a snapshot of the filesystem containing the current directory and
+/// returns a name by which the snapshot can be identified.
+///
+/// Fails if the current directory is not on ZFS or Btrfs.
+pub fn create() -> eyre::Result<String> {
+    if !cfg!(target_os = "linux") {
+        bail!(tr!("--snapshot is only supported on Linux"));
+    }
+
+    let cwd = fs::canonicalize(".").wrap_err(tr!("Can't resolve current working directory"))?;
+    let mount = find_mount(&cwd)?;
+    let name = format!("leave-{}", datetime::timestamp());
+    match mount.fs_type.as_str() {
+        "zfs" => {
+            let snapshot = format!("{}@{name}", mount.source);
+            run(Command::new("zfs").arg("snapshot").arg(&snapshot))?;
+            record("zfs", &snapshot)?;
+            Ok(snapshot)
+        }
+        "btrfs" => {
+            let subvolume = find_btrfs_subvolume(&cwd)?;
+            let dir = subvolume.join(BTRFS_SNAPSHOT_DIR);
+            fs::create_dir_all(&dir)
+                .wrap_err_with(|| tr!("Can't create {path}", path = dir.display()))?;
+            let snapshot = dir.join(name);
+            run(Command::new("btrfs")
+                .args(["subvolume", "snapshot", "-r"])
+                .arg(&subvolume)
+                .arg(&snapshot))?;
+            let snapshot = snapshot.display().to_string();
+            record("btrfs", &snapshot)?;
+            Ok(snapshot)
+        }
+        other => bail!(tr!(
+            "{path} is on {other}, but snapshots require ZFS or Btrfs",
+            path = cwd.display(),
+            other = other
+        )),
+    }
+}
+
+/// Appends a record of a snapshot of the given kind, `zfs` or `btrfs`, to
+/// the journal.
+fn record(kind: &str, snapshot: &str) -> eyre::Result<()> {
+    let write = || -> eyre::Result<()> {
+        let dir = state::dir()?;
+        fs::create_dir_all(&dir)
+            .wrap_err_with(|| tr!("Can't create {path}", path = dir.display()))?;
+        let path = dir.join(JOURNAL_NAME);
+        let line = format!("{}\t{kind}\t{}\n", state::now(), state::escape(snapshot));
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .wrap_err_with(|| tr!("Can't write to {path}", path = path.display()))
+    };
+    write().wrap_err_with(|| {
+        tr!(
+            "Snapshot {name} was created, but can't be recorded for leave gc",
+            name = snapshot
+        )
+    })
+}
+
+/// Deletes the snapshots recorded in the journal which were created more
+/// than `older_than` ago, if given, and drops the records of snapshots which
+/// no longer exist. Returns whether deleting or checking a snapshot failed.
+pub fn prune(older_than: Option<Duration>, out: &Output) -> eyre::Result<bool> {
+    let path = state::dir()?.join(JOURNAL_NAME);
+    let journal = match fs::read_to_string(&path) {
+        Ok(journal) => journal,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| tr!("Can't read {path}", path = path.display()));
+        }
+    };
+    let cutoff = older_than.map(|older_than| state::now().saturating_sub(older_than.as_secs()));
+    let mut remaining = String::new();
+    let mut had_failure = false;
+    let mut forgotten = 0;
+    for line in journal.lines() {
+        let Some((time, kind, snapshot)) = parse_record(line) else {
+            out.warning(tr!(
+                "Ignoring malformed record in {path}: {record}",
+                path = path.display(),
+                record = line
+            ));
+            remaining.push_str(line);
+            remaining.push('\n');
+            continue;
+        };
+        let result = exists(kind, &snapshot).and_then(|exists| {
+            if !exists {
+                forgotten += 1;
+                Ok(false)
+            } else if cutoff.is_some_and(|cutoff| time <= cutoff) {
+                delete(kind, &snapshot)?;
+                out.message(format_args!("deleted snapshot '{snapshot}'"));
+                Ok(false)
+            } else {
+                Ok(true)
+            }
+        });
+        match result {
+            Ok(false) => (),
+            Ok(true) => {
+                remaining.push_str(line);
+                remaining.push('\n');
+            }
+            Err(err) => {
+                had_failure = true;
+                crate::print_error(out, &err);
+                remaining.push_str(line);
+                remaining.push('\n');
+            }
+        }
+    }
+    if forgotten > 0 {
+        out.message(format_args!(
+            "{}",
+            tr!(
+                "Forgot {count} snapshots which no longer exist",
+                count = forgotten
+            )
+        ));
+    }
+    if remaining != journal {
+        state::replace(&path, &remaining)?;
+    }
+    Ok(had_failure)
+}
+
+/// Parses a journal record into the time the snapshot was created, its kind
+/// and its name.
+fn parse_record(line: &str) -> Option<(u64, &str, String)> {
+    let mut fields = line.split('\t');
+    let time = fields.next()?.parse().ok()?;
+    let kind = fields
+        .next()
+        .filter(|kind| ["zfs", "btrfs"].contains(kind))?;
+    let snapshot = state::unescape(fields.next()?);
+    fields.next().is_none().then_some((time, kind, snapshot))
+}
+
+/// Returns whether the given snapshot still exists.
+fn exists(kind: &str, snapshot: &str) -> eyre::Result<bool> {
+    if kind == "btrfs" {
+        return Ok(fs::symlink_metadata(snapshot).is_ok());
+    }
+    let status = Command::new("zfs")
+        .args(["list", "-H", "-t", "snapshot", "-o", "name"])
+        .arg(snapshot)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .wrap_err_with(|| tr!("Can't run {program}", program = "zfs"))?;
+    Ok(status.success())
+}
+
+/// Deletes the given snapshot.
+fn delete(kind: &str, snapshot: &str) -> eyre::Result<()> {
+    let result = if kind == "btrfs" {
+        run(Command::new("btrfs")
+            .args(["subvolume", "delete"])
+            .arg(snapshot))
+    } else {
+        run(Command::new("zfs").arg("destroy").arg(snapshot))
+    };
+    result.wrap_err_with(|| tr!("Can't delete snapshot {name}", name = snapshot))
+}
+
+/// Runs a snapshot command, failing if it doesn't exit successfully.
+fn run(command: &mut Command) -> eyre::Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .status()
+        .wrap_err_with(|| tr!("Can't run {program}", program = program))?;
+    if !status.success() {
+        bail!(tr!(
+            "{program} failed with {status}",
+            program = program,
+            status = status
+        ));
+    }
+    Ok(())
+}
+
+/// Finds the mount containing the given canonical path.
+fn find_mount(path: &Path) -> eyre::Result<Mount> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")
+        .wrap_err(tr!("Can't read /proc/self/mountinfo"))?;
+    mountinfo
+        .lines()
+        .filter_map(parse_mountinfo_line)
+        .filter(|mount| path.starts_with(&mount.path))
+        .max_by_key(|mount| mount.path.as_os_str().len())
+        .ok_or_eyre(tr!("Can't find the mount containing the current directory"))
+}
+
+/// Parses a line of `/proc/self/mountinfo`.
+///
+/// The format is described in proc(5). The fields we care about are the mount
+/// point (5th field) and the filesystem type and source, which follow the `-`
+/// separator.
+fn parse_mountinfo_line(line: &str) -> Option<Mount> {
+    let (before, after) = line.split_once(" - ")?;
+    let mount_point = before.split(' ').nth(4)?;
+    let mut after = after.split(' ');
+    let fs_type = after.next()?;
+    let source = after.next()?;
+    Some(Mount {
+        path: PathBuf::from(unescape(mount_point)),
+        fs_type: fs_type.to_owned(),
+        source: unescape(source),
+    })
+}
+
+/// Decodes the octal escapes (e.g. `\040` for a space) used in mountinfo.
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let code = rest
+            .get(pos + 1..pos + 4)
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        if let Some(code) = code {
+            out.push(char::from(code));
+            rest = &rest[pos + 4..];
+        } else {
+            out.push('\\');
+            rest = &rest[pos + 1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Finds the root of the Btrfs subvolume containing the given canonical path.
+#[cfg(unix)]
+fn find_btrfs_subvolume(path: &Path) -> eyre::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    for dir in path.ancestors() {
+        let metadata = fs::metadata(dir)
+            .wrap_err_with(|| tr!("Can't get metadata of {dir}", dir = dir.display()))?;
+        if metadata.ino() == BTRFS_SUBVOLUME_ROOT_INODE {
+            return Ok(dir.to_owned());
+        }
+    }
+    bail!(tr!(
+        "Can't find the Btrfs subvolume containing {path}",
+        path = path.display()
+    ))
+}
+
+#[cfg(not(unix))]
+fn find_btrfs_subvolume(path: &Path) -> eyre::Result<PathBuf> {
+    bail!(tr!(
+        "Can't find the Btrfs subvolume containing {path}",
+        path = path.display()
+    ))
+}
+
+/// Returns whether the given file name is that of the Btrfs snapshot
+/// directory.
+pub fn is_snapshot_dir(name: &OsStr) -> bool {
+    name == BTRFS_SNAPSHOT_DIR
+}
@@ -0,0 +1,61 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Ordering of entries before they're processed, for `--sort`.
+//!
+//! Directories list their entries in an order which depends on the file
+//! system, so without sorting, output can differ between runs and machines.
+
+use std::{cmp::Reverse, time::UNIX_EPOCH};
+
+use clap::ValueEnum;
+
+use crate::dirfd::DirEntry;
+
+/// The order to process entries in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// By name
+    Name,
+    /// By modification time, newest first
+    Mtime,
+    /// By size, largest first. Directories are sized without their contents
+    Size,
+    /// In the order the file system lists them, which is fastest
+    None,
+}
+
+/// Sorts the given entries in the given order. Ties are broken by name.
+pub fn sort(order: SortOrder, entries: &mut [DirEntry]) {
+    if order == SortOrder::None {
+        return;
+    }
+    let rank = |entry: &DirEntry| -> u128 {
+        let metadata = entry.metadata();
+        match order {
+            SortOrder::Mtime => metadata
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_nanos()),
+            SortOrder::Size => metadata.map_or(0, |metadata| metadata.len().into()),
+            SortOrder::Name | SortOrder::None => 0,
+        }
+    };
+    entries.sort_by_cached_key(|entry| (Reverse(rank(entry)), entry.file_name()));
+}
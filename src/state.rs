@@ -0,0 +1,94 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! The directory where leave keeps records between runs, such as the journal
+//! of trashed entries, and helpers for the journals kept there.
+//!
+//! It's `leave` in `$XDG_STATE_HOME` (`~/.local/state` by default), or in
+//! `%LOCALAPPDATA%` on Windows. Journals have one record per line, with
+//! tab-separated fields starting with the time the record was made.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Context, OptionExt};
+
+use crate::i18n::tr;
+
+/// Returns the path of the state directory, which may not exist yet.
+pub fn dir() -> eyre::Result<PathBuf> {
+    let non_empty = |name| env::var_os(name).filter(|value| !value.is_empty());
+    let base = if cfg!(windows) {
+        non_empty("LOCALAPPDATA")
+            .map(PathBuf::from)
+            .ok_or_eyre(tr!("LOCALAPPDATA is not set"))?
+    } else if let Some(state_home) = non_empty("XDG_STATE_HOME") {
+        PathBuf::from(state_home)
+    } else {
+        PathBuf::from(non_empty("HOME").ok_or_eyre(tr!("HOME is not set"))?).join(".local/state")
+    };
+    Ok(base.join("leave"))
+}
+
+/// Extension of the temporary files written by [`replace`].
+pub const TEMPORARY_EXTENSION: &str = "tmp";
+
+/// Replaces the contents of the given journal at once, so it's never left
+/// half-written.
+pub fn replace(path: &Path, contents: &str) -> eyre::Result<()> {
+    let temporary = path.with_extension(TEMPORARY_EXTENSION);
+    fs::write(&temporary, contents)
+        .and_then(|()| fs::rename(&temporary, path))
+        .wrap_err_with(|| tr!("Can't write to {path}", path = path.display()))
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Escapes a path for a journal, so that it fits in one field.
+pub fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+/// Reverses [`escape`].
+pub fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
@@ -0,0 +1,114 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Making removals durable with `--sync`.
+//!
+//! Removing or moving an entry changes the directory which contained it, and
+//! moving it also changes the directory it was moved into. Those directories
+//! are recorded as entries are removed, including those inside directories
+//! removed with `-r`, and flushed to disk afterwards.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    mem,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// The directories changed by a run since they were last synced, which are
+/// only recorded with `--sync`.
+#[derive(Debug, Default)]
+pub struct Changes {
+    dirs: Mutex<Option<HashSet<PathBuf>>>,
+}
+
+impl Changes {
+    /// Starts recording the directories changed by removals.
+    pub fn start(&self) {
+        self.dirs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert_default();
+    }
+
+    /// Records that the entry at the given path was removed, or created by
+    /// moving an entry there, which changed the directory containing it. Does
+    /// nothing unless recording was started.
+    pub fn entry_changed(&self, path: &Path) {
+        let mut changed = self.dirs.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(changed) = changed.as_mut() else {
+            return;
+        };
+        // If the entry was a directory, it's gone and can't be synced
+        changed.remove(path);
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        changed.insert(parent.to_owned());
+    }
+
+    /// Returns the directories changed since the last call, sorted, and
+    /// forgets them.
+    pub fn take(&self) -> Vec<PathBuf> {
+        let mut changed = self.dirs.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut dirs: Vec<_> = changed
+            .as_mut()
+            .map(mem::take)
+            .into_iter()
+            .flatten()
+            .collect();
+        dirs.sort_unstable();
+        dirs
+    }
+}
+
+/// Flushes changes to the given directory's entries to disk, so that removals
+/// and renames survive a crash or power loss.
+pub fn sync_dir(path: &Path) -> eyre::Result<()> {
+    File::open(path)
+        .and_then(|dir| dir.sync_all())
+        .wrap_err_with(|| tr!("Can't sync {path}", path = path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_surviving_parents() {
+        let changes = Changes::default();
+        changes.entry_changed(Path::new("untracked"));
+        assert!(changes.take().is_empty());
+        changes.start();
+        changes.entry_changed(Path::new("a/b/c/file"));
+        changes.entry_changed(Path::new("a/b/c"));
+        changes.entry_changed(Path::new("a/d/file"));
+        changes.entry_changed(Path::new("top"));
+        assert_eq!(
+            changes.take(),
+            [Path::new("."), Path::new("a/b"), Path::new("a/d")]
+        );
+        assert!(changes.take().is_empty());
+    }
+}
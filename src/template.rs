@@ -0,0 +1,229 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Per-entry output templates, for `--format`.
+//!
+//! A template is a string containing `{field}` placeholders, which are
+//! replaced for each entry. The available fields are:
+//!
+//! - `action`: what was done with the entry (`kept`, `removed`,
+//!   `quarantined`, or `failed`)
+//! - `path`: the path of the entry
+//! - `size`: the size of the entry in bytes (not including the contents of
+//!   directories)
+//! - `mtime`: the modification time of the entry, in UTC (ISO 8601)
+//! - `type`: the type of the entry (`file`, `dir`, `symlink`, or a special file
+//!   type)
+//! - `reason`: why the entry was kept, or the error if it couldn't be removed
+//!
+//! `{{` and `}}` produce literal braces, and the escapes `\t`, `\n`, and `\\`
+//! are recognized so templates are easy to type in a shell.
+
+use std::{fmt::Write as _, fs::Metadata, path::Path, time::UNIX_EPOCH};
+
+use crate::{datetime, i18n::tr};
+
+/// A field which can be substituted into a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Action,
+    Path,
+    Size,
+    Mtime,
+    Type,
+    Reason,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 6] = [
+        ("action", Field::Action),
+        ("path", Field::Path),
+        ("size", Field::Size),
+        ("mtime", Field::Mtime),
+        ("type", Field::Type),
+        ("reason", Field::Reason),
+    ];
+}
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Literal(String),
+    Field(Field),
+}
+
+/// A parsed output template.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+/// The information about an entry which can be shown using a template.
+pub struct EntryInfo<'a> {
+    pub action: &'a str,
+    pub path: &'a Path,
+    pub metadata: Option<&'a Metadata>,
+    pub reason: &'a str,
+}
+
+impl Template {
+    /// Parses a template string.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') | None => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    }
+                },
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| {
+                        tr!(
+                            "unclosed {{ in template {template}",
+                            template = format!("{s:?}")
+                        )
+                    })?;
+                    let name = &rest[..end];
+                    let field = Field::ALL
+                        .iter()
+                        .find(|(field_name, _)| *field_name == name)
+                        .map(|(_, field)| *field)
+                        .ok_or_else(|| {
+                            let names: Vec<&str> = Field::ALL.iter().map(|(n, _)| *n).collect();
+                            format!(
+                                "unknown field {{{name}}}; expected one of {}",
+                                names.join(", ")
+                            )
+                        })?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(field));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => {
+                    return Err(tr!(
+                        "unmatched }} in template {template}",
+                        template = format!("{s:?}")
+                    ));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Self { pieces })
+    }
+
+    /// Returns whether rendering this template needs the entry's metadata.
+    pub fn needs_metadata(&self) -> bool {
+        self.pieces.iter().any(|piece| {
+            matches!(
+                piece,
+                Piece::Field(Field::Size | Field::Mtime | Field::Type)
+            )
+        })
+    }
+
+    /// Renders the template for the given entry.
+    pub fn render(&self, info: &EntryInfo) -> String {
+        let mut out = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(literal) => out.push_str(literal),
+                Piece::Field(Field::Action) => out.push_str(info.action),
+                Piece::Field(Field::Path) => {
+                    let _ = write!(out, "{}", info.path.display());
+                }
+                Piece::Field(Field::Size) => {
+                    if let Some(metadata) = info.metadata {
+                        let _ = write!(out, "{}", metadata.len());
+                    }
+                }
+                Piece::Field(Field::Mtime) => {
+                    if let Some(mtime) = info
+                        .metadata
+                        .and_then(|metadata| metadata.modified().ok())
+                        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+                    {
+                        out.push_str(&datetime::format_iso8601(mtime.as_secs()));
+                    }
+                }
+                Piece::Field(Field::Type) => {
+                    if let Some(metadata) = info.metadata {
+                        out.push_str(type_name(metadata));
+                    }
+                }
+                Piece::Field(Field::Reason) => out.push_str(info.reason),
+            }
+        }
+        out
+    }
+}
+
+/// Returns a short name for the type of the given entry.
+pub fn type_name(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        "dir"
+    } else if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_file() {
+        "file"
+    } else {
+        special_type_name(file_type)
+    }
+}
+
+#[cfg(unix)]
+fn special_type_name(file_type: std::fs::FileType) -> &'static str {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block"
+    } else if file_type.is_char_device() {
+        "char"
+    } else {
+        "unknown"
+    }
+}
+
+#[cfg(not(unix))]
+fn special_type_name(_file_type: std::fs::FileType) -> &'static str {
+    "unknown"
+}
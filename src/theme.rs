@@ -0,0 +1,235 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Color themes, chosen with `theme` in the configuration file.
+//!
+//! A theme gives the style of each kind of marker, e.g. the `removed` in
+//! `removed 'foo'`. The built-in themes are `default`, `colorblind` and
+//! `none`, and the style of a single marker can be changed with
+//! `theme_removed`, `theme_kept`, `theme_protected`, `theme_error` or
+//! `theme_dry_run`. Styles are written as space-separated words: the
+//! attributes `bold`, `dim`, `italic`, `underline` and `reverse`, a color
+//! name such as `red` or `bright-blue`, a 256-color palette index, or a
+//! `#rrggbb` color. `none` leaves the marker unstyled.
+
+use std::fmt::Write as _;
+
+use eyre::bail;
+
+use crate::i18n::tr;
+
+/// A kind of text which is styled by the theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// An entry which is removed, or moved to the trash or quarantine.
+    Removed,
+    /// An entry which is kept.
+    Kept,
+    /// An entry which is kept because it's protected, e.g. by
+    /// `--keep-packaged`.
+    Protected,
+    /// An error.
+    Error,
+    /// An entry which would be removed, with `--dry-run`.
+    DryRun,
+}
+
+impl Marker {
+    /// Returns the marker named in a `theme_<NAME>` setting.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "removed" => Self::Removed,
+            "kept" => Self::Kept,
+            "protected" => Self::Protected,
+            "error" => Self::Error,
+            "dry_run" => Self::DryRun,
+            _ => return None,
+        })
+    }
+}
+
+/// The style of each marker, as the parameters of an SGR escape sequence,
+/// e.g. `1;31` for bold red.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    removed: String,
+    kept: String,
+    protected: String,
+    error: String,
+    dry_run: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            removed: "31".into(),
+            kept: "32".into(),
+            protected: "1;36".into(),
+            error: "1;31".into(),
+            dry_run: "33".into(),
+        }
+    }
+}
+
+impl Theme {
+    /// Returns the built-in theme with the given name: `default`,
+    /// `colorblind`, which tells markers apart by blue and orange rather than
+    /// red and green, or `none`.
+    pub fn preset(name: &str) -> eyre::Result<Self> {
+        Ok(match name {
+            "default" => Self::default(),
+            "colorblind" => Self {
+                removed: "38;5;208".into(),
+                kept: "38;5;33".into(),
+                protected: "1;4;38;5;33".into(),
+                error: "1;38;5;208".into(),
+                dry_run: "3;38;5;220".into(),
+            },
+            "none" => Self {
+                removed: String::new(),
+                kept: String::new(),
+                protected: String::new(),
+                error: String::new(),
+                dry_run: String::new(),
+            },
+            _ => bail!(tr!("Unknown theme {name}", name = name)),
+        })
+    }
+
+    /// Sets the style of the given marker, written as described in the
+    /// module documentation.
+    pub fn set(&mut self, marker: Marker, style: &str) -> eyre::Result<()> {
+        *self.style_mut(marker) = parse_style(style)?;
+        Ok(())
+    }
+
+    /// Returns the escape sequence starting the style of the given marker,
+    /// or an empty string if it's unstyled.
+    pub fn start(&self, marker: Marker) -> String {
+        let style = match marker {
+            Marker::Removed => &self.removed,
+            Marker::Kept => &self.kept,
+            Marker::Protected => &self.protected,
+            Marker::Error => &self.error,
+            Marker::DryRun => &self.dry_run,
+        };
+        if style.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{style}m")
+        }
+    }
+
+    /// Returns the given text in the style of the given marker.
+    pub fn paint(&self, marker: Marker, text: &str) -> String {
+        let start = self.start(marker);
+        if start.is_empty() {
+            text.to_owned()
+        } else {
+            format!("{start}{text}\x1b[0m")
+        }
+    }
+
+    fn style_mut(&mut self, marker: Marker) -> &mut String {
+        match marker {
+            Marker::Removed => &mut self.removed,
+            Marker::Kept => &mut self.kept,
+            Marker::Protected => &mut self.protected,
+            Marker::Error => &mut self.error,
+            Marker::DryRun => &mut self.dry_run,
+        }
+    }
+}
+
+/// Names of the basic colors, in the order of their SGR codes.
+const COLORS: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Converts a style written as described in the module documentation to SGR
+/// parameters.
+fn parse_style(style: &str) -> eyre::Result<String> {
+    let mut params = Vec::new();
+    for word in style.split_whitespace() {
+        let param = match word {
+            "none" => continue,
+            "bold" => "1".into(),
+            "dim" => "2".into(),
+            "italic" => "3".into(),
+            "underline" => "4".into(),
+            "reverse" => "7".into(),
+            _ => {
+                if let Some(i) = COLORS.iter().position(|&color| color == word) {
+                    (30 + i).to_string()
+                } else if let Some(i) = word
+                    .strip_prefix("bright-")
+                    .and_then(|color| COLORS.iter().position(|&c| c == color))
+                {
+                    (90 + i).to_string()
+                } else if let Ok(index) = word.parse::<u8>() {
+                    format!("38;5;{index}")
+                } else if let Some(rgb) = parse_hex(word) {
+                    let mut param = String::from("38;2");
+                    for component in rgb {
+                        let _ = write!(param, ";{component}");
+                    }
+                    param
+                } else {
+                    bail!(tr!("Invalid style {word}", word = format!("{word:?}")));
+                }
+            }
+        };
+        params.push(param);
+    }
+    Ok(params.join(";"))
+}
+
+/// Parses a `#rrggbb` color.
+fn parse_hex(word: &str) -> Option<[u8; 3]> {
+    let hex = word.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let component = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([component(0)?, component(2)?, component(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_styles() {
+        assert_eq!("1;31", parse_style("bold red").unwrap());
+        assert_eq!("94;4", parse_style("bright-blue underline").unwrap());
+        assert_eq!("38;5;208", parse_style("208").unwrap());
+        assert_eq!("38;2;0;128;255", parse_style("#0080ff").unwrap());
+        assert_eq!("", parse_style("none").unwrap());
+        assert!(parse_style("purple").is_err());
+        assert!(parse_style("#0080f").is_err());
+    }
+
+    #[test]
+    fn paints_markers() {
+        let mut theme = Theme::preset("none").unwrap();
+        assert_eq!("removed", theme.paint(Marker::Removed, "removed"));
+        theme.set(Marker::Removed, "bold").unwrap();
+        assert_eq!(
+            "\x1b[1mremoved\x1b[0m",
+            theme.paint(Marker::Removed, "removed")
+        );
+        assert!(Theme::preset("solarized").is_err());
+    }
+}
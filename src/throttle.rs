@@ -0,0 +1,104 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Slowing down removal for `--nice-io`, so that a large cleanup doesn't
+//! compete with other workloads for I/O.
+//!
+//! Removals, including those of entries inside directories removed with
+//! `-r`, are spaced out to stay under the given rate. On Linux, the process
+//! is also moved to the idle I/O scheduling class, so its I/O is only served
+//! when no other process needs the disk.
+
+use std::{
+    num::NonZeroU32,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{i18n::tr, output::Output};
+
+/// The pace of removals, if `--nice-io` is given. This is global so that
+/// removals can be throttled from anywhere.
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+struct State {
+    /// Minimum time between removals.
+    interval: Duration,
+    /// When the next removal may happen.
+    next: Instant,
+}
+
+/// Limits removals to `rate` per second for the rest of the run, and lowers
+/// the I/O priority of the process where possible. Threads started later
+/// inherit the priority. Failing to lower it is a warning printed to `out`.
+pub fn start(rate: NonZeroU32, out: &Output) {
+    *STATE.lock().unwrap_or_else(PoisonError::into_inner) = Some(State {
+        interval: Duration::from_secs(1) / rate.get(),
+        next: Instant::now(),
+    });
+    lower_priority(out);
+}
+
+/// Returns whether removals are throttled.
+pub fn is_enabled() -> bool {
+    STATE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Waits until the next removal may happen. Returns immediately if removals
+/// aren't throttled.
+pub fn wait() {
+    let deadline = {
+        let mut state = STATE.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(state) = state.as_mut() else {
+            return;
+        };
+        // Don't let idle time build up into a burst
+        let deadline = state.next.max(Instant::now());
+        state.next = deadline + state.interval;
+        deadline
+    };
+    std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+}
+
+/// Moves the process to the idle I/O scheduling class using `ionice`.
+#[cfg(target_os = "linux")]
+fn lower_priority(out: &Output) {
+    let result = std::process::Command::new("ionice")
+        .args(["-c", "3", "-p"])
+        .arg(std::process::id().to_string())
+        .status();
+    match result {
+        Ok(status) if status.success() => (),
+        Ok(status) => out.warning(tr!(
+            "Can't lower I/O priority: ionice failed with {status}",
+            status = status
+        )),
+        Err(err) => out.warning(tr!(
+            "Can't lower I/O priority: can't run ionice: {error}",
+            error = err
+        )),
+    }
+}
+
+/// I/O scheduling classes are specific to Linux, so only the rate limit
+/// applies on this platform.
+#[cfg(not(target_os = "linux"))]
+fn lower_priority(_out: &Output) {}
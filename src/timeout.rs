@@ -0,0 +1,161 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Abandoning removals which take too long, for `--timeout`.
+//!
+//! An operation on a hung network file system can block forever, and a thread
+//! stuck in a system call can't be interrupted portably. Instead, with a
+//! timeout, each operation which removes or moves an entry runs on a thread of
+//! its own, which is abandoned if it doesn't finish in time. The thread owns
+//! everything it uses, so it can keep running safely until it finishes or the
+//! process exits.
+//!
+//! The limit applies to each operation rather than to whole entries, so a
+//! large directory which is being removed steadily never times out. A
+//! directory which is abandoned after some of its contents were removed is
+//! reported as partially removed.
+
+use std::{
+    error::Error,
+    fmt,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use eyre::{Context, eyre};
+
+use crate::i18n::tr;
+
+/// The error for an operation which was abandoned because it didn't finish
+/// in time.
+#[derive(Debug)]
+pub struct TimedOut(Duration);
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = format!("{:?}", self.0);
+        f.write_str(&tr!("Timed out after {limit}", limit = limit))
+    }
+}
+
+impl Error for TimedOut {}
+
+/// Context added to a [`TimedOut`] error when some of the directory being
+/// removed was already gone.
+#[derive(Debug)]
+pub struct Partial;
+
+impl fmt::Display for Partial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(tr!("Abandoned after removing part of it"))
+    }
+}
+
+/// Runs the given operation on a new thread, and waits for it for up to
+/// `limit`. If it doesn't finish in time, it's left running and a
+/// [`TimedOut`] error is returned.
+pub fn run<T: Send + 'static>(
+    limit: Duration,
+    op: impl FnOnce() -> T + Send + 'static,
+) -> eyre::Result<T> {
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("leave-removal".into())
+        .spawn(move || {
+            let result = op();
+            // The receiver is gone if the operation was abandoned
+            let _ = sender.send(result);
+        })
+        .wrap_err(tr!("Can't start a thread for the removal"))?;
+    match receiver.recv_timeout(limit) {
+        Ok(result) => Ok(result),
+        Err(RecvTimeoutError::Timeout) => Err(TimedOut(limit).into()),
+        Err(RecvTimeoutError::Disconnected) => Err(eyre!(tr!("The removal thread panicked"))),
+    }
+}
+
+/// Marks the given error from removing part of a directory as
+/// [`Partial`] if it's a timeout and `removed_any` says some of the
+/// directory was already removed.
+pub fn mark_partial(err: eyre::Report, removed_any: bool) -> eyre::Report {
+    if removed_any && is_timeout(&err) && !is_partial(&err) {
+        err.wrap_err(Partial)
+    } else {
+        err
+    }
+}
+
+/// Returns whether the given error is or was caused by a timeout.
+pub fn is_timeout(err: &eyre::Report) -> bool {
+    err.downcast_ref::<TimedOut>().is_some()
+}
+
+/// Returns whether the given error abandoned an entry which was partially
+/// removed.
+pub fn is_partial(err: &eyre::Report) -> bool {
+    err.downcast_ref::<Partial>().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::{is_partial, is_timeout, mark_partial, run};
+
+    #[test]
+    fn finishes_in_time() {
+        assert_eq!(42, run(Duration::from_secs(10), || 42).unwrap());
+    }
+
+    #[test]
+    fn abandons_stuck_operation() {
+        let err = run(Duration::from_millis(10), || {
+            thread::sleep(Duration::from_secs(10));
+        })
+        .unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(!is_partial(&err));
+    }
+
+    /// Tests that timeouts are found, and marked as partial, through the
+    /// context added on the way up a directory tree.
+    #[test]
+    fn partial_through_context() {
+        let timed_out = || {
+            run(Duration::from_millis(10), || {
+                thread::sleep(Duration::from_secs(10));
+            })
+            .unwrap_err()
+            .wrap_err("Can't remove ./a/b")
+        };
+        assert!(!is_partial(&mark_partial(timed_out(), false)));
+
+        let err = mark_partial(timed_out(), true).wrap_err("Can't remove ./a");
+        assert!(is_timeout(&err));
+        assert!(is_partial(&err));
+        // A directory above one which was partially removed stays partial,
+        // and isn't marked twice
+        let err = mark_partial(err, false);
+        assert!(is_partial(&err));
+        assert_eq!(4, mark_partial(err, true).chain().count());
+
+        let other = eyre::eyre!("Permission denied");
+        assert!(!is_partial(&mark_partial(other, true)));
+    }
+}
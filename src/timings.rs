@@ -0,0 +1,101 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Measurement of how long each phase of a run takes, and how the removal
+//! was shared between workers with `--jobs`, for `--timings`.
+
+use std::time::{Duration, Instant};
+
+use crate::i18n::tr;
+
+/// Records the durations of consecutive phases of a run.
+pub struct Timings {
+    start: Instant,
+    last: Instant,
+    phases: Vec<(&'static str, Duration)>,
+    workers: Vec<Worker>,
+}
+
+/// What one thread removing entries did.
+#[derive(Default, Clone, Copy)]
+pub struct Worker {
+    /// Number of entries processed.
+    pub entries: usize,
+    /// Time spent processing them, not counting waiting for the next one.
+    pub busy: Duration,
+}
+
+impl Timings {
+    /// Starts timing. The first phase begins now.
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            last: now,
+            phases: Vec::new(),
+            workers: Vec::new(),
+        }
+    }
+
+    /// Records what each worker removing entries did.
+    pub fn record_workers(&mut self, workers: Vec<Worker>) {
+        self.workers = workers;
+    }
+
+    /// Ends the current phase, recording its duration under the given name,
+    /// and begins the next one.
+    pub fn lap(&mut self, name: &'static str) {
+        let now = Instant::now();
+        self.phases.push((name, now - self.last));
+        self.last = now;
+    }
+
+    /// Prints the recorded phase durations and the total to standard error.
+    pub fn report(&self) {
+        let total = tr!("total");
+        let width = self
+            .phases
+            .iter()
+            .map(|(name, _)| tr!(name).chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(total.chars().count());
+        eprintln!("{}", tr!("Timings:"));
+        for (name, duration) in &self.phases {
+            eprintln!("  {:<width$}  {duration:?}", tr!(name));
+        }
+        eprintln!("  {total:<width$}  {:?}", self.last - self.start);
+        // With one worker, its time is that of the removal phase
+        if self.workers.len() > 1 {
+            let width = self.workers.len().to_string().len() + 1;
+            let busy: Vec<_> = self
+                .workers
+                .iter()
+                .map(|worker| format!("{:?}", worker.busy))
+                .collect();
+            let busy_width = busy.iter().map(|busy| busy.chars().count()).max();
+            let busy_width = busy_width.unwrap_or(0);
+            eprintln!("{}", tr!("Workers:"));
+            for (i, (worker, busy)) in self.workers.iter().zip(&busy).enumerate() {
+                let number = format!("#{}", i + 1);
+                let entries = tr!("{count} entries", count = worker.entries);
+                eprintln!("  {number:<width$}  {busy:<busy_width$}  {entries}");
+            }
+        }
+    }
+}
@@ -0,0 +1,507 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Moving entries to the platform trash, for `--trash`.
+//!
+//! On Linux and other Unix-like systems, this follows the freedesktop.org
+//! Trash specification: entries on the same filesystem as the home directory
+//! go to the home trash, and others go to a `.Trash-<UID>` directory at the
+//! top of their filesystem, so moving them is always a cheap rename. On macOS,
+//! entries are moved into `~/.Trash`. On Windows, entries are moved to the
+//! Recycle Bin through the shell, using the `trash` crate.
+//!
+//! Each trashed entry is recorded in a journal in the
+//! [state directory](crate::state), one line per entry holding the time it
+//! was trashed, its path in the trash and its original path, so that
+//! `leave trash-empty` can purge what leave trashed without touching anything
+//! else in the trash. `leave gc` drops the records of entries which left the
+//! trash some other way.
+
+use std::{
+    ffi::OsStr,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use eyre::Context;
+
+use crate::{i18n::tr, output::Output, state};
+
+/// Name of the journal of trashed entries in the state directory.
+const JOURNAL_NAME: &str = "trash-journal";
+
+/// The journal of trashed entries, which is opened the first time an entry
+/// is trashed.
+#[derive(Default)]
+pub struct Journal {
+    file: Mutex<Option<File>>,
+}
+
+impl Journal {
+    /// Moves the given entry to the trash and records it in the journal.
+    /// Returns the entry's new path.
+    pub fn move_in(&self, path: &Path) -> eyre::Result<PathBuf> {
+        let trashed = imp::move_to_trash(path)?;
+        let original = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+        self.record(&trashed, &original).wrap_err_with(|| {
+            tr!(
+                "{path} was trashed, but can't be recorded for leave trash-empty",
+                path = path.display()
+            )
+        })?;
+        Ok(trashed)
+    }
+
+    /// Appends a record of a trashed entry to the journal.
+    fn record(&self, trashed: &Path, original: &Path) -> eyre::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let file = match &mut *file {
+            Some(file) => file,
+            None => file.insert(open_journal()?),
+        };
+        let line = format!(
+            "{}\t{}\t{}\n",
+            state::now(),
+            state::escape(&trashed.to_string_lossy()),
+            state::escape(&original.to_string_lossy())
+        );
+        // One write per record, so concurrent runs don't interleave them
+        file.write_all(line.as_bytes())
+            .wrap_err(tr!("Can't write to the trash journal"))
+    }
+}
+
+/// Opens the journal for appending, creating it and the state directory if
+/// needed.
+fn open_journal() -> eyre::Result<File> {
+    let dir = state::dir()?;
+    fs::create_dir_all(&dir).wrap_err_with(|| tr!("Can't create {path}", path = dir.display()))?;
+    let path = dir.join(JOURNAL_NAME);
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .wrap_err_with(|| tr!("Can't open {path}", path = path.display()))
+}
+
+/// Permanently deletes the entries recorded in the journal which were
+/// trashed more than `older_than` ago. Records of entries which are no longer
+/// in the trash, e.g. because they were restored, are dropped.
+pub fn empty(older_than: Duration, out: &Output) -> eyre::Result<ExitCode> {
+    let path = state::dir()?.join(JOURNAL_NAME);
+    let journal = match fs::read_to_string(&path) {
+        Ok(journal) => journal,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(ExitCode::SUCCESS),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| tr!("Can't read {path}", path = path.display()));
+        }
+    };
+    let cutoff = state::now().saturating_sub(older_than.as_secs());
+    let mut remaining = String::new();
+    let mut had_failure = false;
+    for line in journal.lines() {
+        let Some((time, trashed, original)) = parse_record(line) else {
+            out.warning(tr!(
+                "Ignoring malformed record in {path}: {record}",
+                path = path.display(),
+                record = line
+            ));
+            remaining.push_str(line);
+            remaining.push('\n');
+            continue;
+        };
+        if time > cutoff {
+            remaining.push_str(line);
+            remaining.push('\n');
+            continue;
+        }
+        match imp::purge(&trashed) {
+            Ok(true) => out.message(format_args!("purged '{}'", original.display())),
+            // Restored or deleted from the trash some other way
+            Ok(false) => (),
+            Err(err) => {
+                had_failure = true;
+                crate::print_error(
+                    out,
+                    &err.wrap_err(tr!("Can't purge {path}", path = original.display())),
+                );
+                remaining.push_str(line);
+                remaining.push('\n');
+            }
+        }
+    }
+
+    state::replace(&path, &remaining)?;
+    Ok(if had_failure {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Drops the records of entries which are no longer in the trash from the
+/// journal, for `leave gc`. Malformed records are kept. Returns the number of
+/// records dropped.
+pub fn compact() -> eyre::Result<usize> {
+    let path = state::dir()?.join(JOURNAL_NAME);
+    let journal = match fs::read_to_string(&path) {
+        Ok(journal) => journal,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| tr!("Can't read {path}", path = path.display()));
+        }
+    };
+    let mut remaining = String::new();
+    let mut dropped = 0;
+    for line in journal.lines() {
+        if let Some((_, trashed, _)) = parse_record(line)
+            && fs::symlink_metadata(&trashed).is_err()
+        {
+            dropped += 1;
+            continue;
+        }
+        remaining.push_str(line);
+        remaining.push('\n');
+    }
+    if dropped > 0 {
+        state::replace(&path, &remaining)?;
+    }
+    Ok(dropped)
+}
+
+/// Parses a journal record into the time the entry was trashed, its path in
+/// the trash and its original path.
+fn parse_record(line: &str) -> Option<(u64, PathBuf, PathBuf)> {
+    let mut fields = line.split('\t');
+    let time = fields.next()?.parse().ok()?;
+    let trashed = PathBuf::from(state::unescape(fields.next()?));
+    let original = PathBuf::from(state::unescape(fields.next()?));
+    fields.next().is_none().then_some((time, trashed, original))
+}
+
+/// Deletes the entry at the given path in the trash, whatever its type.
+/// Returns false if it doesn't exist.
+#[cfg(unix)]
+fn remove_trashed(path: &Path) -> eyre::Result<bool> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => Err(err),
+    };
+    result
+        .map(|()| true)
+        .wrap_err_with(|| tr!("Can't remove {path}", path = path.display()))
+}
+
+/// Returns whether the given file name is that of a trash directory which
+/// may appear at the top of a filesystem.
+pub fn is_trash_dir(name: &OsStr) -> bool {
+    name == ".Trash"
+        || name.as_encoded_bytes().starts_with(b".Trash-")
+        || (cfg!(windows) && name.eq_ignore_ascii_case("$Recycle.Bin"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod imp {
+    use std::{
+        env,
+        fmt::Write as _,
+        fs::{self, DirBuilder, OpenOptions},
+        io::{ErrorKind, Write as _},
+        os::unix::{
+            ffi::OsStrExt,
+            fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        },
+        path::{Path, PathBuf},
+        time::SystemTime,
+    };
+
+    use eyre::{Context, OptionExt, eyre};
+
+    use crate::{datetime, i18n::tr};
+
+    /// Moves the given entry into the appropriate trash directory and writes
+    /// its `.trashinfo` file. Returns the entry's new path.
+    pub fn move_to_trash(path: &Path) -> eyre::Result<PathBuf> {
+        let absolute = std::path::absolute(path).wrap_err(tr!("Can't make path absolute"))?;
+        let name = absolute
+            .file_name()
+            .ok_or_eyre(tr!("Path has no file name"))?;
+        let trash = trash_dir_for(&absolute)?;
+        let files_dir = trash.join("files");
+        let info_dir = trash.join("info");
+        for dir in [&files_dir, &info_dir] {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)
+                .wrap_err_with(|| tr!("Can't create trash directory {dir}", dir = dir.display()))?;
+        }
+
+        // Claim a unique name by creating the info file first, as the
+        // specification requires
+        let mut trashed_name = name.to_owned();
+        let mut n = 2;
+        let (mut info_file, info_path) = loop {
+            let mut info_name = trashed_name.clone();
+            info_name.push(".trashinfo");
+            let info_path = info_dir.join(&info_name);
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_path)
+            {
+                Ok(file) => break (file, info_path),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    // Names needn't be UTF-8, so the number is appended to
+                    // the raw name
+                    name.clone_into(&mut trashed_name);
+                    trashed_name.push(format!(".{n}"));
+                    n += 1;
+                }
+                Err(err) => {
+                    return Err(err)
+                        .wrap_err_with(|| tr!("Can't create {path}", path = info_path.display()));
+                }
+            }
+        };
+
+        // The specification asks for local time, but UTC is all we have
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let date = datetime::format_iso8601(secs);
+        let info = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode(absolute.as_os_str().as_bytes()),
+            date.trim_end_matches('Z')
+        );
+        let destination = files_dir.join(&trashed_name);
+        let result = info_file
+            .write_all(info.as_bytes())
+            .wrap_err_with(|| tr!("Can't write {path}", path = info_path.display()))
+            .and_then(|()| {
+                fs::rename(&absolute, &destination)
+                    .wrap_err_with(|| tr!("Can't move into {dir}", dir = files_dir.display()))
+            });
+        if result.is_err() {
+            let _ = fs::remove_file(&info_path);
+        }
+        result.map(|()| destination)
+    }
+
+    /// Deletes the given entry in a trash directory along with its
+    /// `.trashinfo` file. Returns false if the entry isn't there anymore.
+    pub fn purge(trashed: &Path) -> eyre::Result<bool> {
+        if !super::remove_trashed(trashed)? {
+            return Ok(false);
+        }
+        if let Some(files_dir) = trashed.parent()
+            && let Some(name) = trashed.file_name()
+        {
+            let mut info_name = name.to_owned();
+            info_name.push(".trashinfo");
+            let info_path = files_dir.with_file_name("info").join(info_name);
+            match fs::remove_file(&info_path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => {
+                    return Err(err)
+                        .wrap_err_with(|| tr!("Can't remove {path}", path = info_path.display()));
+                }
+                _ => (),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the trash directory to use for the given absolute path.
+    fn trash_dir_for(path: &Path) -> eyre::Result<PathBuf> {
+        let uid = rustix::process::geteuid().as_raw();
+        let device = fs::symlink_metadata(path)
+            .wrap_err_with(|| tr!("Can't get metadata of {path}", path = path.display()))?
+            .dev();
+
+        let home_trash = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+            Some(data_home) => PathBuf::from(data_home).join("Trash"),
+            None => PathBuf::from(env::var_os("HOME").ok_or_eyre(tr!("HOME is not set"))?)
+                .join(".local/share/Trash"),
+        };
+        // The home trash may not exist yet, so check the nearest existing
+        // ancestor
+        let home_device = home_trash
+            .ancestors()
+            .find_map(|dir| fs::metadata(dir).ok())
+            .map(|metadata| metadata.dev());
+        if home_device == Some(device) {
+            return Ok(home_trash);
+        }
+
+        let top_dir = top_dir(path, device)?;
+        // An administrator-created $topdir/.Trash is used if it's a sticky,
+        // non-symlink directory
+        let shared = top_dir.join(".Trash");
+        if let Ok(metadata) = fs::symlink_metadata(&shared)
+            && metadata.is_dir()
+            && metadata.permissions().mode() & 0o1000 != 0
+        {
+            return Ok(shared.join(uid.to_string()));
+        }
+        Ok(top_dir.join(format!(".Trash-{uid}")))
+    }
+
+    /// Returns the top directory of the filesystem containing the given
+    /// path, i.e. its mount point.
+    fn top_dir(path: &Path, device: u64) -> eyre::Result<PathBuf> {
+        let mut top = path
+            .parent()
+            .ok_or_else(|| eyre!(tr!("Can't trash {path}", path = path.display())))?;
+        while let Some(parent) = top.parent() {
+            let parent_device = fs::metadata(parent)
+                .wrap_err_with(|| tr!("Can't get metadata of {path}", path = parent.display()))?
+                .dev();
+            if parent_device != device {
+                break;
+            }
+            top = parent;
+        }
+        Ok(top.to_owned())
+    }
+
+    /// Percent-encodes a path for a `.trashinfo` file.
+    fn percent_encode(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut encoded, &byte| {
+            if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+                encoded.push(char::from(byte));
+            } else {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+            encoded
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::{
+        env,
+        ffi::OsString,
+        fs,
+        io::ErrorKind,
+        path::{Path, PathBuf},
+    };
+
+    use eyre::{Context, OptionExt};
+
+    use crate::i18n::tr;
+
+    /// Moves the given entry into `~/.Trash`, renaming it if an entry with
+    /// the same name is already there. Returns the entry's new path.
+    pub fn move_to_trash(path: &Path) -> eyre::Result<PathBuf> {
+        let name = path.file_name().ok_or_eyre(tr!("Path has no file name"))?;
+        let trash =
+            PathBuf::from(env::var_os("HOME").ok_or_eyre(tr!("HOME is not set"))?).join(".Trash");
+        let mut destination = trash.join(name);
+        let mut n = 2;
+        while fs::symlink_metadata(&destination).is_ok() {
+            let mut numbered = OsString::from(name);
+            numbered.push(format!(" {n}"));
+            destination = trash.join(numbered);
+            n += 1;
+        }
+        match fs::rename(path, &destination) {
+            Ok(()) => Ok(destination),
+            Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+                Err(err).wrap_err(tr!("Can't move to the trash from a different volume"))
+            }
+            Err(err) => {
+                Err(err).wrap_err_with(|| tr!("Can't move into {dir}", dir = trash.display()))
+            }
+        }
+    }
+
+    /// Deletes the given entry in `~/.Trash`. Returns false if it isn't there
+    /// anymore.
+    pub fn purge(trashed: &Path) -> eyre::Result<bool> {
+        super::remove_trashed(trashed)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::path::{Path, PathBuf};
+
+    use eyre::{Context, OptionExt};
+
+    use crate::i18n::tr;
+
+    /// Moves the given entry to the Recycle Bin. Returns the entry's new path,
+    /// which the shell doesn't report, so it's looked up among the items in
+    /// the Recycle Bin.
+    pub fn move_to_trash(path: &Path) -> eyre::Result<PathBuf> {
+        let absolute = std::path::absolute(path).wrap_err(tr!("Can't make path absolute"))?;
+        trash::delete(&absolute)
+            .wrap_err_with(|| tr!("Can't trash {path}", path = path.display()))?;
+        // Entries with the same original path may have been trashed before
+        trash::os_limited::list()
+            .wrap_err(tr!("Can't list the contents of the Recycle Bin"))?
+            .into_iter()
+            .filter(|item| item.original_path() == absolute)
+            .max_by_key(|item| item.time_deleted)
+            .map(|item| PathBuf::from(item.id))
+            .ok_or_eyre(tr!(
+                "{path} was moved to the Recycle Bin, but can't be found there",
+                path = path.display()
+            ))
+    }
+
+    /// Deletes the item in the Recycle Bin whose ID is the given path, as
+    /// returned by [`move_to_trash`]. Returns false if it isn't there
+    /// anymore.
+    pub fn purge(trashed: &Path) -> eyre::Result<bool> {
+        let items: Vec<_> = trash::os_limited::list()
+            .wrap_err(tr!("Can't list the contents of the Recycle Bin"))?
+            .into_iter()
+            .filter(|item| Path::new(&item.id) == trashed)
+            .collect();
+        if items.is_empty() {
+            return Ok(false);
+        }
+        trash::os_limited::purge_all(items)
+            .wrap_err_with(|| tr!("Can't remove {path}", path = trashed.display()))?;
+        Ok(true)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::path::{Path, PathBuf};
+
+    use crate::i18n::tr;
+
+    pub fn move_to_trash(_path: &Path) -> eyre::Result<PathBuf> {
+        eyre::bail!(tr!("--trash is only supported on Unix, macOS, and Windows"));
+    }
+
+    pub fn purge(_trashed: &Path) -> eyre::Result<bool> {
+        eyre::bail!(tr!("--trash is only supported on Unix, macOS, and Windows"));
+    }
+}
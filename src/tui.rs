@@ -0,0 +1,282 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! The terminal picker for `--pick`, which lists the entries of the target
+//! directory and lets the user choose which of the ones selected for removal
+//! to keep, before anything is removed.
+//!
+//! Typing filters the list to entries whose path contains the typed
+//! characters in order, like `fzf`. Space toggles the highlighted entry,
+//! Tab toggles every entry shown, Enter confirms, and Escape cancels.
+
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use eyre::{Context, bail};
+
+use crate::{
+    i18n::tr,
+    theme::{Marker, Theme},
+};
+
+/// An entry shown in the picker.
+pub struct Item {
+    pub label: String,
+    /// Why the entry is kept regardless of what the user picks, if it is.
+    /// Such entries are shown but can't be toggled.
+    pub kept_reason: Option<String>,
+    /// Whether the entry will be kept.
+    pub keep: bool,
+}
+
+/// Number of lines above the list.
+const HEADER_LINES: u16 = 2;
+
+/// Shows the picker until the user confirms or cancels, coloring entries
+/// with the given theme, if any. Returns whether the user confirmed, in which
+/// case [`Item::keep`] holds their choices.
+pub fn pick(items: &mut [Item], theme: Option<&Theme>) -> eyre::Result<bool> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        bail!(tr!(
+            "--pick needs a terminal to show the entries; standard input or error isn't one"
+        ));
+    }
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|&a, &b| items[a].label.cmp(&items[b].label));
+
+    let _screen = Screen::enter()?;
+    let mut picker = Picker {
+        query: String::new(),
+        shown: order.clone(),
+        cursor: 0,
+        scroll: 0,
+    };
+    loop {
+        picker
+            .draw(items, theme)
+            .wrap_err(tr!("Can't draw the picker"))?;
+        let Event::Key(key) = event::read().wrap_err(tr!("Can't read from the terminal"))? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        match key {
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Esc, ..
+            } => return Ok(false),
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => return Ok(true),
+            KeyEvent { code, .. } => picker.handle(code, items, &order),
+        }
+    }
+}
+
+/// The picker's state.
+struct Picker {
+    query: String,
+    /// Indexes of the items matching the query, in display order.
+    shown: Vec<usize>,
+    /// Position of the highlighted item in `shown`.
+    cursor: usize,
+    /// Position in `shown` of the first item on the screen.
+    scroll: usize,
+}
+
+impl Picker {
+    /// Updates the state for a key press.
+    fn handle(&mut self, code: KeyCode, items: &mut [Item], order: &[usize]) {
+        match code {
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => {
+                self.cursor = (self.cursor + 1).min(self.shown.len().saturating_sub(1));
+            }
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(usize::from(page_size())),
+            KeyCode::PageDown => {
+                self.cursor = (self.cursor + usize::from(page_size()))
+                    .min(self.shown.len().saturating_sub(1));
+            }
+            KeyCode::Char(' ') => {
+                if let Some(item) = self.shown.get(self.cursor).map(|&i| &mut items[i])
+                    && item.kept_reason.is_none()
+                {
+                    item.keep = !item.keep;
+                }
+            }
+            KeyCode::Tab => {
+                // Keep all shown entries unless they're all kept already
+                let toggleable = || {
+                    self.shown
+                        .iter()
+                        .copied()
+                        .filter(|&i| items[i].kept_reason.is_none())
+                };
+                let keep = !toggleable().all(|i| items[i].keep);
+                for i in toggleable().collect::<Vec<_>>() {
+                    items[i].keep = keep;
+                }
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.filter(items, order);
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.filter(items, order);
+            }
+            _ => (),
+        }
+    }
+
+    /// Updates the shown items after the query changed.
+    fn filter(&mut self, items: &[Item], order: &[usize]) {
+        self.shown = order
+            .iter()
+            .copied()
+            .filter(|&i| fuzzy_match(&self.query, &items[i].label))
+            .collect();
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    /// Redraws the whole screen.
+    fn draw(&mut self, items: &[Item], theme: Option<&Theme>) -> io::Result<()> {
+        let (width, _) = screen_size();
+        let page = usize::from(page_size());
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + page {
+            self.scroll = self.cursor + 1 - page;
+        }
+        let to_remove = items
+            .iter()
+            .filter(|item| !item.keep && item.kept_reason.is_none())
+            .count();
+
+        let mut out = io::stderr().lock();
+        queue!(
+            out,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0),
+            Print(truncate(
+                &format!(
+                    "{to_remove} of {} will be removed | Space: keep  Tab: all  Enter: confirm  Esc: cancel",
+                    items.len()
+                ),
+                width
+            )),
+            cursor::MoveTo(0, 1),
+            Print(truncate(&format!("> {}", self.query), width)),
+        )?;
+        for (line, (position, &i)) in self
+            .shown
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(page)
+            .enumerate()
+        {
+            let item = &items[i];
+            let (text, marker) = match &item.kept_reason {
+                Some(reason) => (format!("  kept    {} ({reason})", item.label), Marker::Kept),
+                None if item.keep => (format!("  keep    {}", item.label), Marker::Kept),
+                None => (format!("  remove  {}", item.label), Marker::Removed),
+            };
+            #[allow(clippy::cast_possible_truncation)]
+            queue!(out, cursor::MoveTo(0, HEADER_LINES + line as u16))?;
+            if position == self.cursor {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            if let Some(theme) = theme {
+                queue!(out, Print(theme.start(marker)))?;
+            }
+            queue!(
+                out,
+                Print(truncate(&text, width)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+        out.flush()
+    }
+}
+
+/// Puts the terminal in raw mode on an alternate screen, restoring it when
+/// dropped.
+struct Screen;
+
+impl Screen {
+    fn enter() -> eyre::Result<Self> {
+        terminal::enable_raw_mode().wrap_err(tr!("Can't set up the terminal"))?;
+        // Restore the terminal if the rest of the setup fails
+        let screen = Self;
+        execute!(io::stderr(), terminal::EnterAlternateScreen, cursor::Hide)
+            .wrap_err(tr!("Can't set up the terminal"))?;
+        Ok(screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        // Nothing can be done if restoring the terminal fails
+        let _ = execute!(io::stderr(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Returns the number of columns and lines of the terminal. Some terminals
+/// don't report their size, so a common one is assumed then.
+fn screen_size() -> (u16, u16) {
+    match terminal::size() {
+        Ok((width, height)) if width > 0 && height > 0 => (width, height),
+        _ => (80, 24),
+    }
+}
+
+/// Returns the number of items which fit on the screen.
+fn page_size() -> u16 {
+    let (_, height) = screen_size();
+    height.saturating_sub(HEADER_LINES).max(1)
+}
+
+/// Returns whether the characters of `query` appear in `label` in order,
+/// ignoring case.
+fn fuzzy_match(query: &str, label: &str) -> bool {
+    let mut label = label.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|c| label.any(|l| l == c))
+}
+
+/// Shortens a line to fit in the given number of columns.
+fn truncate(line: &str, width: u16) -> String {
+    line.chars().take(usize::from(width)).collect()
+}
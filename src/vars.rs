@@ -0,0 +1,157 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Built-in template variables in keep arguments and patterns.
+//!
+//! Keep arguments, the lines of keep files and rules files, and the `keep`
+//! list and presets in the configuration file may contain the following
+//! variables, which are expanded before they're used:
+//!
+//! - `{date}`: the current UTC date, as `YYYY-MM-DD`
+//! - `{date:FORMAT}`: the current UTC date and time, formatted using a subset
+//!   of `strftime` conversions (see [`datetime::strftime`])
+//! - `{hostname}`: the name of this machine
+//!
+//! Anything else in braces is left as-is, so file names which happen to
+//! contain braces keep working.
+//!
+//! Keep files and the `keep` list in the configuration file may also refer
+//! to environment variables as `${NAME}`, so one shared policy can serve many
+//! machines. Unlike template variables, an undefined environment variable is
+//! an error, and `$${` stands for a literal `${`.
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::bail;
+
+use crate::{datetime, i18n::tr};
+
+/// Expands the template variables in a keep argument.
+pub fn expand(arg: &OsStr) -> eyre::Result<OsString> {
+    // Variables can only be expanded in valid Unicode arguments
+    match arg.to_str() {
+        Some(arg) => expand_str(arg).map(OsString::from),
+        None => Ok(arg.to_owned()),
+    }
+}
+
+/// Expands the template variables in a keep pattern, such as a line of a
+/// keep file or rules file.
+pub fn expand_str(text: &str) -> eyre::Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let name = &rest[1..end];
+        match expand_var(name)? {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Expands the `${NAME}` environment variable references in a line of a
+/// keep file or a configured keep pattern. Fails if a variable isn't set.
+pub fn expand_env(text: &str) -> eyre::Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            out.push_str("${");
+            rest = after;
+            continue;
+        }
+        let name = rest
+            .strip_prefix("${")
+            .and_then(|after| after.split_once('}'))
+            .map(|(name, _)| name)
+            .filter(|name| is_env_name(name));
+        let Some(name) = name else {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        match env::var(name) {
+            Ok(value) => out.push_str(&value),
+            Err(_) => bail!(tr!("Undefined variable ${{{name}}}", name = name)),
+        }
+        rest = &rest[name.len() + 3..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Returns whether `name` is a valid environment variable name: letters,
+/// digits and underscores, not starting with a digit.
+fn is_env_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns the value of the variable with the given name (the text between
+/// the braces), or `None` if it isn't a known variable.
+fn expand_var(name: &str) -> eyre::Result<Option<String>> {
+    let now = || {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    };
+    if name == "date" {
+        return Ok(datetime::strftime(now(), "%Y-%m-%d").ok());
+    }
+    if let Some(format) = name.strip_prefix("date:") {
+        return match datetime::strftime(now(), format) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) => bail!(tr!("Invalid {{{name}}}: {err}", name = name, err = err)),
+        };
+    }
+    if name == "hostname" {
+        return hostname().map(Some);
+    }
+    Ok(None)
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)] // Fallible on other platforms
+fn hostname() -> eyre::Result<String> {
+    Ok(rustix::system::uname()
+        .nodename()
+        .to_string_lossy()
+        .into_owned())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> eyre::Result<String> {
+    match std::env::var("COMPUTERNAME") {
+        Ok(name) => Ok(name),
+        Err(_) => bail!(tr!("Can't determine hostname for {hostname}")),
+    }
+}
@@ -0,0 +1,113 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Waiting for new entries in the current directory, for `--watch`.
+//!
+//! On Linux, new entries are reported by inotify. Elsewhere, we have no way
+//! to be notified, so the directory is rescanned periodically instead.
+
+use std::time::Duration;
+
+use eyre::Context;
+
+use crate::i18n::tr;
+
+/// How often the directory is rescanned when changes can't be watched.
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches the current directory for new entries.
+pub struct Watcher {
+    #[cfg(target_os = "linux")]
+    inotify: rustix::fd::OwnedFd,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    /// Starts watching the current directory.
+    pub fn new() -> eyre::Result<Self> {
+        use rustix::fs::inotify;
+
+        let inotify = inotify::init(inotify::CreateFlags::CLOEXEC | inotify::CreateFlags::NONBLOCK)
+            .wrap_err(tr!("Can't create inotify instance"))?;
+        // Written-to entries are reported too, so --settle is rechecked once
+        // a download finishes
+        let flags = inotify::WatchFlags::CREATE
+            | inotify::WatchFlags::MOVED_TO
+            | inotify::WatchFlags::CLOSE_WRITE;
+        inotify::add_watch(&inotify, ".", flags).wrap_err(tr!("Can't watch current directory"))?;
+        Ok(Self { inotify })
+    }
+
+    /// Blocks until the directory changes or `timeout` passes. Returns whether
+    /// it changed.
+    pub fn wait(&self, timeout: Option<Duration>) -> eyre::Result<bool> {
+        use rustix::event::{PollFd, PollFlags, Timespec, poll};
+
+        let timeout = timeout
+            .map(Timespec::try_from)
+            .transpose()
+            .wrap_err(tr!("Timeout is too long"))?;
+        let mut fds = [PollFd::new(&self.inotify, PollFlags::IN)];
+        let ready = poll(&mut fds, timeout.as_ref()).wrap_err(tr!("Can't wait for changes"))?;
+        if ready == 0 {
+            return Ok(false);
+        }
+        // We rescan the whole directory anyways, so the events themselves
+        // don't matter
+        let mut buf = [0; 4096];
+        loop {
+            match rustix::io::read(&self.inotify, &mut buf) {
+                Ok(_) => {}
+                Err(rustix::io::Errno::AGAIN) => return Ok(true),
+                Err(err) => return Err(err).wrap_err(tr!("Can't read changes")),
+            }
+        }
+    }
+
+    /// Blocks until the directory hasn't changed for `quiet`, so that a burst
+    /// of changes is handled at once.
+    pub fn debounce(&self, quiet: Duration) -> eyre::Result<()> {
+        while self.wait(Some(quiet))? {}
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    /// Starts watching the current directory.
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn new() -> eyre::Result<Self> {
+        Ok(Self {})
+    }
+
+    /// Blocks until it's time to rescan the directory, at most `timeout`.
+    /// Returns whether the directory may have changed.
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn wait(&self, timeout: Option<Duration>) -> eyre::Result<bool> {
+        std::thread::sleep(timeout.map_or(POLL_INTERVAL, |timeout| timeout.min(POLL_INTERVAL)));
+        Ok(true)
+    }
+
+    /// Waits for `quiet`, so that a burst of changes is handled at once.
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn debounce(&self, quiet: Duration) -> eyre::Result<()> {
+        std::thread::sleep(quiet);
+        Ok(())
+    }
+}
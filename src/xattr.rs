@@ -0,0 +1,93 @@
+//
+// Copyright (C) 2025 Kian Kasad <kian@kasad.com>
+//
+// This file is part of Leave.
+//
+// Leave is free software: you can redistribute it and/or modify it under the
+// terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// Leave is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// Leave. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Reading extended attributes, for `--keep-xattr`.
+//!
+//! Attributes are read from entries themselves, without following symbolic
+//! links. Values are compared byte for byte, so attributes stored in a binary
+//! format, like macOS Finder tags, can only be matched by name.
+
+use std::{io, path::Path};
+
+/// An extended attribute, optionally with a value, whose entries are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xattr {
+    name: String,
+    value: Option<String>,
+}
+
+impl Xattr {
+    /// Parses `NAME` or `NAME=VALUE`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if !cfg!(any(target_os = "linux", target_os = "macos")) {
+            return Err("--keep-xattr is only supported on Linux and macOS".into());
+        }
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value.into())),
+            None => (s, None),
+        };
+        if name.is_empty() {
+            return Err("no attribute name given".into());
+        }
+        Ok(Self {
+            name: name.into(),
+            value,
+        })
+    }
+
+    /// Returns whether the entry at the given path has this attribute, with
+    /// the given value if there is one. File systems without extended
+    /// attributes have none.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn matches(&self, path: &Path) -> io::Result<bool> {
+        use rustix::{fs::lgetxattr, io::Errno};
+
+        #[cfg(target_os = "linux")]
+        const MISSING: Errno = Errno::NODATA;
+        #[cfg(target_os = "macos")]
+        const MISSING: Errno = Errno::NOATTR;
+
+        // An empty buffer only gets the length of the value
+        let len = match lgetxattr(path, self.name.as_str(), &mut [0; 0][..]) {
+            Ok(len) => len,
+            Err(err) if err == MISSING || err == Errno::NOTSUP => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        let Some(expected) = &self.value else {
+            return Ok(true);
+        };
+        let mut value = vec![0; len];
+        let len = lgetxattr(path, self.name.as_str(), &mut value[..])?;
+        Ok(value[..len] == *expected.as_bytes())
+    }
+
+    /// Returns whether the entry at the given path has this attribute.
+    /// Extended attributes aren't supported on this platform.
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn matches(&self, _path: &Path) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// Returns the reason to keep entries with this attribute.
+    pub fn reason(&self) -> String {
+        match &self.value {
+            Some(value) => format!("has extended attribute {}={value}", self.name),
+            None => format!("has extended attribute {}", self.name),
+        }
+    }
+}
@@ -18,7 +18,8 @@
 
 use std::{
     collections::HashSet,
-    path::Path,
+    io::Write,
+    path::{Path, PathBuf},
     process::{Command, Output, Stdio},
 };
 
@@ -30,14 +31,30 @@ use crate::utils::TestTree;
 mod utils;
 
 fn run_and_expect(cwd: impl AsRef<Path>, args: &[&str], expected_exit_code: i32) -> Output {
+    run_with_input(cwd, args, "", expected_exit_code)
+}
+
+/// Like [`run_and_expect`], but feeds `input` to the command's standard input.
+fn run_with_input(
+    cwd: impl AsRef<Path>,
+    args: &[&str],
+    input: &str,
+    expected_exit_code: i32,
+) -> Output {
     println!("Running command: leave {}", args.join(" "));
-    let output = Command::new(env!("CARGO_BIN_EXE_leave"))
+    let mut child = leave_command()
         .args(args)
+        .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .current_dir(cwd)
-        .output()
+        .spawn()
         .unwrap();
+    // The command may exit without reading its input
+    if let Err(err) = child.stdin.take().unwrap().write_all(input.as_bytes()) {
+        assert_eq!(std::io::ErrorKind::BrokenPipe, err.kind());
+    }
+    let output = child.wait_with_output().unwrap();
     let actual_exit_code = output.status.code().unwrap();
     assert_eq!(
         expected_exit_code, actual_exit_code,
@@ -46,6 +63,23 @@ fn run_and_expect(cwd: impl AsRef<Path>, args: &[&str], expected_exit_code: i32)
     output
 }
 
+/// Returns a command running Leave, isolated from the user's environment:
+/// messages are in English, and no configuration file or `LEAVE_*` variable
+/// changes the defaults.
+fn leave_command() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_leave"));
+    command
+        .env("LC_ALL", "C")
+        .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env("XDG_STATE_HOME", env!("CARGO_TARGET_TMPDIR"));
+    for (name, _) in std::env::vars_os() {
+        if name.to_string_lossy().starts_with("LEAVE_") {
+            command.env_remove(name);
+        }
+    }
+    command
+}
+
 fn set<I, T>(args: I) -> HashSet<String>
 where
     I: IntoIterator<Item = T>,
@@ -76,6 +110,42 @@ pub fn chdir() {
     assert_eq!(set(["file1"]), tt.contents());
 }
 
+/// Tests cleaning another directory with --in, where paths given to other
+/// options stay relative to the current directory
+#[test]
+pub fn in_dir() {
+    let tt = TestTree::new(json!({
+        "list.txt": null,
+        "target": {
+            "file1": null,
+            "file2": null,
+            "file3": null,
+        },
+    }));
+    std::fs::write(tt.path().join("list.txt"), "file2\n").unwrap();
+    run_and_expect(
+        tt.path(),
+        &[
+            "--in",
+            "target",
+            "--files-from",
+            "list.txt",
+            "--log",
+            "removal.log",
+            "file1",
+        ],
+        0,
+    );
+    assert_eq!(set(["list.txt", "removal.log", "target"]), tt.contents());
+    let log = std::fs::read_to_string(tt.path().join("removal.log")).unwrap();
+    assert!(log.trim_end().ends_with("/target/file3"));
+    let remaining: HashSet<_> = std::fs::read_dir(tt.path().join("target"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(set(["file1", "file2"]), remaining);
+}
+
 #[test]
 pub fn dirs() {
     let tt = TestTree::new(json!({
@@ -162,18 +232,37 @@ pub fn nonexistent_args() {
     let tt = TestTree::new(json!({
         "file1": null,
     }));
-    run_and_expect(tt.path(), &["file2"], 1);
+    run_and_expect(tt.path(), &["file2"], 2);
     assert_eq!(set(["file1"]), tt.contents());
 }
 
+/// Tests that a run without anything to keep is refused, unless some option
+/// other than a keep argument keeps entries
+#[test]
+pub fn no_keep_criteria() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2.txt": null,
+    }));
+    let output = run_and_expect(tt.path(), &[], 2);
+    assert_eq!(set(["file1", "file2.txt"]), tt.contents());
+    assert_eq!(
+        "Error: No files provided. This is likely a mistake. To continue anyways, use -f/--force.\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    run_and_expect(tt.path(), &["--regex", r"\.txt$"], 0);
+    assert_eq!(set(["file2.txt"]), tt.contents());
+}
+
 /// Test that the existence check is overridden by -f/--force
 #[test]
 pub fn nonexistent_args_force() {
     let tt = TestTree::new(json!({
         "file1": null,
     }));
-    run_and_expect(tt.path(), &["-f", "file2"], 0);
-    assert!(tt.is_empty());
+    run_and_expect(tt.path(), &["-f", "file1", "zzz"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
 }
 
 #[test]
@@ -197,7 +286,7 @@ pub fn bail_on_nested_file() {
             "file": null
         }
     }));
-    let output = run_and_expect(tt.path(), &["dir/file"], 1);
+    let output = run_and_expect(tt.path(), &["dir/file"], 2);
     assert_eq!(set(["dir"]), tt.contents());
     let stderr = str::from_utf8(&output.stderr).unwrap();
     assert_eq!(
@@ -205,3 +294,3848 @@ pub fn bail_on_nested_file() {
         stderr
     );
 }
+
+/// Tests that -r descends into directories containing nested arguments
+#[test]
+pub fn nested_keep() {
+    let tt = TestTree::new(json!({
+        "Cargo.toml": null,
+        "other": null,
+        "src": {
+            "main.rs": null,
+            "lib.rs": null,
+            "sub": {
+                "x": null,
+            },
+        },
+    }));
+    let output = run_and_expect(tt.path(), &["why", "src/lib.rs", "-r", "src/main.rs"], 0);
+    assert_eq!(
+        "src/lib.rs would be removed\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    run_and_expect(tt.path(), &["-r", "src/main.rs", "Cargo.toml"], 0);
+    tt.assert_tree(json!({
+        "Cargo.toml": null,
+        "src": {
+            "main.rs": null,
+        },
+    }));
+}
+
+/// Tests reverting a run with --journal using `leave undo`
+#[test]
+pub fn journal_undo() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": null,
+        "file\nwith newline": null,
+        "dir1": {
+            "file2": null,
+            "file3": null,
+        },
+    }));
+    let original = tt.contents();
+    run_and_expect(tt.path(), &["undo"], 1);
+    run_and_expect(tt.path(), &["--journal", "-r", "keep", "dir1/file2"], 0);
+    assert_eq!(3, tt.contents().len());
+    run_and_expect(tt.path(), &["undo"], 0);
+    assert_eq!(original, tt.contents());
+    assert!(tt.path().join("dir1/file3").is_file());
+
+    // Entries which were recreated aren't overwritten
+    run_and_expect(tt.path(), &["--journal", "-r", "keep", "dir1"], 0);
+    std::fs::write(tt.path().join("file1"), "new").unwrap();
+    let output = run_and_expect(tt.path(), &["undo"], 1);
+    assert_eq!(
+        "Error: Can't restore file1: it already exists\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert_eq!(5, tt.contents().len());
+    assert_eq!(
+        "new",
+        std::fs::read_to_string(tt.path().join("file1")).unwrap()
+    );
+    std::fs::remove_file(tt.path().join("file1")).unwrap();
+    run_and_expect(tt.path(), &["undo"], 0);
+    assert_eq!(original, tt.contents());
+    assert_eq!(
+        "",
+        std::fs::read_to_string(tt.path().join("file1")).unwrap()
+    );
+}
+
+/// Tests that --quarantine moves entries aside instead of deleting them
+#[test]
+pub fn quarantine() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+        },
+    }));
+    run_and_expect(tt.path(), &["--quarantine", "-r", "file1"], 0);
+    let contents = tt.contents();
+    assert_eq!(2, contents.len());
+    assert!(contents.contains("file1"));
+    let trash = contents
+        .iter()
+        .find(|name| name.starts_with(".leave-trash-"))
+        .expect("No quarantine directory created");
+    let trash_contents: HashSet<String> = tt
+        .path()
+        .join(trash)
+        .read_dir()
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(set(["file2", "dir1"]), trash_contents);
+    assert!(tt.path().join(trash).join("dir1/file3").exists());
+
+    // A second run must not quarantine the first quarantine directory
+    run_and_expect(tt.path(), &["--quarantine", "-r", "-f"], 0);
+    assert_eq!(2, tt.contents().len());
+
+    run_and_expect(tt.path(), &["purge"], 0);
+    assert!(tt.is_empty());
+}
+
+#[test]
+pub fn timings() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    let output = run_and_expect(tt.path(), &["--timings", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    for phase in ["validation", "scan", "matching", "removal", "total"] {
+        assert!(stderr.contains(phase), "Missing {phase} in timings output");
+    }
+    assert!(!stderr.contains("Workers:"));
+
+    // Each worker's share is reported with --jobs
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+    }));
+    let output = run_and_expect(tt.path(), &["--timings", "--jobs", "2", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    let (_, workers) = stderr.split_once("Workers:\n").unwrap();
+    let lines: Vec<_> = workers.lines().collect();
+    assert_eq!(2, lines.len());
+    assert!(lines[0].starts_with("  #1  "));
+    assert!(lines[1].starts_with("  #2  "));
+    let entries: usize = lines
+        .iter()
+        .map(|line| {
+            let count = line.strip_suffix(" entries").unwrap();
+            count.rsplit(' ').next().unwrap().parse::<usize>().unwrap()
+        })
+        .sum();
+    assert_eq!(2, entries);
+}
+
+/// Tests that nothing is removed if a snapshot can't be created
+#[test]
+pub fn snapshot_unsupported_fs() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    // Temporary directories aren't on ZFS or Btrfs in CI
+    let output = run_and_expect(tt.path(), &["--snapshot", "file1"], 1);
+    assert_eq!(set(["file1", "file2"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.starts_with("Error: Can't create snapshot: "));
+}
+
+/// Tests --remote using a fake SSH program which runs the command locally
+#[cfg(unix)]
+#[test]
+pub fn remote() {
+    let tt = TestTree::new(json!({
+        "file 1": null,
+        "file2": null,
+        "file3": null,
+    }));
+    let ssh = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(ssh.path(), "#!/bin/sh\nshift; shift; exec sh -c \"$1\"\n").unwrap();
+    let ssh = ssh.into_temp_path();
+    std::fs::set_permissions(&ssh, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    let remote = format!("localhost:{}", tt.path().display());
+    let output = leave_command()
+        .args(["--remote", &remote, "--remote-command"])
+        .arg(env!("CARGO_BIN_EXE_leave"))
+        .arg("file 1")
+        .env("LEAVE_SSH", &ssh)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(set(["file 1"]), tt.contents());
+
+    // The remote command can have arguments of its own
+    std::fs::File::create(tt.path().join("file2")).unwrap();
+    let output = leave_command()
+        .args(["--remote", &remote, "--remote-command"])
+        .arg(format!("env LC_ALL=C {}", env!("CARGO_BIN_EXE_leave")))
+        .arg("file 1")
+        .env("LEAVE_SSH", &ssh)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(set(["file 1"]), tt.contents());
+
+    // Options naming local files would be resolved on the remote host
+    std::fs::File::create(tt.path().join("file2")).unwrap();
+    for option in [
+        "--log",
+        "--files-from",
+        "--keep-file",
+        "--rules",
+        "--manifest",
+        "--audit-log",
+        "--control-socket",
+    ] {
+        let output = leave_command()
+            .args(["--remote", &remote, option, "local", "file 1"])
+            .env("LEAVE_SSH", &ssh)
+            .output()
+            .unwrap();
+        assert_eq!(Some(2), output.status.code(), "{option}");
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+        assert!(stderr.contains("cannot be used with"), "{option}: {stderr}");
+    }
+    assert_eq!(set(["file 1", "file2"]), tt.contents());
+}
+
+/// Builds a minimal little-endian JPEG file whose EXIF metadata records the
+/// given original capture date.
+fn jpeg_with_exif_date(date: &str) -> Vec<u8> {
+    let mut tiff = Vec::new();
+    tiff.extend(b"II*\0");
+    tiff.extend(8u32.to_le_bytes());
+    // IFD0 at offset 8: one entry pointing to the EXIF IFD at offset 26
+    tiff.extend(1u16.to_le_bytes());
+    tiff.extend(0x8769u16.to_le_bytes());
+    tiff.extend(4u16.to_le_bytes());
+    tiff.extend(1u32.to_le_bytes());
+    tiff.extend(26u32.to_le_bytes());
+    tiff.extend(0u32.to_le_bytes());
+    // EXIF IFD at offset 26: DateTimeOriginal stored at offset 44
+    tiff.extend(1u16.to_le_bytes());
+    tiff.extend(0x9003u16.to_le_bytes());
+    tiff.extend(2u16.to_le_bytes());
+    tiff.extend(20u32.to_le_bytes());
+    tiff.extend(44u32.to_le_bytes());
+    tiff.extend(0u32.to_le_bytes());
+    tiff.extend(format!("{date} 12:00:00\0").bytes());
+
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    jpeg.extend(u16::try_from(2 + 6 + tiff.len()).unwrap().to_be_bytes());
+    jpeg.extend(b"Exif\0\0");
+    jpeg.extend(tiff);
+    jpeg.extend([0xFF, 0xD9]);
+    jpeg
+}
+
+#[test]
+pub fn keep_exif_dates() {
+    let tt = TestTree::new(json!({
+        "new.jpg": null,
+        "old.jpg": null,
+        "ancient.jpg": null,
+        "not-a-photo.txt": null,
+    }));
+    std::fs::write(tt.path().join("new.jpg"), jpeg_with_exif_date("2024:07:15")).unwrap();
+    std::fs::write(tt.path().join("old.jpg"), jpeg_with_exif_date("2023:01:02")).unwrap();
+    std::fs::write(
+        tt.path().join("ancient.jpg"),
+        jpeg_with_exif_date("1999:12:31"),
+    )
+    .unwrap();
+    run_and_expect(
+        tt.path(),
+        &[
+            "--keep-exif-after",
+            "2024-06-01",
+            "--keep-exif-before",
+            "2000-01-01",
+        ],
+        0,
+    );
+    assert_eq!(set(["new.jpg", "ancient.jpg"]), tt.contents());
+}
+
+/// Tests that --settle keeps recently-modified entries
+#[test]
+pub fn settle() {
+    let tt = TestTree::new(json!({
+        "old": null,
+        "new": null,
+    }));
+    let two_hours_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 60 * 60);
+    std::fs::File::options()
+        .write(true)
+        .open(tt.path().join("old"))
+        .unwrap()
+        .set_modified(two_hours_ago)
+        .unwrap();
+    run_and_expect(tt.path(), &["-f", "--settle", "1h"], 0);
+    assert_eq!(set(["new"]), tt.contents());
+}
+
+/// Tests the summary printed with --summary
+#[test]
+pub fn summary() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": { "content": "x".repeat(1000) },
+        "dir1": {
+            "file2": { "content": "x".repeat(2000) },
+            "dir2": {},
+        },
+    }));
+    let output = run_and_expect(tt.path(), &["--summary", "-n", "-r", "keep"], 0);
+    assert_eq!(
+        "would remove 2 files, 2 directories, would free 2.9 KiB\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    let output = run_and_expect(tt.path(), &["--summary", "-r", "keep"], 0);
+    assert_eq!(
+        "removed 2 files, 2 directories, freed 2.9 KiB\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert_eq!(set(["keep"]), tt.contents());
+}
+
+/// Tests that files with hard links which aren't removed don't count as freed
+#[cfg(unix)]
+#[test]
+pub fn summary_hard_links() {
+    let tt = TestTree::new(json!({
+        "keep": { "content": "x".repeat(2048) },
+        "linked": { "content": "x".repeat(1024) },
+        "dir1": {},
+    }));
+    // Both links to this file are removed, so its space is freed
+    std::fs::hard_link(tt.path().join("linked"), tt.path().join("dir1/linked")).unwrap();
+    // This one stays linked from the kept file
+    std::fs::hard_link(tt.path().join("keep"), tt.path().join("dir1/kept")).unwrap();
+    let output = run_and_expect(tt.path(), &["--summary", "-n", "-r", "keep"], 0);
+    assert_eq!(
+        "would remove 3 files, 1 directory, would free 1.0 KiB, 2.0 KiB pending on other hard links\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    let output = run_and_expect(tt.path(), &["--summary", "-r", "keep"], 0);
+    assert_eq!(
+        "removed 3 files, 1 directory, freed 1.0 KiB, 2.0 KiB pending on other hard links\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert_eq!(set(["keep"]), tt.contents());
+}
+
+/// Tests throttling removal with --nice-io
+#[test]
+pub fn nice_io() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": null,
+        "dir1": {
+            "file2": null,
+            "file3": null,
+        },
+    }));
+    let start = std::time::Instant::now();
+    run_and_expect(tt.path(), &["-r", "--nice-io=20", "keep"], 0);
+    // Four removals 50 ms apart
+    assert!(start.elapsed() >= std::time::Duration::from_millis(150));
+    assert_eq!(set(["keep"]), tt.contents());
+
+    // The rate is optional and must be attached to the option
+    std::fs::write(tt.path().join("file1"), "").unwrap();
+    run_and_expect(tt.path(), &["--nice-io", "keep"], 0);
+    assert_eq!(set(["keep"]), tt.contents());
+    run_and_expect(tt.path(), &["--nice-io=0", "keep"], 2);
+}
+
+/// Tests the disk usage printed with --report-size
+#[test]
+pub fn report_size() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": null,
+        "dir1": {
+            "file2": null,
+        },
+    }));
+    std::fs::write(tt.path().join("file1"), [1; 5000]).unwrap();
+    std::fs::write(tt.path().join("dir1/file2"), [1; 5000]).unwrap();
+    let output = run_and_expect(tt.path(), &["--report-size", "-n", "-r", "keep"], 0);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    let lines: Vec<(&str, &str)> = stderr
+        .lines()
+        .map(|line| line.split_once('\t').unwrap())
+        .collect();
+    assert_eq!(
+        set(["./file1", "./dir1", "total"]),
+        set(lines.iter().map(|(_, path)| *path))
+    );
+    assert_eq!("total", lines.last().unwrap().1);
+    // Sizes are whole blocks, so they depend on the file system
+    assert!(lines.iter().all(|(size, _)| size.ends_with("iB")));
+    assert_eq!(set(["keep", "file1", "dir1"]), tt.contents());
+}
+
+/// Tests keeping entries by size with --smaller-than and --larger-than
+#[test]
+pub fn size_filters() {
+    let tree = json!({
+        "small": null,
+        "big": null,
+        "dir": {
+            "big": null,
+        },
+    });
+    let fill = |tt: &TestTree| {
+        std::fs::write(tt.path().join("small"), [0; 10]).unwrap();
+        std::fs::write(tt.path().join("big"), [0; 2048]).unwrap();
+        std::fs::write(tt.path().join("dir/big"), [0; 2048]).unwrap();
+    };
+
+    let tt = TestTree::new(tree.clone());
+    fill(&tt);
+    run_and_expect(tt.path(), &["-r", "--larger-than", "1K"], 0);
+    assert_eq!(set(["big", "dir"]), tt.contents());
+
+    let tt = TestTree::new(tree);
+    fill(&tt);
+    run_and_expect(tt.path(), &["-r", "--smaller-than", "1KiB"], 0);
+    assert_eq!(set(["small"]), tt.contents());
+    run_and_expect(tt.path(), &["--smaller-than", "1Q"], 2);
+}
+
+/// Tests keeping the most recently modified entries with --keep-recent
+#[test]
+pub fn keep_recent() {
+    let tt = TestTree::new(json!({
+        "backup1": null,
+        "backup2": null,
+        "backup3": null,
+        "backup4": null,
+        "backup5": null,
+        ".hidden": null,
+    }));
+    let now = std::time::SystemTime::now();
+    for (i, name) in ["backup3", "backup1", "backup5", "backup2", "backup4"]
+        .iter()
+        .enumerate()
+    {
+        let age = std::time::Duration::from_secs(60 * 60 * (u64::try_from(i).unwrap() + 1));
+        std::fs::File::options()
+            .write(true)
+            .open(tt.path().join(name))
+            .unwrap()
+            .set_modified(now - age)
+            .unwrap();
+    }
+    run_and_expect(tt.path(), &["--keep-recent", "2", "backup4"], 0);
+    assert_eq!(
+        set(["backup3", "backup1", "backup4", ".hidden"]),
+        tt.contents()
+    );
+}
+
+/// Tests keeping the largest entries with --keep-largest
+#[test]
+pub fn keep_largest() {
+    let tt = TestTree::new(json!({
+        "small": null,
+        "medium": null,
+        "large": null,
+        "dir1": {
+            "part1": null,
+            "part2": null,
+        },
+        "keep": null,
+    }));
+    for (name, size) in [
+        ("small", 10),
+        ("medium", 500),
+        ("large", 1000),
+        ("dir1/part1", 400),
+        ("dir1/part2", 400),
+    ] {
+        std::fs::write(tt.path().join(name), vec![0; size]).unwrap();
+    }
+    run_and_expect(tt.path(), &["-r", "--keep-largest", "2", "keep"], 0);
+    assert_eq!(set(["large", "dir1", "keep"]), tt.contents());
+}
+
+/// Tests keeping entries by age with --newer-than and --older-than
+#[test]
+pub fn age_filters() {
+    let tree = json!({
+        "old": null,
+        "new": null,
+        "keep": null,
+    });
+    let two_days_ago =
+        std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 24 * 60 * 60);
+    let age = |tt: &TestTree| {
+        std::fs::File::options()
+            .write(true)
+            .open(tt.path().join("old"))
+            .unwrap()
+            .set_modified(two_days_ago)
+            .unwrap();
+    };
+
+    let tt = TestTree::new(tree.clone());
+    age(&tt);
+    run_and_expect(tt.path(), &["--newer-than", "1d"], 0);
+    assert_eq!(set(["new", "keep"]), tt.contents());
+
+    let tt = TestTree::new(tree);
+    age(&tt);
+    run_and_expect(tt.path(), &["--older-than", "1d12h", "keep"], 0);
+    assert_eq!(set(["old", "keep"]), tt.contents());
+    run_and_expect(tt.path(), &["--older-than", "1x"], 2);
+}
+
+#[test]
+pub fn invalid_duration() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+    }));
+    run_and_expect(tt.path(), &["-f", "--settle", "10x"], 2);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests that -r removes trees on the same file system with either of
+/// --one-file-system and --cross-file-systems
+#[test]
+pub fn file_systems() {
+    for flag in ["--one-file-system", "--cross-file-systems"] {
+        let tt = TestTree::new(json!({
+            "file1": null,
+            "dir1": {
+                "dir2": {
+                    "file2": null,
+                },
+            },
+        }));
+        let output = run_and_expect(tt.path(), &[flag, "-rv", "file1"], 0);
+        assert_eq!(
+            "removed './dir1/dir2/file2'\nremoved directory './dir1/dir2'\nremoved directory './dir1'\n",
+            str::from_utf8(&output.stdout).unwrap()
+        );
+        assert_eq!(set(["file1"]), tt.contents());
+    }
+}
+
+/// Tests that hidden entries are kept unless --hidden is given
+#[test]
+pub fn hidden() {
+    let tree = json!({
+        "file1": null,
+        "file2": null,
+        ".env": null,
+        ".direnv": {
+            "file3": null,
+        },
+    });
+    let tt = TestTree::new(tree.clone());
+    let output = run_and_expect(tt.path(), &["why", ".env", "file1"], 0);
+    assert_eq!(
+        ".env would be kept: hidden\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    run_and_expect(tt.path(), &["--keep-hidden", "-r", "file1"], 0);
+    assert_eq!(set(["file1", ".env", ".direnv"]), tt.contents());
+    run_and_expect(tt.path(), &["--keep-hidden", "--hidden", "-r", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+
+    // The default can be changed using the environment
+    let tt = TestTree::new(tree);
+    let output = leave_command()
+        .args(["-r", "file1"])
+        .env("LEAVE_HIDDEN", "remove")
+        .current_dir(tt.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests that in-progress downloads are kept unless --delete-partial is given
+#[test]
+pub fn partial_downloads() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "movie.mkv.part": null,
+        "installer.crdownload": null,
+        ".~tmp~": {
+            "file2": null,
+        },
+        "file3": null,
+    }));
+    run_and_expect(tt.path(), &["--hidden", "-r", "file1"], 0);
+    assert_eq!(
+        set(["file1", "movie.mkv.part", "installer.crdownload", ".~tmp~"]),
+        tt.contents()
+    );
+    run_and_expect(
+        tt.path(),
+        &["--hidden", "-r", "--delete-partial", "file1"],
+        0,
+    );
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests that -f removes trees containing write-protected directories
+#[cfg(unix)]
+#[test]
+pub fn force_write_protected() {
+    let tt = TestTree::new(json!({
+        "file1": { "content": "x", "mode": "0444" },
+        "dir1": {
+            "dir2": {
+                ".": { "mode": "0555" },
+                "file2": { "content": "", "mode": "0444" },
+                "dir3": {
+                    ".": { "mode": "0000" },
+                    "file3": null,
+                },
+            },
+        },
+    }));
+    // Protection is cleared during the walk, so every entry is removed once
+    let output = run_and_expect(tt.path(), &["-r", "-f", "-v", "file1"], 0);
+    tt.assert_tree(json!({
+        "file1": { "content": "x", "mode": "0444" },
+    }));
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert_eq!(
+        set([
+            "removed './dir1/dir2/dir3/file3'",
+            "removed directory './dir1/dir2/dir3'",
+            "removed './dir1/dir2/file2'",
+            "removed directory './dir1/dir2'",
+            "removed directory './dir1'",
+        ]),
+        set(stdout.lines())
+    );
+}
+
+/// Tests asking before removing write-protected files when standard input is
+/// a terminal, and keeping them without -f when it isn't
+#[cfg(unix)]
+#[test]
+pub fn prompt_write_protected() {
+    // Root can write to any file, so nothing is write-protected for it
+    if rustix::process::geteuid().is_root() {
+        return;
+    }
+    let tree = json!({
+        "keep": null,
+        "protected": { "content": "x", "mode": "0444" },
+        "plain": null,
+    });
+
+    let tt = TestTree::new(tree.clone());
+    let output = leave_command()
+        .arg("keep")
+        .stdin(Stdio::null())
+        .current_dir(tt.path())
+        .output()
+        .unwrap();
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(set(["keep", "protected"]), tt.contents());
+    assert_eq!(
+        "Error: Not removing write-protected regular file './protected', since standard input isn't a terminal to ask on. Use -f to remove it anyways.\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    for (answer, expected) in [("n", set(["keep", "protected"])), ("y", set(["keep"]))] {
+        let tt = TestTree::new(tree.clone());
+        let (mut terminal, stdin) = open_terminal();
+        writeln!(terminal, "{answer}").unwrap();
+        let output = leave_command()
+            .arg("keep")
+            .stdin(stdin)
+            .current_dir(tt.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{answer}");
+        assert_eq!(expected, tt.contents(), "{answer}");
+        assert_eq!(
+            "leave: remove write-protected regular file './protected'? ",
+            str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+}
+
+/// Opens a pseudo-terminal and returns its controlling side and the terminal
+/// itself, to be given to a command as standard input.
+#[cfg(unix)]
+fn open_terminal() -> (std::fs::File, std::fs::File) {
+    use rustix::pty;
+
+    let controller = pty::openpt(pty::OpenptFlags::RDWR | pty::OpenptFlags::NOCTTY).unwrap();
+    pty::grantpt(&controller).unwrap();
+    pty::unlockpt(&controller).unwrap();
+    let name = pty::ptsname(&controller, Vec::new()).unwrap();
+    let terminal = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(name.to_str().unwrap())
+        .unwrap();
+    (controller.into(), terminal)
+}
+
+/// Tests that --relative and --absolute control how paths are shown
+#[test]
+pub fn path_display() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "dir1": {},
+    }));
+    let output = run_and_expect(tt.path(), &["file1"], 1);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!("Error: Can't remove ./dir1: Is a directory\n", stderr);
+
+    let output = run_and_expect(tt.path(), &["--relative", "file1"], 1);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!("Error: Can't remove dir1: Is a directory\n", stderr);
+
+    let output = run_and_expect(tt.path(), &["--absolute", "file1"], 1);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    let expected = format!(
+        "Error: Can't remove {}: Is a directory\n",
+        std::path::absolute(tt.path().join("dir1"))
+            .unwrap()
+            .display()
+    );
+    assert_eq!(expected, stderr);
+}
+
+/// Tests that @FILE arguments are expanded to the lines of FILE
+#[test]
+pub fn argfile() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file 2": null,
+        "@file3": null,
+        "file4": null,
+        "dir1": {
+            "file5": null,
+        },
+    }));
+    let args = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(args.path(), "-r\nfile1\n\nfile 2\n").unwrap();
+    let argfile = format!("@{}", args.path().display());
+    run_and_expect(tt.path(), &[&argfile, "--", "@file3"], 0);
+    assert_eq!(set(["file1", "file 2", "@file3"]), tt.contents());
+}
+
+#[cfg(not(windows))]
+#[test]
+pub fn strip_zone_identifier_unsupported() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    run_and_expect(tt.path(), &["--strip-zone-identifier", "file1"], 2);
+    assert_eq!(set(["file1", "file2"]), tt.contents());
+}
+
+/// Tests that special files are kept unless --special is given
+#[cfg(unix)]
+#[test]
+pub fn special_files() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    let _listener = std::os::unix::net::UnixListener::bind(tt.path().join("socket")).unwrap();
+    let output = run_and_expect(tt.path(), &["file1"], 0);
+    assert_eq!(set(["file1", "socket"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(
+        "Warning: Skipping special file ./socket (socket). Use --special to remove it.\n",
+        stderr
+    );
+    run_and_expect(tt.path(), &["--special", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests that special files inside directories removed with -r are left,
+/// along with their ancestors, unless --special is given
+#[cfg(unix)]
+#[test]
+pub fn nested_special_files() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "dir1": {
+            "file1": null,
+            "dir2": {},
+        },
+    }));
+    let _listener =
+        std::os::unix::net::UnixListener::bind(tt.path().join("dir1/dir2/socket")).unwrap();
+    let warning =
+        "Warning: Skipping special file ./dir1/dir2/socket (socket). Use --special to remove it.\n";
+    let output = run_and_expect(tt.path(), &["-r", "-n", "keep"], 0);
+    assert_eq!(warning, str::from_utf8(&output.stderr).unwrap());
+    assert_eq!("", str::from_utf8(&output.stdout).unwrap());
+    let output = run_and_expect(tt.path(), &["-r", "keep"], 0);
+    assert_eq!(warning, str::from_utf8(&output.stderr).unwrap());
+    assert_eq!(set(["keep", "dir1"]), tt.contents());
+    assert!(!tt.path().join("dir1/file1").exists());
+    run_and_expect(tt.path(), &["-r", "--special", "keep"], 0);
+    assert_eq!(set(["keep"]), tt.contents());
+}
+
+/// Tests that directories containing mount points are refused unless
+/// --allow-mounts is given. Mounting requires privileges, so this test does
+/// nothing when they're missing.
+#[cfg(target_os = "linux")]
+#[test]
+pub fn mount_points() {
+    use std::process::Command;
+
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "dir1": {
+            "mnt": {},
+            "file1": null,
+        },
+    }));
+    let source = tempfile::tempdir().unwrap();
+    std::fs::write(source.path().join("file2"), "").unwrap();
+    let mount_point = tt.path().join("dir1/mnt");
+    let mounted = Command::new("mount")
+        .arg("--bind")
+        .arg(source.path())
+        .arg(&mount_point)
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !mounted {
+        return;
+    }
+    let output = run_and_expect(tt.path(), &["-r", "keep"], 1);
+    let result = Command::new("umount").arg(&mount_point).status();
+    assert_eq!(
+        "Error: Can't remove ./dir1: ./dir1 is or contains mount points (./dir1/mnt). Use --allow-mounts to remove it.\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert!(result.unwrap().success());
+    // Nothing is removed before the mount point is found
+    assert!(tt.path().join("dir1/file1").exists());
+    assert!(source.path().join("file2").exists());
+}
+
+/// Tests retrying removals which fail temporarily with --retries, using a
+/// mount point, which can't be removed while it's mounted. Needs permission
+/// to create bind mounts, so it checks nothing without it.
+#[cfg(target_os = "linux")]
+#[test]
+pub fn retries() {
+    use std::process::Command;
+
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "mnt": {},
+    }));
+    let source = tempfile::tempdir().unwrap();
+    let mount_point = tt.path().join("mnt");
+    let mounted = Command::new("mount")
+        .arg("--bind")
+        .arg(source.path())
+        .arg(&mount_point)
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !mounted {
+        return;
+    }
+    let output = run_and_expect(tt.path(), &["-d", "--retries", "2", "keep"], 1);
+    let result = Command::new("umount").arg(&mount_point).status();
+    assert_eq!(
+        "Error: Can't remove ./mnt: Gave up after trying 3 times: Device or resource busy (os error 16)\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert!(result.unwrap().success());
+    run_and_expect(tt.path(), &["-d", "--retries", "2", "keep"], 0);
+    assert_eq!(set(["keep"]), tt.contents());
+}
+
+/// Tests that --timeout limits each removal rather than whole entries, so a
+/// directory which takes longer than the limit to remove, here because of
+/// --nice-io, is still removed. Abandoning a stuck removal is tested in the
+/// timeout module, since a hung file system can't be made here.
+#[test]
+pub fn timeout() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file": null,
+        "dir": {
+            "a": null,
+            "b": null,
+            "sub": {
+                "c": null,
+                "d": null,
+                "e": null,
+            },
+        },
+    }));
+    let start = std::time::Instant::now();
+    run_and_expect(
+        tt.path(),
+        &["-r", "--nice-io=4", "--timeout", "1s", "keep"],
+        0,
+    );
+    assert!(start.elapsed() > std::time::Duration::from_secs(1));
+    assert_eq!(set(["keep"]), tt.contents());
+}
+
+/// Tests removing entries whose names Windows path parsing treats specially,
+/// which other tools can create using verbatim paths
+#[cfg(windows)]
+#[test]
+pub fn windows_special_names() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+    }));
+    let root = std::path::absolute(tt.path()).unwrap();
+    for name in ["nul.txt", "CON", "trailing.", "space "] {
+        std::fs::write(format!(r"\\?\{}\{name}", root.display()), "").unwrap();
+    }
+    run_and_expect(tt.path(), &["file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests that entries owned by other users are kept unless --all-owners is
+/// given. Changing ownership requires privileges, so this test does nothing
+/// when they're missing.
+#[cfg(unix)]
+#[test]
+pub fn other_owners() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    if std::os::unix::fs::chown(tt.path().join("file2"), Some(65534), None).is_err() {
+        return;
+    }
+    let output = run_and_expect(tt.path(), &["file1"], 0);
+    assert_eq!(set(["file1", "file2"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(
+        "Warning: Skipping ./file2, which is owned by another user. Use --all-owners to remove it.\n",
+        stderr
+    );
+    run_and_expect(tt.path(), &["--all-owners", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests custom per-entry output with --format
+#[test]
+pub fn format_template() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {},
+    }));
+    std::fs::write(tt.path().join("file2"), "abc").unwrap();
+    let output = run_and_expect(
+        tt.path(),
+        &[
+            "--relative",
+            "--format",
+            r"{action}\t{type}\t{path}\t{{{reason}}}",
+            "file1",
+        ],
+        1,
+    );
+    assert_eq!(set(["file1", "dir1"]), tt.contents());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert_eq!(
+        set([
+            "kept\tfile\tfile1\t{listed as an argument}",
+            "removed\tfile\tfile2\t{}",
+            "failed\tdir\tdir1\t{Is a directory}",
+        ]),
+        set(stdout.lines())
+    );
+
+    std::fs::write(tt.path().join("file1"), "hello").unwrap();
+    let output = run_and_expect(
+        tt.path(),
+        &["--relative", "--format", "{path} {size}", "dir1"],
+        0,
+    );
+    assert_eq!(set(["dir1"]), tt.contents());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line == "file1 5"));
+}
+
+#[test]
+pub fn format_template_invalid() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+    }));
+    run_and_expect(tt.path(), &["-f", "--format", "{nope}"], 2);
+    run_and_expect(tt.path(), &["-f", "--format", "{path"], 2);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests that {hostname} and {date:...} are expanded in keep arguments
+#[test]
+pub fn template_variables() {
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname").unwrap();
+    let year = 1970
+        + std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            / 31_556_952;
+    let host_file = format!("{}.conf", hostname.trim());
+    let year_file = format!("backup-{year}.tar");
+    let tt = TestTree::new(json!({
+        &host_file: null,
+        &year_file: null,
+        "{other}": null,
+        "file1": null,
+    }));
+    run_and_expect(
+        tt.path(),
+        &["{hostname}.conf", "backup-{date:%Y}.tar", "{other}"],
+        0,
+    );
+    assert_eq!(
+        set([host_file.as_str(), &year_file, "{other}"]),
+        tt.contents()
+    );
+}
+
+/// Tests that template variables are expanded in keep files, the configured
+/// `keep` list, presets, and rules files
+#[test]
+pub fn template_variables_in_keep_lists() {
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname").unwrap();
+    let hostname = hostname.trim();
+    let sources = ["keep-file", "config", "preset", "rules"];
+    let names: Vec<String> = sources
+        .iter()
+        .map(|source| format!("{hostname}.{source}"))
+        .collect();
+    let tt = TestTree::new(json!({
+        &names[0]: null,
+        &names[1]: null,
+        &names[2]: null,
+        &names[3]: null,
+    }));
+    let lists = tempfile::tempdir().unwrap();
+    let list = |name: &str, contents: &str| {
+        let path = lists.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.into_os_string().into_string().unwrap()
+    };
+    let keep_file = list("keep", "{hostname}.keep-file\n");
+    let rules = list("rules", "{hostname}.rules\n");
+    let config = list(
+        "config.toml",
+        "keep = ['{hostname}.config']\npreset_mine = ['{hostname}.preset']\n",
+    );
+    let empty_config = list("empty.toml", "");
+
+    for (source, args, config) in [
+        ("keep-file", vec!["--keep-file", &keep_file], &empty_config),
+        ("config", vec![], &config),
+        ("preset", vec!["--preset", "mine"], &config),
+        ("rules", vec!["--rules", &rules], &empty_config),
+    ] {
+        let output = leave_command()
+            .arg("--dry-run")
+            .args(&args)
+            .current_dir(tt.path())
+            .env("LEAVE_CONFIG", config)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{source}");
+        let expected: HashSet<String> = names
+            .iter()
+            .filter(|name| !name.ends_with(&format!(".{source}")))
+            .map(|name| format!("would remove ./{name}"))
+            .collect();
+        let expected = if source == "preset" {
+            // The configured keep list applies too
+            expected
+                .into_iter()
+                .filter(|line| !line.ends_with(".config"))
+                .collect()
+        } else {
+            expected
+        };
+        assert_eq!(
+            expected,
+            set(str::from_utf8(&output.stdout).unwrap().lines()),
+            "{source}"
+        );
+    }
+}
+
+/// Tests the targeted message for keep arguments differing only in case
+#[test]
+pub fn sanity_case_mismatch() {
+    let tt = TestTree::new(json!({
+        "README.md": null,
+        "file1": null,
+    }));
+    let output = run_and_expect(tt.path(), &["readme.md", "file1"], 2);
+    assert_eq!(set(["README.md", "file1"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(
+        "Warning: readme.md doesn't exist, but README.md does. Keep arguments are case-sensitive unless --ignore-case is given.\n\
+         Error: One or more provided files don't exist. This is likely a mistake. To continue anyways, use -f/--force.\n",
+        stderr
+    );
+}
+
+/// Tests the suggestion for keep arguments which are likely typos
+#[test]
+pub fn sanity_typo() {
+    let tt = TestTree::new(json!({
+        "file1.txt": null,
+        "notes.md": null,
+    }));
+    let output = run_and_expect(tt.path(), &["fiel1.txt", "notes.md", "zzz"], 2);
+    assert_eq!(set(["file1.txt", "notes.md"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(
+        "Warning: fiel1.txt doesn't exist. Did you mean 'file1.txt'?\n\
+         Warning: zzz doesn't exist.\n\
+         Error: One or more provided files don't exist. This is likely a mistake. To continue anyways, use -f/--force.\n",
+        stderr
+    );
+}
+
+/// Tests that -f still refuses a run whose missing keep arguments look like
+/// mistakes, unless --no-sanity-check is given
+#[test]
+pub fn sanity_force() {
+    let tt = TestTree::new(json!({
+        "README.md": null,
+        "file1": null,
+    }));
+    let output = run_and_expect(tt.path(), &["-f", "readme.md", "file1"], 3);
+    assert_eq!(set(["README.md", "file1"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(
+        "Warning: readme.md doesn't exist, but README.md does. Keep arguments are case-sensitive unless --ignore-case is given.\n\
+         Error: One or more provided files don't exist. Refusing to continue, even with -f. Use --no-sanity-check to override.\n",
+        stderr
+    );
+
+    // Keeping nothing at all is refused too
+    let output = run_and_expect(tt.path(), &["-f", "file2"], 3);
+    assert_eq!(set(["README.md", "file1"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.ends_with(
+        "Error: None of the provided files exist, so everything would be removed. Refusing to continue, even with -f. Use --no-sanity-check to override.\n"
+    ));
+
+    // Previewing is harmless
+    run_and_expect(tt.path(), &["-f", "-n", "readme.md", "file1"], 0);
+    assert_eq!(set(["README.md", "file1"]), tt.contents());
+
+    run_and_expect(
+        tt.path(),
+        &["-f", "--no-sanity-check", "readme.md", "file1"],
+        0,
+    );
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests matching keep arguments and keep files regardless of case
+#[test]
+pub fn ignore_case() {
+    let tt = TestTree::new(json!({
+        "README.md": null,
+        "Notes.txt": null,
+        "Docs": {
+            "Guide.md": null,
+            "old.md": null,
+        },
+        "file1": null,
+        "keep.txt": null,
+    }));
+    std::fs::write(tt.path().join("keep.txt"), "notes.TXT\n").unwrap();
+
+    run_and_expect(
+        tt.path(),
+        &[
+            "--ignore-case",
+            "-r",
+            "--keep-file",
+            "keep.txt",
+            "readme.MD",
+            "docs/guide.md",
+        ],
+        0,
+    );
+    assert_eq!(
+        set(["README.md", "Notes.txt", "Docs", "keep.txt"]),
+        tt.contents()
+    );
+    assert!(tt.path().join("Docs/Guide.md").exists());
+    assert!(!tt.path().join("Docs/old.md").exists());
+}
+
+/// Tests the targeted message when no keep argument exists
+#[test]
+pub fn sanity_nothing_kept() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+    }));
+    let output = run_and_expect(tt.path(), &["file2", "file3"], 2);
+    assert_eq!(set(["file1"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.ends_with(
+        "Error: None of the provided files exist, so everything would be removed. This is likely a mistake. To continue anyways, use -f/--force.\n"
+    ));
+}
+
+/// Tests printing why each entry is kept or removed with --explain
+#[test]
+pub fn explain() {
+    let tt = TestTree::new(json!({
+        "main.rs": null,
+        "notes.txt": null,
+        ".env": null,
+        "other": null,
+    }));
+    let output = run_and_expect(
+        tt.path(),
+        &["--explain", "-n", "--regex", r"\.rs$", "notes"],
+        0,
+    );
+    assert_eq!(
+        "./.env: kept: hidden\n\
+         ./main.rs: kept: matches regular expression '\\.rs$'\n\
+         ./notes.txt: kept: matches regular expression 'notes'\n\
+         ./other: deleted: no rule matched\n\
+         would remove ./other\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+}
+
+/// Tests explaining the decision for a single entry with `leave why`
+#[test]
+pub fn why() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+        },
+    }));
+    let expected = tt.contents();
+    let cases: &[(&[&str], &str)] = &[
+        (
+            &["why", "file1", "file1"],
+            "file1 would be kept: listed as an argument\n",
+        ),
+        (&["why", "file2", "file1"], "file2 would be removed\n"),
+        (
+            &["why", "./dir1", "-r", "file1"],
+            "./dir1 would be removed\n",
+        ),
+        (
+            &["why", "file2", "--settle", "1h", "file1"],
+            "file2 would be kept: modified too recently\n",
+        ),
+        (
+            &["why", "dir1/file3", "file1"],
+            "dir1/file3 would be removed along with ./dir1\n",
+        ),
+        (
+            &["why", "dir1/file3", "-r", "dir1/file3"],
+            "dir1/file3 would be kept: listed as an argument\n",
+        ),
+        (
+            &["why", "../file1", "file1"],
+            "../file1 would be kept: not in the current directory\n",
+        ),
+    ];
+    for (args, stdout) in cases {
+        let output = run_and_expect(tt.path(), args, 0);
+        assert_eq!(*stdout, str::from_utf8(&output.stdout).unwrap());
+    }
+    run_and_expect(tt.path(), &["why", "file4", "file1"], 1);
+    assert_eq!(expected, tt.contents());
+}
+
+/// Tests flushing removals to disk with --sync
+#[cfg(unix)]
+#[test]
+pub fn sync() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+        },
+    }));
+    run_and_expect(tt.path(), &["--sync", "--quarantine", "-r", "file1"], 0);
+    assert_eq!(2, tt.contents().len());
+    run_and_expect(tt.path(), &["--sync", "--hidden", "-r", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests printing what's left after removal with --list-remaining
+#[test]
+pub fn list_remaining() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "dir1": {},
+    }));
+    let output = run_and_expect(
+        tt.path(),
+        &["--list-remaining", "file3", "dir1", "file1"],
+        0,
+    );
+    assert_eq!(set(["dir1", "file1", "file3"]), tt.contents());
+    assert_eq!(
+        "./dir1\n./file1\n./file3\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    let output = run_and_expect(
+        tt.path(),
+        &[
+            "--list-remaining",
+            "--relative",
+            "--format",
+            "{action} {path}",
+            "file1",
+            "dir1",
+        ],
+        0,
+    );
+    assert_eq!(set(["dir1", "file1"]), tt.contents());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.ends_with("removed file3\nremaining dir1\nremaining file1\n"));
+
+    // With --print0, which lists instead of removing, paths end with NUL
+    let output = run_and_expect(tt.path(), &["--list-remaining", "--print0", "file1"], 0);
+    assert_eq!(set(["dir1", "file1"]), tt.contents());
+    assert_eq!(
+        "./dir1\0./dir1\0./file1\0",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+}
+
+/// Tests keeping entries in use by a process with --protect-pid
+#[cfg(target_os = "linux")]
+#[test]
+pub fn protect_pid() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "dir1": {},
+    }));
+    let mut child = std::process::Command::new("sh")
+        .args(["-c", "exec sleep 30 3<../file2"])
+        .current_dir(tt.path().join("dir1"))
+        .spawn()
+        .unwrap();
+    // Wait for the shell to open the file
+    let fd = format!("/proc/{}/fd/3", child.id());
+    while std::fs::read_link(&fd).is_err() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let pid = child.id().to_string();
+    let result = std::panic::catch_unwind(|| {
+        run_and_expect(tt.path(), &["-r", "--protect-pid", &pid, "file1"], 0);
+        assert_eq!(set(["dir1", "file1", "file2"]), tt.contents());
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    result.unwrap();
+}
+
+/// Tests keeping entries other processes have open with --skip-in-use
+#[cfg(target_os = "linux")]
+#[test]
+pub fn skip_in_use() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+        },
+    }));
+    let mut child = std::process::Command::new("sh")
+        .args(["-c", "exec sleep 30 3<file2 4<dir1/file3"])
+        .current_dir(tt.path())
+        .spawn()
+        .unwrap();
+    // Wait for the shell to open the files
+    let fd = format!("/proc/{}/fd/4", child.id());
+    while std::fs::read_link(&fd).is_err() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let result = std::panic::catch_unwind(|| {
+        let output = run_and_expect(tt.path(), &["-r", "--skip-in-use", "file1"], 0);
+        assert_eq!(set(["dir1", "file1", "file2"]), tt.contents());
+        assert_eq!(
+            set([
+                "Warning: Skipping ./dir1, which is in use by another process",
+                "Warning: Skipping ./file2, which is in use by another process",
+            ]),
+            set(str::from_utf8(&output.stderr).unwrap().lines())
+        );
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    result.unwrap();
+}
+
+/// Tests writing and verifying a hash-chained audit log
+#[test]
+pub fn audit_log() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "audit.log": null,
+    }));
+    // The user is looked up rather than taken from the environment
+    let status = leave_command()
+        .current_dir(tt.path())
+        .args(["--audit-log", "audit.log", "audit.log", "file1", "file2"])
+        .env("USER", "mallory")
+        .status()
+        .unwrap();
+    assert!(status.success());
+    run_and_expect(
+        tt.path(),
+        &["--audit-log", "audit.log", "audit.log", "file1"],
+        0,
+    );
+    assert_eq!(set(["audit.log", "file1"]), tt.contents());
+    let output = run_and_expect(tt.path(), &["audit", "verify", "audit.log"], 0);
+    assert_eq!(
+        "audit.log: 2 records verified\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    let log_path = tt.path().join("audit.log");
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let user = log.split('\t').nth(2).unwrap();
+    assert!(!user.contains("mallory"), "{user}");
+
+    // Cutting records off the end must be detected too
+    let last_line_start = log.trim_end().rfind('\n').unwrap() + 1;
+    std::fs::write(&log_path, &log[..last_line_start]).unwrap();
+    let output = run_and_expect(tt.path(), &["audit", "verify", "audit.log"], 1);
+    assert_eq!(
+        "Error: audit.log has been truncated: 2 records were written, but 1 remain\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    // Tampering with a record must be detected
+    std::fs::write(&log_path, log.replacen("file3", "file4", 1)).unwrap();
+    let output = run_and_expect(tt.path(), &["audit", "verify", "audit.log"], 1);
+    assert_eq!(
+        "Error: Record 1 of audit.log has been modified\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    // So must removing one
+    let first_line_len = log.find('\n').unwrap() + 1;
+    std::fs::write(&log_path, &log[first_line_len..]).unwrap();
+    let output = run_and_expect(tt.path(), &["audit", "verify", "audit.log"], 1);
+    assert_eq!(
+        "Error: Record 1 of audit.log doesn't follow the previous record\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+}
+
+/// Tests logging removed entries, including the contents of directories
+#[test]
+pub fn log() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "dir1": {
+            "file2": null,
+        },
+        "removal.log": null,
+    }));
+    let root = tt.path().canonicalize().unwrap();
+    let root = root.display();
+    run_and_expect(
+        tt.path(),
+        &["-rn", "--log", "removal.log", "removal.log"],
+        0,
+    );
+    assert_eq!(
+        "",
+        std::fs::read_to_string(tt.path().join("removal.log")).unwrap()
+    );
+
+    run_and_expect(tt.path(), &["-r", "--log", "removal.log", "removal.log"], 0);
+    assert_eq!(set(["removal.log"]), tt.contents());
+    let log = std::fs::read_to_string(tt.path().join("removal.log")).unwrap();
+    let records: HashSet<String> = log
+        .lines()
+        .map(|line| line.split_once('\t').unwrap().1.to_owned())
+        .collect();
+    assert_eq!(
+        set([
+            format!("removed\t{root}/file1"),
+            format!("removed\t{root}/dir1/file2"),
+            format!("removed\t{root}/dir1"),
+        ]),
+        records
+    );
+
+    std::fs::write(tt.path().join("file3"), "").unwrap();
+    run_and_expect(
+        tt.path(),
+        &[
+            "--log",
+            "removal.log",
+            "--log-format",
+            "json",
+            "removal.log",
+        ],
+        0,
+    );
+    let log = std::fs::read_to_string(tt.path().join("removal.log")).unwrap();
+    let record: serde_json::Value = serde_json::from_str(log.lines().last().unwrap()).unwrap();
+    assert_eq!("removed", record["action"]);
+    assert_eq!(format!("{root}/file3"), record["path"]);
+    assert!(record["time"].as_str().unwrap().ends_with('Z'));
+}
+
+/// Tests listing unexpected entries without removing them with `leave check`
+#[test]
+pub fn check() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "dir1": {},
+    }));
+    let expected = tt.contents();
+    let output = run_and_expect(tt.path(), &["check", "-d", "file1"], 1);
+    assert_eq!(expected, tt.contents());
+    assert_eq!(
+        "./dir1\n./file2\n./file3\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    assert_eq!(
+        "Error: 3 unexpected entries found\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    let output = run_and_expect(tt.path(), &["check", "file1", "file2", "file3", "dir1"], 0);
+    assert!(output.stdout.is_empty());
+    assert_eq!(expected, tt.contents());
+}
+
+/// Tests comparing plans and reports with `leave diff`
+#[test]
+pub fn diff() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "dir1": {},
+    }));
+    let plans = tempfile::tempdir().unwrap();
+    let write_plan = |name: &str, args: &[&str], code: i32| {
+        let output = run_and_expect(tt.path(), args, code);
+        let path = plans.path().join(name);
+        std::fs::write(&path, output.stdout).unwrap();
+        path.into_os_string().into_string().unwrap()
+    };
+    let old = write_plan(
+        "old.json",
+        &["-n", "--output", "json", "-d", "file1", "file2"],
+        0,
+    );
+    let new = write_plan("new.json", &["-n", "--output", "json", "file1"], 1);
+
+    let output = run_and_expect(tt.path(), &["diff", &old, &new], 1);
+    assert_eq!(
+        "newly failing ./dir1 (Is a directory)\nnewly doomed ./file2\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    let output = run_and_expect(tt.path(), &["diff", &new, &old], 1);
+    assert_eq!(
+        "newly doomed ./dir1\nnewly kept ./file2 (listed as an argument)\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    let output = run_and_expect(tt.path(), &["diff", &old, &old], 0);
+    assert!(output.stdout.is_empty());
+
+    // A run report matches the plan it carried out
+    let report = write_plan(
+        "report.json",
+        &["--output", "json", "-d", "file1", "file2"],
+        0,
+    );
+    assert_eq!(set(["file1", "file2"]), tt.contents());
+    let output = run_and_expect(tt.path(), &["diff", &old, &report], 0);
+    assert!(output.stdout.is_empty());
+
+    std::fs::write(plans.path().join("bad.json"), "{}\n").unwrap();
+    let bad = plans.path().join("bad.json");
+    let output = run_and_expect(tt.path(), &["diff", &old, bad.to_str().unwrap()], 1);
+    assert_eq!(
+        format!(
+            "Error: {}:1: Not an event written by --output json\n",
+            bad.display()
+        ),
+        str::from_utf8(&output.stderr).unwrap()
+    );
+}
+
+/// Tests printing the man page with the manpage subcommand
+#[test]
+pub fn manpage() {
+    let tt = TestTree::new(json!({ "file1": null }));
+    let output = run_and_expect(tt.path(), &["manpage"], 0);
+    let page = str::from_utf8(&output.stdout).unwrap();
+    assert!(page.contains(".TH leave 1"));
+    assert!(page.contains(".SH OPTIONS"));
+    assert!(page.contains("\\fB\\-\\-dry\\-run\\fR"));
+    assert!(page.contains(".SH EXAMPLES"));
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Starts a run with --control-socket on a tree with many entries, and
+/// connects to the socket. Returns `None` if the run finished before the test
+/// could connect.
+#[cfg(unix)]
+fn start_controlled_run(
+    tt: &TestTree,
+    socket_path: &Path,
+) -> Option<(std::process::Child, std::os::unix::net::UnixStream)> {
+    for i in 0..5000 {
+        std::fs::File::create(tt.path().join(format!("file{i}"))).unwrap();
+    }
+    let child = leave_command()
+        .arg("--control-socket")
+        .arg(socket_path)
+        .arg("keep")
+        .current_dir(tt.path())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stream = loop {
+        if let Ok(stream) = std::os::unix::net::UnixStream::connect(socket_path) {
+            break Some(stream);
+        }
+        if std::fs::exists(socket_path).is_ok_and(|exists| !exists) && tt.contents().len() == 1 {
+            break None;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    };
+    let Some(stream) = stream else {
+        assert!(child.wait_with_output().unwrap().status.success());
+        return None;
+    };
+    Some((child, stream))
+}
+
+/// Sends a command to a control socket and returns the response.
+#[cfg(unix)]
+fn send_control(stream: &mut std::os::unix::net::UnixStream, command: &str) -> String {
+    use std::io::{BufRead, BufReader};
+
+    writeln!(stream, "{command}").unwrap();
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).unwrap();
+    response
+}
+
+/// Tests pausing and cancelling a run through --control-socket, and that a
+/// client is answered while another stays connected. If the run finishes
+/// before the test can connect, there's nothing to check.
+#[cfg(unix)]
+#[test]
+pub fn control_socket() {
+    let tt = TestTree::new(json!({ "keep": null }));
+    let socket_dir = tempfile::tempdir().unwrap();
+    let socket_path = socket_dir.path().join("control");
+    let Some((child, mut stream)) = start_controlled_run(&tt, &socket_path) else {
+        return;
+    };
+    assert_eq!("paused\n", send_control(&mut stream, "pause"));
+    let mut other = std::os::unix::net::UnixStream::connect(&socket_path).unwrap();
+    assert!(send_control(&mut other, "status").starts_with("paused "));
+    assert_eq!("cancelled\n", send_control(&mut stream, "cancel"));
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(1), output.status.code());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.starts_with("Cancelled; "));
+    assert!(tt.contents().len() > 1);
+    assert!(!socket_path.exists());
+}
+
+/// Tests that a run paused through --control-socket can be interrupted. If
+/// the run finishes before the test can connect, there's nothing to check.
+#[cfg(unix)]
+#[test]
+pub fn control_socket_interrupt() {
+    use rustix::process::{Pid, Signal, kill_process};
+
+    let tt = TestTree::new(json!({ "keep": null }));
+    let socket_dir = tempfile::tempdir().unwrap();
+    let socket_path = socket_dir.path().join("control");
+    let Some((child, mut stream)) = start_controlled_run(&tt, &socket_path) else {
+        return;
+    };
+    assert_eq!("paused\n", send_control(&mut stream, "pause"));
+    kill_process(Pid::from_child(&child), Signal::INT).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(130), output.status.code());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.starts_with("Interrupted; "));
+    assert!(tt.contents().len() > 1);
+}
+
+/// Tests that an interrupted run finishes the entry being removed, reports
+/// what wasn't removed, and exits with a distinct code
+#[cfg(unix)]
+#[test]
+pub fn interrupt() {
+    use rustix::process::{Pid, Signal, kill_process};
+
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": null,
+        "file2": null,
+        "file3": null,
+    }));
+    let marker_dir = tempfile::tempdir().unwrap();
+    let marker = marker_dir.path().join("started");
+    let child = leave_command()
+        .args(["--pre-delete-hook", ": > \"$MARKER\"; sleep 1", "keep"])
+        .env("MARKER", &marker)
+        .current_dir(tt.path())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    while !marker.exists() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    kill_process(Pid::from_child(&child), Signal::INT).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(130), output.status.code());
+    assert_eq!(
+        "Interrupted; 1 entries were removed and 2 were not\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert_eq!(3, tt.contents().len());
+}
+
+/// Tests printing what would be removed with -n/--dry-run
+#[test]
+pub fn dry_run() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+        },
+        "dir2": {},
+    }));
+    let expected = tt.contents();
+    let output = run_and_expect(tt.path(), &["-n", "-d", "--quarantine", "file1"], 1);
+    assert_eq!(expected, tt.contents());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort_unstable();
+    assert_eq!(vec!["would remove ./dir2", "would remove ./file2"], lines);
+    assert_eq!(
+        "Error: Can't remove ./dir1: Directory is not empty\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    let output = run_and_expect(tt.path(), &["--dry-run", "-r", "file1"], 0);
+    assert_eq!(expected, tt.contents());
+    assert_eq!(3, str::from_utf8(&output.stdout).unwrap().lines().count());
+}
+
+/// Tests processing entries in order with --sort, and by name by default when
+/// they're listed
+#[test]
+pub fn sort() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "b": { "content": "bbb", "mtime": 1_700_000_030 },
+        "c": { "content": "cc", "mtime": 1_700_000_020 },
+        "a": { "content": "a", "mtime": 1_700_000_010 },
+        "dir": {},
+    }));
+    let removed = |args: &[&str]| {
+        let output = run_and_expect(tt.path(), args, 0);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        "would remove ./a\nwould remove ./b\nwould remove ./c\nwould remove ./dir\n",
+        removed(&["-n", "-d", "keep"])
+    );
+    assert_eq!(
+        "would remove ./b\nwould remove ./c\nwould remove ./a\n",
+        removed(&["-n", "--sort", "size", "keep", "dir"])
+    );
+    assert_eq!(
+        "would remove ./b\nwould remove ./c\nwould remove ./a\n",
+        removed(&["-n", "--sort", "mtime", "keep", "dir"])
+    );
+    assert_eq!(
+        "removed './a'\nremoved './b'\nremoved './c'\n",
+        removed(&["-v", "--sort", "name", "keep", "dir"])
+    );
+    tt.assert_tree(json!({
+        "keep": null,
+        "dir": {},
+    }));
+}
+
+/// Tests listing the entries which would be removed with --list and --print0
+#[test]
+pub fn list() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+        },
+    }));
+    let expected = tt.contents();
+    let output = run_and_expect(tt.path(), &["--list", "-r", "file1"], 0);
+    assert_eq!(expected, tt.contents());
+    assert_eq!("./dir1\n./file2\n", str::from_utf8(&output.stdout).unwrap());
+    assert!(output.stderr.is_empty());
+    let output = run_and_expect(tt.path(), &["--print0", "-r", "file1"], 0);
+    assert_eq!(expected, tt.contents());
+    assert_eq!(b"./dir1\0./file2\0", &output.stdout[..]);
+}
+
+/// Tests printing statistics about what would be removed and kept with --stat
+#[test]
+pub fn stat() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": null,
+        "link": "file1",
+        "dir1": {
+            "file2": null,
+            "dir2": {},
+        },
+    }));
+    std::fs::write(tt.path().join("file1"), "12345").unwrap();
+    std::fs::write(tt.path().join("dir1/file2"), "12").unwrap();
+    let expected = tt.contents();
+    let output = run_and_expect(tt.path(), &["--stat", "-r", "keep", "dir1/dir2"], 0);
+    assert_eq!(expected, tt.contents());
+    assert_eq!(
+        "remove: 2 files, 0 directories, 1 symbolic link, 12 B\n\
+         keep: 1 file, 2 directories, 0 symbolic links, 0 B\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    run_and_expect(tt.path(), &["--stat", "--list", "keep"], 2);
+}
+
+/// Tests asking before each removal with -i/--interactive
+#[test]
+pub fn interactive() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+    }));
+    let output = run_with_input(tt.path(), &["-i", "file1"], "n\nno\n", 0);
+    assert_eq!(set(["file1", "file2", "file3"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(2, stderr.matches("leave: remove ./file").count());
+    assert!(stderr.ends_with("? [y/N/a] "));
+
+    run_with_input(tt.path(), &["-i", "file1"], "y\n\n", 0);
+    assert_eq!(2, tt.contents().len());
+
+    std::fs::File::create(tt.path().join("file4")).unwrap();
+    let output = run_with_input(tt.path(), &["--interactive", "file1"], "all\n", 0);
+    assert_eq!(set(["file1"]), tt.contents());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(1, stderr.matches("leave: remove").count());
+}
+
+/// Tests removing otherwise-kept entries with --exclude
+#[test]
+pub fn exclude() {
+    let tt = TestTree::new(json!({
+        "app.log": null,
+        "debug1.log": null,
+        "debug2.log": null,
+        "trace.log": null,
+        "other": null,
+    }));
+    run_and_expect(
+        tt.path(),
+        &[
+            "-g",
+            "*.log",
+            "--exclude",
+            "debug*.log",
+            "--exclude",
+            "trace.*",
+        ],
+        0,
+    );
+    assert_eq!(set(["app.log"]), tt.contents());
+    run_and_expect(tt.path(), &["app.log", "--exclude", "[a"], 2);
+    assert_eq!(set(["app.log"]), tt.contents());
+}
+
+/// Tests keeping entries whose names match regular expressions with --regex
+#[test]
+pub fn regex() {
+    let tree = json!({
+        "report-2024.pdf": null,
+        "report-24.pdf": null,
+        "notes.txt": null,
+        "Notes.md": null,
+        "a.rs": null,
+        "b.rs": null,
+        "x": null,
+    });
+    let cases: &[(&[&str], &[&str])] = &[
+        (&[r"^report-\d{4}\.pdf$"], &["report-2024.pdf"]),
+        (&[r"\.(rs|md)$", "^x$"], &["a.rs", "b.rs", "Notes.md", "x"]),
+        (&[r"^[a-z]+\.t.{1,2}t$"], &["notes.txt"]),
+        (
+            &[r"^(?:report-)?\d{2}\.", r"^[^a-z]\w*\.\S+"],
+            &["report-24.pdf", "Notes.md"],
+        ),
+        (&["port-2+4"], &["report-24.pdf"]),
+    ];
+    for (patterns, expected) in cases {
+        let tt = TestTree::new(tree.clone());
+        let mut args = vec!["--regex"];
+        args.extend_from_slice(patterns);
+        run_and_expect(tt.path(), &args, 0);
+        assert_eq!(set(*expected), tt.contents(), "patterns: {patterns:?}");
+    }
+
+    // Invalid patterns are rejected before anything is removed
+    let tt = TestTree::new(tree);
+    for pattern in ["(a", "a)", "*a", "a{2,1}", "[a", r"\q"] {
+        run_and_expect(tt.path(), &["--regex", pattern], 2);
+    }
+    assert_eq!(7, tt.contents().len());
+}
+
+/// Tests keeping entries matching glob patterns with -g/--glob
+#[test]
+pub fn glob() {
+    let tt = TestTree::new(json!({
+        "main.rs": null,
+        "lib.rs": null,
+        ".hidden.rs": null,
+        "Cargo.toml": null,
+        "Cargo.lock": null,
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "x[1]": null,
+    }));
+    run_and_expect(
+        tt.path(),
+        &["--hidden", "-g", "*.rs", "./Cargo.*", "file[!2]", "x\\[1]"],
+        0,
+    );
+    assert_eq!(
+        set([
+            "main.rs",
+            "lib.rs",
+            "Cargo.toml",
+            "Cargo.lock",
+            "file1",
+            "file3",
+            "x[1]"
+        ]),
+        tt.contents()
+    );
+
+    // Patterns which match nothing count as missing
+    let output = run_and_expect(tt.path(), &["--glob", "*.rs", "*.py"], 2);
+    assert!(str::from_utf8(&output.stderr).unwrap().starts_with(
+        "Warning: *.py doesn't exist. It looks like a pattern, but it matched no entries.\n"
+    ));
+    run_and_expect(tt.path(), &["--glob", "src/*.rs"], 2);
+    assert_eq!(7, tt.contents().len());
+}
+
+/// Tests moving entries to the freedesktop.org trash with -t/--trash
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+pub fn trash() {
+    let data_home = tempfile::tempdir().unwrap();
+    let trash = data_home.path().join("Trash");
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+        },
+    }));
+    let run = |args: &[&str]| {
+        let status = leave_command()
+            .args(args)
+            .env("XDG_DATA_HOME", data_home.path())
+            .current_dir(tt.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    run(&["--trash", "-r", "file1"]);
+    assert_eq!(set(["file1"]), tt.contents());
+    let trashed: HashSet<String> = std::fs::read_dir(trash.join("files"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(set(["file2", "dir1"]), trashed);
+    let info = std::fs::read_to_string(trash.join("info/file2.trashinfo")).unwrap();
+    let expected_path = format!(
+        "Path={}/file2\n",
+        tt.path().canonicalize().unwrap().display()
+    );
+    assert!(info.starts_with("[Trash Info]\n"));
+    assert!(info.contains(&expected_path));
+    assert!(info.contains("\nDeletionDate="));
+
+    // Name collisions get a numeric suffix
+    std::fs::File::create(tt.path().join("file2")).unwrap();
+    run(&["-t", "file1"]);
+    assert!(trash.join("files/file2.2").exists());
+    assert!(trash.join("info/file2.2.trashinfo").exists());
+
+    // Non-UTF-8 names are kept intact when they collide
+    let name = <std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(b"bad\xff");
+    for _ in 0..2 {
+        std::fs::File::create(tt.path().join(name)).unwrap();
+        run(&["-t", "file1"]);
+    }
+    let mut numbered = name.to_owned();
+    numbered.push(".2");
+    assert!(trash.join("files").join(name).exists());
+    assert!(trash.join("files").join(numbered).exists());
+}
+
+/// Tests that trash-empty purges only what leave trashed, and only once it's
+/// old enough
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+pub fn trash_empty() {
+    let data_home = tempfile::tempdir().unwrap();
+    let state_home = tempfile::tempdir().unwrap();
+    let trash = data_home.path().join("Trash");
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "old": null,
+        "new": null,
+        "restored": null,
+    }));
+    let run = |args: &[&str]| {
+        leave_command()
+            .args(args)
+            .env("XDG_DATA_HOME", data_home.path())
+            .env("XDG_STATE_HOME", state_home.path())
+            .current_dir(tt.path())
+            .output()
+            .unwrap()
+    };
+    assert!(run(&["--trash", "keep"]).status.success());
+    std::fs::write(trash.join("files/other"), "").unwrap();
+    std::fs::rename(trash.join("files/restored"), tt.path().join("restored")).unwrap();
+
+    // Pretend that one entry was trashed long ago
+    let journal_path = state_home.path().join("leave/trash-journal");
+    let journal = std::fs::read_to_string(&journal_path).unwrap();
+    assert_eq!(3, journal.lines().count());
+    let journal: String = journal
+        .lines()
+        .map(|line| {
+            let (_, rest) = line.split_once('\t').unwrap();
+            if rest.contains("/files/old\t") {
+                format!("0\t{rest}\n")
+            } else {
+                format!("{line}\n")
+            }
+        })
+        .collect();
+    std::fs::write(&journal_path, journal).unwrap();
+
+    let output = run(&["trash-empty", "--older-than", "30d"]);
+    assert!(output.status.success());
+    assert_eq!(
+        format!("purged '{}/old'\n", tt.path().display()),
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    assert!(!trash.join("files/old").exists());
+    assert!(!trash.join("info/old.trashinfo").exists());
+    assert!(trash.join("files/new").exists());
+
+    let output = run(&["trash-empty"]);
+    assert!(output.status.success());
+    assert_eq!(
+        format!("purged '{}/new'\n", tt.path().display()),
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    let left: HashSet<String> = std::fs::read_dir(trash.join("files"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(set(["other"]), left);
+    assert_eq!("", std::fs::read_to_string(&journal_path).unwrap());
+    assert_eq!(set(["keep", "restored"]), tt.contents());
+}
+
+/// Tests tidying the state directory with `leave gc`, using a fake btrfs
+/// program to delete snapshots
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+pub fn gc() {
+    let data_home = tempfile::tempdir().unwrap();
+    let state_home = tempfile::tempdir().unwrap();
+    let state_dir = state_home.path().join("leave");
+    let trash = data_home.path().join("Trash");
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "trashed": null,
+        "restored": null,
+        "snapshots": {
+            "old": {},
+            "new": {},
+        },
+    }));
+    let bin = tempfile::tempdir().unwrap();
+    let btrfs = bin.path().join("btrfs");
+    std::fs::write(
+        &btrfs,
+        "#!/bin/sh\n[ \"$1 $2\" = 'subvolume delete' ] && rm -r \"$3\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&btrfs, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let run = |args: &[&str]| {
+        leave_command()
+            .args(args)
+            .env("XDG_DATA_HOME", data_home.path())
+            .env("XDG_STATE_HOME", state_home.path())
+            .env(
+                "PATH",
+                format!(
+                    "{}:{}",
+                    bin.path().display(),
+                    std::env::var("PATH").unwrap()
+                ),
+            )
+            .current_dir(tt.path())
+            .output()
+            .unwrap()
+    };
+
+    // Nothing to tidy yet
+    let output = run(&["gc"]);
+    assert!(output.status.success());
+    assert_eq!(
+        format!("{} doesn't exist\n", state_dir.display()),
+        str::from_utf8(&output.stdout).unwrap()
+    );
+
+    assert!(run(&["--trash", "keep", "snapshots"]).status.success());
+    std::fs::rename(trash.join("files/restored"), tt.path().join("restored")).unwrap();
+    let snapshot = |name: &str| tt.path().join("snapshots").join(name);
+    std::fs::write(
+        state_dir.join("snapshot-journal"),
+        format!(
+            "0\tbtrfs\t{}\n0\tbtrfs\t{}\n{}\tbtrfs\t{}\n",
+            snapshot("gone").display(),
+            snapshot("old").display(),
+            u64::MAX,
+            snapshot("new").display(),
+        ),
+    )
+    .unwrap();
+    std::fs::write(state_dir.join("other-journal.tmp"), "").unwrap();
+
+    let output = run(&["gc", "--older-than", "30d"]);
+    assert!(output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        [
+            "Forgot 1 trashed entries which are no longer in the trash".to_owned(),
+            format!("deleted snapshot '{}'", snapshot("old").display()),
+            "Forgot 1 snapshots which no longer exist".to_owned(),
+            format!(
+                "removed '{}'",
+                state_dir.join("other-journal.tmp").display()
+            ),
+        ],
+        lines[..4]
+    );
+    assert!(lines[4].starts_with(&format!("{} takes ", state_dir.display())));
+    assert_eq!(5, lines.len());
+    assert!(!snapshot("old").exists());
+    assert!(snapshot("new").exists());
+
+    let journal = std::fs::read_to_string(state_dir.join("trash-journal")).unwrap();
+    assert_eq!(1, journal.lines().count());
+    assert!(journal.contains("/files/trashed\t"));
+    let journal = std::fs::read_to_string(state_dir.join("snapshot-journal")).unwrap();
+    assert_eq!(
+        format!("{}\tbtrfs\t{}\n", u64::MAX, snapshot("new").display()),
+        journal
+    );
+}
+
+/// Tests planning and executing removals using the library interface
+#[test]
+pub fn library() {
+    let tt = TestTree::new(json!({
+        "Cargo.toml": null,
+        "target": {
+            "debug": {},
+        },
+        "notes.txt": null,
+    }));
+    let plan = leave::LeaveOptions::new()
+        .keep("Cargo.toml")
+        .recursive(true)
+        .plan(tt.path())
+        .unwrap();
+    let mut to_remove: Vec<PathBuf> = plan.to_remove().collect();
+    to_remove.sort();
+    assert_eq!(
+        vec![PathBuf::from("./notes.txt"), PathBuf::from("./target")],
+        to_remove
+    );
+    let to_keep: Vec<(PathBuf, &str)> = plan.to_keep().collect();
+    assert_eq!(
+        vec![(PathBuf::from("./Cargo.toml"), "listed as an argument")],
+        to_keep
+    );
+    assert_eq!(set(["Cargo.toml", "target", "notes.txt"]), tt.contents());
+    leave::execute(plan).unwrap();
+    assert_eq!(set(["Cargo.toml"]), tt.contents());
+}
+
+/// Tests that plans made through the library hold on to their directory, so
+/// they can be executed in any order without changing the current directory
+#[test]
+pub fn library_plans_in_reverse() {
+    let first = TestTree::new(json!({
+        "keep.txt": null,
+        "remove.txt": null,
+    }));
+    let second = TestTree::new(json!({
+        "keep.txt": null,
+        "other.txt": null,
+    }));
+    let cwd = std::env::current_dir().unwrap();
+    let first_plan = leave::LeaveOptions::new()
+        .keep("keep.txt")
+        .plan(first.path())
+        .unwrap();
+    let second_plan = leave::LeaveOptions::new()
+        .keep("keep.txt")
+        .plan(second.path())
+        .unwrap();
+    assert_eq!(cwd, std::env::current_dir().unwrap());
+    assert_eq!(
+        vec![PathBuf::from("./remove.txt")],
+        first_plan.to_remove().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![PathBuf::from("./other.txt")],
+        second_plan.to_remove().collect::<Vec<_>>()
+    );
+    leave::execute(second_plan).unwrap();
+    assert_eq!(set(["keep.txt", "remove.txt"]), first.contents());
+    assert_eq!(set(["keep.txt"]), second.contents());
+    leave::execute(first_plan).unwrap();
+    assert_eq!(set(["keep.txt"]), first.contents());
+    assert_eq!(cwd, std::env::current_dir().unwrap());
+}
+
+/// Tests that a plan cleans the directory it was made for, through the handle
+/// it opened, even if another directory has taken its place since
+#[test]
+pub fn library_plan_follows_moved_dir() {
+    let tt = TestTree::new(json!({
+        "dir": {
+            "keep.txt": null,
+            "remove.txt": null,
+            "subdir": {
+                "file": null,
+            },
+        },
+    }));
+    let dir = tt.path().join("dir");
+    let plan = leave::LeaveOptions::new()
+        .keep("keep.txt")
+        .recursive(true)
+        .plan(&dir)
+        .unwrap();
+    let moved = tt.path().join("moved");
+    std::fs::rename(&dir, &moved).unwrap();
+    std::fs::create_dir_all(dir.join("subdir")).unwrap();
+    std::fs::write(dir.join("remove.txt"), "").unwrap();
+    std::fs::write(dir.join("subdir/file"), "").unwrap();
+    leave::execute(plan).unwrap();
+    assert!(moved.join("keep.txt").exists());
+    assert!(!moved.join("remove.txt").exists());
+    assert!(!moved.join("subdir").exists());
+    assert!(dir.join("remove.txt").exists());
+    assert!(dir.join("subdir/file").exists());
+}
+
+/// Tests that --confirm refuses to run without a terminal to ask on
+#[test]
+pub fn confirm() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    let output = run_with_input(tt.path(), &["--confirm", "file1"], "y\n", 1);
+    assert_eq!(
+        "Error: --confirm needs a terminal to ask for confirmation; standard input isn't one\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert_eq!(set(["file1", "file2"]), tt.contents());
+    run_and_expect(tt.path(), &["--confirm", "-n", "file1"], 2);
+}
+
+/// Tests --confirm --dialog using a fake zenity which answers with the exit
+/// status given in the tree
+#[cfg(unix)]
+#[test]
+pub fn confirm_dialog() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    let bin = tempfile::tempdir().unwrap();
+    let zenity = bin.path().join("zenity");
+    std::fs::write(
+        &zenity,
+        "#!/bin/sh\nprintf '%s\\n' \"$@\" > ../args\nexit $(cat ../answer)\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&zenity, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let path = format!("{}:/bin:/usr/bin", bin.path().display());
+    let run = |answer: &str| {
+        let work = tt.path().join("work");
+        std::fs::create_dir_all(&work).unwrap();
+        std::fs::write(tt.path().join("answer"), answer).unwrap();
+        for name in ["file1", "file2"] {
+            std::fs::File::create(work.join(name)).unwrap();
+        }
+        let output = leave_command()
+            .args(["--confirm", "--dialog", "file1"])
+            .env("PATH", &path)
+            .env("DISPLAY", ":0")
+            .stdin(Stdio::null())
+            .current_dir(&work)
+            .output()
+            .unwrap();
+        let mut contents: Vec<String> = std::fs::read_dir(&work)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        contents.sort_unstable();
+        (output, contents)
+    };
+
+    // Cancelling keeps everything
+    let (output, contents) = run("1");
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(["file1", "file2"], contents.as_slice());
+    let args = std::fs::read_to_string(tt.path().join("args")).unwrap();
+    assert!(args.starts_with("--question\n"));
+    assert!(args.ends_with("1 entries (0 B) will be removed and 1 kept.\n\nContinue?\n"));
+
+    let (output, contents) = run("0");
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(["file1"], contents.as_slice());
+
+    // Without a display, no dialog can be shown
+    let output = leave_command()
+        .args(["--confirm", "--dialog", "file1"])
+        .env("PATH", &path)
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .stdin(Stdio::null())
+        .current_dir(tt.path().join("work"))
+        .output()
+        .unwrap();
+    assert_eq!(Some(1), output.status.code());
+    assert!(str::from_utf8(&output.stderr).unwrap().ends_with(
+        "Error: --dialog needs zenity or kdialog and a graphical display to ask for confirmation\n"
+    ));
+    run_and_expect(tt.path(), &["--dialog", "file1"], 2);
+}
+
+/// Tests that --pick refuses to run without a terminal to show the picker on
+#[test]
+pub fn pick() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    let output = run_with_input(tt.path(), &["--pick", "file1"], "\n", 1);
+    assert_eq!(
+        "Error: --pick needs a terminal to show the entries; standard input or error isn't one\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert_eq!(set(["file1", "file2"]), tt.contents());
+    run_and_expect(tt.path(), &["--pick", "--confirm", "file1"], 2);
+}
+
+/// Tests reporting events as JSON with --output json
+#[test]
+pub fn output_json() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": null,
+        "dir1": {},
+    }));
+    let output = run_and_expect(tt.path(), &["--output", "json", "-v", "keep"], 1);
+    let mut stdout: Vec<&str> = str::from_utf8(&output.stdout).unwrap().lines().collect();
+    stdout.sort_unstable();
+    assert_eq!(
+        vec![
+            r#"{"action":"error","path":"./dir1","type":"dir","reason":"Is a directory"}"#,
+            r#"{"action":"removed","path":"./file1","type":"file"}"#,
+            r#"{"action":"skipped","path":"./keep","type":"file","reason":"listed as an argument"}"#,
+        ],
+        stdout
+    );
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.contains("Error: Can't remove ./dir1: Is a directory\n"));
+    assert!(stderr.contains("removed './file1'\n"));
+
+    let output = run_and_expect(tt.path(), &["--output", "json", "missing"], 2);
+    assert_eq!(
+        r#"{"action":"error","reason":"None of the provided files exist, so everything would be removed. This is likely a mistake. To continue anyways, use -f/--force."}"#.to_owned() + "\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    assert_eq!(set(["keep", "dir1"]), tt.contents());
+}
+
+/// Tests running commands before removing each entry and after the run
+#[cfg(unix)]
+#[test]
+pub fn hooks() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1.txt": null,
+        "file2.log": null,
+        "file3.log": null,
+    }));
+    run_and_expect(
+        tt.path(),
+        &[
+            "--pre-delete-hook",
+            r#"case "$1" in *.log) [ "$LEAVE_PATH" = ./file3.log ];; esac"#,
+            "--post-run-hook",
+            r#"echo "$LEAVE_REMOVED $LEAVE_KEPT $LEAVE_STATUS" > stats"#,
+            "keep",
+        ],
+        0,
+    );
+    assert_eq!(set(["keep", "file2.log", "stats"]), tt.contents());
+    assert_eq!(
+        "2 2 success\n",
+        std::fs::read_to_string(tt.path().join("stats")).unwrap()
+    );
+
+    let output = run_and_expect(tt.path(), &["--post-run-hook", "exit 3", "-n", "keep"], 1);
+    assert_eq!(
+        "Error: Post-run hook \"exit 3\" failed with exit status: 3\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+}
+
+/// Tests printing each removed entry with -v/--verbose
+#[test]
+pub fn verbose() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+            "dir2": {
+                "file4": null,
+            },
+        },
+    }));
+    let output = run_and_expect(tt.path(), &["-v", "-r", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert_eq!(
+        set([
+            "removed './file2'",
+            "removed './dir1/file3'",
+            "removed './dir1/dir2/file4'",
+            "removed directory './dir1/dir2'",
+            "removed directory './dir1'",
+        ]),
+        set(stdout.lines())
+    );
+    // Contents are printed before the directories containing them
+    let position = |line: &str| stdout.find(line).unwrap();
+    assert!(position("'./dir1/dir2/file4'") < position("'./dir1/dir2'\n"));
+    assert!(position("'./dir1/dir2'\n") < position("'./dir1'\n"));
+}
+
+/// Tests keeping entries listed in .leavekeep and --keep-file
+#[test]
+pub fn keep_files() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "dir1": {},
+    }));
+    std::fs::write(
+        tt.path().join(".leavekeep"),
+        "# Always keep these\nfile1\n\n  dir1  \nnonexistent\n",
+    )
+    .unwrap();
+    let other_dir = tempfile::tempdir().unwrap();
+    let other_keep_file = other_dir.path().join("shared");
+    std::fs::write(&other_keep_file, "file2\n").unwrap();
+
+    // No arguments are needed when a keep file exists
+    let output = run_and_expect(tt.path(), &["-n"], 0);
+    assert_eq!(
+        set(["would remove ./file2", "would remove ./file3"]),
+        set(str::from_utf8(&output.stdout).unwrap().lines())
+    );
+    run_and_expect(
+        tt.path(),
+        &["--keep-file", other_keep_file.to_str().unwrap()],
+        0,
+    );
+    assert_eq!(set([".leavekeep", "file1", "file2", "dir1"]), tt.contents());
+
+    let output = run_and_expect(tt.path(), &["why", "dir1"], 0);
+    assert_eq!(
+        "dir1 would be kept: listed in a keep file\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    run_and_expect(tt.path(), &["--keep-file", "missing", "file1"], 1);
+}
+
+/// Tests include directives in keep files
+#[test]
+pub fn keep_file_includes() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "file4": null,
+        "include file5": null,
+        "file6": null,
+        "shared.leavekeep": null,
+    }));
+    let shared = tempfile::tempdir().unwrap();
+    let absolute = shared.path().join("absolute");
+    std::fs::write(&absolute, "file2\ninclude nested\n").unwrap();
+    std::fs::write(shared.path().join("nested"), "file3\n").unwrap();
+    std::fs::write(tt.path().join("shared.leavekeep"), "file1\n").unwrap();
+    std::fs::write(
+        tt.path().join(".leavekeep"),
+        format!(
+            "include shared.leavekeep\ninclude {}\ninclude ~/home\n./include file5\n",
+            absolute.display()
+        ),
+    )
+    .unwrap();
+    let home = tempfile::tempdir().unwrap();
+    std::fs::write(home.path().join("home"), "file4\n").unwrap();
+
+    let output = leave_command()
+        .current_dir(tt.path())
+        .env("HOME", home.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        set([
+            ".leavekeep",
+            "file1",
+            "file2",
+            "file3",
+            "file4",
+            "include file5",
+            "shared.leavekeep",
+        ]),
+        tt.contents()
+    );
+
+    // Missing includes are reported with where they're included
+    std::fs::write(tt.path().join(".leavekeep"), "file1\ninclude missing\n").unwrap();
+    let output = run_and_expect(tt.path(), &["-n"], 1);
+    assert_eq!(
+        "Error: missing, included at .leavekeep:2, doesn't exist\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    // Cycles are refused
+    std::fs::write(tt.path().join(".leavekeep"), "include shared.leavekeep\n").unwrap();
+    std::fs::write(tt.path().join("shared.leavekeep"), "include .leavekeep\n").unwrap();
+    let output = run_and_expect(tt.path(), &["-n"], 1);
+    assert!(
+        str::from_utf8(&output.stderr)
+            .unwrap()
+            .starts_with("Error: Keep files include each other: ")
+    );
+}
+
+/// Tests keeping entries with a --rules file
+#[test]
+pub fn rules() {
+    let tt = TestTree::new(json!({
+        "notes.txt": null,
+        "draft.txt": null,
+        "build.log": null,
+        "src": {
+            "main.rs": null,
+            "main.o": null,
+        },
+        "target": {
+            "debug": {},
+        },
+    }));
+    let other_dir = tempfile::tempdir().unwrap();
+    let rules = other_dir.path().join("rules");
+    std::fs::write(
+        &rules,
+        "# Keep text and sources
+*.txt
+!draft.txt
+src/*.rs
+target/
+",
+    )
+    .unwrap();
+    let output = run_and_expect(
+        tt.path(),
+        &["-r", "--explain", "--rules", rules.to_str().unwrap()],
+        0,
+    );
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains("./notes.txt: kept: matches rule '*.txt'"));
+    assert!(stdout.contains("./target: kept: matches rule 'target/'"));
+    assert_eq!(set(["notes.txt", "src", "target"]), tt.contents());
+    assert!(tt.path().join("src/main.rs").exists());
+    assert!(!tt.path().join("src/main.o").exists());
+
+    std::fs::write(
+        &rules,
+        "[unclosed
+",
+    )
+    .unwrap();
+    run_and_expect(tt.path(), &["--rules", rules.to_str().unwrap()], 1);
+}
+
+/// Tests reading files to keep with --files-from and -0/--null
+#[test]
+pub fn files_from() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+        "file\nwith newline": null,
+    }));
+    run_with_input(
+        tt.path(),
+        &["--files-from", "-", "-0", "file1"],
+        "file\nwith newline\0file2\0",
+        0,
+    );
+    assert_eq!(set(["file1", "file2", "file\nwith newline"]), tt.contents());
+
+    std::fs::write(tt.path().join("list"), "list\nfile1\n\n").unwrap();
+    run_and_expect(tt.path(), &["--files-from", "list"], 0);
+    assert_eq!(set(["file1", "list"]), tt.contents());
+
+    // Listed paths are checked for existence like arguments
+    run_with_input(tt.path(), &["--files-from", "-", "file1"], "file9\n", 2);
+    assert_eq!(set(["file1", "list"]), tt.contents());
+}
+
+/// Tests refusing to remove more entries than --max-delete allows
+#[test]
+pub fn max_delete() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+    }));
+    let output = run_and_expect(tt.path(), &["--max-delete", "1", "file1"], 3);
+    assert_eq!(set(["file1", "file2", "file3"]), tt.contents());
+    assert_eq!(
+        "Error: 2 entries would be removed, which is more than the limit of 1 set by --max-delete\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    run_and_expect(tt.path(), &["--max-delete", "2", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests refusing to run in protected directories
+#[test]
+pub fn protected_dirs() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    let run = |args: &[&str], env: (&str, &Path)| {
+        leave_command()
+            .args(args)
+            .env(env.0, env.1)
+            .current_dir(tt.path())
+            .output()
+            .unwrap()
+    };
+    let dummy = Path::new("/nonexistent");
+    for (args, env) in [
+        (&["file1"][..], ("HOME", tt.path())),
+        (&["file1"][..], ("LEAVE_PROTECT", tt.path())),
+        (&["--protect", ".", "file1"][..], ("LEAVE_PROTECT", dummy)),
+    ] {
+        let output = run(args, env);
+        assert_eq!(Some(3), output.status.code());
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+        assert!(stderr.starts_with("Error: Refusing to operate in "));
+        assert!(stderr.ends_with("which is protected. Use --no-preserve-root to override.\n"));
+        assert_eq!(set(["file1", "file2"]), tt.contents());
+    }
+
+    // Dry runs aren't guarded, so this only fails because the argument is
+    // missing
+    let output = run(&["-C", "/", "-n", "nonexistent"], ("HOME", dummy));
+    assert_eq!(Some(2), output.status.code());
+    assert!(!str::from_utf8(&output.stderr).unwrap().contains("Refusing"));
+    let output = run(&["-C", "/", "nonexistent"], ("HOME", dummy));
+    assert_eq!(
+        "Error: Refusing to operate in the root directory. Use --no-preserve-root to override.\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    let output = run(&["--no-preserve-root", "file1"], ("HOME", tt.path()));
+    assert!(output.status.success());
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests keeping files tracked by Git with --git
+#[test]
+pub fn git() {
+    let tt = TestTree::new(json!({
+        "tracked": null,
+        "untracked": null,
+        "src": {
+            "main.rs": null,
+        },
+        "target": {
+            "debug": null,
+        },
+    }));
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(tt.path())
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "--quiet"]);
+    git(&["add", "tracked", "src/main.rs"]);
+
+    let output = run_and_expect(tt.path(), &["why", "src", "--git"], 0);
+    assert_eq!(
+        "src would be kept: tracked by git\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    run_and_expect(tt.path(), &["--git", "-r"], 0);
+    assert_eq!(set([".git", "tracked", "src"]), tt.contents());
+}
+
+/// Tests deleting only entries ignored by Git with --ignored-only
+#[test]
+pub fn ignored_only() {
+    let tt = TestTree::new(json!({
+        ".gitignore": null,
+        "main.rs": null,
+        "debug.log": null,
+        "target": {
+            "debug": null,
+        },
+        "build": {
+            "out.o": null,
+            "notes.txt": null,
+        },
+    }));
+    std::fs::write(tt.path().join(".gitignore"), "*.log\n/target\n*.o\n").unwrap();
+    let status = Command::new("git")
+        .args(["init", "--quiet"])
+        .current_dir(tt.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    run_and_expect(tt.path(), &["--ignored-only", "-r"], 0);
+    // build contains a file which isn't ignored, so it's kept as a whole
+    assert_eq!(
+        set([".git", ".gitignore", "main.rs", "build"]),
+        tt.contents()
+    );
+    assert!(tt.path().join("build/out.o").exists());
+}
+
+/// Tests removing entries on multiple threads with -j/--jobs
+#[test]
+pub fn jobs() {
+    let mut tree = serde_json::Map::new();
+    for i in 0..200 {
+        tree.insert(format!("file{i}"), serde_json::Value::Null);
+    }
+    tree.insert("dir1".into(), json!({ "file": null }));
+    let tt = TestTree::new(serde_json::Value::Object(tree));
+
+    // Failures are still reported in the exit code
+    let output = run_and_expect(tt.path(), &["--jobs", "4", "file0"], 1);
+    assert_eq!(set(["file0", "dir1"]), tt.contents());
+    assert_eq!(
+        "Error: Can't remove ./dir1: Is a directory\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    run_and_expect(tt.path(), &["-j", "4", "-r", "--quarantine", "file0"], 0);
+    assert_eq!(2, tt.contents().len());
+    run_and_expect(tt.path(), &["-j", "0", "file0"], 2);
+}
+
+/// Tests reading default options from a configuration file
+#[test]
+pub fn config() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "README.md": null,
+        ".hidden": null,
+        "dir": {
+            "file": null,
+        },
+    }));
+    let config = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        config.path(),
+        "# Defaults\nrecursive = true\nhidden = true\nkeep = [\"*.md\", 'other'] # trailing\n",
+    )
+    .unwrap();
+    let leave = |args: &[&str]| {
+        leave_command()
+            .args(args)
+            .current_dir(tt.path())
+            .env("LEAVE_CONFIG", config.path())
+            .output()
+            .unwrap()
+    };
+
+    // Options given on the command line win over the configuration
+    let output = leave(&["--keep-hidden", "--dry-run", "keep"]);
+    assert!(output.status.success());
+    assert_eq!(
+        "would remove ./dir\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    // Single settings can be turned off, and the last flag given wins
+    let not_recursive = "Error: Can't remove ./dir: Is a directory\n";
+    let output = leave(&["--no-recursive", "--keep-hidden", "--dry-run", "keep"]);
+    assert_eq!(not_recursive, str::from_utf8(&output.stderr).unwrap());
+    let output = leave(&["-r", "--no-recursive", "--keep-hidden", "--dry-run", "keep"]);
+    assert_eq!(not_recursive, str::from_utf8(&output.stderr).unwrap());
+    let output = leave(&["--no-recursive", "-r", "--keep-hidden", "--dry-run", "keep"]);
+    assert!(output.status.success());
+    assert_eq!(
+        "would remove ./dir\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    let output = leave(&["--no-config", "keep"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(set(["keep", ".hidden", "dir"]), tt.contents());
+    std::fs::write(tt.path().join("README.md"), "").unwrap();
+    let output = leave(&["keep"]);
+    assert!(output.status.success());
+    assert_eq!(set(["keep", "README.md"]), tt.contents());
+
+    std::fs::write(config.path(), "recursive = true\ncolour = 'never'\n").unwrap();
+    let output = leave(&["keep"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        format!(
+            "Error: {}:2: Unknown setting colour\n",
+            config.path().display()
+        ),
+        str::from_utf8(&output.stderr).unwrap()
+    );
+}
+
+/// Tests coloring markers with a theme from the configuration file
+#[test]
+pub fn config_theme() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file": null,
+    }));
+    let config = tempfile::NamedTempFile::new().unwrap();
+    let leave = |args: &[&str]| {
+        leave_command()
+            .args(args)
+            .current_dir(tt.path())
+            .env("LEAVE_CONFIG", config.path())
+            .output()
+            .unwrap()
+    };
+
+    // The default theme
+    std::fs::write(config.path(), "").unwrap();
+    let output = leave(&["--color=always", "-n", "keep"]);
+    assert_eq!(
+        "\x1b[33mwould remove\x1b[0m ./file\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    let output = leave(&["--color=never", "-n", "keep"]);
+    assert_eq!(
+        "would remove ./file\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+
+    // Single markers can be changed on top of a preset, in either order
+    std::fs::write(
+        config.path(),
+        "theme-kept = 'bold #00ff00'\ntheme = 'colorblind'\n",
+    )
+    .unwrap();
+    let output = leave(&["--color=always", "-n", "--explain", "keep"]);
+    assert_eq!(
+        "./file: \x1b[38;5;208mdeleted: no rule matched\x1b[0m\n\
+         ./keep: \x1b[1;38;2;0;255;0mkept: listed as an argument\x1b[0m\n\
+         \x1b[3;38;5;220mwould remove\x1b[0m ./file\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    let output = leave(&["--color=always", "-v", "keep"]);
+    assert_eq!(
+        "\x1b[38;5;208mremoved\x1b[0m './file'\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    assert_eq!(set(["keep"]), tt.contents());
+
+    std::fs::write(config.path(), "theme-error = 'purple'\n").unwrap();
+    let output = leave(&["keep"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        format!(
+            "Error: {}:1: Invalid style \"purple\"\n",
+            config.path().display()
+        ),
+        str::from_utf8(&output.stderr).unwrap()
+    );
+}
+
+/// Tests expanding environment variables in configured keep patterns and keep
+/// files, and that undefined ones are errors
+#[test]
+pub fn env_vars_in_keep_lists() {
+    let tt = TestTree::new(json!({
+        "web1-access.log": null,
+        "web2-access.log": null,
+        "web1.conf": null,
+        "web2.conf": null,
+        "${literal}": null,
+    }));
+    let config = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(config.path(), "keep = ['${KEEP_HOST}-*.log']\n").unwrap();
+    std::fs::write(
+        tt.path().join(".leavekeep"),
+        "${KEEP_HOST}.conf\n$${literal}\n",
+    )
+    .unwrap();
+    let leave = |vars: &[(&str, &str)]| {
+        leave_command()
+            .arg("--dry-run")
+            .current_dir(tt.path())
+            .env("LEAVE_CONFIG", config.path())
+            .env_remove("KEEP_HOST")
+            .envs(vars.iter().copied())
+            .output()
+            .unwrap()
+    };
+
+    let output = leave(&[("KEEP_HOST", "web1")]);
+    assert!(output.status.success());
+    assert_eq!(
+        set(["would remove ./web2-access.log", "would remove ./web2.conf"]),
+        set(str::from_utf8(&output.stdout).unwrap().lines())
+    );
+
+    let output = leave(&[]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        format!(
+            "Error: {}:1: Undefined variable ${{KEEP_HOST}}\n",
+            config.path().display()
+        ),
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    std::fs::write(config.path(), "").unwrap();
+    let output = leave(&[]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Error: .leavekeep:1: Undefined variable ${KEEP_HOST}\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+}
+
+/// Tests that messages are translated into the language of the locale, and
+/// that output meant for scripts isn't
+#[test]
+pub fn translated_messages() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file": null,
+    }));
+    let leave = |locale: &[(&str, &str)], args: &[&str]| {
+        leave_command()
+            .args(args)
+            .current_dir(tt.path())
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .env_remove("LANG")
+            .envs(locale.iter().copied())
+            .output()
+            .unwrap()
+    };
+
+    let output = leave(&[("LANG", "de_DE.UTF-8")], &["missing"]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!(
+        "Warnung: missing existiert nicht.\n\
+         Fehler: Keine der angegebenen Dateien existiert, also würde alles entfernt. \
+         Das ist wahrscheinlich ein Versehen. Um trotzdem fortzufahren, verwende -f/--force.\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    // LC_ALL takes precedence, and unknown languages fall back to English
+    let output = leave(
+        &[("LANG", "de_DE.UTF-8"), ("LC_ALL", "xx_XX")],
+        &["missing"],
+    );
+    assert_eq!(Some(2), output.status.code());
+    assert!(
+        str::from_utf8(&output.stderr)
+            .unwrap()
+            .starts_with("Warning: missing doesn't exist.\n")
+    );
+
+    let output = leave(&[("LC_MESSAGES", "de")], &["--dry-run", "keep"]);
+    assert!(output.status.success());
+    assert_eq!(
+        "would remove ./file\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+}
+
+/// Tests keeping the files of a type of project with --preset, and changing
+/// presets in the configuration file
+#[test]
+pub fn preset() {
+    let tt = TestTree::new(json!({
+        "Cargo.toml": null,
+        "Cargo.lock": null,
+        "README.md": null,
+        "src": {
+            "main.rs": null,
+        },
+        "target": {
+            "debug": {},
+        },
+        "notes.txt": null,
+        "package.json": null,
+    }));
+    let config = tempfile::NamedTempFile::new().unwrap();
+    let leave = |args: &[&str]| {
+        leave_command()
+            .args(args)
+            .current_dir(tt.path())
+            .env("LEAVE_CONFIG", config.path())
+            .output()
+            .unwrap()
+    };
+
+    let output = leave(&["--preset", "go"]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!(
+        "Error: Unknown preset go; the built-in presets are latex, node, python, rust\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    // Configured presets can extend the built-in ones
+    std::fs::write(config.path(), "preset-rust = ['...', '*.txt']\n").unwrap();
+    let output = leave(&["-r", "--preset", "rust"]);
+    assert!(output.status.success());
+    assert_eq!(
+        set(["Cargo.toml", "Cargo.lock", "README.md", "src", "notes.txt",]),
+        tt.contents()
+    );
+
+    // Or replace them
+    std::fs::write(config.path(), "preset_rust = ['src']\n").unwrap();
+    std::fs::write(tt.path().join("package.json"), "").unwrap();
+    let output = leave(&["-r", "--preset", "rust", "--preset", "node"]);
+    assert!(output.status.success());
+    assert_eq!(set(["README.md", "src", "package.json"]), tt.contents());
+}
+
+/// Tests keeping a directory clean as entries appear with --watch
+#[test]
+pub fn watch() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "a.pdf": null,
+        "junk": null,
+    }));
+    let mut child = leave_command()
+        .args(["--watch", "--glob", "keep", "*.pdf"])
+        .current_dir(tt.path())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    // Waits until the watcher leaves the tree with the given contents
+    let wait_for = |expected: HashSet<String>| {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while tt.contents() != expected && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        assert_eq!(expected, tt.contents());
+    };
+    let result = std::panic::catch_unwind(|| {
+        wait_for(set(["keep", "a.pdf"]));
+        // New entries matching a glob pattern are kept too
+        std::fs::write(tt.path().join("b.pdf"), "").unwrap();
+        std::fs::write(tt.path().join("new"), "").unwrap();
+        wait_for(set(["keep", "a.pdf", "b.pdf"]));
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    result.unwrap();
+
+    run_and_expect(tt.path(), &["--debounce", "1s", "keep"], 2);
+    run_and_expect(tt.path(), &["--watch", "--dry-run", "keep"], 2);
+}
+
+/// Tests warning about kept links whose targets would be removed, and keeping
+/// the targets with --keep-link-targets
+#[cfg(unix)]
+#[test]
+pub fn keep_link_targets() {
+    use std::os::unix::fs::symlink;
+
+    let tt = TestTree::new(json!({
+        "dir1": {
+            "file1": null,
+        },
+        "file2": null,
+        "file3": null,
+    }));
+    symlink("file2", tt.path().join("link1")).unwrap();
+    symlink("dir1/file1", tt.path().join("link2")).unwrap();
+    let output = run_and_expect(tt.path(), &["-n", "-r", "link1", "link2"], 0);
+    assert_eq!(
+        "Warning: Keeping link1, but its target file2 would be removed. Use --keep-link-targets to keep it too.\n\
+         Warning: Keeping link2, but its target dir1/file1 would be removed. Use --keep-link-targets to keep it too.\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+
+    run_and_expect(
+        tt.path(),
+        &["-r", "--keep-link-targets", "link1", "link2"],
+        0,
+    );
+    assert_eq!(set(["dir1", "file2", "link1", "link2"]), tt.contents());
+    assert!(tt.path().join("dir1/file1").exists());
+}
+
+/// Tests --keep-symlink-targets and --follow-symlinks
+#[cfg(unix)]
+#[test]
+pub fn symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let tt = TestTree::new(json!({
+        "keep": {
+            "file1": null,
+        },
+        "file2": null,
+    }));
+    symlink("keep", tt.path().join("link1")).unwrap();
+    symlink("keep/file1", tt.path().join("link2")).unwrap();
+    symlink("file2", tt.path().join("link3")).unwrap();
+    symlink("missing", tt.path().join("link4")).unwrap();
+    run_and_expect(tt.path(), &["--keep-symlink-targets", "keep"], 0);
+    assert_eq!(set(["keep", "link1", "link2"]), tt.contents());
+
+    // Links are removed as they are by default
+    run_and_expect(tt.path(), &["-r", "keep", "link2"], 0);
+    assert_eq!(set(["keep", "link2"]), tt.contents());
+    assert!(tt.path().join("keep/file1").exists());
+
+    symlink("keep", tt.path().join("link1")).unwrap();
+    let output = run_and_expect(tt.path(), &["--follow-symlinks", "keep"], 1);
+    assert_eq!(
+        "Error: Can't remove ./link1: Is a directory\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    run_and_expect(tt.path(), &["-r", "--follow-symlinks", "keep"], 0);
+    assert_eq!(set(["keep"]), tt.contents());
+    assert_eq!(
+        0,
+        std::fs::read_dir(tt.path().join("keep")).unwrap().count()
+    );
+}
+
+/// Tests keeping hard links to kept files with --keep-hardlinks
+#[cfg(unix)]
+#[test]
+pub fn keep_hardlinks() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "dir1": {
+            "file3": null,
+        },
+    }));
+    std::fs::hard_link(tt.path().join("file1"), tt.path().join("link1")).unwrap();
+    std::fs::hard_link(tt.path().join("dir1/file3"), tt.path().join("link3")).unwrap();
+    std::fs::hard_link(tt.path().join("file2"), tt.path().join("link2")).unwrap();
+    let output = run_and_expect(
+        tt.path(),
+        &["--keep-hardlinks", "-r", "-v", "file1", "dir1/file3"],
+        0,
+    );
+    assert_eq!(
+        set(["removed './file2'", "removed './link2'"]),
+        set(str::from_utf8(&output.stdout).unwrap().lines())
+    );
+    assert_eq!(set(["file1", "link1", "dir1", "link3"]), tt.contents());
+
+    run_and_expect(tt.path(), &["-r", "file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests printing the commands a dry run would run with --output sh
+#[cfg(unix)]
+#[test]
+pub fn output_sh() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "-n": null,
+        "it's": null,
+        "dir 1": {
+            "file": null,
+        },
+        "dir2": {},
+    }));
+    let output = run_and_expect(tt.path(), &["-n", "--output", "sh", "-r", "keep"], 0);
+    let script = str::from_utf8(&output.stdout).unwrap();
+    assert_eq!(
+        set([
+            "rm -- ./-n",
+            r"rm -- './it'\''s'",
+            "rm -r -- './dir 1'",
+            "rm -r -- ./dir2",
+        ]),
+        set(script.lines())
+    );
+    let output = run_and_expect(tt.path(), &["-n", "--output", "sh", "-d", "keep"], 1);
+    assert!(
+        str::from_utf8(&output.stdout)
+            .unwrap()
+            .contains("rmdir -- ./dir2\n")
+    );
+    assert_eq!(5, tt.contents().len());
+
+    // The script does what leave would have done
+    let status = Command::new("sh")
+        .args(["-c", script])
+        .current_dir(tt.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(set(["keep"]), tt.contents());
+
+    run_and_expect(tt.path(), &["--output", "sh", "keep"], 2);
+    run_and_expect(tt.path(), &["-n", "--output", "sh", "--trash", "keep"], 2);
+}
+
+/// Tests suppressing warnings with -q/--quiet and --no-warnings
+#[test]
+pub fn quiet() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2.part": null,
+        "file3": null,
+    }));
+    let output = run_and_expect(tt.path(), &["file1", "missing"], 2);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.starts_with("Warning: missing doesn't exist.\n"));
+    // Errors are still printed
+    let output = run_and_expect(tt.path(), &["-q", "file1", "missing"], 2);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.starts_with("Error: "));
+    assert!(!stderr.contains("Warning"));
+
+    let output = run_and_expect(tt.path(), &["--no-warnings", "file1"], 0);
+    assert_eq!("", str::from_utf8(&output.stderr).unwrap());
+    assert_eq!(set(["file1", "file2.part"]), tt.contents());
+}
+
+/// Tests colored, multi-line errors and warnings with --color
+#[test]
+pub fn color() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "dir1": {},
+    }));
+    let output = run_and_expect(tt.path(), &["--color", "always", "file1"], 1);
+    assert_eq!(
+        "\x1b[1;31mError:\x1b[0m \x1b[1mCan't remove ./dir1\x1b[0m\n  \x1b[2mcaused by:\x1b[0m Is a directory\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    let output = run_and_expect(
+        tt.path(),
+        &["--color", "always", "-n", "file1", "missing"],
+        2,
+    );
+    assert!(
+        str::from_utf8(&output.stderr)
+            .unwrap()
+            .starts_with("\x1b[1;33mWarning:\x1b[0m missing doesn't exist.\n")
+    );
+
+    // Output which isn't a terminal isn't colored by default
+    for args in [&["file1"][..], &["--color", "never", "file1"]] {
+        let output = run_and_expect(tt.path(), args, 1);
+        assert_eq!(
+            "Error: Can't remove ./dir1: Is a directory\n",
+            str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+}
+
+/// Tests keeping entries by owner or group with --keep-owned-by
+#[cfg(unix)]
+#[test]
+pub fn keep_owned_by() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+    }));
+    let metadata = std::fs::metadata(tt.path().join("file2")).unwrap();
+    let uid = metadata.uid().to_string();
+    let gid = format!(":{}", metadata.gid());
+    let output = Command::new("id").arg("-un").output().unwrap();
+    let user = str::from_utf8(&output.stdout).unwrap().trim();
+    for (owner, reason) in [
+        (uid.as_str(), format!("user {uid}")),
+        (&gid, format!("group {}", &gid[1..])),
+        (user, format!("user {user}")),
+    ] {
+        let output = run_and_expect(
+            tt.path(),
+            &["why", "file2", "--keep-owned-by", owner, "file1"],
+            0,
+        );
+        assert_eq!(
+            format!("file2 would be kept: owned by {reason}\n"),
+            str::from_utf8(&output.stdout).unwrap()
+        );
+    }
+
+    let output = run_and_expect(tt.path(), &["--keep-owned-by", "no-such-user", "file1"], 2);
+    assert!(
+        str::from_utf8(&output.stderr)
+            .unwrap()
+            .contains("no such user \"no-such-user\"")
+    );
+    run_and_expect(tt.path(), &["--keep-owned-by", ":", "file1"], 2);
+    // The owner is enough to decide what to keep, without naming any files
+    run_and_expect(tt.path(), &["--keep-owned-by", &uid], 0);
+    assert_eq!(set(["file1", "file2"]), tt.contents());
+    run_and_expect(tt.path(), &["file1"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests keeping entries by extended attribute with --keep-xattr. Needs
+/// setfattr(1) and a file system with user attributes, so it checks nothing
+/// without them.
+#[cfg(target_os = "linux")]
+#[test]
+pub fn keep_xattr() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "file2": null,
+        "file3": null,
+    }));
+    for (file, value) in [("file1", "yes"), ("file2", "no")] {
+        let tagged = Command::new("setfattr")
+            .args(["-n", "user.important", "-v", value])
+            .arg(tt.path().join(file))
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !tagged {
+            return;
+        }
+    }
+
+    run_and_expect(tt.path(), &["--keep-xattr", "=yes"], 2);
+    let output = run_and_expect(
+        tt.path(),
+        &["why", "file1", "--keep-xattr", "user.important=yes"],
+        0,
+    );
+    assert_eq!(
+        "file1 would be kept: has extended attribute user.important=yes\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    run_and_expect(tt.path(), &["--keep-xattr", "user.important"], 0);
+    assert_eq!(set(["file1", "file2"]), tt.contents());
+    run_and_expect(tt.path(), &["--keep-xattr", "user.important=yes"], 0);
+    assert_eq!(set(["file1"]), tt.contents());
+}
+
+/// Tests showing a progress line with --progress
+#[test]
+pub fn progress() {
+    let tree = json!({
+        "file1": null,
+        "dir1": {
+            "file2": null,
+        },
+    });
+    let tt = TestTree::new(tree.clone());
+    let output = run_and_expect(tt.path(), &["-r", "--progress", "file1"], 0);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.starts_with("\r\x1b[K1 removed: ./dir1/file2"));
+    assert!(stderr.ends_with("\r\x1b[K"));
+    assert_eq!(set(["file1"]), tt.contents());
+
+    // It's off by default when standard error isn't a terminal
+    let tt = TestTree::new(tree);
+    let output = run_and_expect(
+        tt.path(),
+        &["-r", "--progress", "--no-progress", "file1"],
+        0,
+    );
+    assert_eq!("", str::from_utf8(&output.stderr).unwrap());
+    let output = run_and_expect(tt.path(), &["-r", "file1"], 0);
+    assert_eq!("", str::from_utf8(&output.stderr).unwrap());
+}
+
+/// Tests that the progress line counts bytes when the entries are measured
+/// beforehand for --summary
+#[test]
+pub fn progress_bytes() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": { "content": "x".repeat(2048) },
+    }));
+    let output = run_and_expect(tt.path(), &["--progress", "--summary", "keep"], 0);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.starts_with("\r\x1b[K1 removed, 2.0 KiB of 2.0 KiB (100%): ./file1"));
+    assert_eq!(set(["keep"]), tt.contents());
+}
+
+/// Tests not removing anything unless everything can be removed with
+/// --all-or-nothing
+#[test]
+pub fn all_or_nothing() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "file1": null,
+        "dir1": {
+            "file2": null,
+        },
+        "dir2": {},
+    }));
+    let output = run_and_expect(tt.path(), &["--all-or-nothing", "keep"], 3);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.contains("Error: Can't remove ./dir1: Is a directory\n"));
+    assert!(stderr.contains("Error: Can't remove ./dir2: Is a directory\n"));
+    assert!(stderr.ends_with("Error: 2 entries can't be removed, so nothing was removed\n"));
+    assert_eq!(4, tt.contents().len());
+
+    let output = run_and_expect(tt.path(), &["--all-or-nothing", "-d", "keep"], 3);
+    assert_eq!(
+        "Error: Can't remove ./dir1: Directory is not empty\n\
+         Error: 1 entries can't be removed, so nothing was removed\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert_eq!(4, tt.contents().len());
+
+    run_and_expect(tt.path(), &["--all-or-nothing", "-r", "keep"], 0);
+    assert_eq!(set(["keep"]), tt.contents());
+}
+
+/// Tests resetting a directory to the entries recorded by `leave snapshot`
+/// with --manifest
+#[test]
+pub fn manifest() {
+    let tt = TestTree::new(json!({
+        "file1": null,
+        "#file2": null,
+        "file\\3": null,
+        "dir1": {
+            "file4": null,
+        },
+    }));
+    if cfg!(unix) {
+        std::fs::write(tt.path().join("file\n5"), "").unwrap();
+    }
+    let original = tt.contents();
+    let output = run_and_expect(tt.path(), &["snapshot"], 0);
+    let manifest = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(manifest.path(), &output.stdout).unwrap();
+    assert!(
+        str::from_utf8(&output.stdout)
+            .unwrap()
+            .starts_with("# leave manifest\n\\#file2\ndir1\n")
+    );
+
+    std::fs::write(tt.path().join("file6"), "").unwrap();
+    std::fs::create_dir(tt.path().join("dir2")).unwrap();
+    std::fs::write(tt.path().join("dir1/file7"), "").unwrap();
+    let manifest_arg = manifest.path().to_str().unwrap();
+    run_and_expect(tt.path(), &["-r", "--manifest", manifest_arg], 0);
+    assert_eq!(original, tt.contents());
+    // Only the top level is recorded
+    assert!(tt.path().join("dir1/file7").exists());
+
+    // A manifest in the directory keeps itself
+    std::fs::copy(manifest.path(), tt.path().join("manifest")).unwrap();
+    run_and_expect(tt.path(), &["--manifest", "manifest"], 0);
+    assert_eq!(original.len() + 1, tt.contents().len());
+}
+
+#[test]
+pub fn keep_type() {
+    let tt = TestTree::new(json!({
+        "report.PDF": null,
+        "photo.jpg": null,
+        "notes.txt": null,
+        "archive.zip": null,
+        "download": null,
+        "data.bin": null,
+        "dir.pdf": {
+            "file1": null,
+        },
+    }));
+    std::fs::write(tt.path().join("download"), b"%PDF-1.7\n").unwrap();
+    std::fs::write(tt.path().join("data.bin"), b"\x89PNG\r\n\x1a\n\0\0").unwrap();
+
+    let output = run_and_expect(
+        tt.path(),
+        &[
+            "why",
+            "report.PDF",
+            "--keep-type",
+            ".pdf",
+            "--keep-type",
+            "image",
+        ],
+        0,
+    );
+    assert_eq!(
+        "report.PDF would be kept: is of type .pdf\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+
+    // Sniffing finds types of files without a matching extension
+    for (file, kind) in [("download", "pdf"), ("data.bin", "image")] {
+        let output = run_and_expect(tt.path(), &["why", file, "--keep-type", kind], 0);
+        assert_eq!(
+            format!("{file} would be removed\n"),
+            str::from_utf8(&output.stdout).unwrap()
+        );
+        let output = run_and_expect(tt.path(), &["why", file, "--sniff", "--keep-type", kind], 0);
+        assert!(
+            str::from_utf8(&output.stdout)
+                .unwrap()
+                .contains("would be kept")
+        );
+    }
+
+    run_and_expect(
+        tt.path(),
+        &[
+            "-r",
+            "--keep-type",
+            "pdf",
+            "--keep-type",
+            "image",
+            "--keep-type",
+            "text/*",
+        ],
+        0,
+    );
+    assert_eq!(set(["report.PDF", "photo.jpg", "notes.txt"]), tt.contents());
+
+    run_and_expect(tt.path(), &["--keep-type", "*/png", "notes.txt"], 2);
+}
+
+/// Tests removing directories left empty around nested keep arguments with
+/// --prune-empty
+#[test]
+pub fn prune_empty() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "dir1": {
+            "file1": null,
+            "dir2": {
+                "file2": null,
+            },
+        },
+        "dir3": {
+            "file3": null,
+            "file4": null,
+        },
+    }));
+    let original = tt.contents();
+    let args = [
+        "-rf",
+        "--prune-empty",
+        "keep",
+        "dir1/dir2/gone",
+        "dir3/file3",
+    ];
+
+    let output = run_and_expect(tt.path(), &[&["-n"][..], &args].concat(), 0);
+    assert_eq!(
+        set([
+            "would remove ./dir1/file1",
+            "would remove ./dir1/dir2/file2",
+            "would remove ./dir3/file4",
+            "would remove ./dir1/dir2",
+            "would remove ./dir1",
+        ]),
+        set(str::from_utf8(&output.stdout).unwrap().lines())
+    );
+    assert_eq!(original, tt.contents());
+
+    // Emptied directories are removed with rmdir in a shell script
+    #[cfg(unix)]
+    {
+        let output = run_and_expect(
+            tt.path(),
+            &[&["-n", "--output", "sh"][..], &args].concat(),
+            0,
+        );
+        let script = str::from_utf8(&output.stdout).unwrap();
+        assert!(script.contains("rmdir -- ./dir1/dir2\n"), "{script}");
+        assert!(script.contains("rmdir -- ./dir1\n"), "{script}");
+        assert!(!script.contains("would remove"), "{script}");
+        assert_eq!(original, tt.contents());
+    }
+
+    run_and_expect(tt.path(), &args, 0);
+    assert_eq!(set(["keep", "dir3"]), tt.contents());
+    assert!(tt.path().join("dir3/file3").exists());
+}
+
+/// --empty-kept-dirs only empties directories named without a trailing slash
+#[test]
+pub fn empty_kept_dirs() {
+    let tt = TestTree::new(json!({
+        "emptied": {
+            "file1": null,
+            "dir1": {
+                "file2": null,
+            },
+        },
+        "kept": {
+            "file3": null,
+        },
+        "link": "kept",
+        "gone": null,
+    }));
+    let original = tt.contents();
+    let args = ["-r", "--empty-kept-dirs", "emptied", "kept/", "link"];
+
+    let output = run_and_expect(tt.path(), &[&["-n"][..], &args].concat(), 0);
+    assert_eq!(
+        set([
+            "would remove ./emptied/file1",
+            "would remove ./emptied/dir1",
+            "would remove ./gone",
+        ]),
+        set(str::from_utf8(&output.stdout).unwrap().lines())
+    );
+    assert_eq!(original, tt.contents());
+
+    run_and_expect(tt.path(), &args, 0);
+    assert_eq!(set(["emptied", "kept", "link"]), tt.contents());
+    assert_eq!(
+        0,
+        std::fs::read_dir(tt.path().join("emptied"))
+            .unwrap()
+            .count()
+    );
+    assert!(tt.path().join("kept/file3").exists());
+}
+
+#[test]
+pub fn max_depth() {
+    let tt = TestTree::new(json!({
+        "keep": null,
+        "dir1": {
+            "file1": null,
+            "dir2": {
+                "file2": null,
+                "dir3": {
+                    "file3": null,
+                },
+            },
+        },
+        "dir4": {
+            "dir5": {
+                "file4": null,
+            },
+        },
+    }));
+    let original = tt.contents();
+
+    let output = run_and_expect(tt.path(), &["-rn", "--max-depth", "3", "keep"], 0);
+    assert_eq!(
+        "would leave ./dir1/dir2/dir3: it has entries deeper than --max-depth\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+    assert_eq!(
+        "would remove ./dir4\n",
+        str::from_utf8(&output.stdout).unwrap()
+    );
+    assert_eq!(original, tt.contents());
+
+    run_and_expect(tt.path(), &["-r", "--max-depth", "3", "keep"], 0);
+    assert_eq!(set(["keep", "dir1"]), tt.contents());
+    assert!(tt.path().join("dir1/dir2/dir3/file3").exists());
+    assert!(!tt.path().join("dir1/file1").exists());
+    assert!(!tt.path().join("dir1/dir2/file2").exists());
+
+    run_and_expect(tt.path(), &["--max-depth", "3", "keep"], 2);
+}
+
+/// Tests keeping entries which belong to an installed package, as reported
+/// by a stand-in for dpkg-query or rpm
+#[cfg(target_os = "linux")]
+#[test]
+pub fn keep_packaged() {
+    // Both claim the entries whose names start with "pkg"
+    let dpkg_query = "#!/bin/sh\n\
+        [ \"$1\" = --version ] && exit 0\n\
+        shift\n\
+        status=0\n\
+        for path; do\n\
+            case \"${path##*/}\" in\n\
+                pkg*) echo \"fakepkg: $path\" ;;\n\
+                *) echo \"dpkg-query: no path found matching pattern $path\" >&2; status=1 ;;\n\
+            esac\n\
+        done\n\
+        exit $status\n";
+    let rpm = "#!/bin/sh\n\
+        [ \"$1\" = --version ] && exit 0\n\
+        case \"${3##*/}\" in pkg*) exit 0 ;; esac\n\
+        exit 1\n";
+    for (program, script) in [("dpkg-query", dpkg_query), ("rpm", rpm)] {
+        let tt = TestTree::new(json!({
+            "pkg1": null,
+            "pkg2": null,
+            "notes": null,
+            "scratch": null,
+        }));
+        // Only the stand-in is on the path, so the real package manager isn't
+        // found instead
+        let bin = tempfile::tempdir().unwrap();
+        let path = bin.path().join(program);
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        let output = leave_command()
+            .args(["--keep-packaged", "-v", "notes"])
+            .env("PATH", bin.path())
+            .current_dir(tt.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{program}");
+        assert_eq!(set(["pkg1", "pkg2", "notes"]), tt.contents(), "{program}");
+        assert_eq!(
+            "removed './scratch'\n",
+            str::from_utf8(&output.stdout).unwrap(),
+            "{program}"
+        );
+    }
+
+    // Without a package manager, nothing is removed
+    let tt = TestTree::new(json!({
+        "pkg1": null,
+        "notes": null,
+    }));
+    let bin = tempfile::tempdir().unwrap();
+    let output = leave_command()
+        .args(["--keep-packaged", "notes"])
+        .env("PATH", bin.path())
+        .current_dir(tt.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(set(["pkg1", "notes"]), tt.contents());
+    assert_eq!(
+        "Error: Can't find dpkg-query or rpm to look up package ownership\n",
+        str::from_utf8(&output.stderr).unwrap()
+    );
+}
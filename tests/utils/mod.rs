@@ -16,7 +16,12 @@
 // Leave. If not, see <https://www.gnu.org/licenses/>.
 //
 
-use std::{collections::HashSet, path::Path};
+use std::{
+    collections::HashSet,
+    fs::File,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
 
 use eyre::WrapErr as _;
 use serde_json::Value as JsonValue;
@@ -32,7 +37,19 @@ use tempfile::{TempDir, tempdir};
 /// represents a file that will be created. If the value is an
 /// object, it represents a directory which will be treated recursively. If the
 /// value is a string, the field represents a symbolic link and the value is the
-/// link target.
+/// link target. On Windows, a link to a directory must come after the
+/// directory, since the kind of link depends on its target.
+///
+/// A file can also be given as an object with a `content` field, which is the
+/// file's contents, and optionally the following attributes:
+///
+/// - `mode`: the permissions, in octal, e.g. `"0444"`. On Windows, the file is
+///   read-only if no write bit is set.
+/// - `mtime`: the modification time, in seconds since the Unix epoch.
+///
+/// A directory's own attributes are given as an object in its `.` field, e.g.
+/// `{"dir": {".": {"mode": "0555"}, "file": null}}`. They're applied after
+/// its contents are created, so read-only directories can have contents.
 pub struct TestTree(TempDir);
 
 type JsonObject = serde_json::Map<String, JsonValue>;
@@ -74,25 +91,229 @@ impl TestTree {
             .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
             .collect()
     }
+
+    /// Asserts that the directory's contents match the given JSON tree, which
+    /// uses the same format as [`TestTree::new`]. Descends into directories.
+    /// Contents and attributes are only compared for files and directories
+    /// which give them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the contents don't match, showing the differences.
+    pub fn assert_tree(&self, expected: JsonValue) {
+        let obj = expected
+            .as_object()
+            .expect("Argument must be a JSON object");
+        pretty_assertions::assert_eq!(expected, describe_dir(self.path(), Some(obj)));
+    }
+}
+
+impl Drop for TestTree {
+    /// Makes directories writable again, so the temporary directory can be
+    /// removed.
+    fn drop(&mut self) {
+        make_writable(self.path());
+    }
 }
 
 fn populate_from_object(dir: &Path, obj: &JsonObject) {
-    for (key, value) in obj {
+    for (key, value) in obj.iter().filter(|(key, _)| *key != ".") {
         let path = dir.join(key);
         match value {
-            JsonValue::String(dest) => std::os::unix::fs::symlink(dest, &path)
+            JsonValue::String(dest) => symlink(dest, &path)
                 .wrap_err_with(|| format!("Can't link {} -> {}", path.display(), dest))
                 .unwrap(),
             JsonValue::Null => std::fs::write(&path, "")
                 .wrap_err_with(|| format!("Can't write to {}", path.display()))
                 .unwrap(),
+            JsonValue::Object(file) if file.contains_key("content") => {
+                let content = file["content"]
+                    .as_str()
+                    .expect("File content must be a string");
+                std::fs::write(&path, content)
+                    .wrap_err_with(|| format!("Can't write to {}", path.display()))
+                    .unwrap();
+                set_attributes(&path, file);
+            }
             JsonValue::Object(inner) => {
                 std::fs::create_dir(&path)
                     .wrap_err_with(|| format!("Can't create directory {}", path.display()))
                     .unwrap();
                 populate_from_object(&path, inner);
             }
-            _ => panic!("Field value must be a string or an object"),
+            _ => panic!("Field value must be null, a string, or an object"),
         }
     }
+    if let Some(attributes) = obj.get(".") {
+        let attributes = attributes
+            .as_object()
+            .expect("Directory attributes must be an object");
+        set_attributes(dir, attributes);
+    }
+}
+
+/// Sets the `mode` and `mtime` attributes of a file or directory. Other fields
+/// of `attributes` are ignored.
+fn set_attributes(path: &Path, attributes: &JsonObject) {
+    if let Some(mtime) = attributes.get("mtime") {
+        let secs = mtime
+            .as_u64()
+            .expect("mtime must be a non-negative integer");
+        File::open(path)
+            .and_then(|file| file.set_modified(UNIX_EPOCH + Duration::from_secs(secs)))
+            .wrap_err_with(|| format!("Can't set modification time of {}", path.display()))
+            .unwrap();
+    }
+    if let Some(mode) = attributes.get("mode") {
+        let mode = mode
+            .as_str()
+            .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+            .expect("mode must be an octal string");
+        set_mode(path, mode)
+            .wrap_err_with(|| format!("Can't set permissions of {}", path.display()))
+            .unwrap();
+    }
+}
+
+/// Describes the given directory as a JSON tree. Where `expected` gives a
+/// file's contents or an entry's attributes, they're included so they can be
+/// compared.
+fn describe_dir(dir: &Path, expected: Option<&JsonObject>) -> JsonValue {
+    let mut obj = JsonObject::new();
+    if let Some(attributes) = expected.and_then(|expected| expected.get(".")) {
+        obj.insert(".".into(), describe_attributes(dir, attributes));
+    }
+    let entries = dir
+        .read_dir()
+        .wrap_err_with(|| format!("Can't read contents of {}", dir.display()))
+        .unwrap();
+    for entry in entries {
+        let entry = entry.unwrap();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+        let expected = expected.and_then(|expected| expected.get(&name));
+        let file_type = entry.file_type().unwrap();
+        let value = if file_type.is_symlink() {
+            let dest = std::fs::read_link(&path).unwrap();
+            JsonValue::String(dest.to_string_lossy().to_string())
+        } else if file_type.is_dir() {
+            describe_dir(&path, expected.and_then(JsonValue::as_object))
+        } else {
+            match expected {
+                Some(expected @ JsonValue::Object(file)) if file.contains_key("content") => {
+                    describe_attributes(&path, expected)
+                }
+                _ => JsonValue::Null,
+            }
+        };
+        obj.insert(name, value);
+    }
+    JsonValue::Object(obj)
+}
+
+/// Describes the contents and attributes of a file or directory which are
+/// given in `expected`. Modes are written the same way as in `expected` if
+/// they're equal, so the comparison doesn't depend on how they're written.
+fn describe_attributes(path: &Path, expected: &JsonValue) -> JsonValue {
+    let mut obj = JsonObject::new();
+    let Some(expected) = expected.as_object() else {
+        return JsonValue::Object(obj);
+    };
+    let metadata = std::fs::metadata(path).unwrap();
+    if expected.contains_key("content") {
+        let content = std::fs::read(path).unwrap();
+        obj.insert(
+            "content".into(),
+            String::from_utf8_lossy(&content).into_owned().into(),
+        );
+    }
+    if let Some(expected_mode) = expected.get("mode") {
+        let mode = mode(&metadata);
+        let written = expected_mode
+            .as_str()
+            .filter(|written| u32::from_str_radix(written, 8).ok() == Some(mode));
+        let mode = written.map_or_else(|| format!("{mode:04o}"), str::to_owned);
+        obj.insert("mode".into(), mode.into());
+    }
+    if expected.contains_key("mtime") {
+        let mtime = metadata
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap();
+        obj.insert("mtime".into(), mtime.as_secs().into());
+    }
+    JsonValue::Object(obj)
+}
+
+/// Makes the given directory and those inside it writable by their owner.
+fn make_writable(dir: &Path) {
+    let Ok(metadata) = std::fs::symlink_metadata(dir) else {
+        return;
+    };
+    if !metadata.is_dir() {
+        return;
+    }
+    let _ = set_mode(dir, mode(&metadata) | 0o700);
+    for entry in dir.read_dir().into_iter().flatten().flatten() {
+        make_writable(&entry.path());
+    }
+}
+
+/// Sets the permissions of the given file or directory.
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+/// Sets the permissions of the given file or directory. Windows only has a
+/// read-only flag, which is set if no write bit is.
+#[cfg(windows)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    std::fs::set_permissions(path, permissions)
+}
+
+/// Returns the permission bits of a file or directory.
+#[cfg(unix)]
+fn mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o7777
+}
+
+/// Returns the permission bits of a file or directory, as far as Windows has
+/// them.
+#[cfg(windows)]
+fn mode(metadata: &std::fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o666
+    }
+}
+
+/// Creates a symbolic link at `path` pointing to `dest`, which is relative to
+/// the link's directory.
+#[cfg(unix)]
+fn symlink(dest: &str, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(dest, path)
+}
+
+/// Creates a symbolic link at `path` pointing to `dest`, which is relative to
+/// the link's directory. Windows has separate kinds of links to files and
+/// directories, so links to directories must be created after their targets.
+#[cfg(windows)]
+fn symlink(dest: &str, path: &Path) -> std::io::Result<()> {
+    let target = path
+        .parent()
+        .map_or_else(|| dest.into(), |dir| dir.join(dest));
+    if target.is_dir() {
+        std::os::windows::fs::symlink_dir(dest, path)
+    } else {
+        std::os::windows::fs::symlink_file(dest, path)
+    }
 }